@@ -0,0 +1,133 @@
+use tauri::State;
+
+use klok_core::encoding::decode_text;
+use klok_core::lyrics::{LyricLine, LyricWord};
+use klok_core::midi::Note;
+use klok_core::phrases::phrase_gaps;
+
+use crate::commands::get_metadata::find_lyrics;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::commands::save_lyrics::write_lrc;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// Split a line into sung units: one per CJK character or kana, one per word otherwise.
+/// Units keep their trailing whitespace so they concatenate back to the line.
+fn syllables(text: &str) -> Vec<String> {
+  let is_cjk = |c: char| matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}');
+  let mut units: Vec<String> = Vec::new();
+  let mut current = String::new();
+  for c in text.chars() {
+    if c.is_whitespace() {
+      current.push(c);
+      continue;
+    }
+    let ends_unit = is_cjk(c) || current.ends_with(char::is_whitespace) || current.ends_with(is_cjk);
+    if ends_unit && !current.trim().is_empty() {
+      units.push(std::mem::take(&mut current));
+    }
+    current.push(c);
+  }
+  if !current.trim().is_empty() {
+    units.push(current);
+  }
+  units
+}
+
+/// Time untimed lyric lines against the vocal notes. The song is cut into one segment per line
+/// at the largest silences between notes (usually phrase boundaries), and each line's syllables
+/// are spread over the notes of its segment.
+pub fn align_text(text: &str, notes: &[Note]) -> Vec<LyricLine> {
+  let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+  let mut starts: Vec<f64> = notes.iter().map(|n| n.start).collect();
+  starts.sort_by(f64::total_cmp);
+  let (Some(&first), false) = (starts.first(), lines.is_empty()) else {
+    return Vec::new();
+  };
+  let song_end = notes.iter().map(|n| n.start + n.duration).fold(first, f64::max);
+
+  // boundaries between lines, the largest gaps first
+  let mut gaps = phrase_gaps(notes, 0.0);
+  gaps.sort_by(|a, b| (b.1 - b.0).total_cmp(&(a.1 - a.0)));
+  gaps.truncate(lines.len() - 1);
+  gaps.sort_by(|a, b| a.0.total_cmp(&b.0));
+  // not enough gaps: split the remaining time evenly by the last segment
+  while gaps.len() < lines.len() - 1 {
+    let from = gaps.last().map_or(first, |g| g.1);
+    let left = (lines.len() - 1 - gaps.len()) as f64 + 1.0;
+    let at = from + (song_end - from) / left;
+    gaps.push((at, at));
+  }
+
+  let mut aligned = Vec::with_capacity(lines.len());
+  for (i, line) in lines.iter().enumerate() {
+    let start = if i == 0 { first } else { gaps[i - 1].1 };
+    let end = gaps.get(i).map_or(song_end, |g| g.0);
+    let sung: Vec<f64> = starts.iter().copied().filter(|&t| t >= start && t < end.max(start + f64::EPSILON)).collect();
+
+    let units = syllables(line);
+    let words = units
+      .iter()
+      .enumerate()
+      .map(|(j, unit)| {
+        let time = if units.len() <= sung.len() {
+          sung[j * sung.len() / units.len()]
+        } else {
+          start + (end - start) * j as f64 / units.len() as f64
+        };
+        LyricWord { time, text: unit.clone() }
+      })
+      .collect();
+    aligned.push(LyricLine { time: start, end: Some(end), text: line.to_string(), words, ..Default::default() });
+  }
+  aligned
+}
+
+/// Untimed lyrics from the song's `.txt` companion aligned to its vocal MIDI, or `Ok(None)` when
+/// either file is missing.
+pub(crate) fn find_aligned_lyrics(state: &AppState, path: &str) -> Result<Option<Vec<LyricLine>>, String> {
+  let Some(resolved) = state.resolve(with_extension(path, ".txt")) else {
+    return Ok(None);
+  };
+  let Some(notes) = find_vocal_notes(state, path)? else {
+    return Ok(None);
+  };
+  let content = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  let lyrics = align_text(&decode_text(&content), &notes);
+  info!(lines = lyrics.len(), notes = notes.len(), "aligned plain lyrics to vocal midi");
+  Ok(Some(lyrics))
+}
+
+/// Align the song's `.txt` lyrics to its vocal MIDI and write the result as `song.lrc`.
+/// An existing lyrics file is only replaced with `overwrite`.
+#[tauri::command]
+pub fn align_lyrics(state: State<'_, AppState>, path: String, overwrite: Option<bool>) -> Result<Vec<LyricLine>, String> {
+  ensure_unlocked(&state, "align_lyrics")?;
+  if !overwrite.unwrap_or(false) && find_lyrics(&state, &path)?.is_some() {
+    return Err(format!("{} already has timed lyrics", path));
+  }
+  let lyrics = find_aligned_lyrics(&state, &path)?.ok_or_else(|| format!("no .txt lyrics or vocal midi for {}", path))?;
+  write_lrc(&state, &path, &lyrics)?;
+  Ok(lyrics)
+}
+
+#[test]
+pub fn test_align_text() {
+  let note = |start: f64, duration: f64| Note { note: 60, start, duration, velocity: 1.0, channel: 0, confidence: None, bend: Vec::new() };
+  let notes = vec![note(1.0, 0.4), note(1.5, 0.4), note(2.0, 0.5), note(5.0, 0.5), note(5.5, 1.0)];
+
+  assert_eq!(syllables("你好 world, hi"), vec!["你", "好 ", "world, ", "hi"]);
+
+  let lines = align_text("你好啊\n\nhello world\n", &notes);
+  assert_eq!(lines.len(), 2);
+  assert_eq!((lines[0].time, lines[0].end), (1.0, Some(2.5)));
+  assert_eq!(lines[0].words.iter().map(|w| w.time).collect::<Vec<_>>(), vec![1.0, 1.5, 2.0]);
+  assert_eq!((lines[1].time, lines[1].end), (5.0, Some(6.5)));
+  assert_eq!(lines[1].words.iter().map(|w| (w.time, w.text.as_str())).collect::<Vec<_>>(), vec![(5.0, "hello "), (5.5, "world")]);
+
+  // more lines than gaps: the tail is split evenly
+  let lines = align_text("a\nb\nc", &notes[3..]);
+  assert_eq!(lines.iter().map(|l| l.time).collect::<Vec<_>>(), vec![5.0, 5.5, 6.0]);
+  assert!(align_text("", &notes).is_empty());
+}
@@ -0,0 +1,41 @@
+use tauri::State;
+
+use klok_core::lyrics::{LinePart, LyricLine};
+
+use crate::commands::get_metadata::find_lyrics;
+use crate::AppState;
+
+/// Build a pass-the-mic line assignment for the lyrics next to `path`.
+/// Returns one player index (0-based) per lyric line, in the same order as `Metadata.lyrics`.
+/// Turns pass after `lines_per_turn` sung lines (default 1); lines without text
+/// (instrumental breaks) go to the player who sings next and don't count towards a turn.
+/// With two or more players, duet lines marked for part 1 or 2 go to the first or second player.
+#[tauri::command]
+pub fn assign_mic_turns(state: State<'_, AppState>, path: String, players: usize, lines_per_turn: Option<usize>) -> Result<Vec<usize>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  if players == 0 {
+    return Err("players must be at least 1".to_string());
+  }
+  let lyrics = find_lyrics(&state, &path)?.ok_or_else(|| format!("lyrics file not found for provided path: {}", path))?;
+  Ok(assign_turns(&lyrics, players, lines_per_turn.unwrap_or(1).max(1)))
+}
+
+fn assign_turns(lyrics: &[LyricLine], players: usize, lines_per_turn: usize) -> Vec<usize> {
+  let mut sung = 0usize;
+  lyrics
+    .iter()
+    .map(|line| {
+      let player = match line.part {
+        Some(LinePart::P1) if players > 1 => 0,
+        Some(LinePart::P2) if players > 1 => 1,
+        _ => (sung / lines_per_turn) % players,
+      };
+      if !line.text.trim().is_empty() {
+        sung += 1;
+      }
+      player
+    })
+    .collect()
+}
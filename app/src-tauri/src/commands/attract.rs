@@ -0,0 +1,59 @@
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::settings::AttractSettings;
+use crate::AppState;
+
+/// The attract mode settings: songs played at reduced volume once the app has been idle for
+/// `idle_minutes`, until a song is queued. The playlist skips songs since removed from the library
+/// or hidden.
+#[tauri::command]
+pub fn get_attract_mode(state: State<'_, AppState>) -> Result<AttractSettings, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  let mut attract = settings.attract.clone();
+  attract.playlist.retain(|path| !settings.hidden_songs.contains(path) && state.resolve(path).is_some());
+  Ok(attract)
+}
+
+/// Replace the attract mode settings and persist settings.
+#[tauri::command]
+pub fn set_attract_mode(state: State<'_, AppState>, attract: AttractSettings) -> Result<(), String> {
+  ensure_unlocked(&state, "set_attract_mode")?;
+  let attract = checked(attract, |path| state.resolve(path).is_some())?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  info!(enabled = attract.enabled, songs = attract.playlist.len(), idle_minutes = attract.idle_minutes, "attract mode set");
+  settings.attract = attract;
+  settings.save(&state.config_dir)
+}
+
+// `attract` with its volume clamped, or why it can't be used; `exists` tells library songs.
+fn checked(mut attract: AttractSettings, exists: impl Fn(&str) -> bool) -> Result<AttractSettings, String> {
+  if !(attract.idle_minutes.is_finite() && attract.idle_minutes > 0.0) {
+    return Err(format!("invalid idle time: {} minutes", attract.idle_minutes));
+  }
+  if attract.volume.is_nan() {
+    return Err("invalid attract volume".to_string());
+  }
+  if let Some(missing) = attract.playlist.iter().find(|path| !exists(path)) {
+    return Err(format!("resource not found: {}", missing));
+  }
+  if attract.enabled && attract.playlist.is_empty() {
+    return Err("attract mode needs at least one song".to_string());
+  }
+  attract.volume = attract.volume.clamp(0.0, 1.0);
+  Ok(attract)
+}
+
+#[test]
+pub fn test_checked_attract() {
+  let exists = |path: &str| path != "gone.mp3";
+  let attract = AttractSettings { enabled: true, playlist: vec!["a.mp3".to_string()], volume: 1.5, ..Default::default() };
+  assert_eq!(checked(attract.clone(), exists).unwrap().volume, 1.0);
+
+  assert!(checked(AttractSettings { idle_minutes: 0.0, ..attract.clone() }, exists).is_err());
+  assert!(checked(AttractSettings { volume: f64::NAN, ..attract.clone() }, exists).is_err());
+  assert_eq!(checked(AttractSettings { playlist: vec!["gone.mp3".to_string()], ..attract.clone() }, exists).unwrap_err(), "resource not found: gone.mp3");
+  assert!(checked(AttractSettings { playlist: Vec::new(), ..attract.clone() }, exists).is_err());
+  // off, it may be emptied
+  assert!(checked(AttractSettings { enabled: false, playlist: Vec::new(), ..attract }, exists).is_ok());
+}
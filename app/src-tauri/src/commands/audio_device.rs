@@ -0,0 +1,326 @@
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::player::{restart_output, MAX_OUTPUT_DELAY};
+use crate::settings::SecondOutput;
+use crate::AppState;
+
+/// Seconds of audio the output device buffers; playback is heard this long after it is written.
+pub const OUTPUT_LATENCY: f64 = 0.1;
+/// Seconds of audio the input device buffers, short so the microphone reacts quickly.
+pub const INPUT_LATENCY: f64 = 0.02;
+
+/// An audio device, e.g. the built-in speakers, an HDMI output or a USB mixer.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+  /// for `set_audio_output`
+  pub id: String,
+  pub name: String,
+}
+
+/// Devices of one direction ("playback" or "capture") in an ALSA `/proc/asound/pcm` listing, with
+/// lines such as `00-03: HDMI 0 : HDMI 0 : playback 1`; `cards` names the card of each number.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_pcm_list(listing: &str, direction: &str, cards: impl Fn(u32) -> Option<String>) -> Vec<AudioDevice> {
+  listing
+    .lines()
+    .filter_map(|line| {
+      let (numbers, rest) = line.split_once(':')?;
+      let (card, device) = numbers.trim().split_once('-')?;
+      let (card, device): (u32, u32) = (card.parse().ok()?, device.parse().ok()?);
+      let mut fields = rest.split(" : ").map(str::trim);
+      let name = fields.next()?.to_string();
+      fields.any(|f| f.starts_with(direction)).then(|| {
+        let name = cards(card).map_or(name.clone(), |card| format!("{} - {}", card, name));
+        AudioDevice { id: format!("plughw:{},{}", card, device), name }
+      })
+    })
+    .collect()
+}
+
+// Audio goes through ALSA's libasound, loaded when a device is first opened so the app still
+// starts, without native audio, where it isn't installed.
+#[cfg(target_os = "linux")]
+pub(crate) mod backend {
+  use libc::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+  use std::ffi::{CStr, CString};
+  use std::sync::OnceLock;
+
+  use super::{parse_pcm_list, AudioDevice, INPUT_LATENCY};
+
+  const LIBRARY: &CStr = c"libasound.so.2";
+  // from alsa/pcm.h
+  const STREAM_PLAYBACK: c_int = 0;
+  const STREAM_CAPTURE: c_int = 1;
+  const FORMAT_FLOAT_LE: c_int = 14;
+  const ACCESS_RW_INTERLEAVED: c_int = 3;
+
+  type Handle = *mut c_void;
+
+  // the libasound functions used, see alsa/pcm.h
+  struct Alsa {
+    open: unsafe extern "C" fn(*mut Handle, *const c_char, c_int, c_int) -> c_int,
+    set_params: unsafe extern "C" fn(Handle, c_int, c_int, c_uint, c_uint, c_int, c_uint) -> c_int,
+    writei: unsafe extern "C" fn(Handle, *const c_void, c_ulong) -> c_long,
+    readi: unsafe extern "C" fn(Handle, *mut c_void, c_ulong) -> c_long,
+    recover: unsafe extern "C" fn(Handle, c_int, c_int) -> c_int,
+    drop: unsafe extern "C" fn(Handle) -> c_int,
+    close: unsafe extern "C" fn(Handle) -> c_int,
+    strerror: unsafe extern "C" fn(c_int) -> *const c_char,
+  }
+
+  // The function `name` of `library`, as `F`.
+  //
+  // SAFETY: `F` must be the function pointer type of `name`.
+  unsafe fn symbol<F: Copy>(library: *mut c_void, name: &CStr) -> Result<F, String> {
+    let found = libc::dlsym(library, name.as_ptr());
+    if found.is_null() {
+      return Err(format!("{} has no {}", LIBRARY.to_string_lossy(), name.to_string_lossy()));
+    }
+    Ok(std::mem::transmute_copy::<*mut c_void, F>(&found))
+  }
+
+  impl Alsa {
+    fn load() -> Result<Alsa, String> {
+      // SAFETY: libasound has no load-time side effects and is never unloaded; the fields have
+      // the signatures of alsa/pcm.h.
+      unsafe {
+        let library = libc::dlopen(LIBRARY.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if library.is_null() {
+          return Err(format!("failed to load {} (ALSA) for native audio", LIBRARY.to_string_lossy()));
+        }
+        Ok(Alsa {
+          open: symbol(library, c"snd_pcm_open")?,
+          set_params: symbol(library, c"snd_pcm_set_params")?,
+          writei: symbol(library, c"snd_pcm_writei")?,
+          readi: symbol(library, c"snd_pcm_readi")?,
+          recover: symbol(library, c"snd_pcm_recover")?,
+          drop: symbol(library, c"snd_pcm_drop")?,
+          close: symbol(library, c"snd_pcm_close")?,
+          strerror: symbol(library, c"snd_strerror")?,
+        })
+      }
+    }
+
+    fn error(&self, code: c_int) -> String {
+      // SAFETY: snd_strerror returns a static string for any code
+      unsafe { CStr::from_ptr((self.strerror)(code)).to_string_lossy().to_string() }
+    }
+  }
+
+  fn alsa() -> Result<&'static Alsa, String> {
+    static ALSA: OnceLock<Result<Alsa, String>> = OnceLock::new();
+    ALSA.get_or_init(Alsa::load).as_ref().map_err(Clone::clone)
+  }
+
+  // An open PCM of interleaved f32 samples; dropping it stops at once rather than playing out
+  // what's buffered.
+  #[derive(Debug)]
+  struct Pcm {
+    handle: Handle,
+    channels: usize,
+  }
+
+  // SAFETY: a PCM handle may be used from any thread, one at a time, which `&mut self` ensures
+  unsafe impl Send for Pcm {}
+
+  impl Pcm {
+    fn open(device: &str, stream: c_int, sample_rate: u32, channels: u16, latency: f64) -> Result<Pcm, String> {
+      let alsa = alsa()?;
+      let name = CString::new(device).map_err(|_| format!("invalid audio device: {}", device))?;
+      let mut handle: Handle = std::ptr::null_mut();
+      // SAFETY: `handle` is written by a successful open, and closed by drop
+      let code = unsafe { (alsa.open)(&mut handle, name.as_ptr(), stream, 0) };
+      if code < 0 {
+        return Err(format!("failed to open audio device {}: {}", device, alsa.error(code)));
+      }
+      let pcm = Pcm { handle, channels: channels.max(1) as usize };
+      // SAFETY: `pcm.handle` is open
+      let code = unsafe { (alsa.set_params)(pcm.handle, FORMAT_FLOAT_LE, ACCESS_RW_INTERLEAVED, channels.max(1) as c_uint, sample_rate, 1, (latency * 1e6) as c_uint) };
+      if code < 0 {
+        return Err(format!("failed to set up audio device {}: {}", device, alsa.error(code)));
+      }
+      Ok(pcm)
+    }
+
+    // Move the frames of a buffer of `len` samples through `transfer` (writei or readi, given the
+    // sample offset and the frames left) until all are done, recovering from under- and overruns.
+    fn transfer(&mut self, len: usize, mut transfer: impl FnMut(&Alsa, Handle, usize, c_ulong) -> c_long) -> Result<(), String> {
+      let alsa = alsa()?;
+      let mut done = 0;
+      while done < len / self.channels {
+        let count = transfer(alsa, self.handle, done * self.channels, (len / self.channels - done) as c_ulong);
+        if count >= 0 {
+          done += count as usize;
+          continue;
+        }
+        // SAFETY: `self.handle` is open
+        let code = unsafe { (alsa.recover)(self.handle, count as c_int, 1) };
+        if code < 0 {
+          return Err(alsa.error(code));
+        }
+      }
+      Ok(())
+    }
+  }
+
+  impl Drop for Pcm {
+    fn drop(&mut self) {
+      if let Ok(alsa) = alsa() {
+        // SAFETY: `self.handle` is open and not used after
+        unsafe {
+          (alsa.drop)(self.handle);
+          (alsa.close)(self.handle);
+        }
+      }
+    }
+  }
+
+  fn card_name(card: u32) -> Option<String> {
+    let id = std::fs::read_to_string(format!("/proc/asound/card{}/id", card)).ok()?;
+    Some(id.trim().to_string()).filter(|id| !id.is_empty())
+  }
+
+  /// The system default, then every ALSA device that can play.
+  pub fn outputs() -> Result<Vec<AudioDevice>, String> {
+    let listing = std::fs::read_to_string("/proc/asound/pcm").unwrap_or_default();
+    let mut devices = vec![AudioDevice { id: "default".to_string(), name: "System default".to_string() }];
+    devices.extend(parse_pcm_list(&listing, "playback", card_name));
+    Ok(devices)
+  }
+
+  /// An open output; samples written are played in order, `write` blocks while the device is full.
+  #[derive(Debug)]
+  pub struct OutputStream(Pcm);
+
+  impl OutputStream {
+    /// Play interleaved samples.
+    pub fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+      // SAFETY: the frames passed start at `offset` and lie within `samples`
+      let written = self.0.transfer(samples.len(), |alsa, handle, offset, frames| unsafe { (alsa.writei)(handle, samples[offset..].as_ptr().cast(), frames) });
+      written.map_err(|e| format!("audio output failed: {}", e))
+    }
+  }
+
+  /// Open the output `device` (an ALSA device name, the default one when `None`), buffering
+  /// `latency` seconds.
+  pub fn open_output(device: Option<&str>, sample_rate: u32, channels: u16, latency: f64) -> Result<OutputStream, String> {
+    Pcm::open(device.unwrap_or("default"), STREAM_PLAYBACK, sample_rate, channels, latency).map(OutputStream)
+  }
+
+  /// An open input; `read` blocks until samples arrive.
+  #[derive(Debug)]
+  pub struct InputStream(Pcm);
+
+  impl InputStream {
+    /// Fill `samples` with the next interleaved samples recorded.
+    pub fn read(&mut self, samples: &mut [f32]) -> Result<(), String> {
+      let len = samples.len();
+      // SAFETY: the frames passed start at `offset` and lie within `samples`
+      let read = self.0.transfer(len, |alsa, handle, offset, frames| unsafe { (alsa.readi)(handle, samples[offset..].as_mut_ptr().cast(), frames) });
+      read.map_err(|e| format!("audio input failed: {}", e))
+    }
+  }
+
+  /// Open the default capture device (the system's microphone).
+  pub fn open_input(sample_rate: u32, channels: u16) -> Result<InputStream, String> {
+    Pcm::open("default", STREAM_CAPTURE, sample_rate, channels, INPUT_LATENCY).map(InputStream)
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) mod backend {
+  use super::AudioDevice;
+
+  #[derive(Debug)]
+  pub struct OutputStream;
+
+  pub fn outputs() -> Result<Vec<AudioDevice>, String> {
+    Ok(Vec::new())
+  }
+
+  impl OutputStream {
+    pub fn write(&mut self, _samples: &[f32]) -> Result<(), String> {
+      Err("audio output is not supported on this platform".to_string())
+    }
+  }
+
+  pub fn open_output(_device: Option<&str>, _sample_rate: u32, _channels: u16, _latency: f64) -> Result<OutputStream, String> {
+    Err("audio output is not supported on this platform".to_string())
+  }
+
+  #[derive(Debug)]
+  pub struct InputStream;
+
+  impl InputStream {
+    pub fn read(&mut self, _samples: &mut [f32]) -> Result<(), String> {
+      Err("audio input is not supported on this platform".to_string())
+    }
+  }
+
+  pub fn open_input(_sample_rate: u32, _channels: u16) -> Result<InputStream, String> {
+    Err("audio input is not supported on this platform".to_string())
+  }
+}
+
+/// The audio outputs native playback can be sent to.
+#[tauri::command]
+pub fn list_audio_outputs() -> Result<Vec<AudioDevice>, String> {
+  backend::outputs()
+}
+
+/// Send native playback to `device_id` (from `list_audio_outputs`), or the system default when
+/// `None`. Kept in the settings; what is playing moves over at once.
+#[tauri::command]
+pub fn set_audio_output(app: AppHandle, state: State<'_, AppState>, device_id: Option<String>) -> Result<(), String> {
+  ensure_unlocked(&state, "set_audio_output")?;
+  // only listed devices, so the id can't name any other ALSA device
+  if let Some(id) = &device_id {
+    if !backend::outputs()?.iter().any(|d| &d.id == id) {
+      return Err(format!("unknown audio output: {}", id));
+    }
+  }
+  {
+    let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    settings.audio_output = device_id.clone();
+    settings.save(&state.config_dir)?;
+  }
+  info!(device = ?device_id, "audio output selected");
+  restart_output(app, &state)
+}
+
+/// Play the music on a second device along with the selected output, e.g. a monitor or another
+/// room, held back by `delay` seconds to line up with it (negative holds back the selected output
+/// instead); `None` plays on the selected output alone. Kept in the settings, heard at once.
+#[tauri::command]
+pub fn set_second_output(app: AppHandle, state: State<'_, AppState>, output: Option<SecondOutput>) -> Result<(), String> {
+  ensure_unlocked(&state, "set_second_output")?;
+  if let Some(output) = &output {
+    if !(output.delay.is_finite() && output.delay.abs() <= MAX_OUTPUT_DELAY) {
+      return Err(format!("output delay must be within {} seconds: {}", MAX_OUTPUT_DELAY, output.delay));
+    }
+    if !backend::outputs()?.iter().any(|d| d.id == output.device) {
+      return Err(format!("unknown audio output: {}", output.device));
+    }
+  }
+  {
+    let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    settings.second_output = output.clone();
+    settings.save(&state.config_dir)?;
+  }
+  info!(output = ?output, "second audio output set");
+  restart_output(app, &state)
+}
+
+#[test]
+pub fn test_parse_pcm_list() {
+  let listing = "00-00: ALC892 Analog : ALC892 Analog : playback 1 : capture 1\n00-03: HDMI 0 : HDMI 0 : playback 1\n01-00: USB Audio : USB Audio : capture 1\n";
+  let cards = |card: u32| (card == 0).then(|| "PCH".to_string());
+  assert_eq!(
+    parse_pcm_list(listing, "playback", cards),
+    vec![AudioDevice { id: "plughw:0,0".to_string(), name: "PCH - ALC892 Analog".to_string() }, AudioDevice { id: "plughw:0,3".to_string(), name: "PCH - HDMI 0".to_string() }]
+  );
+  assert_eq!(parse_pcm_list(listing, "capture", cards).iter().map(|d| d.id.as_str()).collect::<Vec<_>>(), vec!["plughw:0,0", "plughw:1,0"]);
+}
@@ -0,0 +1,62 @@
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// Companion files picked up as a song's background when none is assigned, in lookup order.
+pub(crate) const BACKGROUND_SUFFIXES: [&str; 6] = ["_background.jpg", "_background.jpeg", "_background.png", "_background.webp", "_background.mp4", "_background.webm"];
+
+// file types the webview can show as a background
+const BACKGROUND_EXT: [&str; 7] = [".jpg", ".jpeg", ".png", ".webp", ".gif", ".mp4", ".webm"];
+
+fn is_background_file(path: &str) -> bool {
+  let lower = path.to_ascii_lowercase();
+  BACKGROUND_EXT.iter().any(|ext| lower.ends_with(ext))
+}
+
+/// Background image or video of the song at `path`, relative to the library root: the one
+/// assigned with `set_song_background`, else a `song_background.jpg` (or .png, .mp4, ...) companion.
+/// Missing files are skipped.
+pub(crate) fn find_background(state: &AppState, path: &str) -> Result<Option<String>, String> {
+  let assigned = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.song_backgrounds.get(path).cloned();
+  if let Some(assigned) = assigned {
+    if state.resolve(&assigned).is_some() {
+      return Ok(Some(assigned));
+    }
+    warn!(%path, background = %assigned, "assigned background not found");
+  }
+  Ok(BACKGROUND_SUFFIXES.iter().map(|suffix| with_extension(path, suffix)).find(|candidate| state.resolve(candidate).is_some()))
+}
+
+/// Assign a background image or video (a path relative to the library root) to a song, or clear
+/// the assignment when `background` is omitted, and persist settings.
+#[tauri::command]
+pub fn set_song_background(state: State<'_, AppState>, path: String, background: Option<String>) -> Result<(), String> {
+  ensure_unlocked(&state, "set_song_background")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  if let Some(background) = &background {
+    if !is_background_file(background) {
+      return Err(format!("unsupported background file: {}", background));
+    }
+    if state.resolve(background).is_none() {
+      return Err(format!("background not found: {}", background));
+    }
+  }
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  match background {
+    Some(background) => settings.song_backgrounds.insert(path, background),
+    None => settings.song_backgrounds.remove(&path),
+  };
+  settings.save(&state.config_dir)
+}
+
+#[test]
+pub fn test_is_background_file() {
+  assert!(is_background_file("covers/Song.JPG"));
+  assert!(is_background_file("song_background.webm"));
+  assert!(!is_background_file("song.mp3"));
+  assert!(!is_background_file("song.lrc"));
+}
@@ -0,0 +1,37 @@
+use tauri::ipc::Response;
+use tauri::State;
+
+use klok_core::melody::detect_melody_track;
+use klok_core::midi::{apply_sustain, ConfidenceSource, OverlapPolicy};
+use klok_core::synth::{self, encode_wav, SAMPLE_RATE};
+
+use crate::commands::load_midi::find_vocal_midi;
+use crate::commands::midi_cache::load_midi_tracks_cached;
+use crate::commands::timeout::run_blocking;
+use crate::AppState;
+
+// silence kept after the last note of a rendered backing
+const BACKING_TAIL: f64 = 2.0;
+
+/// Synthesize a backing track, as WAV bytes, from the multi-track MIDI of `path` (e.g. a `.kar`
+/// file) with the melody track left out, so songs without audio can still be rehearsed.
+/// Tracks are played by the built-in synth; percussion becomes noise bursts.
+#[tauri::command]
+pub async fn render_backing(state: State<'_, AppState>, path: String) -> Result<Response, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let st = state.inner().clone();
+  let wav = run_blocking(&state, "render_backing", move |_| {
+    let midi = find_vocal_midi(&st, &path).ok_or_else(|| format!("no midi found for provided path: {}", path))?;
+    let bytes = std::fs::read(&midi).map_err(|e| format!("failed to read {}: {}", midi.display(), e))?;
+    let mut tracks = load_midi_tracks_cached(&st, &midi, &bytes, ConfidenceSource::None, OverlapPolicy::default())?;
+    tracks.iter_mut().for_each(apply_sustain);
+    let melody = detect_melody_track(&tracks);
+    let samples = synth::render_backing(&tracks, melody, BACKING_TAIL);
+    info!(path = %midi.display(), ?melody, seconds = samples.len() as f64 / SAMPLE_RATE as f64, "rendered backing");
+    Ok(encode_wav(&samples, SAMPLE_RATE))
+  })
+  .await?;
+  Ok(Response::new(wav))
+}
@@ -0,0 +1,37 @@
+use tauri::State;
+
+use klok_core::midi::encode_click_midi;
+use klok_core::phrases::{estimate_tempo, estimate_tonic};
+
+use crate::commands::get_metadata::get_duration_and_artist;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::commands::storage::write_export;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// Write a click-track MIDI (`song_click.mid`) for musicians re-recording the backing track.
+/// The tempo is estimated from the vocal MIDI unless `bpm` is given, and the key signature comes
+/// from the melody. With `include_melody` the vocal notes are added as a second track.
+/// `target` names an export target to write to instead of the library. Returns the path of the
+/// written file.
+#[tauri::command]
+pub fn export_click_track(state: State<'_, AppState>, path: String, bpm: Option<f64>, include_melody: Option<bool>, target: Option<String>) -> Result<String, String> {
+  ensure_unlocked(&state, "export_click_track")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let notes = find_vocal_notes(&state, &path)?.unwrap_or_default();
+  let bpm = bpm.or_else(|| estimate_tempo(&notes)).ok_or_else(|| format!("no tempo given and too few vocal notes to estimate one for {}", path))?;
+  let duration = state
+    .resolve(&path)
+    .and_then(get_duration_and_artist)
+    .map(|(d, _)| d)
+    .unwrap_or_else(|| notes.iter().map(|n| n.start + n.duration).fold(0.0, f64::max));
+
+  let melody = include_melody.unwrap_or(false).then_some(notes.as_slice());
+  let bytes = encode_click_midi(bpm, duration, estimate_tonic(&notes), melody)?;
+  let written = write_export(&state, target.as_deref(), &with_extension(&path, "_click.mid"), &bytes)?;
+  info!(path = %written, bpm, duration, "exported click track");
+  Ok(written)
+}
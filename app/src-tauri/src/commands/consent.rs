@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::settings::Feature;
+use crate::AppState;
+
+const FEATURES: [Feature; 3] = [Feature::Microphone, Feature::Server, Feature::Network];
+
+fn feature_name(feature: Feature) -> &'static str {
+  match feature {
+    Feature::Microphone => "microphone",
+    Feature::Server => "server",
+    Feature::Network => "network",
+  }
+}
+
+/// Fail with `consent: <command> needs <feature> permission` unless the user granted `feature`.
+/// Commands that use the microphone, serve or reach the network call this first, so klok runs
+/// fully offline until allowed; the frontend can prompt on the `consent:` prefix.
+pub(crate) fn ensure_consent(state: &AppState, feature: Feature, command: &str) -> Result<(), String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  if !settings.consents.contains(&feature) {
+    info!(%command, feature = feature_name(feature), "refused without consent");
+    return Err(format!("consent: {} needs {} permission", command, feature_name(feature)));
+  }
+  Ok(())
+}
+
+/// Whether each feature is granted, e.g. `{"microphone": false, "server": false, "network": true}`.
+#[tauri::command]
+pub fn get_consents(state: State<'_, AppState>) -> Result<BTreeMap<Feature, bool>, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  Ok(FEATURES.iter().map(|f| (*f, settings.consents.contains(f))).collect())
+}
+
+/// Allow `feature` and persist settings.
+#[tauri::command]
+pub fn grant_consent(state: State<'_, AppState>, feature: Feature) -> Result<(), String> {
+  ensure_unlocked(&state, "grant_consent")?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  if settings.consents.insert(feature) {
+    info!(feature = feature_name(feature), "consent granted");
+    settings.save(&state.config_dir)?;
+  }
+  Ok(())
+}
+
+/// Withdraw `feature` and persist settings. Allowed in kiosk mode too, since it only takes
+/// permissions away.
+#[tauri::command]
+pub fn revoke_consent(state: State<'_, AppState>, feature: Feature) -> Result<(), String> {
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  if settings.consents.remove(&feature) {
+    info!(feature = feature_name(feature), "consent revoked");
+    settings.save(&state.config_dir)?;
+  }
+  Ok(())
+}
+
+#[test]
+pub fn test_ensure_consent() {
+  use std::sync::{Arc, Mutex};
+
+  let mut settings = crate::settings::Settings::default();
+  settings.consents.insert(Feature::Network);
+  let state = AppState { settings: Arc::new(Mutex::new(settings)), ..Default::default() };
+  assert!(ensure_consent(&state, Feature::Network, "fetch_lyrics").is_ok());
+  assert_eq!(ensure_consent(&state, Feature::Microphone, "record").unwrap_err(), "consent: record needs microphone permission");
+}
@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use std::path::Path;
+use tauri::State;
+
+use klok_core::lrc::format_lrc;
+use klok_core::lyrics::{format_srt, format_vtt};
+
+use crate::commands::get_metadata::{find_lyrics, read_lyrics_file};
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// Formats `convert_lyrics` can write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LyricsFormat {
+  Lrc,
+  Srt,
+  Vtt,
+}
+
+impl LyricsFormat {
+  fn extension(self) -> &'static str {
+    match self {
+      LyricsFormat::Lrc => ".lrc",
+      LyricsFormat::Srt => ".srt",
+      LyricsFormat::Vtt => ".vtt",
+    }
+  }
+}
+
+/// Convert lyrics to another format, e.g. for video editors, and write them next to the source.
+/// `input` is a lyrics file (`song.lrc`, `song.ass`, ...) or a song, whose lyrics are found as in
+/// `get_metadata`. Returns the path of the written file.
+#[tauri::command]
+pub fn convert_lyrics(state: State<'_, AppState>, input: String, output_format: LyricsFormat) -> Result<String, String> {
+  ensure_unlocked(&state, "convert_lyrics")?;
+  if input.is_empty() {
+    return Err("input argument is empty".to_string());
+  }
+  let (base, lines) = match read_lyrics_file(&state, &input)? {
+    Some((ext, lines)) => (input[..input.len() - ext.len()].to_string(), lines),
+    None => {
+      let lines = find_lyrics(&state, &input)?.ok_or_else(|| format!("lyrics file not found for provided path: {}", input))?;
+      (with_extension(&input, ""), lines)
+    }
+  };
+
+  let target = format!("{}{}", base, output_format.extension());
+  if target == input {
+    return Err(format!("{} is already {:?}", input, output_format));
+  }
+  let content = match output_format {
+    LyricsFormat::Lrc => {
+      let title = Path::new(&base).file_name().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+      format_lrc(&lines, &[("ti", title), ("re", "klok".to_string())])
+    }
+    LyricsFormat::Srt => format_srt(&lines),
+    LyricsFormat::Vtt => format_vtt(&lines),
+  };
+
+  let resolved = state.res_dir.join(&target);
+  std::fs::write(&resolved, content).map_err(|e| format!("failed to write {}: {}", resolved.display(), e))?;
+  info!(from = %input, to = %target, lines = lines.len(), "converted lyrics");
+  Ok(target)
+}
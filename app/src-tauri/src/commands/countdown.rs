@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use klok_core::lyrics::LyricLine;
+use klok_core::midi::Note;
+use klok_core::phrases::{lyric_gaps, phrase_gaps};
+
+use crate::commands::get_metadata::find_lyrics;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::settings::CountdownSettings;
+use crate::AppState;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CountdownCue {
+  /// when to show the cue, in seconds
+  pub time: f64,
+  /// number to show: `count`, ..., 2, 1
+  pub count: u32,
+  /// time of the vocal entry the cue counts towards
+  pub entry: f64,
+}
+
+/// Countdown cues before every vocal entry that follows a long instrumental gap.
+/// Gaps come from the vocal MIDI when it exists, otherwise from lyric timing.
+/// Thresholds are the song's override in settings, or the global countdown settings.
+#[tauri::command]
+pub fn get_countdown_cues(state: State<'_, AppState>, path: String) -> Result<Vec<CountdownCue>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.countdown(&path).clone();
+
+  let notes = find_vocal_notes(&state, &path)?.filter(|n| !n.is_empty());
+  let lyrics = if notes.is_none() { find_lyrics(&state, &path)? } else { None };
+  song_countdown_cues(&settings, notes.as_deref(), lyrics.as_deref()).ok_or_else(|| format!("no vocal midi or lyrics found for provided path: {}", path))
+}
+
+/// Countdown cues before long gaps in `notes` when there are any, otherwise in `lyrics` timing.
+/// `None` without either.
+pub(crate) fn song_countdown_cues(settings: &CountdownSettings, notes: Option<&[Note]>, lyrics: Option<&[LyricLine]>) -> Option<Vec<CountdownCue>> {
+  let gaps = match notes {
+    Some(notes) if !notes.is_empty() => {
+      let first = notes.iter().map(|n| n.start).fold(f64::INFINITY, f64::min);
+      let mut gaps = phrase_gaps(notes, settings.min_gap);
+      if first >= settings.min_gap {
+        gaps.insert(0, (0.0, first));
+      }
+      gaps
+    }
+    _ => lyric_gaps(lyrics?, settings.min_gap),
+  };
+  Some(countdown_cues(&gaps, settings))
+}
+
+/// Set (or clear, when `countdown` is omitted) the countdown settings for one song and persist settings.
+#[tauri::command]
+pub fn set_countdown_settings(state: State<'_, AppState>, path: String, countdown: Option<CountdownSettings>) -> Result<(), String> {
+  ensure_unlocked(&state, "set_countdown_settings")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  match countdown {
+    Some(countdown) => settings.song_countdowns.insert(path, countdown),
+    None => settings.song_countdowns.remove(&path),
+  };
+  settings.save(&state.config_dir)
+}
+
+// Cues counting down to each gap end; cues that would fall before the gap start are dropped.
+fn countdown_cues(gaps: &[(f64, f64)], settings: &CountdownSettings) -> Vec<CountdownCue> {
+  let mut cues = Vec::new();
+  for &(start, entry) in gaps {
+    for count in (1..=settings.count).rev() {
+      let time = entry - count as f64 * settings.interval;
+      if time >= start {
+        cues.push(CountdownCue { time, count, entry });
+      }
+    }
+  }
+  cues
+}
+
+#[test]
+pub fn test_countdown_cues() {
+  let line = |time: f64, end: Option<f64>, text: &str| LyricLine { time, end, text: text.to_string(), ..Default::default() };
+  let lyrics = vec![line(2.0, None, "a"), line(4.0, None, "b"), line(6.0, None, ""), line(20.0, Some(22.0), "c"), line(24.0, None, "d")];
+  let settings = CountdownSettings { min_gap: 8.0, count: 3, interval: 1.0 };
+
+  let gaps = lyric_gaps(&lyrics, settings.min_gap);
+  assert_eq!(gaps, vec![(6.0, 20.0)]);
+  let cues = countdown_cues(&gaps, &settings);
+  assert_eq!(cues.iter().map(|c| (c.time, c.count)).collect::<Vec<_>>(), vec![(17.0, 3), (18.0, 2), (19.0, 1)]);
+
+  // a cue that would land before the gap starts is skipped
+  let cues = countdown_cues(&[(0.0, 2.5)], &settings);
+  assert_eq!(cues.len(), 2);
+}
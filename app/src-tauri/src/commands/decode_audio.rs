@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tauri::ipc::Response;
+use tauri::State;
+
+use klok_core::pcm::{self, Pcm, DECODABLE_EXTENSIONS};
+
+use crate::commands::timeout::run_blocking;
+use crate::AppState;
+
+// bytes of decoded samples kept for re-use, about 12 minutes of stereo 44.1 kHz audio
+const PCM_CACHE_BYTES: usize = 256 << 20;
+
+// A decoded file, and the file as it was when decoded.
+#[derive(Debug)]
+struct CachedPcm {
+  path: PathBuf,
+  modified: Option<SystemTime>,
+  len: u64,
+  pcm: Arc<Pcm>,
+}
+
+/// Recently decoded files, so looping a practice section or redrawing a waveform doesn't decode
+/// them again. Holds up to `capacity` bytes of samples, dropping the least recently used first.
+#[derive(Debug)]
+pub struct PcmCache {
+  capacity: usize,
+  // least recently used first
+  entries: Vec<CachedPcm>,
+}
+
+impl Default for PcmCache {
+  fn default() -> Self {
+    PcmCache::new(PCM_CACHE_BYTES)
+  }
+}
+
+fn pcm_bytes(pcm: &Pcm) -> usize {
+  pcm.samples.len() * std::mem::size_of::<f32>()
+}
+
+impl PcmCache {
+  pub fn new(capacity: usize) -> PcmCache {
+    PcmCache { capacity, entries: Vec::new() }
+  }
+
+  // The decoded `path`, now the most recently used, unless the file changed since.
+  fn get(&mut self, path: &Path, modified: Option<SystemTime>, len: u64) -> Option<Arc<Pcm>> {
+    let at = self.entries.iter().position(|e| e.path == path)?;
+    let entry = self.entries.remove(at);
+    if entry.modified != modified || entry.len != len {
+      return None;
+    }
+    let pcm = entry.pcm.clone();
+    self.entries.push(entry);
+    Some(pcm)
+  }
+
+  fn insert(&mut self, path: &Path, modified: Option<SystemTime>, len: u64, pcm: Arc<Pcm>) {
+    self.entries.retain(|e| e.path != path);
+    if pcm_bytes(&pcm) > self.capacity {
+      return;
+    }
+    self.entries.push(CachedPcm { path: path.to_path_buf(), modified, len, pcm });
+    let mut size: usize = self.entries.iter().map(|e| pcm_bytes(&e.pcm)).sum();
+    while size > self.capacity {
+      size -= pcm_bytes(&self.entries.remove(0).pcm);
+    }
+  }
+}
+
+// The extension of `resolved` if it can be decoded, before the file is read.
+pub(crate) fn decodable_extension(resolved: &Path) -> Result<String, String> {
+  let ext = resolved.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+  if !DECODABLE_EXTENSIONS.contains(&ext.as_str()) {
+    return Err(format!("can't decode {}: only wav files can be decoded", resolved.display()));
+  }
+  Ok(ext)
+}
+
+/// Decode the library audio file `resolved`, which must be a WAV file, or take it from the cache
+/// of `state` while the file is unchanged.
+pub(crate) fn decode_file(state: &AppState, resolved: &Path) -> Result<Arc<Pcm>, String> {
+  let ext = decodable_extension(resolved)?;
+  let meta = std::fs::metadata(resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  let (modified, len) = (meta.modified().ok(), meta.len());
+  let cache = || state.pcm_cache.lock().map_err(|e| format!("pcm cache lock poisoned: {}", e));
+  if let Some(pcm) = cache()?.get(resolved, modified, len) {
+    return Ok(pcm);
+  }
+  let bytes = std::fs::read(resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  let pcm = Arc::new(pcm::decode_audio(&bytes, &ext).map_err(|e| format!("failed to decode {}: {}", resolved.display(), e))?);
+  cache()?.insert(resolved, modified, len, pcm.clone());
+  Ok(pcm)
+}
+
+/// Decode the WAV file `path` to raw PCM, `duration` seconds from `start` (the whole file by
+/// default), as `Pcm::to_bytes`: sample rate and channel count (u32), then interleaved f32 samples.
+/// Lets analysis and custom visualizations work without the WebAudio decoder.
+#[tauri::command]
+pub async fn decode_audio(state: State<'_, AppState>, path: String, start: Option<f64>, duration: Option<f64>) -> Result<Response, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+  let st = state.inner().clone();
+  let bytes = run_blocking(&state, "decode_audio", move |_| {
+    let pcm = decode_file(&st, &resolved)?;
+    Ok(pcm.slice(start.unwrap_or(0.0), duration).to_bytes())
+  })
+  .await?;
+  Ok(Response::new(bytes))
+}
+
+#[test]
+pub fn test_decodable_extension() {
+  assert_eq!(decodable_extension(Path::new("songs/song_non_vocals.WAV")).unwrap(), "wav");
+  assert!(decodable_extension(Path::new("songs/song.mp3")).unwrap_err().contains("only wav"));
+  assert!(decodable_extension(Path::new("songs/song")).is_err());
+}
+
+#[test]
+pub fn test_pcm_cache() {
+  let pcm = |frames: usize| Arc::new(Pcm { sample_rate: 10, channels: 1, samples: vec![0.0; frames] });
+  let (a, b, c) = (Path::new("a.wav"), Path::new("b.wav"), Path::new("c.wav"));
+  // room for 20 samples
+  let mut cache = PcmCache::new(80);
+  cache.insert(a, None, 1, pcm(8));
+  cache.insert(b, None, 1, pcm(8));
+  assert!(cache.get(a, None, 1).is_some());
+  // over the cap: b, used least recently, goes
+  cache.insert(c, None, 1, pcm(8));
+  assert!(cache.get(b, None, 1).is_none());
+  assert!(cache.get(a, None, 1).is_some() && cache.get(c, None, 1).is_some());
+  // a changed file is decoded again
+  assert!(cache.get(a, None, 2).is_none());
+  assert!(cache.get(a, None, 1).is_none());
+  // too big to keep
+  cache.insert(b, None, 1, pcm(21));
+  assert!(cache.get(b, None, 1).is_none());
+  assert_eq!(cache.entries.len(), 1);
+}
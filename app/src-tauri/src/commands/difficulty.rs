@@ -0,0 +1,36 @@
+use std::time::UNIX_EPOCH;
+use tauri::State;
+
+use klok_core::difficulty::{note_stats, rate_difficulty, MidiStats, SongDifficulty};
+
+use crate::commands::load_midi::{find_vocal_midi, find_vocal_notes};
+use crate::AppState;
+
+/// Difficulty of the song at playlist url `path`, from `cached` when the vocal MIDI hasn't changed
+/// since. Returns whether it had to be computed, so callers can store it.
+pub(crate) fn song_difficulty(state: &AppState, path: &str, cached: Option<&SongDifficulty>) -> Result<Option<(SongDifficulty, bool)>, String> {
+  let Some(midi) = find_vocal_midi(state, path) else {
+    return Ok(None);
+  };
+  let modified = std::fs::metadata(&midi)
+    .and_then(|m| m.modified())
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |d| d.as_secs());
+  if let Some(cached) = cached.filter(|c| c.midi_modified == modified) {
+    return Ok(Some((cached.clone(), false)));
+  }
+  let notes = find_vocal_notes(state, path)?.unwrap_or_default();
+  Ok(rate_difficulty(&notes, modified).map(|d| (d, true)))
+}
+
+/// Note statistics (range, note count, phrase length, density over time) of the vocal MIDI of
+/// `path`, for showing with its difficulty in the library.
+#[tauri::command]
+pub fn midi_stats(state: State<'_, AppState>, path: String) -> Result<MidiStats, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let notes = find_vocal_notes(&state, &path)?.ok_or_else(|| format!("no vocal midi found for provided path: {}", path))?;
+  note_stats(&notes).ok_or_else(|| format!("vocal midi has no notes: {}", path))
+}
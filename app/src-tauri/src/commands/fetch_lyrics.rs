@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use klok_core::lrc::parse_lrc;
+use klok_core::lyrics::{merge_duplicate_timestamps, LyricLine};
+
+use crate::commands::get_metadata::find_lyrics;
+use crate::commands::lyrics_provider::{fetch_with_providers, Candidate, LyricsProvider, SongQuery};
+use crate::commands::timeout::run_async;
+use crate::commands::{sanitize_file_name, with_extension};
+use crate::AppState;
+
+const LRCLIB_API: &str = "https://lrclib.net/api";
+pub(crate) const USER_AGENT: &str = concat!("klok/", env!("CARGO_PKG_VERSION"), " (https://github.com/clouds-game/klok)");
+// lyrics fetched for songs outside res are cached here, under `AppState.config_dir`
+const CACHE_DIR: &str = "lyrics_cache";
+
+/// [LRCLIB](https://lrclib.net), an open database of synced lyrics.
+pub struct Lrclib;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LrclibRecord {
+  id: u64,
+  #[serde(default)]
+  track_name: String,
+  #[serde(default)]
+  artist_name: String,
+  /// seconds
+  duration: Option<f64>,
+  #[serde(default)]
+  instrumental: bool,
+  synced_lyrics: Option<String>,
+}
+
+impl LyricsProvider for Lrclib {
+  const NAME: &'static str = "lrclib";
+
+  async fn search(&self, query: &SongQuery) -> Result<Vec<Candidate>, String> {
+    let records: Vec<LrclibRecord> = reqwest::Client::new()
+      .get(format!("{}/search", LRCLIB_API))
+      .header(reqwest::header::USER_AGENT, USER_AGENT)
+      .query(&[("track_name", query.title.as_str()), ("artist_name", query.artist.as_str())])
+      .send()
+      .await
+      .and_then(|r| r.error_for_status())
+      .map_err(|e| format!("lrclib search failed: {}", e))?
+      .json()
+      .await
+      .map_err(|e| format!("invalid lrclib response: {}", e))?;
+    Ok(
+      records
+        .into_iter()
+        .filter(|r| !r.instrumental && r.synced_lyrics.is_some())
+        .map(|r| Candidate { id: r.id.to_string(), title: r.track_name, artists: vec![r.artist_name], duration: r.duration })
+        .collect(),
+    )
+  }
+
+  async fn fetch(&self, candidate: &Candidate) -> Result<Option<Vec<LyricLine>>, String> {
+    let record: LrclibRecord = reqwest::Client::new()
+      .get(format!("{}/get/{}", LRCLIB_API, candidate.id))
+      .header(reqwest::header::USER_AGENT, USER_AGENT)
+      .send()
+      .await
+      .and_then(|r| r.error_for_status())
+      .map_err(|e| format!("lrclib request failed: {}", e))?
+      .json()
+      .await
+      .map_err(|e| format!("invalid lrclib response: {}", e))?;
+    Ok(record.synced_lyrics.filter(|s| !s.trim().is_empty()).map(|s| merge_duplicate_timestamps(parse_lrc(&s))))
+  }
+}
+
+/// Fetch synced lyrics from LRCLIB when there is no local lyrics file.
+/// With `path` (the song in res), existing local lyrics are returned as-is and fetched lyrics are
+/// cached as the song's `.lrc`; otherwise they are cached under `lyrics_cache/` in the config dir.
+/// Returns `None` when LRCLIB has no synced lyrics for the song.
+#[tauri::command]
+pub async fn fetch_lyrics(state: State<'_, AppState>, title: String, artist: String, duration: Option<f64>, path: Option<String>) -> Result<Option<Vec<LyricLine>>, String> {
+  run_async(&state, "fetch_lyrics", fetch_with_providers(&state, &[Lrclib::NAME], SongQuery { title, artist, duration }, path.as_deref())).await
+}
+
+/// Local or previously fetched lyrics for a song, plus the file fetched lyrics should be cached to:
+/// the song's `.lrc` in res when `path` is given, else `lyrics_cache/<artist> - <title>.lrc`.
+pub(crate) fn find_cached_lyrics(state: &AppState, title: &str, artist: &str, path: Option<&str>) -> Result<(Option<Vec<LyricLine>>, PathBuf), String> {
+  if let Some(path) = path {
+    if let Some(lyrics) = find_lyrics(state, path)? {
+      return Ok((Some(lyrics), state.res_dir.join(with_extension(path, ".lrc"))));
+    }
+  }
+  let cache = match path {
+    Some(path) => state.res_dir.join(with_extension(path, ".lrc")),
+    None => cache_path(state, title, artist),
+  };
+  match std::fs::read_to_string(&cache) {
+    Ok(content) => {
+      debug!(path = %cache.display(), "using cached lyrics");
+      Ok((Some(merge_duplicate_timestamps(parse_lrc(&content))), cache))
+    }
+    Err(_) => Ok((None, cache)),
+  }
+}
+
+/// Write fetched LRC content to `cache`; failures are only logged.
+pub(crate) fn store_cached_lyrics(cache: &Path, content: &str) {
+  if let Some(dir) = cache.parent() {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  if let Err(e) = std::fs::write(cache, content) {
+    warn!(path = %cache.display(), error = %e, "failed to cache fetched lyrics");
+  }
+}
+
+fn cache_path(state: &AppState, title: &str, artist: &str) -> PathBuf {
+  state.config_dir.join(CACHE_DIR).join(format!("{}.lrc", sanitize_file_name(&format!("{} - {}", artist, title))))
+}
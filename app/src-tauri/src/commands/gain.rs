@@ -0,0 +1,83 @@
+use lofty::{ItemKey, Probe, TaggedFileExt};
+use std::path::Path;
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::settings::GainSettings;
+use crate::AppState;
+
+// ReplayGain 2 reference loudness: a track tagged with gain `g` measures `REFERENCE - g`
+const REPLAYGAIN_REFERENCE: f64 = -18.0;
+
+/// Linear gain that brings a song measuring `loudness` dBFS to the target, within the limits.
+fn stage_gain(settings: &GainSettings, loudness: f64) -> f64 {
+  if !settings.enabled {
+    return 1.0;
+  }
+  let db = (settings.target - loudness).clamp(-settings.max_cut.abs(), settings.max_boost.abs());
+  10f64.powf(db / 20.0)
+}
+
+// Loudness from the ReplayGain track gain tag (e.g. "-6.20 dB") of the audio at `path`.
+fn replaygain_loudness(path: &Path) -> Option<f64> {
+  let tagged = Probe::open(path).ok()?.read().ok()?;
+  let value = tagged.primary_tag()?.get_string(&ItemKey::ReplayGainTrackGain)?;
+  let gain: f64 = value.trim().trim_end_matches("dB").trim().parse().ok()?;
+  Some(REPLAYGAIN_REFERENCE - gain)
+}
+
+/// Gain to play the song at `path` with so songs play at a consistent level: from its loudness
+/// stored by [`set_song_loudness`], else its ReplayGain tag. `None` when neither is known, in
+/// which case the frontend measures the song and reports it. Always 1 with gain staging off.
+#[tauri::command]
+pub fn song_gain(state: State<'_, AppState>, path: String) -> Result<Option<f64>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let (settings, cached) = {
+    let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    (settings.gain.clone(), settings.song_loudness.get(&path).copied())
+  };
+  if !settings.enabled {
+    return Ok(Some(1.0));
+  }
+  let loudness = cached.or_else(|| state.resolve(&path).as_deref().and_then(replaygain_loudness));
+  Ok(loudness.map(|l| stage_gain(&settings, l)))
+}
+
+/// Store the loudness (dBFS, measured by the frontend) of the song at `path` and return the gain
+/// it plays with, see [`song_gain`].
+#[tauri::command]
+pub fn set_song_loudness(state: State<'_, AppState>, path: String, loudness: f64) -> Result<f64, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  if !loudness.is_finite() {
+    return Err(format!("invalid loudness for {}: {}", path, loudness));
+  }
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.song_loudness.insert(path.clone(), loudness);
+  settings.save(&state.config_dir)?;
+  let gain = stage_gain(&settings.gain, loudness);
+  info!(%path, loudness, gain, "stored song loudness");
+  Ok(gain)
+}
+
+/// Replace the gain staging settings and persist settings.
+#[tauri::command]
+pub fn set_gain_settings(state: State<'_, AppState>, gain: GainSettings) -> Result<(), String> {
+  ensure_unlocked(&state, "set_gain_settings")?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.gain = gain;
+  settings.save(&state.config_dir)
+}
+
+#[test]
+pub fn test_stage_gain() {
+  let settings = GainSettings::default();
+  assert!((stage_gain(&settings, -18.0) - 1.0).abs() < 1e-9);
+  // a loud track goes down 6 dB, a quiet one is boosted no more than the limit
+  assert!((stage_gain(&settings, -12.0) - 0.501).abs() < 1e-3);
+  assert!((stage_gain(&settings, -40.0) - 10f64.powf(6.0 / 20.0)).abs() < 1e-9);
+  assert_eq!(stage_gain(&GainSettings { enabled: false, ..settings }, -40.0), 1.0);
+}
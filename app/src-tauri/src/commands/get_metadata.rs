@@ -1,30 +1,60 @@
 use std::path::Path;
-use tauri::State;
+use tauri::{AppHandle, State};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use lofty::{Accessor, Probe, AudioFile, TaggedFileExt};
 
-use crate::commands::with_extension;
-
+use klok_core::language::detect_language;
+use klok_core::lyrics::{merge_translation, LyricLine, LyricsParser, LYRICS_FORMATS};
 
-#[derive(Serialize)]
-struct LyricLine {
-  time: f64,
-  text: String,
-}
+use crate::commands::background::find_background;
+use crate::commands::countdown::{song_countdown_cues, CountdownCue};
+use crate::commands::library::{check_library, load_offline, store_offline};
+use crate::commands::load_lyrics::{finish_lyrics, song_lyrics, LyricsError};
+use crate::commands::load_midi::find_vocal_notes;
+use crate::commands::timeout::run_blocking;
+use crate::commands::with_extension;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Metadata {
   title: String,
   artist: String,
   url: String,
   duration: f64,
+  /// detected lyrics language (ISO 639-1), absent without lyrics
+  #[serde(skip_serializing_if = "Option::is_none")]
+  language: Option<String>,
   lyrics: Vec<LyricLine>,
+  /// "3-2-1" cues before vocal entries after long instrumental gaps
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  countdown: Vec<CountdownCue>,
+  /// background image or video, relative to the library root; served over the `klok` scheme
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  background: Option<String>,
 }
 
 // Return a minimal Metadata object matching the frontend `Metadata` type.
+// `translation` selects the language of a `song.<lang>.lrc` companion merged into `LyricLine.translation`.
+// Metadata read from a network library is cached and served while the library is unreachable.
 #[tauri::command]
-pub fn get_metadata(state: State<'_, crate::AppState>, path: String) -> Result<Metadata, String> {
+pub async fn get_metadata(app: AppHandle, state: State<'_, crate::AppState>, path: String, translation: Option<String>) -> Result<Metadata, String> {
+  let st = state.inner().clone();
+  run_blocking(&state, "get_metadata", move |_| {
+    let cache_key = format!("metadata {} {}", path, translation.as_deref().unwrap_or_default());
+    let status = check_library(&app, &st)?;
+    if !status.available {
+      return load_offline(&st, &cache_key).ok_or_else(|| format!("library unavailable: {}", status.root));
+    }
+    let metadata = read_metadata(&st, path, translation)?;
+    if status.network {
+      store_offline(&st, &cache_key, &metadata);
+    }
+    Ok(metadata)
+  })
+  .await
+}
+
+fn read_metadata(state: &crate::AppState, path: String, translation: Option<String>) -> Result<Metadata, String> {
   debug!(%path, "get_metadata called");
   // use provided path, fallback to bundled resource when empty
   if path.is_empty() {
@@ -37,47 +67,30 @@ pub fn get_metadata(state: State<'_, crate::AppState>, path: String) -> Result<M
     .map(|s| s.to_string())
     .ok_or_else(|| "failed to extract title from path".to_string())?;
 
-  // Attempt to locate a corresponding .lrc file and parse lyrics from it.
-  let mut lyrics: Vec<LyricLine> = Vec::new();
-
-  // no longer using a helper closure here
-
-  let lrc_path = with_extension(&path, ".lrc");
-
-  // Build candidate paths
-  let lrc_resolved_path = state.resolve(lrc_path);
-
-  // Try to read any candidate; if a candidate exists but fails to read, return an error.
-  let lrc_content = if let Some(lrc_resolved_path) = lrc_resolved_path {
-    debug!(lrc_resolved_path = %lrc_resolved_path.display(), "resolved lrc path");
-    match std::fs::read_to_string(&lrc_resolved_path) {
-      Ok(s) => {
-        Some(s)
-      }
-      Err(e) => {
-        error!(lrc_resolved_path = %lrc_resolved_path.display(), error = %e, "failed to read candidate");
-        return Err(format!("failed to read {}: {}", lrc_resolved_path.display(), e));
-      }
-    }
-  } else {
+  // Suggest breath marks and line ends from phrase gaps in the vocal MIDI, when the pipeline produced one
+  let notes = find_vocal_notes(state, &path).unwrap_or_else(|e| {
+    warn!(error = %e, "failed to load vocal midi for breath marks");
     None
+  });
+  let mut lyrics = match song_lyrics(state, &path, translation.as_deref(), notes.as_deref()) {
+    Ok(lyrics) => lyrics,
+    // If the caller explicitly passed an .lrc path and we couldn't find it, return error
+    Err(LyricsError::NotFound(_)) if path.ends_with(".lrc") => return Err(format!(".lrc file not found for provided path: {}", path)),
+    Err(LyricsError::NotFound(_)) => Vec::new(),
+    Err(e) => return Err(e.to_string()),
   };
 
-  if let Some(ref s) = lrc_content {
-    lyrics = parse_lrc(s);
-    info!(lines = lyrics.len(), "parsed lrc lines");
-  }
+  let language = detect_language(&lyrics);
 
-  // If the caller explicitly passed an .lrc path and we couldn't find it, return error
-  if path.ends_with(".lrc") && lrc_content.is_none() {
-    return Err(format!(".lrc file not found for provided path: {}", path));
-  }
+  // Cue the singer after long instrumental gaps; lyric gaps are only used without MIDI
+  let countdown_settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.countdown(&path).clone();
+  let countdown = song_countdown_cues(&countdown_settings, notes.as_deref(), Some(&lyrics)).unwrap_or_default();
 
   // If no lyrics found, fallback to small sample
   if lyrics.is_empty() {
     lyrics = vec![
-      LyricLine { time: 0.0, text: title.to_string() },
-      LyricLine { time: 1.0, text: "暂无歌词".to_string() },
+      LyricLine { time: 0.0, text: title.to_string(), ..Default::default() },
+      LyricLine { time: 1.0, text: "暂无歌词".to_string(), ..Default::default() },
     ];
   }
 
@@ -93,54 +106,108 @@ pub fn get_metadata(state: State<'_, crate::AppState>, path: String) -> Result<M
     }
   }
 
-  Ok(Metadata { title, artist, url: path, duration: duration_secs, lyrics })
+  finish_lyrics(state, &mut lyrics, notes.as_deref(), duration_secs)?;
+
+  let background = find_background(state, &path)?;
+
+  Ok(Metadata { title, artist, url: path, duration: duration_secs, language, lyrics, countdown, background })
 }
 
-// Parse LRC content into a vector of LyricLine. Handles multiple timestamps per line.
-#[instrument(level = "debug", skip(content))]
-fn parse_lrc(content: &str) -> Vec<LyricLine> {
-  let mut lyrics: Vec<LyricLine> = Vec::new();
+// Read and parse one lyrics file with the parser for its format.
+fn read_lyrics(resolved: &Path, ext: &str, parse: LyricsParser) -> Result<Vec<LyricLine>, String> {
+  debug!(resolved = %resolved.display(), "resolved lyrics path");
+  let content = std::fs::read(resolved).map_err(|e| {
+    error!(resolved = %resolved.display(), error = %e, "failed to read candidate");
+    format!("failed to read {}: {}", resolved.display(), e)
+  })?;
+  let lyrics = parse(&content).map_err(|e| format!("failed to parse {}: {}", resolved.display(), e))?;
+  info!(lines = lyrics.len(), %ext, "parsed lyrics lines");
+  Ok(lyrics)
+}
 
-  for raw_line in content.lines() {
-    let line = raw_line.trim();
-    if line.is_empty() {
-      continue;
+/// Locate a lyrics file next to `path` (see `LYRICS_FORMATS` for the lookup order) and parse it.
+/// Returns `Ok(None)` when no lyrics file exists; a lyrics file that exists but can't be read is an error.
+pub(crate) fn find_lyrics(state: &crate::AppState, path: &str) -> Result<Option<Vec<LyricLine>>, String> {
+  for (ext, parse) in LYRICS_FORMATS {
+    if let Some(resolved) = state.resolve(with_extension(path, ext)) {
+      return read_lyrics(&resolved, ext, parse).map(Some);
     }
+  }
+  Ok(None)
+}
 
-    // collect timestamps at start like [mm:ss.xx][mm:ss.xx]Text
-    let mut times: Vec<f64> = Vec::new();
-    let mut rest = line;
-    while rest.starts_with('[') {
-      if let Some(idx) = rest.find(']') {
-        let stamp = &rest[1..idx];
-        // parse mm:ss.xx (allow seconds with decimals)
-        if let Some(colon) = stamp.find(':') {
-          let mm = &stamp[0..colon];
-          let ss = &stamp[colon + 1..];
-          let mmv: f64 = mm.parse::<f64>().unwrap_or(0.0);
-          let ssv: f64 = ss.parse::<f64>().unwrap_or(0.0);
-          let total = mmv * 60.0 + ssv;
-          times.push(total);
-        }
-        // advance rest past this timestamp
-        rest = &rest[idx + 1..];
-      } else {
-        break;
+/// Parse a lyrics file addressed by its own path, e.g. `song.srt`. Returns the format extension
+/// with the lines, or `Ok(None)` when `path` is not a supported lyrics file or doesn't exist.
+pub(crate) fn read_lyrics_file(state: &crate::AppState, path: &str) -> Result<Option<(&'static str, Vec<LyricLine>)>, String> {
+  let Some((ext, parse)) = LYRICS_FORMATS.into_iter().find(|(ext, _)| path.ends_with(ext)) else {
+    return Ok(None);
+  };
+  match state.resolve(path) {
+    Some(resolved) => read_lyrics(&resolved, ext, parse).map(|lines| Some((ext, lines))),
+    None => Ok(None),
+  }
+}
+
+/// Language-tagged lyrics next to `path`, e.g. `song.zh.lrc` and `song.en.lrc` for `song.mp3`,
+/// sorted by language code.
+fn find_language_variants(state: &crate::AppState, path: &str) -> Vec<(String, std::path::PathBuf, &'static str, LyricsParser)> {
+  let base = state.res_dir.join(with_extension(path, ""));
+  let (Some(dir), Some(stem)) = (base.parent(), base.file_name().and_then(|s| s.to_str())) else {
+    return Vec::new();
+  };
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return Vec::new();
+  };
+  let prefix = format!("{}.", stem);
+
+  let mut variants = Vec::new();
+  for entry in entries.flatten() {
+    let name = entry.file_name();
+    let Some(name) = name.to_str() else {
+      continue;
+    };
+    for (ext, parse) in LYRICS_FORMATS {
+      let lang = name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix(ext));
+      if let Some(lang) = lang.filter(|l| !l.is_empty() && !l.contains('.')) {
+        variants.push((lang.to_string(), entry.path(), ext, parse));
       }
     }
+  }
+  variants.sort_by(|a, b| a.0.cmp(&b.0));
+  variants
+}
 
-    let text = rest.trim().to_string();
-    for t in times {
-      lyrics.push(LyricLine { time: t, text: text.clone() });
+/// Like [`find_lyrics`], and attach translations from a language-tagged companion file.
+/// When there is no untagged lyrics file, the first language variant other than `translation`
+/// is used as the original. `translation` picks the companion language; by default
+/// the first remaining variant is used.
+pub(crate) fn find_bilingual_lyrics(state: &crate::AppState, path: &str, translation: Option<&str>) -> Result<Option<Vec<LyricLine>>, String> {
+  let mut variants = find_language_variants(state, path);
+  let mut lyrics = find_lyrics(state, path)?;
+  if lyrics.is_none() {
+    if let Some(i) = variants.iter().position(|v| Some(v.0.as_str()) != translation) {
+      let (lang, resolved, ext, parse) = variants.remove(i);
+      debug!(%lang, "using language variant as original lyrics");
+      lyrics = Some(read_lyrics(&resolved, ext, parse)?);
     }
   }
+  let Some(mut lyrics) = lyrics else {
+    return Ok(None);
+  };
 
-  lyrics.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
-  lyrics
+  let companion = match translation {
+    Some(lang) => variants.into_iter().find(|v| v.0 == lang),
+    None => variants.into_iter().next(),
+  };
+  if let Some((lang, resolved, ext, parse)) = companion {
+    debug!(%lang, "merging translation lyrics");
+    merge_translation(&mut lyrics, &read_lyrics(&resolved, ext, parse)?);
+  }
+  Ok(Some(lyrics))
 }
 
 // Probe audio candidates derived from `path` and return duration (secs) and artist when found.
-fn get_duration_and_artist<P: AsRef<Path>>(path: P) -> Option<(f64, String)> {
+pub(crate) fn get_duration_and_artist<P: AsRef<Path>>(path: P) -> Option<(f64, String)> {
   let path = path.as_ref();
   if path.exists() {
     match Probe::open(path) {
@@ -175,3 +242,4 @@ fn get_duration_and_artist<P: AsRef<Path>>(path: P) -> Option<(f64, String)> {
     None
   }
 }
+
@@ -0,0 +1,69 @@
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::ipc::Response;
+use tauri::State;
+
+use klok_core::midi::{flatten_tracks, melody_tracks};
+use klok_core::simplify::SimplifyOptions;
+use klok_core::synth::{encode_wav, render_guide, GuideVoice, SAMPLE_RATE};
+
+use crate::commands::load_midi::{find_vocal_midi, read_midi_tracks};
+use crate::commands::midi_cache::hex;
+use crate::commands::timeout::run_blocking;
+use crate::AppState;
+
+// bump when the rendering changes, so guides rendered by an older version are rendered again
+const GUIDE_VERSION: u32 = 1;
+const CACHE_DIR: &str = "cache/guide";
+// silence kept after the last note of a guide
+const GUIDE_TAIL: f64 = 1.0;
+
+// `<key>-<content>.wav`: one entry per song and options, replaced when the melody file changes.
+fn cache_file(state: &AppState, path: &str, voice: GuideVoice, transpose: i32, content: &[u8]) -> (PathBuf, String) {
+  let key = hex(&Sha256::digest(format!("{}|{:?}|{}|{}", path, voice, transpose, GUIDE_VERSION)));
+  let file = state.config_dir.join(CACHE_DIR).join(format!("{}-{}.wav", key, &hex(&Sha256::digest(content))[..16]));
+  (file, key)
+}
+
+// Store `wav`, removing the entries of older versions of the melody. Best effort.
+fn write_cache(file: &Path, key: &str, wav: &[u8]) -> Result<(), String> {
+  let dir = file.parent().ok_or("guide cache has no folder")?;
+  std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+  for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+    if entry.file_name().to_string_lossy().starts_with(&format!("{}-", key)) {
+      std::fs::remove_file(entry.path()).ok();
+    }
+  }
+  std::fs::write(file, wav).map_err(|e| format!("failed to write {}: {}", file.display(), e))
+}
+
+/// Synthesize the melody of `path` (as the player shows it, see `load_midi` with `transpose` and
+/// the default simplification) as a WAV guide track to mix in at low volume while practising,
+/// played by `voice` (default sine). Rendered guides are cached per song and options.
+#[tauri::command]
+pub async fn render_guide_track(state: State<'_, AppState>, path: String, voice: Option<GuideVoice>, transpose: Option<i32>) -> Result<Response, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let st = state.inner().clone();
+  let wav = run_blocking(&state, "render_guide_track", move |_| {
+    let (voice, semitones) = (voice.unwrap_or_default(), transpose.unwrap_or(0));
+    let midi = find_vocal_midi(&st, &path).ok_or_else(|| format!("no midi found for provided path: {}", path))?;
+    let content = std::fs::read(&midi).map_err(|e| format!("failed to read {}: {}", midi.display(), e))?;
+    let (file, key) = cache_file(&st, &path, voice, semitones, &content);
+    if let Ok(wav) = std::fs::read(&file) {
+      debug!(%path, "using cached guide");
+      return Ok(wav);
+    }
+    let tracks = read_midi_tracks(&st, &path, None, None, Some(true), Some(semitones), Some(SimplifyOptions::default()))?;
+    let samples = render_guide(&flatten_tracks(melody_tracks(tracks, None)), voice, GUIDE_TAIL);
+    let wav = encode_wav(&samples, SAMPLE_RATE);
+    info!(%path, ?voice, seconds = samples.len() as f64 / SAMPLE_RATE as f64, "rendered guide");
+    if let Err(e) = write_cache(&file, &key, &wav) {
+      warn!(%path, error = %e, "failed to cache guide");
+    }
+    Ok(wav)
+  })
+  .await?;
+  Ok(Response::new(wav))
+}
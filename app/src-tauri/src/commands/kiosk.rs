@@ -0,0 +1,66 @@
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use tauri::State;
+
+use crate::settings::KioskLock;
+use crate::AppState;
+
+const MIN_PIN_LEN: usize = 4;
+
+fn hash_pin(salt: &str, pin: &str) -> String {
+  Sha256::digest(format!("{}:{}", salt, pin)).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Fail with `kiosk: <command> is locked` while kiosk mode is on. Commands that change settings
+/// or library files call this first, so a public machine can only queue and play songs.
+pub(crate) fn ensure_unlocked(state: &AppState, command: &str) -> Result<(), String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  if settings.kiosk.is_some() {
+    warn!(%command, "refused in kiosk mode");
+    return Err(format!("kiosk: {} is locked", command));
+  }
+  Ok(())
+}
+
+/// Whether kiosk mode is on.
+#[tauri::command]
+pub fn get_kiosk_mode(state: State<'_, AppState>) -> Result<bool, String> {
+  Ok(state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.kiosk.is_some())
+}
+
+/// Turn kiosk mode on with a new PIN (at least 4 characters), or off with the PIN it was turned on
+/// with. Kiosk mode is persisted, so it survives a restart.
+#[tauri::command]
+pub fn set_kiosk_mode(state: State<'_, AppState>, enabled: bool, pin: String) -> Result<(), String> {
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  match (enabled, &settings.kiosk) {
+    (true, Some(_)) => return Err("kiosk mode is already on".to_string()),
+    (true, None) => {
+      if pin.chars().count() < MIN_PIN_LEN {
+        return Err(format!("kiosk pin must have at least {} characters", MIN_PIN_LEN));
+      }
+      let salt = format!("{:016x}", RandomState::new().hash_one(&pin));
+      settings.kiosk = Some(KioskLock { pin_hash: hash_pin(&salt, &pin), salt });
+    }
+    (false, Some(lock)) => {
+      if hash_pin(&lock.salt, &pin) != lock.pin_hash {
+        warn!("wrong kiosk pin");
+        return Err("kiosk: wrong pin".to_string());
+      }
+      settings.kiosk = None;
+    }
+    (false, None) => return Ok(()),
+  }
+  info!(enabled, "kiosk mode changed");
+  settings.save(&state.config_dir)
+}
+
+#[test]
+pub fn test_hash_pin() {
+  let hash = hash_pin("salt", "1234");
+  assert_eq!(hash.len(), 64);
+  assert_eq!(hash, hash_pin("salt", "1234"));
+  assert_ne!(hash, hash_pin("salt", "1235"));
+  assert_ne!(hash, hash_pin("pepper", "1234"));
+}
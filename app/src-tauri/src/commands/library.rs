@@ -0,0 +1,132 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::sanitize_file_name;
+use crate::AppState;
+
+/// Event emitted when the library root becomes reachable or unreachable.
+pub const AVAILABILITY_EVENT: &str = "library-availability";
+
+const CACHE_DIR: &str = "offline_cache";
+
+// file systems that are served over the network
+const NETWORK_FS: [&str; 9] = ["nfs", "nfs4", "cifs", "smb3", "smbfs", "afpfs", "fuse.sshfs", "fuse.rclone", "9p"];
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct LibraryStatus {
+  pub root: String,
+  /// the root is on a network share; metadata is cached for offline use
+  pub network: bool,
+  pub available: bool,
+}
+
+// Decode the octal escapes (`\040` for space) used in /proc/mounts.
+fn unescape_mount_path(path: &str) -> String {
+  let mut out = String::new();
+  let mut rest = path;
+  while let Some(i) = rest.find('\\') {
+    out.push_str(&rest[..i]);
+    match rest.get(i + 1..i + 4).and_then(|o| u8::from_str_radix(o, 8).ok()) {
+      Some(byte) => {
+        out.push(byte as char);
+        rest = &rest[i + 4..];
+      }
+      None => {
+        out.push('\\');
+        rest = &rest[i + 1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+/// File system type of the mount containing `path`, from a `/proc/mounts` style table.
+fn mount_fs_type<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+  mounts
+    .lines()
+    .filter_map(|line| {
+      let mut fields = line.split_whitespace();
+      let (_, mount_point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+      path.starts_with(unescape_mount_path(mount_point)).then_some((mount_point.len(), fs_type))
+    })
+    .max_by_key(|(len, _)| *len)
+    .map(|(_, fs_type)| fs_type)
+}
+
+/// Whether `path` lives on a network share: a UNC path on Windows, or a network file system in the
+/// mount table elsewhere.
+fn is_network_path(path: &Path) -> bool {
+  let display = path.to_string_lossy();
+  if (display.starts_with(r"\\") && !display.starts_with(r"\\?\")) || display.starts_with(r"\\?\UNC\") {
+    return true;
+  }
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  std::fs::read_to_string("/proc/mounts").is_ok_and(|mounts| mount_fs_type(&mounts, &path).is_some_and(|fs| NETWORK_FS.contains(&fs)))
+}
+
+/// Probe the library root and emit [`AVAILABILITY_EVENT`] when its availability changed since the
+/// last check. The `library_on_network` setting overrides mount detection.
+pub(crate) fn check_library(app: &AppHandle, state: &AppState) -> Result<LibraryStatus, String> {
+  let configured = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.library_on_network;
+  let status = LibraryStatus {
+    root: state.res_dir.display().to_string(),
+    network: configured.unwrap_or_else(|| is_network_path(&state.res_dir)),
+    available: std::fs::read_dir(&state.res_dir).is_ok(),
+  };
+
+  let mut last = state.library_available.lock().map_err(|e| format!("library status lock poisoned: {}", e))?;
+  if *last != Some(status.available) {
+    *last = Some(status.available);
+    if status.available {
+      info!(root = %status.root, network = status.network, "library available");
+    } else {
+      warn!(root = %status.root, network = status.network, "library unavailable");
+    }
+    if let Err(e) = app.emit(AVAILABILITY_EVENT, &status) {
+      warn!(error = %e, "failed to emit library availability");
+    }
+  }
+  Ok(status)
+}
+
+fn cache_path(state: &AppState, key: &str) -> PathBuf {
+  state.config_dir.join(CACHE_DIR).join(format!("{}.json", sanitize_file_name(key)))
+}
+
+/// Last value stored under `key` with [`store_offline`], if any.
+pub(crate) fn load_offline<T: DeserializeOwned>(state: &AppState, key: &str) -> Option<T> {
+  let content = std::fs::read_to_string(cache_path(state, key)).ok()?;
+  serde_json::from_str(&content).map_err(|e| warn!(%key, error = %e, "invalid offline cache entry")).ok()
+}
+
+/// Keep a copy of `value` for when the library is unreachable. Failures are only logged.
+pub(crate) fn store_offline<T: Serialize>(state: &AppState, key: &str, value: &T) {
+  let path = cache_path(state, key);
+  let result = path
+    .parent()
+    .map_or(Ok(()), std::fs::create_dir_all)
+    .map_err(|e| e.to_string())
+    .and_then(|_| serde_json::to_string(value).map_err(|e| e.to_string()))
+    .and_then(|s| std::fs::write(&path, s).map_err(|e| e.to_string()));
+  if let Err(e) = result {
+    warn!(path = %path.display(), error = %e, "failed to write offline cache");
+  }
+}
+
+/// Current availability of the library root.
+#[tauri::command]
+pub fn get_library_status(app: AppHandle, state: State<'_, AppState>) -> Result<LibraryStatus, String> {
+  check_library(&app, &state)
+}
+
+#[test]
+pub fn test_mount_fs_type() {
+  let mounts = "/dev/sda1 / ext4 rw 0 0\n//nas/music /mnt/my\\040music cifs rw 0 0\nnas:/srv /mnt/nfs nfs4 rw 0 0\n";
+  assert_eq!(mount_fs_type(mounts, Path::new("/mnt/my music/res")), Some("cifs"));
+  assert_eq!(mount_fs_type(mounts, Path::new("/mnt/nfs")), Some("nfs4"));
+  assert_eq!(mount_fs_type(mounts, Path::new("/mnt/nfsx")), Some("ext4"));
+  assert_eq!(mount_fs_type(mounts, Path::new("/home/res")), Some("ext4"));
+}
@@ -1,5 +1,9 @@
+use crate::commands::perf::record;
 use crate::AppState;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
+use std::time::Instant;
 use tauri::State;
 use tauri::ipc::Response;
 
@@ -7,10 +11,21 @@ pub fn read_audio_file(path: &Path) -> Result<Vec<u8>, String> {
   std::fs::read(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))
 }
 
+/// Up to `length` bytes of the file from `offset` on; fewer (or none) at the end of the file.
+pub fn read_audio_chunk(path: &Path, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+  let mut file = File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+  file.seek(SeekFrom::Start(offset)).map_err(|e| format!("failed to seek {}: {}", path.display(), e))?;
+  let mut bytes = Vec::new();
+  file.take(length).read_to_end(&mut bytes).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+  Ok(bytes)
+}
+
 /// Read a bundled resource file and return binary audio bytes wrapped in an IPC Response.
 /// The frontend can call the command via the Tauri IPC and receive a binary payload.
+/// With `offset` and/or `length` only that part of the file is returned, so big lossless files
+/// can be loaded progressively; a chunk shorter than `length` is the last one.
 #[tauri::command]
-pub fn load_audio(state: State<'_, AppState>, path: String) -> Result<Response, String> {
+pub fn load_audio(state: State<'_, AppState>, path: String, offset: Option<u64>, length: Option<u64>) -> Result<Response, String> {
   if path.is_empty() {
     return Err("path argument is empty".to_string());
   }
@@ -18,6 +33,21 @@ pub fn load_audio(state: State<'_, AppState>, path: String) -> Result<Response,
   // Resolve path using app state (so bundle resources can be found)
   let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
 
-  let bytes = read_audio_file(&resolved)?;
+  let started = Instant::now();
+  let bytes = match (offset, length) {
+    (None, None) => read_audio_file(&resolved)?,
+    (offset, length) => read_audio_chunk(&resolved, offset.unwrap_or(0), length.unwrap_or(u64::MAX))?,
+  };
+  record(&state, |perf| perf.record_audio_read(bytes.len() as u64, started.elapsed()));
   Ok(Response::new(bytes))
 }
+
+#[test]
+pub fn test_read_audio_chunk() {
+  let path = std::env::temp_dir().join(format!("klok_audio_chunk_{}", std::process::id()));
+  std::fs::write(&path, (0..10u8).collect::<Vec<_>>()).unwrap();
+  assert_eq!(read_audio_chunk(&path, 2, 3).unwrap(), vec![2, 3, 4]);
+  assert_eq!(read_audio_chunk(&path, 8, 5).unwrap(), vec![8, 9]);
+  assert_eq!(read_audio_chunk(&path, 20, 5).unwrap(), Vec::<u8>::new());
+  std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,110 @@
+use serde::Serialize;
+use std::fmt;
+use tauri::{AppHandle, State};
+
+use klok_core::lyrics::LyricLine;
+use klok_core::midi::Note;
+use klok_core::phrases::{annotate_pacing, fill_line_ends, mark_breaths, phrase_gaps};
+
+use crate::commands::align::find_aligned_lyrics;
+use crate::commands::get_metadata::{find_bilingual_lyrics, get_duration_and_artist};
+use crate::commands::library::check_library;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::commands::profanity::filter_lyrics;
+use crate::AppState;
+
+// shortest silence in the vocal line that suggests a breath
+const BREATH_MIN_GAP: f64 = 0.3;
+
+/// Why lyrics couldn't be loaded. It reaches the frontend as `{ kind, message }`, so it can tell
+/// a song without lyrics from one worth retrying.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum LyricsError {
+  EmptyPath,
+  /// no timed lyrics file, and no `.txt` lyrics with a vocal MIDI to align them to
+  NotFound(String),
+  /// the library root is unreachable; try again later
+  Unavailable(String),
+  /// a lyrics file exists but can't be read or parsed
+  Failed(String),
+}
+
+impl fmt::Display for LyricsError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      LyricsError::EmptyPath => write!(f, "path argument is empty"),
+      LyricsError::NotFound(path) => write!(f, "no lyrics found for {}", path),
+      LyricsError::Unavailable(root) => write!(f, "library unavailable: {}", root),
+      LyricsError::Failed(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl From<String> for LyricsError {
+  fn from(e: String) -> Self {
+    LyricsError::Failed(e)
+  }
+}
+
+/// Lyrics of the song at `path` with breath marks from `notes`: a timed lyrics file (with its
+/// `translation` companion), else plain `.txt` lyrics aligned to the vocal MIDI.
+pub(crate) fn song_lyrics(state: &AppState, path: &str, translation: Option<&str>, notes: Option<&[Note]>) -> Result<Vec<LyricLine>, LyricsError> {
+  if path.is_empty() {
+    return Err(LyricsError::EmptyPath);
+  }
+  let lyrics = match find_bilingual_lyrics(state, path, translation)?.filter(|l| !l.is_empty()) {
+    Some(lyrics) => Some(lyrics),
+    // an explicit .lrc path must exist itself
+    None if path.ends_with(".lrc") => None,
+    None => find_aligned_lyrics(state, path).unwrap_or_else(|e| {
+      warn!(error = %e, "failed to align plain lyrics");
+      None
+    }),
+  };
+  let mut lyrics = lyrics.filter(|l| !l.is_empty()).ok_or_else(|| LyricsError::NotFound(path.to_string()))?;
+  if let Some(notes) = notes {
+    mark_breaths(&mut lyrics, &phrase_gaps(notes, BREATH_MIN_GAP));
+  }
+  Ok(lyrics)
+}
+
+/// Fill in line ends (from phrase gaps in `notes` when there are any) and prompter pacing, and
+/// apply the profanity filter.
+pub(crate) fn finish_lyrics(state: &AppState, lyrics: &mut [LyricLine], notes: Option<&[Note]>, duration: f64) -> Result<(), String> {
+  let gaps = notes.map(|n| phrase_gaps(n, BREATH_MIN_GAP)).unwrap_or_default();
+  let vocal_end = notes.and_then(|n| n.iter().map(|n| n.start + n.duration).reduce(f64::max));
+  fill_line_ends(lyrics, &gaps, vocal_end.map_or(duration, |end| end.min(duration)));
+  annotate_pacing(lyrics, duration);
+  filter_lyrics(state, lyrics)
+}
+
+/// Load just the lyrics of a song, timed and annotated the same way as in `get_metadata`, so they
+/// can be refreshed without probing the audio tags again.
+#[tauri::command]
+pub fn load_lyrics(app: AppHandle, state: State<'_, AppState>, path: String, translation: Option<String>) -> Result<Vec<LyricLine>, LyricsError> {
+  let status = check_library(&app, &state)?;
+  if !status.available {
+    return Err(LyricsError::Unavailable(status.root));
+  }
+  let notes = find_vocal_notes(&state, &path).unwrap_or_else(|e| {
+    warn!(error = %e, "failed to load vocal midi for breath marks");
+    None
+  });
+  let mut lyrics = song_lyrics(&state, &path, translation.as_deref(), notes.as_deref())?;
+  let duration = state
+    .resolve(&path)
+    .and_then(get_duration_and_artist)
+    .map_or_else(|| lyrics.last().map_or(0.0, |l| l.time) + 10.0, |(d, _)| d);
+  finish_lyrics(&state, &mut lyrics, notes.as_deref(), duration)?;
+  Ok(lyrics)
+}
+
+#[test]
+pub fn test_lyrics_error() {
+  let json = |e: &LyricsError| serde_json::to_string(e).unwrap();
+  assert_eq!(json(&LyricsError::NotFound("a.mp3".to_string())), r#"{"kind":"not_found","message":"a.mp3"}"#);
+  assert_eq!(json(&LyricsError::EmptyPath), r#"{"kind":"empty_path"}"#);
+  assert_eq!(LyricsError::from("failed to read a.lrc".to_string()), LyricsError::Failed("failed to read a.lrc".to_string()));
+  assert_eq!(LyricsError::Unavailable("/mnt/nas".to_string()).to_string(), "library unavailable: /mnt/nas");
+}
@@ -1,123 +1,170 @@
-use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::ipc::Response;
 use tauri::State;
+
+use klok_core::f0::{F0Curve, DEFAULT_HOP};
+use klok_core::midi::{apply_sustain, flatten_tracks, load_midi_meta_from_memory, melody_tracks, pack_notes, select_tracks, transpose, ConfidenceSource, MidiMeta, MidiTrack, Note, OverlapPolicy};
+use klok_core::musicxml::load_score;
+use klok_core::note_events::load_note_events;
+use klok_core::piano_roll::PianoRoll;
+use klok_core::simplify::{simplify_track, SimplifyOptions};
+
+use crate::commands::midi_cache::{load_midi_tracks_cached, midi_warnings_cached};
 use crate::{commands::with_extension, AppState};
-use std::collections::HashMap;
-
-#[derive(Debug, Serialize)]
-pub struct Note {
-  pub note: i32,
-  /// start time in seconds
-  pub start: f64,
-  /// duration in seconds
-  pub duration: f64,
-  pub velocity: f64,
-  /// MIDI channel (0-15)
-  pub channel: u8,
-  pub confidence: Option<f64>,
+
+// Companion files holding a song's melody, in lookup order: the pipeline's pitch MIDI, its note
+// events for songs analysed without writing MIDI, a karaoke file, then sheet music.
+pub(crate) const VOCAL_MIDI_SUFFIXES: [&str; 6] = ["_vocals_pitches.mid", "_vocals_notes.json", "_vocals_notes.csv", ".kar", ".musicxml", ".mxl"];
+const NOTE_EVENT_SUFFIXES: [&str; 2] = [".json", ".csv"];
+const SCORE_SUFFIXES: [&str; 2] = [".musicxml", ".mxl"];
+
+// seconds per column of a piano roll unless the frontend asks for another
+const DEFAULT_PIANO_ROLL_BUCKET: f64 = 0.05;
+
+/// The MIDI file with the melody of the song at `path`, see `VOCAL_MIDI_SUFFIXES`.
+pub(crate) fn find_vocal_midi(state: &AppState, path: &str) -> Option<PathBuf> {
+  VOCAL_MIDI_SUFFIXES.iter().find_map(|suffix| state.resolve(with_extension(path, suffix)))
 }
 
-/// Load a MIDI file (resolved via `AppState::resolve`) and return a list of notes.
-#[tauri::command]
-pub fn load_midi(state: State<'_, AppState>, path: String) -> Result<Vec<Note>, String> {
+// Tracks of a melody file found by `find_vocal_midi`: note events and MusicXML scores give one
+// track of their melody, MIDI is parsed (or taken from the cache) with the given options.
+fn parse_vocal_tracks(state: &AppState, resolved: &Path, bytes: &[u8], confidence: ConfidenceSource, overlap: OverlapPolicy) -> Result<Vec<MidiTrack>, String> {
+  let name = resolved.to_string_lossy();
+  let notes = if NOTE_EVENT_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+    load_note_events(bytes)
+  } else if SCORE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+    load_score(bytes)
+  } else {
+    return load_midi_tracks_cached(state, resolved, bytes, confidence, overlap);
+  };
+  let notes = notes.map_err(|e| format!("failed to parse {}: {}", resolved.display(), e))?;
+  Ok(vec![MidiTrack { index: 0, name: None, instrument: None, notes, sustain: Vec::new() }])
+}
+
+fn read_vocal_midi(state: &AppState, path: &str) -> Result<(PathBuf, Vec<u8>), String> {
   if path.is_empty() {
     return Err("path argument is empty".to_string());
   }
+  let resolved = find_vocal_midi(state, path).ok_or_else(|| format!("resource not found: {}", with_extension(path, VOCAL_MIDI_SUFFIXES[0])))?;
+  let bytes = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  Ok((resolved, bytes))
+}
 
-  let path = with_extension(&path, "_vocals_pitches.mid");
+/// Load a MIDI file (resolved via `AppState::resolve`) and return a list of notes.
+/// `confidence` selects how `Note.confidence` is populated (default: left empty).
+/// `tracks` keeps only the notes of the listed tracks (see [`load_midi_tracks`]); without it a
+/// multi-track file gives the notes of its melody track (see `klok_core::melody::detect_melody_track`).
+/// `sustain` holds notes for as long as the sustain pedal keeps them sounding (see `klok_core::midi::apply_sustain`).
+/// `transpose` shifts the notes by that many semitones, to match a key-shifted backing track.
+/// `overlap` picks how a repeated note-on of a sounding key is handled (default: end the earlier note).
+/// `simplify` cleans up auto-transcribed notes: merges micro-gaps, drops blips and snaps starts to
+/// the beat grid of the tempo map (see `klok_core::simplify::simplify_track`).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn load_midi(state: State<'_, AppState>, path: String, confidence: Option<ConfidenceSource>, tracks: Option<Vec<usize>>, sustain: Option<bool>, transpose: Option<i32>, overlap: Option<OverlapPolicy>, simplify: Option<SimplifyOptions>) -> Result<Vec<Note>, String> {
+  let parsed = read_midi_tracks(&state, &path, confidence, overlap, sustain, transpose, simplify)?;
+  Ok(flatten_tracks(melody_tracks(parsed, tracks.as_deref())))
+}
 
-  let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+/// Like [`load_midi`], with the notes packed into the binary layout of `klok_core::midi::pack_notes`
+/// instead of JSON, so long songs load fast.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn load_midi_raw(state: State<'_, AppState>, path: String, confidence: Option<ConfidenceSource>, tracks: Option<Vec<usize>>, sustain: Option<bool>, transpose: Option<i32>, overlap: Option<OverlapPolicy>, simplify: Option<SimplifyOptions>) -> Result<Response, String> {
+  let parsed = read_midi_tracks(&state, &path, confidence, overlap, sustain, transpose, simplify)?;
+  Ok(Response::new(pack_notes(&flatten_tracks(melody_tracks(parsed, tracks.as_deref())))))
+}
 
-  let bytes = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+/// Like [`load_midi`], with the notes grouped by track and the track and instrument names.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn load_midi_tracks(state: State<'_, AppState>, path: String, confidence: Option<ConfidenceSource>, tracks: Option<Vec<usize>>, sustain: Option<bool>, transpose: Option<i32>, overlap: Option<OverlapPolicy>, simplify: Option<SimplifyOptions>) -> Result<Vec<MidiTrack>, String> {
+  let parsed = read_midi_tracks(&state, &path, confidence, overlap, sustain, transpose, simplify)?;
+  Ok(select_tracks(parsed, tracks.as_deref()))
+}
 
-  load_midi_from_memory(&bytes)
+/// Load note events written by the basic-pitch pipeline as JSON (`estimated_notes`) or CSV
+/// (resolved via `AppState::resolve`) without a MIDI intermediate, with `Note.confidence` set from
+/// each note's amplitude. See `klok_core::note_events::load_note_events`.
+#[tauri::command]
+pub fn load_notes_json(state: State<'_, AppState>, path: String) -> Result<Vec<Note>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+  let bytes = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  let notes = load_note_events(&bytes).map_err(|e| format!("failed to parse {}: {}", resolved.display(), e))?;
+  debug!(%path, notes = notes.len(), "loaded note events");
+  Ok(notes)
 }
 
-/// Parse MIDI content from any reader and return a list of notes.
-pub fn load_midi_from_memory(content: &[u8]) -> Result<Vec<Note>, String> {
-  // Parse MIDI using midly
-  let smf = midly::Smf::parse(content).map_err(|e| format!("failed to parse midi: {}", e))?;
+/// Like [`load_midi`], rasterized into a piano roll of `bucket` seconds per column (default 50ms)
+/// and returned as the bytes of `PianoRoll::to_bytes`, so songs with many notes can be drawn as an
+/// image without sending each note as JSON.
+#[tauri::command]
+pub fn load_piano_roll(state: State<'_, AppState>, path: String, bucket: Option<f64>, tracks: Option<Vec<usize>>, sustain: Option<bool>, transpose: Option<i32>) -> Result<Response, String> {
+  let parsed = read_midi_tracks(&state, &path, None, None, sustain, transpose, None)?;
+  let notes = flatten_tracks(melody_tracks(parsed, tracks.as_deref()));
+  let roll = PianoRoll::new(&notes, bucket.unwrap_or(DEFAULT_PIANO_ROLL_BUCKET))?;
+  debug!(%path, notes = notes.len(), rows = roll.rows, columns = roll.columns, "rasterized piano roll");
+  Ok(Response::new(roll.to_bytes()))
+}
 
-  // Only support metrical timing (ticks per quarter-note)
-  let ticks_per_quarter = match smf.header.timing {
-    midly::Timing::Metrical(t) => t.as_int() as u32,
-    _ => return Err("SMPTE time formats are not supported".to_string()),
-  };
+/// Like [`load_midi`], as a reference pitch curve sampled every `hop` seconds (default 10ms) with
+/// pitch bends applied, returned as the bytes of `F0Curve::to_bytes`, so scoring and the pitch
+/// display can compare detected microphone pitch against it frame by frame.
+#[tauri::command]
+pub fn load_f0_curve(state: State<'_, AppState>, path: String, hop: Option<f64>, tracks: Option<Vec<usize>>, sustain: Option<bool>, transpose: Option<i32>, simplify: Option<SimplifyOptions>) -> Result<Response, String> {
+  let parsed = read_midi_tracks(&state, &path, None, None, sustain, transpose, simplify)?;
+  let curve = F0Curve::new(&flatten_tracks(melody_tracks(parsed, tracks.as_deref())), hop.unwrap_or(DEFAULT_HOP))?;
+  debug!(%path, samples = curve.hz.len(), "sampled f0 curve");
+  Ok(Response::new(curve.to_bytes()))
+}
 
-  // Collect all events with absolute tick
-  let mut events: Vec<(u64, midly::TrackEventKind)> = Vec::new();
-  for track in &smf.tracks {
-    let mut abs: u64 = 0;
-    for ev in track {
-      abs = abs.wrapping_add(ev.delta.as_int() as u64);
-      events.push((abs, ev.kind.clone()));
-    }
+// The tracks of the vocal MIDI of `path` with the options of `load_midi` applied.
+pub(crate) fn read_midi_tracks(state: &AppState, path: &str, confidence: Option<ConfidenceSource>, overlap: Option<OverlapPolicy>, sustain: Option<bool>, semitones: Option<i32>, simplify: Option<SimplifyOptions>) -> Result<Vec<MidiTrack>, String> {
+  let (resolved, bytes) = read_vocal_midi(state, path)?;
+  let mut parsed = parse_vocal_tracks(state, &resolved, &bytes, confidence.unwrap_or_default(), overlap.unwrap_or_default())?;
+  if sustain.unwrap_or(false) {
+    parsed.iter_mut().for_each(apply_sustain);
   }
-
-  // Sort by absolute tick
-  events.sort_by_key(|(t, _)| *t);
-
-  // State while iterating events
-  let mut last_tick: u64 = 0;
-  let mut seconds: f64 = 0.0;
-  let mut tempo_micro: u32 = 500_000; // default microseconds per quarter-note
-
-  // ongoing notes keyed by (channel, note) -> (start_seconds, velocity)
-  let mut ongoing: HashMap<(u8, u8), (f64, u8)> = HashMap::new();
-  let mut notes: Vec<Note> = Vec::new();
-
-  for (abs_tick, kind) in events {
-    let delta_ticks = abs_tick.saturating_sub(last_tick);
-    if delta_ticks != 0 {
-      // convert ticks to seconds using current tempo
-      seconds += (delta_ticks as f64) * (tempo_micro as f64) / (ticks_per_quarter as f64) / 1_000_000.0;
-      last_tick = abs_tick;
-    }
-
-    match kind {
-      midly::TrackEventKind::Meta(midly::MetaMessage::Tempo(t)) => {
-        tempo_micro = t.into();
-      }
-      midly::TrackEventKind::Midi { channel, message } => {
-        match message {
-          midly::MidiMessage::NoteOn { key, vel } => {
-            let k = key.as_int();
-            let v = vel.as_int();
-            let ch = channel.as_int();
-            if v > 0 {
-              ongoing.insert((ch, k), (seconds, v));
-            } else {
-              // velocity 0 note_on == note_off
-              if let Some((start, vel0)) = ongoing.remove(&(ch, k)) {
-                let dur = seconds - start;
-                notes.push(Note { note: k as i32, start, duration: dur, velocity: vel0 as f64, channel: ch, confidence: None });
-              }
-            }
-          }
-          midly::MidiMessage::NoteOff { key, vel: _ } => {
-            let k = key.as_int();
-            let ch = channel.as_int();
-            if let Some((start, vel0)) = ongoing.remove(&(ch, k)) {
-              let dur = seconds - start;
-              notes.push(Note { note: k as i32, start, duration: dur, velocity: vel0 as f64, channel: ch, confidence: None });
-            }
-          }
-          _ => {}
-        }
-      }
-      _ => {}
-    }
+  if let Some(options) = simplify {
+    // note events and scores have no tempo map to snap to
+    let tempos = load_midi_meta_from_memory(&bytes).ok().map(|meta| meta.tempos);
+    parsed.iter_mut().for_each(|t| simplify_track(t, tempos.as_deref(), &options));
+  }
+  if let Some(semitones) = semitones.filter(|s| *s != 0) {
+    parsed.iter_mut().for_each(|t| transpose(t, semitones));
   }
+  Ok(parsed)
+}
 
-  // return notes sorted by start time
-  notes.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
-  Ok(notes)
+/// Parse the vocal MIDI next to `path` (`song_vocals_pitches.mid`, note events, `song.kar` or a MusicXML score) when it exists,
+/// keeping the melody track of a multi-track file.
+pub(crate) fn find_vocal_notes(state: &AppState, path: &str) -> Result<Option<Vec<Note>>, String> {
+  let Some(resolved) = find_vocal_midi(state, path) else {
+    return Ok(None);
+  };
+  let bytes = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  let tracks = parse_vocal_tracks(state, &resolved, &bytes, ConfidenceSource::None, OverlapPolicy::default())?;
+  Ok(Some(flatten_tracks(melody_tracks(tracks, None))))
 }
 
-#[test]
-pub fn test_midi() {
-  let content = include_bytes!("../../../../res/我的一个道姑朋友_vocals_pitches.mid");
+/// Problems found in the vocal MIDI of `path` that loading worked around (damaged tracks, notes
+/// without a note-off), so the frontend can tell that the melody may be incomplete. Empty when the
+/// file is intact or the melody comes from note events or a score.
+#[tauri::command]
+pub fn load_midi_warnings(state: State<'_, AppState>, path: String) -> Result<Vec<String>, String> {
+  let (resolved, bytes) = read_vocal_midi(&state, &path)?;
+  let name = resolved.to_string_lossy();
+  if NOTE_EVENT_SUFFIXES.iter().chain(&SCORE_SUFFIXES).any(|suffix| name.ends_with(suffix)) {
+    return Ok(Vec::new());
+  }
+  midi_warnings_cached(&state, &resolved, &bytes)
+}
 
-  let notes = load_midi_from_memory(content).expect("failed to parse midi from memory");
-  println!("{:?}", notes.iter().take(10).collect::<Vec<_>>());
-  assert_eq!(notes.len(), 768);
+/// Load the tempo map, time and key signatures of the vocal MIDI next to `path`.
+#[tauri::command]
+pub fn load_midi_meta(state: State<'_, AppState>, path: String) -> Result<MidiMeta, String> {
+  load_midi_meta_from_memory(&read_vocal_midi(&state, &path)?.1)
 }
@@ -1,22 +1,63 @@
 use lofty::{AudioFile, Probe};
-use serde::Serialize;
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, State};
 
+use klok_core::language::detect_language;
+
+use crate::commands::difficulty::song_difficulty;
+use crate::commands::get_metadata::find_lyrics;
+use crate::commands::library::{check_library, load_offline, store_offline};
+use crate::commands::perf::record;
+use crate::commands::timeout::{run_blocking, Cancel};
 use crate::AppState;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct PlaylistItem {
   pub title: String,
   pub url: String,
   pub artist: Option<String>,
+  /// detected lyrics language (ISO 639-1), for filtering the library
+  pub language: Option<String>,
+  /// sing-along difficulty from 0 (easy) to 10 (hard), when a vocal MIDI exists
+  pub difficulty: Option<f64>,
+  /// a karaoke MIDI without audio, played with a synthesized backing (see `render_backing`)
+  #[serde(default)]
+  pub midi_only: bool,
 }
 
-const UNEXPECTED_SUFFIX: [&str; 2] = ["non_vocals", "vocals"];
+const PLAYLIST_CACHE_KEY: &str = "playlist";
+
+// karaoke files listed as songs of their own when there is no audio of the same name
+const MIDI_SONG_EXT: &str = ".kar";
+
+pub(crate) const UNEXPECTED_SUFFIX: [&str; 2] = ["non_vocals", "vocals"];
 
 /// Scan the state's res_dir for files matching extensions and return a playlist.
 /// `extensions` is optional; when empty the DEFAULT_EXT list is used.
+/// Songs hidden with `hide_song` are skipped unless `include_hidden` is set.
+/// While a network library is unreachable the last playlist scanned from it is returned.
 #[tauri::command]
-pub fn load_playlist(state: State<'_, AppState>, extensions: Option<Vec<String>>) -> Result<Vec<PlaylistItem>, String> {
+pub async fn load_playlist(app: AppHandle, state: State<'_, AppState>, extensions: Option<Vec<String>>, include_hidden: Option<bool>) -> Result<Vec<PlaylistItem>, String> {
+  let st = state.inner().clone();
+  run_blocking(&state, "load_playlist", move |cancel| {
+    let status = check_library(&app, &st)?;
+    if !status.available {
+      return load_offline(&st, PLAYLIST_CACHE_KEY).ok_or_else(|| format!("library unavailable: {}", status.root));
+    }
+    let started = Instant::now();
+    let items = scan_playlist(&st, extensions, include_hidden, &cancel)?;
+    record(&st, |perf| perf.record_scan(started.elapsed(), items.len()));
+    if status.network {
+      store_offline(&st, PLAYLIST_CACHE_KEY, &items);
+    }
+    Ok(items)
+  })
+  .await
+}
+
+fn scan_playlist(state: &AppState, extensions: Option<Vec<String>>, include_hidden: Option<bool>, cancel: &Cancel) -> Result<Vec<PlaylistItem>, String> {
   let exts: Vec<String> = if let Some(v) = extensions {
     if v.is_empty() {
       super::COMMON_EXT.iter().map(|s| s.to_string()).collect()
@@ -27,6 +68,14 @@ pub fn load_playlist(state: State<'_, AppState>, extensions: Option<Vec<String>>
     super::COMMON_EXT.iter().map(|s| s.to_string()).collect()
   };
 
+  let (hidden, mut difficulties) = {
+    let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    // hidden songs stay hidden in kiosk mode
+    let hidden = if include_hidden.unwrap_or(false) && settings.kiosk.is_none() { Default::default() } else { settings.hidden_songs.clone() };
+    (hidden, settings.song_difficulties.clone())
+  };
+  let mut rated = false;
+
   let mut items: Vec<PlaylistItem> = Vec::new();
 
   let dir = &state.res_dir;
@@ -34,18 +83,20 @@ pub fn load_playlist(state: State<'_, AppState>, extensions: Option<Vec<String>>
     return Err(format!("res_dir does not exist: {}", dir.display()));
   }
 
-  // read top-level entries only
-  let entries = match std::fs::read_dir(dir) {
-    Ok(e) => e,
-    Err(e) => return Err(format!("failed to read res_dir {}: {}", dir.display(), e)),
-  };
+  // include subdirectories, so `organize_library` layouts like `Artist/Title.mp3` are listed
+  let mut files = Vec::new();
+  collect_files(dir, &mut files).map_err(|e| format!("failed to read res_dir {}: {}", dir.display(), e))?;
+  files.sort();
 
-  for entry in entries.flatten() {
-    let path = entry.path();
+  for path in files {
+    if cancel.is_cancelled() {
+      return Err("load_playlist cancelled".to_string());
+    }
     if path.is_file() {
       if let Some(os) = path.extension().and_then(|s| s.to_str()) {
         let dot_ext = format!(".{}", os);
-        if exts.iter().any(|e| e == &dot_ext) {
+        let midi_only = dot_ext == MIDI_SONG_EXT && !super::COMMON_EXT.iter().any(|e| path.with_extension(&e[1..]).exists());
+        if midi_only || exts.iter().any(|e| e == &dot_ext) {
           // build title and relative path
           let title = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
 
@@ -54,16 +105,73 @@ pub fn load_playlist(state: State<'_, AppState>, extensions: Option<Vec<String>>
             continue;
           }
 
-          let url = path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string();
+          let Some(url) = relative_url(dir, &path) else {
+            continue;
+          };
+          if hidden.contains(&url) {
+            continue;
+          }
+          let language = match find_lyrics(state, &url) {
+            Ok(lyrics) => lyrics.and_then(|l| detect_language(&l)),
+            Err(e) => {
+              warn!(%url, error = %e, "failed to read lyrics for language detection");
+              None
+            }
+          };
+          let difficulty = match song_difficulty(state, &url, difficulties.get(&url)) {
+            Ok(Some((difficulty, computed))) => {
+              let score = difficulty.score;
+              if computed {
+                difficulties.insert(url.clone(), difficulty);
+                rated = true;
+              }
+              Some(score)
+            }
+            Ok(None) => None,
+            Err(e) => {
+              warn!(%url, error = %e, "failed to rate difficulty");
+              None
+            }
+          };
           items.push(PlaylistItem {
             title,
             url,
             artist: None,
+            language,
+            difficulty,
+            midi_only,
           });
         }
       }
     }
   }
 
+  if rated {
+    let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    settings.song_difficulties.extend(difficulties);
+    settings.save(&state.config_dir)?;
+  }
+
   Ok(items)
 }
+
+pub(crate) fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+  for entry in std::fs::read_dir(dir)?.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      // a broken subdirectory shouldn't hide the rest of the library
+      if let Err(e) = collect_files(&path, out) {
+        warn!(path = %path.display(), error = %e, "failed to read directory");
+      }
+    } else {
+      out.push(path);
+    }
+  }
+  Ok(())
+}
+
+/// Playlist url of a file below `res_dir`: its relative path with `/` separators.
+pub(crate) fn relative_url(res_dir: &Path, path: &Path) -> Option<String> {
+  let parts: Option<Vec<&str>> = path.strip_prefix(res_dir).ok()?.components().map(|c| c.as_os_str().to_str()).collect();
+  Some(parts?.join("/"))
+}
@@ -0,0 +1,77 @@
+use percent_encoding::percent_decode_str;
+use tauri::ipc::{InvokeBody, Request};
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::storage::{ensure_free_space, export_path};
+use crate::commands::with_extension;
+use crate::AppState;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Headers of an `export_lyric_frame` request; the song path is percent-encoded since header
+// values are ASCII only.
+const PATH_HEADER: &str = "x-klok-path";
+const START_HEADER: &str = "x-klok-start";
+const FRAME_HEADER: &str = "x-klok-frame";
+// optional: name of the export target to write to instead of the library, percent-encoded
+const TARGET_HEADER: &str = "x-klok-target";
+
+/// Directory of the frames of a clip of `path` starting at `start` seconds, relative to the library:
+/// `song.mp3` -> `song_clip_83.50/`.
+fn clip_dir(path: &str, start: f64) -> String {
+  with_extension(path, &format!("_clip_{:.2}", start.max(0.0)))
+}
+
+fn header<'a>(request: &'a Request<'_>, name: &str) -> Result<&'a str, String> {
+  request.headers().get(name).and_then(|v| v.to_str().ok()).ok_or_else(|| format!("missing {} header", name))
+}
+
+/// Write one PNG frame of a lyric clip rendered by the frontend. The raw PNG is the request body;
+/// the `x-klok-path` (song, percent-encoded), `x-klok-start` (clip start in seconds) and
+/// `x-klok-frame` (frame index) headers place it at `<clip dir>/00042.png`. Frame 0 starts the
+/// clip over, removing frames of an earlier export. An `x-klok-target` header sends the clip to
+/// that export target. Returns the clip directory.
+#[tauri::command]
+pub fn export_lyric_frame(state: State<'_, AppState>, request: Request<'_>) -> Result<String, String> {
+  ensure_unlocked(&state, "export_lyric_frame")?;
+  let path = percent_decode_str(header(&request, PATH_HEADER)?).decode_utf8().map_err(|e| format!("invalid {} header: {}", PATH_HEADER, e))?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let start: f64 = header(&request, START_HEADER)?.parse().map_err(|e| format!("invalid {} header: {}", START_HEADER, e))?;
+  let frame: u32 = header(&request, FRAME_HEADER)?.parse().map_err(|e| format!("invalid {} header: {}", FRAME_HEADER, e))?;
+  let InvokeBody::Raw(png) = request.body() else {
+    return Err("frame must be sent as raw bytes".to_string());
+  };
+  if !png.starts_with(PNG_SIGNATURE) {
+    return Err(format!("frame {} is not a png", frame));
+  }
+
+  let target = match request.headers().get(TARGET_HEADER) {
+    Some(v) => Some(percent_decode_str(v.to_str().map_err(|e| format!("invalid {} header: {}", TARGET_HEADER, e))?).decode_utf8().map_err(|e| format!("invalid {} header: {}", TARGET_HEADER, e))?),
+    None => None,
+  };
+  let (resolved, dir) = export_path(&state, target.as_deref(), &clip_dir(&path, start))?;
+  if frame == 0 && resolved.is_dir() {
+    for entry in std::fs::read_dir(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?.flatten() {
+      if entry.path().extension().is_some_and(|ext| ext == "png") {
+        std::fs::remove_file(entry.path()).map_err(|e| format!("failed to remove {}: {}", entry.path().display(), e))?;
+      }
+    }
+  }
+  std::fs::create_dir_all(&resolved).map_err(|e| format!("failed to create {}: {}", resolved.display(), e))?;
+  let file = resolved.join(format!("{:05}.png", frame));
+  ensure_free_space(&file, png.len() as u64)?;
+  std::fs::write(&file, png).map_err(|e| format!("failed to write {}: {}", file.display(), e))?;
+  if frame == 0 {
+    info!(path = %resolved.display(), "exporting lyric frames");
+  }
+  Ok(dir)
+}
+
+#[test]
+pub fn test_clip_dir() {
+  assert_eq!(clip_dir("a/song.mp3", 83.5), "a/song_clip_83.50");
+  assert_eq!(clip_dir("song.flac", -1.0), "song_clip_0.00");
+}
@@ -0,0 +1,178 @@
+use std::future::Future;
+use tauri::State;
+
+use klok_core::lrc::format_lrc;
+use klok_core::lyrics::LyricLine;
+
+use crate::commands::consent::ensure_consent;
+use crate::commands::fetch_lyrics::{find_cached_lyrics, store_cached_lyrics, Lrclib};
+use crate::commands::netease::Netease;
+use crate::commands::profanity::filter_lyrics;
+use crate::commands::qqmusic::QqMusic;
+use crate::commands::timeout::run_async;
+use crate::settings::Feature;
+use crate::AppState;
+
+// tracks whose length differs by more than this (seconds) from the local file are rejected
+const MAX_DURATION_DIFF: f64 = 10.0;
+
+/// What we know about the song we want lyrics for.
+#[derive(Debug, Clone)]
+pub struct SongQuery {
+  pub title: String,
+  pub artist: String,
+  /// length of the local audio in seconds
+  pub duration: Option<f64>,
+}
+
+/// A search result from a provider.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+  /// provider-specific track id passed back to `fetch`
+  pub id: String,
+  pub title: String,
+  pub artists: Vec<String>,
+  pub duration: Option<f64>,
+}
+
+/// An online lyrics source. Providers are looked up by `NAME` in [`PROVIDERS`] and the
+/// `lyrics_providers` settings.
+pub trait LyricsProvider {
+  const NAME: &'static str;
+
+  fn search(&self, query: &SongQuery) -> impl Future<Output = Result<Vec<Candidate>, String>> + Send;
+
+  /// Synced lyrics (with translations when the source has them) for a search result.
+  fn fetch(&self, candidate: &Candidate) -> impl Future<Output = Result<Option<Vec<LyricLine>>, String>> + Send;
+
+  /// How badly `candidate` matches `query`, lower is better; `None` rejects it.
+  fn score_match(&self, query: &SongQuery, candidate: &Candidate) -> Option<f64> {
+    match_cost(query, candidate)
+  }
+}
+
+/// Every registered provider, in default priority order.
+pub const PROVIDERS: [&str; 3] = [Lrclib::NAME, Netease::NAME, QqMusic::NAME];
+
+// Lowercase and drop whitespace/punctuation so "Hello, World (Live)" compares loosely.
+fn normalize(s: &str) -> String {
+  s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Default match cost: title and artist mismatches weigh like several seconds of duration difference.
+pub fn match_cost(query: &SongQuery, candidate: &Candidate) -> Option<f64> {
+  let (name, wanted) = (normalize(&candidate.title), normalize(&query.title));
+  let title_cost = if name == wanted {
+    0.0
+  } else if name.contains(&wanted) || wanted.contains(&name) {
+    3.0
+  } else {
+    8.0
+  };
+  let wanted_artist = normalize(&query.artist);
+  let artist_matches = |a: &String| {
+    let a = normalize(a);
+    !a.is_empty() && (a.contains(&wanted_artist) || wanted_artist.contains(&a))
+  };
+  let artist_cost = if wanted_artist.is_empty() || candidate.artists.iter().any(artist_matches) { 0.0 } else { 5.0 };
+  let duration_cost = match (query.duration, candidate.duration) {
+    (Some(wanted), Some(d)) if d > 0.0 => {
+      let diff = (d - wanted).abs();
+      if diff > MAX_DURATION_DIFF {
+        return None;
+      }
+      diff
+    }
+    _ => 0.0,
+  };
+  Some(title_cost + artist_cost + duration_cost)
+}
+
+fn pick_candidate<'a, P: LyricsProvider>(provider: &P, query: &SongQuery, candidates: &'a [Candidate]) -> Option<&'a Candidate> {
+  candidates
+    .iter()
+    .filter_map(|c| provider.score_match(query, c).map(|cost| (cost, c)))
+    .min_by(|a, b| a.0.total_cmp(&b.0))
+    .map(|(_, c)| c)
+}
+
+async fn fetch_from<P: LyricsProvider>(provider: &P, query: &SongQuery) -> Result<Option<Vec<LyricLine>>, String> {
+  let candidates = provider.search(query).await?;
+  let Some(candidate) = pick_candidate(provider, query, &candidates) else {
+    info!(provider = P::NAME, title = %query.title, candidates = candidates.len(), "no matching track");
+    return Ok(None);
+  };
+  debug!(provider = P::NAME, id = %candidate.id, title = %candidate.title, "picked track");
+  Ok(provider.fetch(candidate).await?.filter(|l| !l.is_empty()))
+}
+
+async fn fetch_named(name: &str, query: &SongQuery) -> Result<Option<Vec<LyricLine>>, String> {
+  match name {
+    Lrclib::NAME => fetch_from(&Lrclib, query).await,
+    Netease::NAME => fetch_from(&Netease, query).await,
+    QqMusic::NAME => fetch_from(&QqMusic, query).await,
+    _ => Err(format!("unknown lyrics provider: {}", name)),
+  }
+}
+
+/// Return local or cached lyrics, else try `providers` in order (with network consent) and cache
+/// the first hit as a bilingual LRC. Provider errors are logged and the next provider is tried; when every provider
+/// fails the last error is returned.
+pub(crate) async fn fetch_with_providers(state: &AppState, providers: &[&str], query: SongQuery, path: Option<&str>) -> Result<Option<Vec<LyricLine>>, String> {
+  if query.title.trim().is_empty() {
+    return Err("title argument is empty".to_string());
+  }
+  let (cached, cache) = find_cached_lyrics(state, &query.title, &query.artist, path)?;
+  if let Some(mut lyrics) = cached {
+    filter_lyrics(state, &mut lyrics)?;
+    return Ok(Some(lyrics));
+  }
+  if !providers.is_empty() {
+    ensure_consent(state, Feature::Network, "fetch_lyrics")?;
+  }
+
+  let mut last_error = None;
+  let mut any_ok = false;
+  for &name in providers {
+    match fetch_named(name, &query).await {
+      Ok(Some(mut lyrics)) => {
+        let tags = [("ti", query.title.clone()), ("ar", query.artist.clone()), ("re", format!("klok {}", name))];
+        store_cached_lyrics(&cache, &format_lrc(&lyrics, &tags));
+        filter_lyrics(state, &mut lyrics)?;
+        return Ok(Some(lyrics));
+      }
+      Ok(None) => any_ok = true,
+      Err(e) => {
+        warn!(provider = name, error = %e, "lyrics provider failed");
+        last_error = Some(e);
+      }
+    }
+  }
+  match last_error {
+    Some(e) if !any_ok => Err(e),
+    _ => Ok(None),
+  }
+}
+
+/// Fetch lyrics from every provider enabled in settings, in their configured order.
+#[tauri::command]
+pub async fn fetch_lyrics_auto(state: State<'_, AppState>, title: String, artist: String, duration: Option<f64>, path: Option<String>) -> Result<Option<Vec<LyricLine>>, String> {
+  let enabled: Vec<String> = {
+    let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    settings.lyrics_providers.iter().filter(|p| p.enabled).map(|p| p.name.clone()).collect()
+  };
+  let providers: Vec<&str> = enabled.iter().map(String::as_str).collect();
+  run_async(&state, "fetch_lyrics_auto", fetch_with_providers(&state, &providers, SongQuery { title, artist, duration }, path.as_deref())).await
+}
+
+#[test]
+pub fn test_match_cost() {
+  let candidate = |id: &str, title: &str, artist: &str, duration: f64| Candidate { id: id.to_string(), title: title.to_string(), artists: vec![artist.to_string()], duration: Some(duration) };
+  let query = |artist: &str, duration: f64| SongQuery { title: "杀破狼".to_string(), artist: artist.to_string(), duration: Some(duration) };
+  let candidates = vec![candidate("1", "杀破狼 (Live)", "JS", 250.0), candidate("2", "杀破狼", "JS", 231.0), candidate("3", "杀破狼", "Other", 230.0), candidate("4", "杀破狼", "JS", 300.0)];
+  let pick = |q: &SongQuery| pick_candidate(&Lrclib, q, &candidates).map(|c| c.id.clone());
+
+  assert_eq!(pick(&query("JS", 230.0)).as_deref(), Some("2"));
+  assert_eq!(pick(&query("", 300.5)).as_deref(), Some("4"));
+  assert_eq!(pick(&query("JS", 100.0)), None);
+}
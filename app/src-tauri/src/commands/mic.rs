@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use klok_core::denoise::Denoiser;
+
+use crate::commands::audio_device::backend;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::worker::Worker;
+use crate::AppState;
+
+/// Sample rate of the microphone, mono.
+pub const MIC_RATE: u32 = 48000;
+// samples handed to the consumers at a time, 10 ms
+const BLOCK: usize = MIC_RATE as usize / 100;
+
+type Consumer = Box<dyn FnMut(&[f32]) + Send>;
+
+/// The microphone, captured once and shared: each feature (pitch detection, recording, ...)
+/// attaches a consumer getting every block of samples, and capture runs while any is attached.
+#[derive(Default)]
+pub struct Mic {
+  consumers: Arc<Mutex<BTreeMap<String, Consumer>>>,
+  // the capture thread; it ends by itself when the device fails
+  capture: Option<Worker>,
+  // noise suppression before the consumers, see `set_mic_denoise`
+  denoise: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for Mic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let names: Vec<String> = self.consumers.lock().map(|c| c.keys().cloned().collect()).unwrap_or_default();
+    f.debug_struct("Mic").field("consumers", &names).field("capturing", &self.capture.as_ref().is_some_and(Worker::is_alive)).finish()
+  }
+}
+
+impl Mic {
+  pub fn new(denoise: bool) -> Mic {
+    Mic { denoise: Arc::new(AtomicBool::new(denoise)), ..Mic::default() }
+  }
+
+  /// Send the microphone to `consumer` under `name`, replacing a consumer of that name, and start
+  /// capturing if it wasn't, or if capture stopped on an error.
+  pub fn attach(&mut self, name: &str, consumer: impl FnMut(&[f32]) + Send + 'static) -> Result<(), String> {
+    self.consumers.lock().map_err(|e| format!("mic lock poisoned: {}", e))?.insert(name.to_string(), Box::new(consumer));
+    if !self.capturing() {
+      if let Err(e) = self.start() {
+        self.detach(name);
+        return Err(e);
+      }
+    }
+    Ok(())
+  }
+
+  /// Remove the consumer `name`, and stop capturing when it was the last one. Whether it existed.
+  pub fn detach(&mut self, name: &str) -> bool {
+    let Ok(mut consumers) = self.consumers.lock() else {
+      return false;
+    };
+    let removed = consumers.remove(name).is_some();
+    let idle = consumers.is_empty();
+    drop(consumers);
+    if idle && self.capture.take().is_some() {
+      info!("microphone closed");
+    }
+    removed
+  }
+
+  // Whether the capture thread is running, forgetting it once it has ended.
+  fn capturing(&mut self) -> bool {
+    if self.capture.as_ref().is_some_and(|c| !c.is_alive()) {
+      self.capture = None;
+    }
+    self.capture.is_some()
+  }
+
+  fn start(&mut self) -> Result<(), String> {
+    if self.capturing() {
+      return Ok(());
+    }
+    let input = backend::open_input(MIC_RATE, 1)?;
+    let (consumers, denoise) = (self.consumers.clone(), self.denoise.clone());
+    self.capture = Some(Worker::spawn("mic", "default".to_string(), move |stop| capture(input, consumers, denoise, stop))?);
+    info!("microphone opened");
+    Ok(())
+  }
+}
+
+fn capture(mut input: backend::InputStream, consumers: Arc<Mutex<BTreeMap<String, Consumer>>>, denoise: Arc<AtomicBool>, stop: Arc<AtomicBool>) {
+  let mut block = vec![0.0f32; BLOCK];
+  let mut denoiser = Denoiser::new(MIC_RATE);
+  while !stop.load(Ordering::Relaxed) {
+    if let Err(e) = input.read(&mut block) {
+      warn!(error = %e, "microphone stopped");
+      break;
+    }
+    if denoise.load(Ordering::Relaxed) {
+      denoiser.process(&mut block);
+    }
+    let Ok(mut consumers) = consumers.lock() else {
+      break;
+    };
+    for consumer in consumers.values_mut() {
+      consumer(&block);
+    }
+  }
+}
+
+/// Turn noise suppression of the microphone on or off, at once and for later sessions.
+#[tauri::command]
+pub fn set_mic_denoise(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+  ensure_unlocked(&state, "set_mic_denoise")?;
+  {
+    let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    settings.mic_denoise = enabled;
+    settings.save(&state.config_dir)?;
+  }
+  state.mic.lock().map_err(|e| format!("mic lock poisoned: {}", e))?.denoise.store(enabled, Ordering::Relaxed);
+  info!(enabled, "microphone noise suppression set");
+  Ok(())
+}
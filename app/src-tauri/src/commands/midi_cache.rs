@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use klok_core::midi::{load_midi_tracks_lenient, ConfidenceSource, MidiTrack, OverlapPolicy};
+
+use crate::AppState;
+
+// bump when the parser output changes, so entries written by an older version are parsed again
+const CACHE_VERSION: u32 = 2;
+const CACHE_DIR: &str = "cache/midi";
+
+/// A parsed MIDI file, stored as `<config dir>/cache/midi/<key>.json`.
+#[derive(Serialize, Deserialize)]
+struct CachedMidi {
+  version: u32,
+  /// SHA-256 of the file content the tracks were parsed from
+  hash: String,
+  tracks: Vec<MidiTrack>,
+  /// problems the lenient parse worked around, see `load_midi_tracks_lenient`
+  #[serde(default)]
+  warnings: Vec<String>,
+}
+
+// lowercase hex of a digest, for cache file names
+pub(crate) fn hex(digest: &[u8]) -> String {
+  digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// One entry per file and parse options, overwritten when the file content changes.
+fn cache_path(state: &AppState, midi: &Path, confidence: ConfidenceSource, overlap: OverlapPolicy) -> PathBuf {
+  let key = hex(&Sha256::digest(format!("{}|{:?}|{:?}", midi.display(), confidence, overlap)));
+  state.config_dir.join(CACHE_DIR).join(format!("{}.json", key))
+}
+
+fn read_cache(path: &Path, hash: &str) -> Option<CachedMidi> {
+  let cached: CachedMidi = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+  (cached.version == CACHE_VERSION && cached.hash == hash).then_some(cached)
+}
+
+fn write_cache(path: &Path, cached: &CachedMidi) -> Result<(), String> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+  }
+  let s = serde_json::to_vec(cached).map_err(|e| format!("failed to serialize midi cache: {}", e))?;
+  std::fs::write(path, s).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+// Parse (leniently, so damaged downloads still load) or take from the cache.
+fn load_cached(state: &AppState, midi: &Path, content: &[u8], confidence: ConfidenceSource, overlap: OverlapPolicy) -> Result<CachedMidi, String> {
+  let hash = hex(&Sha256::digest(content));
+  let path = cache_path(state, midi, confidence, overlap);
+  if let Some(cached) = read_cache(&path, &hash) {
+    debug!(midi = %midi.display(), "using cached midi");
+    return Ok(cached);
+  }
+  let (tracks, warnings) = load_midi_tracks_lenient(content, confidence, overlap)?;
+  for warning in &warnings {
+    warn!(midi = %midi.display(), %warning, "salvaged damaged midi");
+  }
+  let cached = CachedMidi { version: CACHE_VERSION, hash, tracks, warnings };
+  if let Err(e) = write_cache(&path, &cached) {
+    warn!(midi = %midi.display(), error = %e, "failed to cache parsed midi");
+  }
+  Ok(cached)
+}
+
+/// `load_midi_tracks_lenient` on the content of the MIDI file at `midi`, reusing the tracks from
+/// the last parse when the content is unchanged. The cache is best effort: unreadable entries are
+/// parsed again and failed writes only logged.
+pub(crate) fn load_midi_tracks_cached(state: &AppState, midi: &Path, content: &[u8], confidence: ConfidenceSource, overlap: OverlapPolicy) -> Result<Vec<MidiTrack>, String> {
+  Ok(load_cached(state, midi, content, confidence, overlap)?.tracks)
+}
+
+/// The problems worked around when parsing the MIDI file at `midi`, empty for an intact file.
+pub(crate) fn midi_warnings_cached(state: &AppState, midi: &Path, content: &[u8]) -> Result<Vec<String>, String> {
+  Ok(load_cached(state, midi, content, ConfidenceSource::None, OverlapPolicy::default())?.warnings)
+}
+
+#[test]
+pub fn test_midi_cache() {
+  let dir = std::env::temp_dir().join(format!("klok_midi_cache_{}", std::process::id()));
+  let state = AppState { res_dir: dir.clone(), config_dir: dir.clone(), ..Default::default() };
+  let midi = dir.join("song.mid");
+  let notes = [klok_core::midi::Note { note: 60, start: 0.0, duration: 1.0, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() }];
+  let content = klok_core::midi::encode_midi(&notes).expect("encode midi");
+
+  let parsed = load_midi_tracks_cached(&state, &midi, &content, ConfidenceSource::None, OverlapPolicy::default()).expect("parse");
+  let path = cache_path(&state, &midi, ConfidenceSource::None, OverlapPolicy::default());
+  let hash = hex(&Sha256::digest(&content));
+  assert_eq!(read_cache(&path, &hash).map(|c| c.tracks.len()), Some(parsed.len()));
+  assert_eq!(midi_warnings_cached(&state, &midi, &content), Ok(Vec::new()));
+  // a changed file isn't served from the cache
+  assert!(read_cache(&path, "other").is_none());
+  std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,166 @@
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use klok_core::live_midi::KeyEvent;
+
+use crate::commands::worker::Worker;
+use crate::AppState;
+
+/// Event carrying each key pressed or released on the open MIDI input, see [`start_midi_input`].
+pub const MIDI_INPUT_EVENT: &str = "midi-input";
+
+/// A MIDI port, e.g. a USB keyboard or an external synth.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiPort {
+  /// stable while the device stays connected, for `start_midi_input` and `start_midi_output`
+  pub id: String,
+  pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MidiInputEvent {
+  port: String,
+  #[serde(flatten)]
+  key: KeyEvent,
+}
+
+// Ports are ALSA raw MIDI devices (`/dev/snd/midiC<card>D<device>`), named after the card. They
+// work both ways, so inputs and outputs list the same devices.
+#[cfg(target_os = "linux")]
+pub(crate) mod backend {
+  use std::fs::File;
+  use std::io::Read;
+  use std::os::fd::AsRawFd;
+  use std::os::unix::fs::OpenOptionsExt;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::Arc;
+  use tauri::{AppHandle, Emitter};
+
+  use klok_core::live_midi::KeyStream;
+
+  use super::{MidiInputEvent, MidiPort, MIDI_INPUT_EVENT};
+
+  const DEVICE_DIR: &str = "/dev/snd";
+  // how often the reader checks whether it should stop
+  const POLL_MS: i32 = 100;
+
+  fn port_name(id: &str) -> Option<String> {
+    let (card, device) = id.strip_prefix("midiC")?.split_once('D')?;
+    let info = std::fs::read_to_string(format!("/proc/asound/card{}/midi{}", card, device)).ok()?;
+    let name = info.lines().next()?.trim();
+    (!name.is_empty()).then(|| name.to_string())
+  }
+
+  pub fn ports() -> Result<Vec<MidiPort>, String> {
+    let Ok(entries) = std::fs::read_dir(DEVICE_DIR) else {
+      return Ok(Vec::new());
+    };
+    let mut ports: Vec<MidiPort> = entries
+      .flatten()
+      .filter_map(|entry| {
+        let id = entry.file_name().to_string_lossy().to_string();
+        id.starts_with("midiC").then(|| MidiPort { name: port_name(&id).unwrap_or_else(|| id.clone()), id })
+      })
+      .collect();
+    ports.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(ports)
+  }
+
+  pub fn open(id: &str) -> Result<File, String> {
+    let path = std::path::Path::new(DEVICE_DIR).join(id);
+    std::fs::OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(&path).map_err(|e| format!("failed to open midi input {}: {}", id, e))
+  }
+
+  pub fn open_output(id: &str) -> Result<File, String> {
+    let path = std::path::Path::new(DEVICE_DIR).join(id);
+    std::fs::OpenOptions::new().write(true).open(&path).map_err(|e| format!("failed to open midi output {}: {}", id, e))
+  }
+
+  pub fn read(app: AppHandle, port: String, mut file: File, stop: Arc<AtomicBool>) {
+    let mut keys = KeyStream::new();
+    let mut buf = [0u8; 256];
+    while !stop.load(Ordering::Relaxed) {
+      let mut fd = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+      // SAFETY: `fd` is one valid pollfd for the open device
+      if unsafe { libc::poll(&mut fd, 1, POLL_MS) } <= 0 {
+        continue;
+      }
+      match file.read(&mut buf) {
+        Ok(0) => break,
+        Ok(n) => {
+          for key in keys.feed(&buf[..n]) {
+            if let Err(e) = app.emit(MIDI_INPUT_EVENT, MidiInputEvent { port: port.clone(), key }) {
+              warn!(error = %e, "failed to emit midi input");
+            }
+          }
+        }
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted) => {}
+        Err(e) => {
+          // unplugged
+          warn!(%port, error = %e, "midi input closed");
+          break;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) mod backend {
+  use std::sync::atomic::AtomicBool;
+  use std::sync::Arc;
+  use tauri::AppHandle;
+
+  use super::MidiPort;
+
+  pub fn ports() -> Result<Vec<MidiPort>, String> {
+    Ok(Vec::new())
+  }
+
+  pub fn open(_id: &str) -> Result<std::fs::File, String> {
+    Err("midi input is not supported on this platform".to_string())
+  }
+
+  pub fn open_output(_id: &str) -> Result<std::fs::File, String> {
+    Err("midi output is not supported on this platform".to_string())
+  }
+
+  pub fn read(_app: AppHandle, _port: String, _file: std::fs::File, _stop: Arc<AtomicBool>) {}
+}
+
+/// The connected MIDI input ports.
+#[tauri::command]
+pub fn list_midi_inputs() -> Result<Vec<MidiPort>, String> {
+  backend::ports()
+}
+
+/// Open the input port `id` (from `list_midi_inputs`) for instrument mode, replacing the one open
+/// before: its note on/off messages are emitted as `midi-input` events
+/// `{port, kind: "note_on" | "note_off", channel, note, velocity}` until `stop_midi_input`.
+#[tauri::command]
+pub fn start_midi_input(app: AppHandle, state: State<'_, AppState>, id: String) -> Result<(), String> {
+  // only listed ports, so the id can't name another file
+  if !backend::ports()?.iter().any(|p| p.id == id) {
+    return Err(format!("unknown midi input: {}", id));
+  }
+  let mut current = state.midi_input.lock().map_err(|e| format!("midi input lock poisoned: {}", e))?;
+  // close the previous port first, a device can only be opened once
+  current.take();
+  let file = backend::open(&id)?;
+  let port = id.clone();
+  *current = Some(Worker::spawn("midi-input", id.clone(), move |stop| backend::read(app, port, file, stop))?);
+  info!(port = %id, "midi input started");
+  Ok(())
+}
+
+/// Close the open MIDI input, if any.
+#[tauri::command]
+pub fn stop_midi_input(state: State<'_, AppState>) -> Result<(), String> {
+  let handle = state.midi_input.lock().map_err(|e| format!("midi input lock poisoned: {}", e))?.take();
+  if let Some(handle) = handle {
+    info!(port = %handle.label, "midi input stopped");
+  }
+  Ok(())
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use klok_core::live_midi::{all_notes_off, key_schedule, KeyEvent};
+use klok_core::midi::{flatten_tracks, melody_tracks};
+use klok_core::simplify::SimplifyOptions;
+
+use crate::commands::load_midi::read_midi_tracks;
+use crate::commands::midi_input::{backend, MidiPort};
+use crate::commands::worker::Worker;
+use crate::AppState;
+
+// longest sleep between checks of the stop flag
+const MAX_SLEEP: Duration = Duration::from_millis(20);
+
+// Send `events` (song seconds) from song time `from` on, at `rate` times real time, then silence
+// the channels used.
+fn play(mut file: File, port: String, events: Vec<(f64, KeyEvent)>, from: f64, rate: f64, stop: Arc<AtomicBool>) {
+  let started = Instant::now();
+  let mut channels = BTreeSet::new();
+  'events: for (time, key) in events {
+    let due = Duration::from_secs_f64(((time - from) / rate).max(0.0));
+    loop {
+      if stop.load(Ordering::Relaxed) {
+        break 'events;
+      }
+      let elapsed = started.elapsed();
+      if elapsed >= due {
+        break;
+      }
+      std::thread::sleep((due - elapsed).min(MAX_SLEEP));
+    }
+    if let KeyEvent::NoteOn { channel, .. } = key {
+      channels.insert(channel);
+    }
+    if let Err(e) = file.write_all(&key.to_bytes()) {
+      warn!(%port, error = %e, "midi output closed");
+      return;
+    }
+  }
+  for channel in channels {
+    file.write_all(&all_notes_off(channel)).ok();
+  }
+}
+
+/// The connected MIDI output ports, for an external synth or a software synth's virtual port.
+#[tauri::command]
+pub fn list_midi_outputs() -> Result<Vec<MidiPort>, String> {
+  backend::ports()
+}
+
+/// Play the reference melody of the song at `path` (as the player shows it: `load_midi` with
+/// `transpose` and the default simplification) to the output port `id`, starting at song time
+/// `position` and running at `rate` (playback speed, default 1), replacing what was playing. Call
+/// again after a seek or speed change to stay in sync with the audio, and `stop_midi_output` on pause.
+#[tauri::command]
+pub fn start_midi_output(state: State<'_, AppState>, id: String, path: String, position: f64, rate: Option<f64>, transpose: Option<i32>) -> Result<(), String> {
+  // only listed ports, so the id can't name another file
+  if !backend::ports()?.iter().any(|p| p.id == id) {
+    return Err(format!("unknown midi output: {}", id));
+  }
+  let rate = rate.unwrap_or(1.0);
+  if !(rate.is_finite() && rate > 0.0) {
+    return Err(format!("invalid playback rate: {}", rate));
+  }
+  let tracks = read_midi_tracks(&state, &path, None, None, Some(true), transpose, Some(SimplifyOptions::default()))?;
+  let events = key_schedule(&flatten_tracks(melody_tracks(tracks, None)), position);
+
+  let mut current = state.midi_output.lock().map_err(|e| format!("midi output lock poisoned: {}", e))?;
+  // the previous melody silences its notes before the port is opened again
+  current.take();
+  let file = backend::open_output(&id)?;
+  let port = id.clone();
+  *current = Some(Worker::spawn("midi-output", id.clone(), move |stop| play(file, port, events, position, rate, stop))?);
+  debug!(port = %id, %path, position, rate, "midi output started");
+  Ok(())
+}
+
+/// Stop the melody sent to the MIDI output, if any, releasing its notes.
+#[tauri::command]
+pub fn stop_midi_output(state: State<'_, AppState>) -> Result<(), String> {
+  let handle = state.midi_output.lock().map_err(|e| format!("midi output lock poisoned: {}", e))?.take();
+  if let Some(handle) = handle {
+    debug!(port = %handle.label, "midi output stopped");
+  }
+  Ok(())
+}
@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use tauri::State;
+
+use klok_core::karaoke::CenterCancel;
+use klok_core::mix::overlay;
+use klok_core::pcm::Pcm;
+use klok_core::pitch_shift::PitchShifter;
+
+use crate::commands::decode_audio::decode_file;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::player::find_stems;
+use crate::commands::recording::{read_session, take_path, Session};
+use crate::commands::storage::write_export;
+use crate::commands::timeout::run_blocking;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// File format of an exported take.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TakeFormat {
+  Wav,
+  Mp3,
+  Ogg,
+}
+
+// The take of `session` sung over what was heard: the song's non-vocal stem (the song through the
+// karaoke filter without stems) from where the take started and shifted to the sung key, the
+// voice moved earlier by the latency. Scaled down if the sum would clip.
+fn mix_take(state: &AppState, session: &Session, song: &std::path::Path, take: &Pcm) -> Result<Pcm, String> {
+  let mut out = match find_stems(song).0 {
+    Some(stem) => decode_file(state, &stem)?.slice(session.song_offset, Some(take.duration())),
+    None => {
+      let mut pcm = decode_file(state, song)?.slice(session.song_offset, Some(take.duration()));
+      CenterCancel::new(pcm.sample_rate).process(&mut pcm.samples, pcm.channels, 1.0);
+      pcm
+    }
+  };
+  if session.key_shift != 0.0 {
+    let mut shifter = PitchShifter::new(out.channels, out.sample_rate);
+    shifter.set_semitones(session.key_shift);
+    shifter.process(&mut out.samples);
+  }
+  // silence under a take running past the end of the song
+  let frames = (take.duration() * out.sample_rate as f64).round() as usize;
+  out.samples.resize(frames.max(out.frames()) * out.channels.max(1) as usize, 0.0);
+  overlay(&mut out, take, -session.latency, 1.0);
+  let peak = out.samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+  if peak > 1.0 {
+    out.samples.iter_mut().for_each(|s| *s /= peak);
+  }
+  Ok(out)
+}
+
+/// Mix the take of `session_id` over the song's backing and write it next to the song as
+/// `song_take_<id>.wav`, or to the export `target` of that name. The backing (the non-vocal stem,
+/// else the song) must be a WAV file. Returns the written path.
+#[tauri::command]
+pub async fn export_take(state: State<'_, AppState>, session_id: String, format: TakeFormat, target: Option<String>) -> Result<String, String> {
+  ensure_unlocked(&state, "export_take")?;
+  if format != TakeFormat::Wav {
+    return Err(format!("exporting takes as {:?} is not supported yet, only wav", format).to_lowercase());
+  }
+  let session = read_session(&state, &session_id)?;
+  let song = state.resolve(&session.song).ok_or_else(|| format!("resource not found: {}", session.song))?;
+  let take = take_path(&state, &session_id)?;
+  let (manifest, st) = (session.clone(), state.inner().clone());
+  let mixed = run_blocking(&state, "export_take", move |_| {
+    let take = decode_file(&st, &take)?;
+    Ok(mix_take(&st, &manifest, &song, &take)?.to_wav())
+  })
+  .await?;
+  let written = write_export(&state, target.as_deref(), &with_extension(&session.song, &format!("_take_{}.wav", session.id)), &mixed)?;
+  info!(path = %written, id = %session.id, "exported take");
+  Ok(written)
+}
+
+#[test]
+pub fn test_mix_take() {
+  let dir = std::env::temp_dir().join(format!("klok_mixdown_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let song = dir.join("song.wav");
+  std::fs::write(&song, b"not decoded, the stem is used").unwrap();
+  let stem = Pcm { sample_rate: 10, channels: 1, samples: (0..40).map(|i| i as f32 / 100.0).collect() };
+  std::fs::write(dir.join("song_non_vocals.wav"), stem.to_wav()).unwrap();
+
+  let session = Session { id: "1".to_string(), song: "song.wav".to_string(), key_shift: 0.0, song_offset: 1.0, latency: 0.2, sample_rate: 10, duration: 0.5, started_at: 0 };
+  let take = Pcm { sample_rate: 10, channels: 1, samples: vec![0.0, 0.0, 0.5, 0.0, 0.0] };
+  let out = mix_take(&AppState::default(), &session, &song, &take).unwrap();
+  std::fs::remove_dir_all(&dir).ok();
+  // the stem from 1 s on, the voice two frames (the latency) earlier
+  assert_eq!(out.frames(), 5);
+  let expected = [0.1, 0.11, 0.12, 0.13, 0.14];
+  assert!((out.samples[0] - 0.5 - expected[0]).abs() < 1e-3);
+  assert!(out.samples[1..].iter().zip(&expected[1..]).all(|(a, b)| (a - b).abs() < 1e-3));
+}
@@ -1,10 +1,62 @@
+pub mod align;
+pub mod assign_mic_turns;
+pub mod attract;
+pub mod audio_device;
+pub mod background;
+pub mod backing;
+pub mod click_track;
+pub mod consent;
+pub mod convert_lyrics;
+pub mod countdown;
+pub mod decode_audio;
+pub mod difficulty;
+pub mod fetch_lyrics;
+pub mod gain;
 pub mod get_metadata;
+pub mod guide;
+pub mod kiosk;
+pub mod library;
 pub mod load_audio;
+pub mod load_lyrics;
 pub mod load_midi;
 pub mod load_playlist;
+pub mod lyric_frames;
+pub mod lyrics_provider;
+pub mod mic;
+pub mod midi_cache;
+pub mod midi_input;
+pub mod midi_output;
+pub mod mixdown;
+pub mod monitor;
+pub mod netease;
+pub mod organize_library;
+pub mod perf;
+pub mod pitch_detection;
+pub mod player;
+pub mod practice_mix;
+pub mod profanity;
+pub mod qqmusic;
+pub mod recording;
+pub mod romanize;
+pub mod roulette;
+pub mod save_lyrics;
+pub mod save_midi;
+pub mod scoring_profile;
+pub mod session;
+pub mod setlist;
+pub mod shift_lyrics;
+pub mod song_library;
+pub mod storage;
+pub mod sylt;
+pub mod timeout;
+pub mod transition;
+pub mod ultrastar;
+pub mod validate_lyrics;
+pub mod waveform;
+pub mod worker;
 
 
-const COMMON_EXT: [&str; 3] = [".mp3", ".m4a", ".flac"];
+pub(crate) const COMMON_EXT: [&str; 3] = [".mp3", ".m4a", ".flac"];
 
 pub fn with_extension(filename: &str, extension: &str) -> String {
   if filename.ends_with(extension) {
@@ -18,3 +70,8 @@ pub fn with_extension(filename: &str, extension: &str) -> String {
     format!("{}{}", filename, extension)
   }
 }
+
+/// Replace characters that can't appear in file names on common platforms.
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+  name.chars().map(|c| if "/\\:*?\"<>|".contains(c) { '_' } else { c }).collect::<String>().trim().to_string()
+}
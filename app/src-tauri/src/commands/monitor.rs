@@ -0,0 +1,65 @@
+use tauri::State;
+
+use klok_core::reverb::Reverb;
+
+use crate::commands::audio_device::backend;
+use crate::commands::consent::ensure_consent;
+use crate::commands::mic::MIC_RATE;
+use crate::commands::player::output_device;
+use crate::settings::Feature;
+use crate::AppState;
+
+const CONSUMER: &str = "monitor";
+// seconds the monitor output buffers, as little as the device keeps up with so singers hear
+// themselves without a distracting delay
+const MONITOR_LATENCY: f64 = 0.02;
+/// Loudest monitoring gain, relative to the microphone.
+pub const MAX_MONITOR_GAIN: f32 = 4.0;
+
+/// How the microphone is played back, see `set_monitoring`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Monitoring {
+  pub enabled: bool,
+  /// 0 (dry) to 1
+  pub reverb: f32,
+  pub gain: f32,
+}
+
+/// Play the microphone back on the audio output, with `reverb` from 0 (dry) to 1 and at `gain`
+/// (up to `MAX_MONITOR_GAIN`), like a karaoke machine. Changing the reverb or gain while on
+/// applies at once; the output is the one selected when monitoring was turned on. Needs the
+/// microphone consent.
+#[tauri::command]
+pub fn set_monitoring(state: State<'_, AppState>, enabled: bool, reverb: f32, gain: f32) -> Result<(), String> {
+  if enabled {
+    ensure_consent(&state, Feature::Microphone, "set_monitoring")?;
+  }
+  let device = output_device(&state)?;
+  let settings = Monitoring { enabled, reverb: reverb.clamp(0.0, 1.0), gain: gain.clamp(0.0, MAX_MONITOR_GAIN) };
+  let was = std::mem::replace(&mut *state.monitoring.lock().map_err(|e| format!("monitoring lock poisoned: {}", e))?, settings).enabled;
+  let mut mic = state.mic.lock().map_err(|e| format!("mic lock poisoned: {}", e))?;
+  if enabled && !was {
+    let started = backend::open_output(device.as_deref(), MIC_RATE, 1, MONITOR_LATENCY).and_then(|mut output| {
+      let (shared, mut room, mut failed) = (state.monitoring.clone(), Reverb::new(MIC_RATE), false);
+      mic.attach(CONSUMER, move |block| {
+        let Monitoring { reverb, gain, .. } = shared.lock().map(|m| *m).unwrap_or_default();
+        let mut out: Vec<f32> = block.iter().map(|s| s * gain).collect();
+        room.process(&mut out, reverb);
+        if let Err(e) = output.write(&out) {
+          if !std::mem::replace(&mut failed, true) {
+            warn!(error = %e, "monitoring output failed");
+          }
+        }
+      })
+    });
+    if let Err(e) = started {
+      state.monitoring.lock().map_err(|e| format!("monitoring lock poisoned: {}", e))?.enabled = false;
+      return Err(e);
+    }
+    info!(reverb = settings.reverb, gain = settings.gain, "monitoring started");
+  } else if !enabled && was {
+    mic.detach(CONSUMER);
+    info!("monitoring stopped");
+  }
+  Ok(())
+}
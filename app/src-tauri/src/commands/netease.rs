@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use tauri::State;
+
+use klok_core::lrc::parse_lrc;
+use klok_core::lyrics::{merge_duplicate_timestamps, merge_translation, LyricLine};
+
+use crate::commands::fetch_lyrics::USER_AGENT;
+use crate::commands::lyrics_provider::{fetch_with_providers, Candidate, LyricsProvider, SongQuery};
+use crate::commands::timeout::run_async;
+use crate::AppState;
+
+const SEARCH_URL: &str = "https://music.163.com/api/search/get";
+const LYRIC_URL: &str = "https://music.163.com/api/song/lyric";
+const SEARCH_LIMIT: u32 = 10;
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+  result: Option<SearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+  #[serde(default)]
+  songs: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Track {
+  id: u64,
+  name: String,
+  #[serde(default)]
+  artists: Vec<Artist>,
+  /// milliseconds
+  #[serde(default)]
+  duration: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artist {
+  name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LyricResponse {
+  lrc: Option<LyricBody>,
+  tlyric: Option<LyricBody>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LyricBody {
+  #[serde(default)]
+  lyric: String,
+}
+
+/// NetEase Cloud Music, which also carries translated lyrics for many songs.
+pub struct Netease;
+
+impl LyricsProvider for Netease {
+  const NAME: &'static str = "netease";
+
+  async fn search(&self, query: &SongQuery) -> Result<Vec<Candidate>, String> {
+    let keywords = format!("{} {}", query.title, query.artist).trim().to_string();
+    let tracks: Vec<Track> = reqwest::Client::new()
+      .get(SEARCH_URL)
+      .header(reqwest::header::USER_AGENT, USER_AGENT)
+      .query(&[("s", keywords), ("type", "1".to_string()), ("limit", SEARCH_LIMIT.to_string())])
+      .send()
+      .await
+      .map_err(|e| format!("netease search failed: {}", e))?
+      .json::<SearchResponse>()
+      .await
+      .map_err(|e| format!("invalid netease search response: {}", e))?
+      .result
+      .map(|r| r.songs)
+      .unwrap_or_default();
+    Ok(
+      tracks
+        .into_iter()
+        .map(|t| Candidate {
+          id: t.id.to_string(),
+          title: t.name,
+          artists: t.artists.into_iter().map(|a| a.name).collect(),
+          duration: (t.duration > 0).then(|| t.duration as f64 / 1000.0),
+        })
+        .collect(),
+    )
+  }
+
+  async fn fetch(&self, candidate: &Candidate) -> Result<Option<Vec<LyricLine>>, String> {
+    let response: LyricResponse = reqwest::Client::new()
+      .get(LYRIC_URL)
+      .header(reqwest::header::USER_AGENT, USER_AGENT)
+      .query(&[("id", candidate.id.as_str()), ("lv", "1"), ("tv", "1")])
+      .send()
+      .await
+      .map_err(|e| format!("netease lyric request failed: {}", e))?
+      .json()
+      .await
+      .map_err(|e| format!("invalid netease lyric response: {}", e))?;
+
+    let mut lyrics = parse_lrc(response.lrc.map(|l| l.lyric).unwrap_or_default().as_str());
+    if lyrics.is_empty() {
+      return Ok(None);
+    }
+    if let Some(translated) = response.tlyric.filter(|t| !t.lyric.trim().is_empty()) {
+      merge_translation(&mut lyrics, &parse_lrc(&translated.lyric));
+    }
+    Ok(Some(merge_duplicate_timestamps(lyrics)))
+  }
+}
+
+/// Fetch lyrics from NetEase Cloud Music: search by title and artist, pick the track that best
+/// matches (title, artist and duration), and download its original and translated LRC. The result
+/// is cached like [`crate::commands::fetch_lyrics::fetch_lyrics`], as a bilingual LRC.
+#[tauri::command]
+pub async fn fetch_lyrics_netease(state: State<'_, AppState>, title: String, artist: String, duration: Option<f64>, path: Option<String>) -> Result<Option<Vec<LyricLine>>, String> {
+  run_async(&state, "fetch_lyrics_netease", fetch_with_providers(&state, &[Netease::NAME], SongQuery { title, artist, duration }, path.as_deref())).await
+}
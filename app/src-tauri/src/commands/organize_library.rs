@@ -0,0 +1,191 @@
+use lofty::{Accessor, Probe, TaggedFileExt};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_playlist::{collect_files, relative_url, UNEXPECTED_SUFFIX};
+use crate::commands::song_library::song_files;
+use crate::commands::{sanitize_file_name, COMMON_EXT};
+use crate::AppState;
+
+const DEFAULT_PATTERN: &str = "{artist}/{title}";
+
+#[derive(Debug, Serialize)]
+pub struct SongMove {
+  /// playlist url before and after organizing
+  pub from: String,
+  pub to: String,
+}
+
+#[derive(Default)]
+struct SongTags {
+  title: Option<String>,
+  artist: Option<String>,
+  album: Option<String>,
+}
+
+fn read_tags(path: &Path) -> SongTags {
+  let Ok(tagged) = Probe::open(path).and_then(|p| p.read()) else {
+    return SongTags::default();
+  };
+  let Some(tag) = tagged.primary_tag().or_else(|| tagged.first_tag()) else {
+    return SongTags::default();
+  };
+  let text = |v: Option<std::borrow::Cow<'_, str>>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+  SongTags { title: text(tag.title()), artist: text(tag.artist()), album: text(tag.album()) }
+}
+
+/// Relative path (without extension) for a song: `pattern` with `{artist}`, `{title}` and
+/// `{album}` filled in. Tag values are sanitized so they can't introduce extra directories.
+fn target_stem(pattern: &str, tags: &SongTags, fallback_title: &str) -> String {
+  let field = |v: Option<&str>, default: &str| sanitize_file_name(v.unwrap_or(default));
+  pattern
+    .split('/')
+    .map(|component| {
+      let filled = component
+        .replace("{artist}", &field(tags.artist.as_deref(), "Unknown Artist"))
+        .replace("{title}", &field(tags.title.as_deref(), fallback_title))
+        .replace("{album}", &field(tags.album.as_deref(), "Unknown Album"));
+      sanitize_file_name(&filled)
+    })
+    .filter(|c| !c.is_empty() && c != "." && c != "..")
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// Move songs in res into a tag-based layout, `{artist}/{title}` by default. Companion files
+/// (lyrics, stems, pitch MIDI) move with their song and settings that refer to songs by url are
+/// updated. All moves are checked for conflicts before anything is renamed and undone if one fails.
+/// With `dry_run` only the planned moves are returned.
+#[tauri::command]
+pub fn organize_library(state: State<'_, AppState>, pattern: Option<String>, dry_run: Option<bool>) -> Result<Vec<SongMove>, String> {
+  if !dry_run.unwrap_or(false) {
+    ensure_unlocked(&state, "organize_library")?;
+  }
+  let pattern = pattern.filter(|p| !p.trim().is_empty()).unwrap_or_else(|| DEFAULT_PATTERN.to_string());
+  let res_dir = &state.res_dir;
+  let mut files = Vec::new();
+  collect_files(res_dir, &mut files).map_err(|e| format!("failed to read res_dir {}: {}", res_dir.display(), e))?;
+  files.sort();
+
+  let mut moves: Vec<SongMove> = Vec::new();
+  // (from, to) for every file, songs and companions
+  let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+  for song in files.iter().filter(|p| is_song(p)) {
+    let (Some(url), Some(ext), Some(stem)) = (relative_url(res_dir, song), song.extension().and_then(|e| e.to_str()), song.file_stem().and_then(|s| s.to_str())) else {
+      continue;
+    };
+    let target = target_stem(&pattern, &read_tags(song), stem);
+    let to = format!("{}.{}", target, ext);
+    if to == url || target.is_empty() {
+      continue;
+    }
+    let target_path = res_dir.join(&target);
+    let (target_dir, target_name) = (target_path.parent().unwrap_or(res_dir), target_path.file_name().and_then(|n| n.to_str()).unwrap_or_default());
+    renames.extend(song_renames(song, target_dir, target_name)?);
+    moves.push(SongMove { from: url, to });
+  }
+
+  let sources: HashSet<&PathBuf> = renames.iter().map(|(from, _)| from).collect();
+  let mut targets: HashSet<&PathBuf> = HashSet::new();
+  for (_, to) in &renames {
+    if !targets.insert(to) || (to.exists() && !sources.contains(to)) {
+      return Err(format!("organize would overwrite {}", to.display()));
+    }
+  }
+  if dry_run.unwrap_or(false) || moves.is_empty() {
+    return Ok(moves);
+  }
+
+  apply_renames(&renames)?;
+  info!(songs = moves.len(), files = renames.len(), %pattern, "organized library");
+
+  let renamed: BTreeMap<&str, &str> = moves.iter().map(|m| (m.from.as_str(), m.to.as_str())).collect();
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.hidden_songs = settings.hidden_songs.iter().map(|u| renamed.get(u.as_str()).map_or_else(|| u.clone(), |t| t.to_string())).collect();
+  settings.song_countdowns = std::mem::take(&mut settings.song_countdowns)
+    .into_iter()
+    .map(|(u, c)| (renamed.get(u.as_str()).map_or(u, |t| t.to_string()), c))
+    .collect();
+  settings.song_difficulties = std::mem::take(&mut settings.song_difficulties)
+    .into_iter()
+    .map(|(u, d)| (renamed.get(u.as_str()).map_or(u, |t| t.to_string()), d))
+    .collect();
+  settings.song_backgrounds = std::mem::take(&mut settings.song_backgrounds)
+    .into_iter()
+    .map(|(u, b)| (renamed.get(u.as_str()).map_or(u, |t| t.to_string()), b))
+    .collect();
+  settings.song_loudness = std::mem::take(&mut settings.song_loudness)
+    .into_iter()
+    .map(|(u, l)| (renamed.get(u.as_str()).map_or(u, |t| t.to_string()), l))
+    .collect();
+  settings.song_channel_modes = std::mem::take(&mut settings.song_channel_modes)
+    .into_iter()
+    .map(|(u, m)| (renamed.get(u.as_str()).map_or(u, |t| t.to_string()), m))
+    .collect();
+  settings.attract.playlist = std::mem::take(&mut settings.attract.playlist).into_iter().map(|u| renamed.get(u.as_str()).map_or(u, |t| t.to_string())).collect();
+  settings.save(&state.config_dir)?;
+  Ok(moves)
+}
+
+// (from, to) for `song` and its companions, moved to `target_dir` and named after `target_name`.
+fn song_renames(song: &Path, target_dir: &Path, target_name: &str) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+  let stem = song.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+  Ok(
+    song_files(song)?
+      .into_iter()
+      .map(|file| {
+        let name = file.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let to = target_dir.join(format!("{}{}", target_name, name.strip_prefix(stem).unwrap_or(name)));
+        (file, to)
+      })
+      .collect(),
+  )
+}
+
+fn is_song(path: &Path) -> bool {
+  let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+  let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+  COMMON_EXT.iter().any(|ext| name.ends_with(ext)) && !UNEXPECTED_SUFFIX.iter().any(|sfx| stem.ends_with(sfx))
+}
+
+// Rename every file, moving already-renamed files back if one fails.
+fn apply_renames(renames: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+  for (i, (from, to)) in renames.iter().enumerate() {
+    let result = to
+      .parent()
+      .map_or(Ok(()), std::fs::create_dir_all)
+      .and_then(|_| std::fs::rename(from, to))
+      .map_err(|e| format!("failed to move {} to {}: {}", from.display(), to.display(), e));
+    if let Err(e) = result {
+      for (from, to) in renames[..i].iter().rev() {
+        if let Err(undo) = std::fs::rename(to, from) {
+          error!(from = %to.display(), to = %from.display(), error = %undo, "failed to undo move");
+        }
+      }
+      return Err(e);
+    }
+  }
+  Ok(())
+}
+
+#[test]
+pub fn test_target_stem() {
+  let tags = SongTags { title: Some("Song: Live".to_string()), artist: Some("AC/DC".to_string()), album: None };
+  assert_eq!(target_stem("{artist}/{title}", &tags, "x"), "AC_DC/Song_ Live");
+  assert_eq!(target_stem("{artist}/{album}/{title}", &SongTags::default(), "杀破狼"), "Unknown Artist/Unknown Album/杀破狼");
+  assert_eq!(target_stem("../{title}", &SongTags::default(), "a"), "a");
+
+  // moving `Love` leaves `Love_Story`, whose name starts with it, where it is
+  let dir = std::env::temp_dir().join(format!("klok_organize_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  for name in ["Love.mp3", "Love.lrc", "Love_vocals.mp3", "Love_Story.mp3", "Love_Story.lrc", "Love_Story_vocals.mp3"] {
+    std::fs::write(dir.join(name), b"").unwrap();
+  }
+  let renames = song_renames(&dir.join("Love.mp3"), &dir.join("Artist"), "Love").unwrap();
+  std::fs::remove_dir_all(&dir).ok();
+  let names: Vec<(&str, &str)> = renames.iter().map(|(from, to)| (from.file_name().unwrap().to_str().unwrap(), to.strip_prefix(&dir).unwrap().to_str().unwrap())).collect();
+  assert_eq!(names, vec![("Love.lrc", "Artist/Love.lrc"), ("Love.mp3", "Artist/Love.mp3"), ("Love_vocals.mp3", "Artist/Love_vocals.mp3")]);
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::AppState;
+
+/// Latency of one command since startup.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct CommandStats {
+  pub calls: u64,
+  /// calls that returned an error, timeouts included
+  pub failures: u64,
+  pub total_ms: f64,
+  pub mean_ms: f64,
+  pub max_ms: f64,
+}
+
+/// The last library scan by `load_playlist`.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct ScanStats {
+  pub seconds: f64,
+  pub songs: usize,
+}
+
+/// Audio files read by `load_audio` since startup.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct ThroughputStats {
+  pub files: u64,
+  pub bytes: u64,
+  pub seconds: f64,
+  /// `None` until something was read
+  pub mb_per_second: Option<f64>,
+}
+
+/// Timings reported by `get_perf_stats`. Playback and pitch display run in the webview, so their
+/// load doesn't show up here.
+#[derive(Clone, Debug, Serialize)]
+pub struct PerfStats {
+  pub uptime_seconds: f64,
+  /// commands that run through `timeout`, keyed by command name
+  pub commands: BTreeMap<String, CommandStats>,
+  pub last_scan: Option<ScanStats>,
+  pub audio_reads: ThroughputStats,
+}
+
+/// Counters behind [`PerfStats`], kept in `AppState` for the whole session.
+#[derive(Debug)]
+pub struct PerfCounters {
+  started: Instant,
+  commands: BTreeMap<String, CommandStats>,
+  last_scan: Option<ScanStats>,
+  audio_reads: ThroughputStats,
+}
+
+impl Default for PerfCounters {
+  fn default() -> Self {
+    PerfCounters { started: Instant::now(), commands: BTreeMap::new(), last_scan: None, audio_reads: ThroughputStats::default() }
+  }
+}
+
+impl PerfCounters {
+  pub fn record_command(&mut self, command: &str, elapsed: Duration, ok: bool) {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    let stats = self.commands.entry(command.to_string()).or_default();
+    stats.calls += 1;
+    stats.failures += u64::from(!ok);
+    stats.total_ms += ms;
+    stats.mean_ms = stats.total_ms / stats.calls as f64;
+    stats.max_ms = stats.max_ms.max(ms);
+  }
+
+  pub fn record_scan(&mut self, elapsed: Duration, songs: usize) {
+    self.last_scan = Some(ScanStats { seconds: elapsed.as_secs_f64(), songs });
+  }
+
+  pub fn record_audio_read(&mut self, bytes: u64, elapsed: Duration) {
+    let reads = &mut self.audio_reads;
+    reads.files += 1;
+    reads.bytes += bytes;
+    reads.seconds += elapsed.as_secs_f64();
+    reads.mb_per_second = (reads.seconds > 0.0).then(|| reads.bytes as f64 / 1_000_000.0 / reads.seconds);
+  }
+
+  pub fn snapshot(&self) -> PerfStats {
+    PerfStats {
+      uptime_seconds: self.started.elapsed().as_secs_f64(),
+      commands: self.commands.clone(),
+      last_scan: self.last_scan.clone(),
+      audio_reads: self.audio_reads.clone(),
+    }
+  }
+}
+
+/// Update the perf counters. Stats are best-effort, so a poisoned lock skips the update rather
+/// than failing the command being measured.
+pub(crate) fn record(state: &AppState, update: impl FnOnce(&mut PerfCounters)) {
+  if let Ok(mut perf) = state.perf.lock() {
+    update(&mut perf);
+  }
+}
+
+/// Internal timings since startup, for attaching to "it's slow" reports.
+#[tauri::command]
+pub fn get_perf_stats(state: State<'_, AppState>) -> Result<PerfStats, String> {
+  let perf = state.perf.lock().map_err(|e| format!("perf lock poisoned: {}", e))?;
+  Ok(perf.snapshot())
+}
+
+#[test]
+pub fn test_perf_counters() {
+  let mut perf = PerfCounters::default();
+  perf.record_command("load_playlist", Duration::from_millis(30), true);
+  perf.record_command("load_playlist", Duration::from_millis(10), false);
+  perf.record_audio_read(4_000_000, Duration::from_secs(2));
+  perf.record_scan(Duration::from_millis(1500), 12);
+
+  let stats = perf.snapshot();
+  assert_eq!(stats.commands["load_playlist"], CommandStats { calls: 2, failures: 1, total_ms: 40.0, mean_ms: 20.0, max_ms: 30.0 });
+  assert_eq!(stats.audio_reads.mb_per_second, Some(2.0));
+  assert_eq!(stats.last_scan, Some(ScanStats { seconds: 1.5, songs: 12 }));
+  assert!(PerfCounters::default().snapshot().audio_reads.mb_per_second.is_none());
+}
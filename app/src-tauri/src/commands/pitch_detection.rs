@@ -0,0 +1,43 @@
+use tauri::{AppHandle, Emitter, State};
+
+use klok_core::yin::PitchTracker;
+
+use crate::commands::consent::ensure_consent;
+use crate::commands::mic::MIC_RATE;
+use crate::settings::Feature;
+use crate::AppState;
+
+/// Event carrying each pitch detected on the microphone, `{time, f0, confidence}`, 100 a second.
+pub const PITCH_EVENT: &str = "pitch";
+const CONSUMER: &str = "pitch";
+// YIN runs on the microphone averaged down to a third of its rate, plenty for voices
+const DECIMATION: usize = 3;
+
+/// Track the pitch of the microphone, emitting `pitch` events with `time` in seconds since the
+/// start, `f0` in Hz (null while unvoiced) and `confidence` from 0 to 1. Needs the microphone
+/// consent.
+#[tauri::command]
+pub fn start_pitch_detection(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+  ensure_consent(&state, Feature::Microphone, "start_pitch_detection")?;
+  let mut tracker = PitchTracker::new(MIC_RATE / DECIMATION as u32);
+  let mut mic = state.mic.lock().map_err(|e| format!("mic lock poisoned: {}", e))?;
+  mic.attach(CONSUMER, move |block| {
+    let decimated: Vec<f32> = block.chunks(DECIMATION).map(|c| c.iter().sum::<f32>() / c.len() as f32).collect();
+    for frame in tracker.feed(&decimated) {
+      if let Err(e) = app.emit(PITCH_EVENT, frame) {
+        warn!(error = %e, "failed to emit pitch");
+      }
+    }
+  })?;
+  info!("pitch detection started");
+  Ok(())
+}
+
+#[tauri::command]
+pub fn stop_pitch_detection(state: State<'_, AppState>) -> Result<(), String> {
+  let mut mic = state.mic.lock().map_err(|e| format!("mic lock poisoned: {}", e))?;
+  if mic.detach(CONSUMER) {
+    info!("pitch detection stopped");
+  }
+  Ok(())
+}
@@ -0,0 +1,579 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+use klok_core::delay::Delay;
+use klok_core::karaoke::CenterCancel;
+use klok_core::mix::{conform, mix_stems, select_channels, ChannelMode};
+use klok_core::pcm::Pcm;
+use klok_core::pitch_shift::{PitchShifter, MAX_SEMITONES};
+use klok_core::stretch::{Wsola, MAX_TEMPO, MIN_TEMPO};
+
+use crate::commands::audio_device::{backend, OUTPUT_LATENCY};
+use crate::commands::decode_audio::decode_file;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::song_library::song_files;
+use crate::commands::timeout::run_blocking;
+use crate::AppState;
+
+/// Event carrying the playback position about every 100 ms, and once more when playback stops.
+pub const PLAYER_POSITION_EVENT: &str = "player-position";
+/// Event emitted when the output fails while playing: it is reopened and playback goes on from
+/// what was heard, or stops there when the device can't be opened again.
+pub const PLAYER_OUTPUT_EVENT: &str = "player-output";
+// frames written to the output at a time
+const CHUNK: usize = 1024;
+// seconds written beyond what the device buffers, so it never runs dry
+const LEAD: f64 = 0.05;
+// seconds of playback between position events
+const POSITION_INTERVAL: f64 = 0.1;
+/// Longest delay of the second output either way, in seconds.
+pub const MAX_OUTPUT_DELAY: f64 = 2.0;
+// seconds a write may block before the output counts as stalled
+const STALL: f64 = 1.0;
+// times a failed output is reopened, and the seconds waited before each
+const REOPEN_ATTEMPTS: u32 = 3;
+const REOPEN_DELAY: f64 = 0.5;
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStatus {
+  /// song loaded with `player_load`
+  pub path: Option<String>,
+  pub playing: bool,
+  /// seconds, of what is heard now (the output latency accounted for)
+  pub position: f64,
+  pub duration: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayerPosition {
+  position: f64,
+  playing: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayerOutputFailure {
+  error: String,
+  /// whether the output was reopened; otherwise playback stopped at `position`
+  reopened: bool,
+  position: f64,
+}
+
+/// Mix settings that can change while playing, read by the render thread for each chunk.
+#[derive(Clone, Debug)]
+pub struct PlaybackParams {
+  /// vocal level, 0 (karaoke) to 1 (the original mix): of the vocal stem, or without stems how
+  /// much the karaoke filter is held back
+  pub vocal_volume: f32,
+  /// semitones the song is shifted by, see `set_key_shift`
+  pub key_shift: f32,
+  /// playback speed, the pitch kept, see `set_tempo`
+  pub tempo: f64,
+  /// channels of the song played, see `set_song_channel_mode`
+  pub channel_mode: ChannelMode,
+}
+
+impl Default for PlaybackParams {
+  fn default() -> PlaybackParams {
+    PlaybackParams { vocal_volume: 0.0, key_shift: 0.0, tempo: 1.0, channel_mode: ChannelMode::Stereo }
+  }
+}
+
+// A device the render thread writes to, with what it takes to open it again.
+struct Output {
+  device: Option<String>,
+  // seconds the music is held back on this device, to line up with the other output
+  delay: f64,
+  stream: backend::OutputStream,
+  line: Delay,
+}
+
+impl Output {
+  fn open(device: Option<String>, delay: f64, pcm: &Pcm) -> Result<Output, String> {
+    let stream = backend::open_output(device.as_deref(), pcm.sample_rate, pcm.channels, OUTPUT_LATENCY)?;
+    let line = Delay::new((delay * pcm.sample_rate as f64).round() as usize, pcm.channels);
+    Ok(Output { device, delay, stream, line })
+  }
+
+  fn write(&mut self, chunk: &[f32]) -> Result<(), String> {
+    let mut delayed = chunk.to_vec();
+    self.line.process(&mut delayed);
+    self.stream.write(&delayed)
+  }
+}
+
+#[derive(Debug)]
+struct Track {
+  path: String,
+  // the backing stem, or the song itself when it has no stems
+  pcm: Arc<Pcm>,
+  // the vocal stem in the backing's format
+  vocal: Option<Pcm>,
+  // whether `pcm` is a backing stem; without stems the vocals are filtered out on the fly
+  separated: bool,
+}
+
+// The thread writing a track to the output; stopped on drop.
+#[derive(Debug)]
+struct Playing {
+  // f64 bits of the position heard
+  position: Arc<AtomicU64>,
+  stop: Arc<AtomicBool>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl Playing {
+  fn position(&self) -> f64 {
+    f64::from_bits(self.position.load(Ordering::Relaxed))
+  }
+
+  fn is_finished(&self) -> bool {
+    self.thread.as_ref().is_none_or(|t| t.is_finished())
+  }
+}
+
+impl Drop for Playing {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::Relaxed);
+    if let Some(thread) = self.thread.take() {
+      thread.join().ok();
+    }
+  }
+}
+
+/// Native playback: the loaded song, and the thread playing it while not paused.
+#[derive(Debug, Default)]
+pub struct Player {
+  track: Option<Arc<Track>>,
+  // where playback resumes while paused
+  paused_at: f64,
+  playing: Option<Playing>,
+  params: Arc<Mutex<PlaybackParams>>,
+}
+
+impl Player {
+  fn position(&self) -> f64 {
+    self.playing.as_ref().map_or(self.paused_at, Playing::position)
+  }
+
+  pub fn status(&self) -> PlayerStatus {
+    PlayerStatus {
+      path: self.track.as_ref().map(|t| t.path.clone()),
+      playing: self.playing.as_ref().is_some_and(|p| !p.is_finished()),
+      position: self.position(),
+      duration: self.track.as_ref().map_or(0.0, |t| t.pcm.duration()),
+    }
+  }
+
+  // Stop the output, keeping the position to resume from.
+  fn halt(&mut self) {
+    if let Some(playing) = self.playing.take() {
+      self.paused_at = playing.position();
+    }
+  }
+
+  // Play on `outputs` (device and delay), the first one giving the position heard.
+  fn start(&mut self, app: AppHandle, outputs: &[(Option<String>, f64)]) -> Result<(), String> {
+    self.halt();
+    let track = self.track.clone().ok_or("no song loaded in the player")?;
+    // playing again after the end starts over
+    if self.paused_at >= track.pcm.duration() {
+      self.paused_at = 0.0;
+    }
+    let outputs = open_outputs(outputs, &track.pcm)?;
+    let from = self.paused_at;
+    let position = Arc::new(AtomicU64::new(from.to_bits()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (pos, flag, params) = (position.clone(), stop.clone(), self.params.clone());
+    let thread = std::thread::Builder::new()
+      .name("player".to_string())
+      .spawn(move || render(app, track, params, from, outputs, pos, flag))
+      .map_err(|e| format!("failed to start player: {}", e))?;
+    self.playing = Some(Playing { position, stop, thread: Some(thread) });
+    Ok(())
+  }
+}
+
+impl Track {
+  // `count` frames of the stems mixed by `mix` from frame `start` on, silence outside the track
+  fn read(&self, start: isize, count: usize, mix: &PlaybackParams) -> Vec<f32> {
+    let channels = self.pcm.channels.max(1) as usize;
+    let (from, to) = (start.clamp(0, self.pcm.frames() as isize) as usize, (start + count as isize).clamp(0, self.pcm.frames() as isize) as usize);
+    let vocal = self.vocal.as_ref().map(|v| &v.samples[(from * channels).min(v.samples.len())..(to * channels).min(v.samples.len())]);
+    let mut mixed = mix_stems(&self.pcm.samples[from * channels..to * channels], vocal, mix.vocal_volume);
+    select_channels(&mut mixed, self.pcm.channels, mix.channel_mode);
+    let mut out = vec![0.0; count * channels];
+    let at = (from as isize - start) as usize * channels;
+    out[at..at + mixed.len()].copy_from_slice(&mixed);
+    out
+  }
+}
+
+// Open `outputs` for `pcm`: the first one must open, the others are left out when they fail.
+fn open_outputs(outputs: &[(Option<String>, f64)], pcm: &Pcm) -> Result<(Output, Vec<Output>), String> {
+  let ((device, delay), rest) = outputs.split_first().ok_or("no audio output")?;
+  let first = Output::open(device.clone(), *delay, pcm)?;
+  let others = rest
+    .iter()
+    .filter_map(|(device, delay)| {
+      Output::open(device.clone(), *delay, pcm).map_err(|e| warn!(device = ?device, error = %e, "second output could not be opened")).ok()
+    })
+    .collect();
+  Ok((first, others))
+}
+
+// Open the outputs again after the first one failed, unless stopped meanwhile.
+fn reopen(outputs: &[(Option<String>, f64)], pcm: &Pcm, stop: &AtomicBool) -> Result<(Output, Vec<Output>), String> {
+  let mut error = "stopped".to_string();
+  for _ in 0..REOPEN_ATTEMPTS {
+    std::thread::sleep(Duration::from_secs_f64(REOPEN_DELAY));
+    if stop.load(Ordering::Relaxed) {
+      break;
+    }
+    match open_outputs(outputs, pcm) {
+      Ok(opened) => return Ok(opened),
+      Err(e) => error = e,
+    }
+  }
+  Err(error)
+}
+
+// Write `track` mixed by `params` from `from` seconds on to `outputs` until its end or `stop`,
+// publishing the position heard on the first one. When the first output fails or stalls, all are
+// reopened; another failing output is left out for the rest of the song.
+fn render(app: AppHandle, track: Arc<Track>, params: Arc<Mutex<PlaybackParams>>, from: f64, outputs: (Output, Vec<Output>), position: Arc<AtomicU64>, stop: Arc<AtomicBool>) {
+  let (mut output, mut others) = outputs;
+  let pcm = &track.pcm;
+  let rate = pcm.sample_rate as f64;
+  let started = Instant::now();
+  let mut karaoke = (!track.separated).then(|| CenterCancel::new(pcm.sample_rate));
+  let mut shifter = PitchShifter::new(pcm.channels, pcm.sample_rate);
+  let mut stretcher = Wsola::new(pcm.channels, pcm.sample_rate);
+  // source frame reached, output seconds written, and the tempo they were written at
+  let mut source = (from * rate).min(pcm.frames() as f64);
+  let mut written = 0.0;
+  let mut tempo = 1.0;
+  // heard after the device's buffer and the delay lining it up with the others; the devices all
+  // get as much as they play, so they are kept ahead of by `OUTPUT_LATENCY` alone
+  let latency = OUTPUT_LATENCY + output.delay;
+  // never reported below where playback (re)started
+  let mut floor = from.min(pcm.duration());
+  // output written but not heard yet, played at the last tempo
+  let heard = |source: f64, written: f64, tempo: f64, floor: f64| {
+    let pending = (written - (started.elapsed().as_secs_f64() - latency).max(0.0)).max(0.0);
+    (source / rate - pending * tempo).clamp(floor, pcm.duration())
+  };
+  let publish = |now: f64, playing: bool| {
+    position.store(now.to_bits(), Ordering::Relaxed);
+    if let Err(e) = app.emit(PLAYER_POSITION_EVENT, PlayerPosition { position: now, playing }) {
+      warn!(error = %e, "failed to emit player position");
+    }
+  };
+
+  let mut reported = f64::NEG_INFINITY;
+  while source < pcm.frames() as f64 && !stop.load(Ordering::Relaxed) {
+    let mix = params.lock().map(|p| p.clone()).unwrap_or_default();
+    // where this chunk starts, to resume from if it can't be written
+    let chunk_from = (source, tempo);
+    tempo = mix.tempo;
+    let mut chunk = if tempo == 1.0 {
+      // straight through, sample-exact
+      stretcher.reset();
+      let chunk = track.read(source as isize, CHUNK, &mix);
+      source += CHUNK as f64;
+      chunk
+    } else {
+      let chunk = stretcher.step(source, |start, count| track.read(start, count, &mix));
+      source += stretcher.hop() as f64 * tempo;
+      chunk
+    };
+    // one channel picked or mixed down is all center, which the karaoke filter would cancel
+    if let Some(karaoke) = karaoke.as_mut().filter(|_| mix.channel_mode == ChannelMode::Stereo) {
+      karaoke.process(&mut chunk, pcm.channels, 1.0 - mix.vocal_volume);
+    }
+    shifter.set_semitones(mix.key_shift);
+    shifter.process(&mut chunk);
+    let began = Instant::now();
+    let result = output.write(&chunk);
+    // another output failing is left out, the song goes on without it
+    others.retain_mut(|other| match other.write(&chunk) {
+      Ok(()) => true,
+      Err(e) => {
+        warn!(device = ?other.device, error = %e, "second output failed");
+        false
+      }
+    });
+    let result = result.and_then(|()| match began.elapsed().as_secs_f64() {
+      blocked if blocked > STALL => Err(format!("output stalled for {:.1}s", blocked)),
+      _ => Ok(()),
+    });
+    if let Err(e) = result {
+      warn!(path = %track.path, error = %e, "player output failed");
+      // resume from what was heard, with the new device's buffer still to fill
+      let resumed = heard(chunk_from.0, written, chunk_from.1, floor);
+      let devices: Vec<(Option<String>, f64)> = std::iter::once(&output).chain(&others).map(|o| (o.device.clone(), o.delay)).collect();
+      drop(output);
+      others.clear();
+      let failure = |error: String, reopened: bool| PlayerOutputFailure { error, reopened, position: resumed };
+      match reopen(&devices, pcm, &stop) {
+        Ok(reopened) => {
+          info!(path = %track.path, position = resumed, "player output reopened");
+          emit_failure(&app, failure(e, true));
+          (output, others) = reopened;
+        }
+        Err(again) => {
+          warn!(path = %track.path, error = %again, "player output could not be reopened");
+          if !stop.load(Ordering::Relaxed) {
+            emit_failure(&app, failure(format!("{}; reopening failed: {}", e, again), false));
+            publish(resumed, false);
+          }
+          return;
+        }
+      }
+      source = resumed * rate;
+      written = started.elapsed().as_secs_f64();
+      floor = resumed;
+      reported = f64::NEG_INFINITY;
+      stretcher.reset();
+      continue;
+    }
+    written += (chunk.len() / pcm.channels.max(1) as usize) as f64 / rate;
+    // keep just ahead of the device, so pausing or seeking is heard at once
+    let ahead = written - started.elapsed().as_secs_f64() - OUTPUT_LATENCY - LEAD;
+    if ahead > 0.0 {
+      std::thread::sleep(Duration::from_secs_f64(ahead));
+    }
+    let now = heard(source, written, tempo, floor);
+    position.store(now.to_bits(), Ordering::Relaxed);
+    if now - reported >= POSITION_INTERVAL * tempo {
+      publish(now, true);
+      reported = now;
+    }
+  }
+  if stop.load(Ordering::Relaxed) {
+    return;
+  }
+  // the end: play out what the delays still hold back, then what the devices buffer
+  let held = std::iter::once(&output).chain(&others).map(|o| o.delay).fold(0.0, f64::max);
+  let silence = vec![0.0; CHUNK * pcm.channels.max(1) as usize];
+  let mut flushed = 0.0;
+  while flushed < held && !stop.load(Ordering::Relaxed) {
+    if std::iter::once(&mut output).chain(&mut others).try_for_each(|o| o.write(&silence)).is_err() {
+      break;
+    }
+    flushed += CHUNK as f64 / rate;
+    let ahead = written + flushed - started.elapsed().as_secs_f64() - OUTPUT_LATENCY - LEAD;
+    if ahead > 0.0 {
+      std::thread::sleep(Duration::from_secs_f64(ahead));
+    }
+  }
+  while heard(source, written, tempo, floor) < pcm.duration() && started.elapsed().as_secs_f64() < written + latency && !stop.load(Ordering::Relaxed) {
+    std::thread::sleep(Duration::from_secs_f64(POSITION_INTERVAL / 4.0));
+  }
+  publish(pcm.duration(), false);
+}
+
+fn emit_failure(app: &AppHandle, failure: PlayerOutputFailure) {
+  if let Err(e) = app.emit(PLAYER_OUTPUT_EVENT, failure) {
+    warn!(error = %e, "failed to emit player output failure");
+  }
+}
+
+// The `<song>_non_vocals.*` and `<song>_vocals.*` stems next to `song`.
+pub(crate) fn find_stems(song: &Path) -> (Option<PathBuf>, Option<PathBuf>) {
+  let stem = song.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+  let files = song_files(song).unwrap_or_default();
+  let find = |suffix: &str| {
+    let prefix = format!("{}{}.", stem, suffix);
+    files.iter().find(|f| f.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(&prefix))).cloned()
+  };
+  (find("_non_vocals"), find("_vocals"))
+}
+
+fn player(state: &AppState) -> Result<MutexGuard<'_, Player>, String> {
+  state.player.lock().map_err(|e| format!("player lock poisoned: {}", e))
+}
+
+// the output selected with `set_audio_output`
+pub(crate) fn output_device(state: &AppState) -> Result<Option<String>, String> {
+  Ok(state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.audio_output.clone())
+}
+
+// The devices music plays on and the seconds each is held back: the output selected with
+// `set_audio_output`, then the one set with `set_second_output`.
+fn output_devices(state: &AppState) -> Result<Vec<(Option<String>, f64)>, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  let mut devices = vec![(settings.audio_output.clone(), 0.0)];
+  if let Some(second) = &settings.second_output {
+    devices[0].1 = (-second.delay).max(0.0);
+    devices.push((Some(second.device.clone()), second.delay.max(0.0)));
+  }
+  Ok(devices)
+}
+
+/// Reopen the output of what is playing, e.g. on another device.
+pub(crate) fn restart_output(app: AppHandle, state: &AppState) -> Result<(), String> {
+  let devices = output_devices(state)?;
+  let mut player = player(state)?;
+  if player.status().playing {
+    player.start(app, &devices)?;
+  }
+  Ok(())
+}
+
+/// Load the song `path` into the native player, paused at the start, replacing what was loaded.
+/// With separated stems (`song_non_vocals.*`, `song_vocals.*`) these are played, mixed with the
+/// vocal at the level set by `set_vocal_volume`; otherwise the song file itself, through a karaoke
+/// filter attenuating the center channel (held back by the vocal volume) until stems exist. Only
+/// WAV songs and stems can be decoded, others are left to the webview player.
+#[tauri::command]
+pub async fn player_load(state: State<'_, AppState>, path: String) -> Result<PlayerStatus, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+  let st = state.inner().clone();
+  let (pcm, vocal, separated) = run_blocking(&state, "player_load", move |_| {
+    let (backing, vocal) = find_stems(&resolved);
+    let Some(backing) = backing else {
+      return Ok((decode_file(&st, &resolved)?, None, false));
+    };
+    let pcm = decode_file(&st, &backing)?;
+    // stems come from the same separation, but may still differ in format
+    let vocal = vocal.map(|v| decode_file(&st, &v)).transpose()?.map(|v| conform(&v, pcm.sample_rate, pcm.channels));
+    Ok((pcm, vocal, true))
+  })
+  .await?;
+  let channel_mode = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.song_channel_modes.get(&path).copied().unwrap_or_default();
+  let mut player = player(&state)?;
+  player.playing.take();
+  player.track = Some(Arc::new(Track { path, pcm, vocal, separated }));
+  player.paused_at = 0.0;
+  player.params.lock().map_err(|e| format!("playback params lock poisoned: {}", e))?.channel_mode = channel_mode;
+  Ok(player.status())
+}
+
+/// Play the loaded song from the current position; `player-position` events follow the playback.
+#[tauri::command]
+pub fn player_play(app: AppHandle, state: State<'_, AppState>) -> Result<PlayerStatus, String> {
+  let devices = output_devices(&state)?;
+  let mut player = player(&state)?;
+  player.start(app, &devices)?;
+  Ok(player.status())
+}
+
+#[tauri::command]
+pub fn player_pause(state: State<'_, AppState>) -> Result<PlayerStatus, String> {
+  let mut player = player(&state)?;
+  player.halt();
+  Ok(player.status())
+}
+
+/// Move to `position` seconds, playing on from there if the player was playing.
+#[tauri::command]
+pub fn player_seek(app: AppHandle, state: State<'_, AppState>, position: f64) -> Result<PlayerStatus, String> {
+  if !position.is_finite() {
+    return Err(format!("invalid position: {}", position));
+  }
+  let devices = output_devices(&state)?;
+  let mut player = player(&state)?;
+  let playing = player.status().playing;
+  player.halt();
+  player.paused_at = position.clamp(0.0, player.status().duration);
+  if playing {
+    player.start(app, &devices)?;
+  }
+  Ok(player.status())
+}
+
+/// Set the vocal level, from 0 (karaoke) to 1 (the original mix), heard at once. Without stems it
+/// sets how much of the center channel the karaoke filter keeps.
+#[tauri::command]
+pub fn set_vocal_volume(state: State<'_, AppState>, volume: f32) -> Result<(), String> {
+  if !volume.is_finite() {
+    return Err(format!("invalid vocal volume: {}", volume));
+  }
+  let player = player(&state)?;
+  let mut params = player.params.lock().map_err(|e| format!("playback params lock poisoned: {}", e))?;
+  params.vocal_volume = volume.clamp(0.0, 1.0);
+  Ok(())
+}
+
+/// Shift the key of the playback by `semitones` (up to an octave either way, 0 for the original),
+/// keeping the tempo. Heard at once; transpose the MIDI reference by the same amount.
+#[tauri::command]
+pub fn set_key_shift(state: State<'_, AppState>, semitones: f32) -> Result<(), String> {
+  if !(semitones.is_finite() && semitones.abs() <= MAX_SEMITONES) {
+    return Err(format!("key shift must be within {} semitones: {}", MAX_SEMITONES, semitones));
+  }
+  let player = player(&state)?;
+  let mut params = player.params.lock().map_err(|e| format!("playback params lock poisoned: {}", e))?;
+  params.key_shift = semitones;
+  Ok(())
+}
+
+/// Play at `tempo` times the original speed (e.g. 0.5 to 1 for practice), keeping the pitch.
+/// Positions stay in song time, so lyrics and notes line up at any tempo.
+#[tauri::command]
+pub fn set_tempo(state: State<'_, AppState>, tempo: f64) -> Result<(), String> {
+  if !(MIN_TEMPO..=MAX_TEMPO).contains(&tempo) {
+    return Err(format!("tempo must be between {} and {}: {}", MIN_TEMPO, MAX_TEMPO, tempo));
+  }
+  let player = player(&state)?;
+  let mut params = player.params.lock().map_err(|e| format!("playback params lock poisoned: {}", e))?;
+  params.tempo = tempo;
+  Ok(())
+}
+
+/// Play only the left or right channel of the song at `path`, or both mixed to mono, for karaoke
+/// files with the instrumental on one channel and a guide vocal on the other; `None` (or stereo)
+/// plays it as it is. Kept in the settings, and heard at once when the song is loaded. The karaoke
+/// filter is off for such songs.
+#[tauri::command]
+pub fn set_song_channel_mode(state: State<'_, AppState>, path: String, mode: Option<ChannelMode>) -> Result<(), String> {
+  ensure_unlocked(&state, "set_song_channel_mode")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let mode = mode.unwrap_or_default();
+  {
+    let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+    match mode {
+      ChannelMode::Stereo => settings.song_channel_modes.remove(&path),
+      mode => settings.song_channel_modes.insert(path.clone(), mode),
+    };
+    settings.save(&state.config_dir)?;
+  }
+  let player = player(&state)?;
+  if player.track.as_ref().is_some_and(|t| t.path == path) {
+    player.params.lock().map_err(|e| format!("playback params lock poisoned: {}", e))?.channel_mode = mode;
+  }
+  Ok(())
+}
+
+#[tauri::command]
+pub fn player_status(state: State<'_, AppState>) -> Result<PlayerStatus, String> {
+  Ok(player(&state)?.status())
+}
+
+#[test]
+pub fn test_find_stems() {
+  let dir = std::env::temp_dir().join(format!("klok_find_stems_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  for name in ["Love.mp3", "Love_Story.mp3", "Love_Story_non_vocals.mp3", "Love_Story_vocals.mp3", "Love_vocals.wav"] {
+    std::fs::write(dir.join(name), b"").unwrap();
+  }
+  let (backing, vocal) = find_stems(&dir.join("Love.mp3"));
+  let story = find_stems(&dir.join("Love_Story.mp3"));
+  std::fs::remove_dir_all(&dir).ok();
+  // another song's stems are never taken
+  assert_eq!((backing, vocal), (None, Some(dir.join("Love_vocals.wav"))));
+  assert_eq!(story, (Some(dir.join("Love_Story_non_vocals.mp3")), Some(dir.join("Love_Story_vocals.mp3"))));
+}
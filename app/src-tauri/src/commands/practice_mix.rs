@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use tauri::State;
+
+use klok_core::synth::{encode_wav, render_notes, SAMPLE_RATE};
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::commands::storage::write_export;
+use crate::commands::with_extension;
+use crate::AppState;
+
+// silence kept after the last note of a rendered melody
+const MELODY_TAIL: f64 = 2.0;
+
+/// What goes into a practice file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PracticePreset {
+  /// the vocal melody played by a simple synth, from the vocal MIDI
+  MelodyOnly,
+  /// the instrumental stem with the vocal stem mixed in quietly
+  InstrumentalGuide,
+}
+
+impl PracticePreset {
+  fn suffix(self) -> &'static str {
+    match self {
+      PracticePreset::MelodyOnly => "_practice_melody.wav",
+      PracticePreset::InstrumentalGuide => "_practice_guide.wav",
+    }
+  }
+}
+
+/// Write a practice file next to the song, e.g. `song_practice_melody.wav`, or to the export
+/// `target` of that name, and return its path.
+#[tauri::command]
+pub fn export_practice_mix(state: State<'_, AppState>, path: String, preset: PracticePreset, target: Option<String>) -> Result<String, String> {
+  ensure_unlocked(&state, "export_practice_mix")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let samples = match preset {
+    PracticePreset::MelodyOnly => {
+      let notes = find_vocal_notes(&state, &path)?.ok_or_else(|| format!("vocal midi not found for {}", path))?;
+      render_notes(&notes, MELODY_TAIL)
+    }
+    // stems are only decoded by the webview so far
+    PracticePreset::InstrumentalGuide => return Err("instrumental_guide needs audio decoding, which is not supported yet".to_string()),
+  };
+
+  let written = write_export(&state, target.as_deref(), &with_extension(&path, preset.suffix()), &encode_wav(&samples, SAMPLE_RATE))?;
+  info!(path = %written, ?preset, seconds = samples.len() as f64 / SAMPLE_RATE as f64, "exported practice mix");
+  Ok(written)
+}
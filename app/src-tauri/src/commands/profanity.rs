@@ -0,0 +1,99 @@
+use tauri::State;
+
+use klok_core::lyrics::LyricLine;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::settings::ProfanityFilter;
+use crate::AppState;
+
+const MASK: char = '*';
+
+// Scripts written without spaces, where a flagged word is matched anywhere in the text.
+fn is_unspaced(c: char) -> bool {
+  matches!(c as u32,
+    0x3040..=0x30ff // hiragana, katakana
+    | 0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xf900..=0xfaff // CJK ideographs
+    | 0xac00..=0xd7af // hangul syllables
+    | 0x0e00..=0x0e7f // thai
+  )
+}
+
+fn fold(c: char) -> char {
+  c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Mask every occurrence of `words` in `text` with `*`, case-insensitively.
+/// Words in spaced scripts only match whole words; CJK/Thai words match anywhere.
+pub fn mask_text(text: &str, words: &[String]) -> String {
+  let mut chars: Vec<char> = text.chars().collect();
+  let folded: Vec<char> = chars.iter().map(|&c| fold(c)).collect();
+  for word in words {
+    let needle: Vec<char> = word.trim().chars().map(fold).collect();
+    let (Some(&first), Some(&last)) = (needle.first(), needle.last()) else {
+      continue;
+    };
+    let mut i = 0;
+    while i + needle.len() <= folded.len() {
+      let end = i + needle.len();
+      let before_ok = is_unspaced(first) || i == 0 || !folded[i - 1].is_alphanumeric();
+      let after_ok = is_unspaced(last) || end == folded.len() || !folded[end].is_alphanumeric();
+      if folded[i..end] == needle[..] && before_ok && after_ok {
+        chars[i..end].iter_mut().filter(|c| !c.is_whitespace()).for_each(|c| *c = MASK);
+        i = end;
+      } else {
+        i += 1;
+      }
+    }
+  }
+  chars.into_iter().collect()
+}
+
+/// Apply the filter to line text, word timings and translations when it is enabled.
+pub fn mask_lines(lines: &mut [LyricLine], filter: &ProfanityFilter) {
+  if !filter.enabled {
+    return;
+  }
+  let words: Vec<String> = filter.wordlists.values().flatten().cloned().collect();
+  if words.is_empty() {
+    return;
+  }
+  for line in lines.iter_mut() {
+    line.text = mask_text(&line.text, &words);
+    for word in line.words.iter_mut() {
+      word.text = mask_text(&word.text, &words);
+    }
+    if let Some(translation) = line.translation.as_mut() {
+      *translation = mask_text(translation, &words);
+    }
+  }
+}
+
+/// Mask lyric lines with the profanity filter from settings (no-op when it is disabled).
+pub(crate) fn filter_lyrics(state: &AppState, lines: &mut [LyricLine]) -> Result<(), String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  mask_lines(lines, &settings.profanity_filter);
+  Ok(())
+}
+
+/// Return the profanity filter settings.
+#[tauri::command]
+pub fn get_profanity_filter(state: State<'_, AppState>) -> Result<ProfanityFilter, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  Ok(settings.profanity_filter.clone())
+}
+
+/// Replace the profanity filter settings and persist them.
+#[tauri::command]
+pub fn save_profanity_filter(state: State<'_, AppState>, filter: ProfanityFilter) -> Result<(), String> {
+  ensure_unlocked(&state, "save_profanity_filter")?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.profanity_filter = filter;
+  settings.save(&state.config_dir)
+}
+
+#[test]
+pub fn test_mask_text() {
+  let words = vec!["darn".to_string(), "混蛋".to_string()];
+  assert_eq!(mask_text("Darn it, darned DARN!", &words), "**** it, darned ****!");
+  assert_eq!(mask_text("你这个混蛋啊", &words), "你这个**啊");
+}
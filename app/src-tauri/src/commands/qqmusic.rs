@@ -0,0 +1,114 @@
+use serde::Deserialize;
+
+use klok_core::lrc::parse_lrc;
+use klok_core::lyrics::{merge_duplicate_timestamps, merge_translation, LyricLine};
+use klok_core::qrc::unescape_xml;
+
+use crate::commands::fetch_lyrics::USER_AGENT;
+use crate::commands::lyrics_provider::{Candidate, LyricsProvider, SongQuery};
+
+const SEARCH_URL: &str = "https://c.y.qq.com/soso/fcgi-bin/client_search_cp";
+const LYRIC_URL: &str = "https://c.y.qq.com/lyric/fcgi-bin/fcg_query_lyric_new.fcg";
+// the lyric endpoint rejects requests without a y.qq.com referer
+const REFERER: &str = "https://y.qq.com/";
+const SEARCH_LIMIT: u32 = 10;
+
+/// QQ Music, via its public web endpoints (line-level LRC; word-timed QRC needs decryption).
+pub struct QqMusic;
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+  data: Option<SearchData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchData {
+  song: Option<SongList>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SongList {
+  #[serde(default)]
+  list: Vec<Song>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Song {
+  songmid: String,
+  songname: String,
+  #[serde(default)]
+  singer: Vec<Singer>,
+  /// seconds
+  #[serde(default)]
+  interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Singer {
+  name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LyricResponse {
+  #[serde(default)]
+  lyric: String,
+  #[serde(default)]
+  trans: String,
+}
+
+impl LyricsProvider for QqMusic {
+  const NAME: &'static str = "qq";
+
+  async fn search(&self, query: &SongQuery) -> Result<Vec<Candidate>, String> {
+    let keywords = format!("{} {}", query.title, query.artist).trim().to_string();
+    let songs: Vec<Song> = reqwest::Client::new()
+      .get(SEARCH_URL)
+      .header(reqwest::header::USER_AGENT, USER_AGENT)
+      .query(&[("w", keywords), ("p", "1".to_string()), ("n", SEARCH_LIMIT.to_string()), ("format", "json".to_string())])
+      .send()
+      .await
+      .map_err(|e| format!("qq music search failed: {}", e))?
+      .json::<SearchResponse>()
+      .await
+      .map_err(|e| format!("invalid qq music search response: {}", e))?
+      .data
+      .and_then(|d| d.song)
+      .map(|s| s.list)
+      .unwrap_or_default();
+    Ok(
+      songs
+        .into_iter()
+        .map(|s| Candidate {
+          id: s.songmid,
+          title: s.songname,
+          artists: s.singer.into_iter().map(|a| a.name).collect(),
+          duration: (s.interval > 0).then_some(s.interval as f64),
+        })
+        .collect(),
+    )
+  }
+
+  async fn fetch(&self, candidate: &Candidate) -> Result<Option<Vec<LyricLine>>, String> {
+    let response: LyricResponse = reqwest::Client::new()
+      .get(LYRIC_URL)
+      .header(reqwest::header::USER_AGENT, USER_AGENT)
+      .header(reqwest::header::REFERER, REFERER)
+      .query(&[("songmid", candidate.id.as_str()), ("format", "json"), ("nobase64", "1")])
+      .send()
+      .await
+      .map_err(|e| format!("qq music lyric request failed: {}", e))?
+      .json()
+      .await
+      .map_err(|e| format!("invalid qq music lyric response: {}", e))?;
+
+    // with nobase64=1 the LRC comes back entity-escaped (`&#58;` for `:` ...)
+    let mut lyrics = parse_lrc(&unescape_xml(&response.lyric));
+    if lyrics.is_empty() {
+      return Ok(None);
+    }
+    if !response.trans.trim().is_empty() {
+      merge_translation(&mut lyrics, &parse_lrc(&unescape_xml(&response.trans)));
+    }
+    Ok(Some(merge_duplicate_timestamps(lyrics)))
+  }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::ipc::Response;
+use tauri::State;
+
+use klok_core::synth::encode_wav;
+
+use crate::commands::audio_device::{INPUT_LATENCY, OUTPUT_LATENCY};
+use crate::commands::consent::ensure_consent;
+use crate::commands::mic::MIC_RATE;
+use crate::commands::storage::{ensure_free_space, recording_bytes};
+use crate::settings::Feature;
+use crate::AppState;
+
+const RECORDINGS_DIR: &str = "recordings";
+const TAKE_FILE: &str = "take.wav";
+const MANIFEST_FILE: &str = "session.json";
+const CONSUMER: &str = "recording";
+// a recording only starts with room for this many seconds
+const MIN_RECORDING_SECONDS: f64 = 600.0;
+
+/// A recorded take and what it was sung over, kept as `session.json` next to its `take.wav`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+  pub id: String,
+  /// the song as passed to `start_recording`
+  pub song: String,
+  /// semitones the song was shifted by
+  pub key_shift: f32,
+  /// song position, in seconds, of the first recorded sample
+  pub song_offset: f64,
+  /// seconds the take lags what was heard, from the input buffering and the output buffering of
+  /// the native player, see `take_latency`
+  pub latency: f64,
+  pub sample_rate: u32,
+  /// seconds recorded, 0 until the recording stops
+  pub duration: f64,
+  /// unix seconds
+  pub started_at: u64,
+}
+
+/// The recording in progress.
+#[derive(Debug)]
+pub struct Recording {
+  session: Session,
+  writer: Arc<Mutex<TakeWriter>>,
+}
+
+// Mono 16-bit WAV written as the samples come, its sizes filled in by `finish`.
+#[derive(Debug)]
+struct TakeWriter {
+  file: BufWriter<File>,
+  path: PathBuf,
+  samples: u64,
+  // the first write error; later samples are dropped
+  error: Option<String>,
+}
+
+impl TakeWriter {
+  fn create(path: &Path, sample_rate: u32) -> Result<TakeWriter, String> {
+    let mut file = BufWriter::new(File::create(path).map_err(|e| format!("failed to create {}: {}", path.display(), e))?);
+    file.write_all(&encode_wav(&[], sample_rate)).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(TakeWriter { file, path: path.to_path_buf(), samples: 0, error: None })
+  }
+
+  fn write(&mut self, samples: &[f32]) {
+    if self.error.is_some() {
+      return;
+    }
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes()).collect();
+    match self.file.write_all(&bytes) {
+      Ok(()) => self.samples += samples.len() as u64,
+      Err(e) => {
+        warn!(path = %self.path.display(), error = %e, "recording stopped writing");
+        self.error = Some(format!("failed to write {}: {}", self.path.display(), e));
+      }
+    }
+  }
+
+  // Complete the header; the samples written.
+  fn finish(&mut self) -> Result<u64, String> {
+    if let Some(e) = &self.error {
+      return Err(e.clone());
+    }
+    let data_len = u32::try_from(self.samples * 2).map_err(|_| format!("recording too long for WAV: {}", self.path.display()))?;
+    let patch = |file: &mut BufWriter<File>| -> std::io::Result<()> {
+      file.seek(SeekFrom::Start(4))?;
+      file.write_all(&(36 + data_len).to_le_bytes())?;
+      file.seek(SeekFrom::Start(40))?;
+      file.write_all(&data_len.to_le_bytes())?;
+      file.flush()
+    };
+    patch(&mut self.file).map_err(|e| format!("failed to write {}: {}", self.path.display(), e))?;
+    Ok(self.samples)
+  }
+}
+
+// ids come from the clock; anything else could point outside the recordings
+fn valid_session_id(id: &str) -> bool {
+  !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+pub(crate) fn session_dir(state: &AppState, id: &str) -> Result<PathBuf, String> {
+  if !valid_session_id(id) {
+    return Err(format!("invalid session id: {}", id));
+  }
+  Ok(state.config_dir.join(RECORDINGS_DIR).join(id))
+}
+
+pub(crate) fn take_path(state: &AppState, id: &str) -> Result<PathBuf, String> {
+  Ok(session_dir(state, id)?.join(TAKE_FILE))
+}
+
+pub(crate) fn read_session(state: &AppState, id: &str) -> Result<Session, String> {
+  let path = session_dir(state, id)?.join(MANIFEST_FILE);
+  let content = std::fs::read(&path).map_err(|e| format!("unknown recording {}: {}", id, e))?;
+  serde_json::from_slice(&content).map_err(|e| format!("invalid session {}: {}", path.display(), e))
+}
+
+fn write_session(state: &AppState, session: &Session) -> Result<(), String> {
+  let path = session_dir(state, &session.id)?.join(MANIFEST_FILE);
+  let content = serde_json::to_vec_pretty(session).map_err(|e| format!("failed to serialize session: {}", e))?;
+  std::fs::write(&path, content).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+// Seconds a take started now lags what is heard: the input buffering, and the output buffering
+// when the native player plays the backing. The webview's output latency isn't known here, and
+// the position of its media elements is already close to what is heard.
+fn take_latency(state: &AppState) -> Result<f64, String> {
+  let native = state.player.lock().map_err(|e| format!("player lock poisoned: {}", e))?.status().playing;
+  Ok(if native { OUTPUT_LATENCY + INPUT_LATENCY } else { INPUT_LATENCY })
+}
+
+/// Record the microphone until `stop_recording`, as a take sung over `song` from `position`
+/// seconds on with the key shifted by `key_shift` semitones. Needs the microphone consent and room
+/// for ten minutes. Returns the new session.
+#[tauri::command]
+pub fn start_recording(state: State<'_, AppState>, song: String, position: f64, key_shift: Option<f32>) -> Result<Session, String> {
+  ensure_consent(&state, Feature::Microphone, "start_recording")?;
+  let mut recording = state.recording.lock().map_err(|e| format!("recording lock poisoned: {}", e))?;
+  if recording.is_some() {
+    return Err("already recording".to_string());
+  }
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+  let id = now.as_millis().to_string();
+  let dir = session_dir(&state, &id)?;
+  ensure_free_space(&dir, recording_bytes(MIN_RECORDING_SECONDS, 1))?;
+  std::fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+  let session = Session { id, song, key_shift: key_shift.unwrap_or(0.0), song_offset: position.max(0.0), latency: take_latency(&state)?, sample_rate: MIC_RATE, duration: 0.0, started_at: now.as_secs() };
+  let started = write_session(&state, &session).and_then(|()| {
+    let writer = Arc::new(Mutex::new(TakeWriter::create(&dir.join(TAKE_FILE), MIC_RATE)?));
+    let sink = writer.clone();
+    state.mic.lock().map_err(|e| format!("mic lock poisoned: {}", e))?.attach(CONSUMER, move |block| {
+      if let Ok(mut sink) = sink.lock() {
+        sink.write(block);
+      }
+    })?;
+    Ok(writer)
+  });
+  // no empty session is left when the microphone can't start
+  let writer = started.inspect_err(|_| {
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+      warn!(dir = %dir.display(), error = %e, "failed to remove the unstarted recording");
+    }
+  })?;
+  info!(id = %session.id, song = %session.song, "recording started");
+  *recording = Some(Recording { session: session.clone(), writer });
+  Ok(session)
+}
+
+/// Stop recording and complete the take. Returns its session, with the duration recorded.
+#[tauri::command]
+pub fn stop_recording(state: State<'_, AppState>) -> Result<Session, String> {
+  let Recording { mut session, writer } = state.recording.lock().map_err(|e| format!("recording lock poisoned: {}", e))?.take().ok_or("not recording")?;
+  state.mic.lock().map_err(|e| format!("mic lock poisoned: {}", e))?.detach(CONSUMER);
+  let samples = writer.lock().map_err(|e| format!("recording lock poisoned: {}", e))?.finish()?;
+  session.duration = samples as f64 / session.sample_rate as f64;
+  write_session(&state, &session)?;
+  info!(id = %session.id, duration = session.duration, "recording stopped");
+  Ok(session)
+}
+
+/// The recorded sessions, newest first.
+#[tauri::command]
+pub fn list_recordings(state: State<'_, AppState>) -> Result<Vec<Session>, String> {
+  let Ok(entries) = std::fs::read_dir(state.config_dir.join(RECORDINGS_DIR)) else {
+    return Ok(Vec::new());
+  };
+  let mut sessions: Vec<Session> = entries
+    .flatten()
+    .filter_map(|entry| {
+      let id = entry.file_name().to_string_lossy().to_string();
+      read_session(&state, &id).map_err(|e| warn!(%id, error = %e, "skipping recording")).ok()
+    })
+    .collect();
+  sessions.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| b.id.cmp(&a.id)));
+  Ok(sessions)
+}
+
+/// The WAV take of `session_id`, to listen back to.
+#[tauri::command]
+pub fn load_take(state: State<'_, AppState>, session_id: String) -> Result<Response, String> {
+  let path = take_path(&state, &session_id)?;
+  let wav = std::fs::read(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+  Ok(Response::new(wav))
+}
+
+#[test]
+pub fn test_take_writer() {
+  let path = std::env::temp_dir().join(format!("klok_take_{}.wav", std::process::id()));
+  let mut writer = TakeWriter::create(&path, 8000).unwrap();
+  writer.write(&[0.0, 0.5]);
+  writer.write(&[-0.5, 2.0]);
+  assert_eq!(writer.finish().unwrap(), 4);
+  drop(writer);
+  let pcm = klok_core::pcm::decode_wav(&std::fs::read(&path).unwrap()).unwrap();
+  std::fs::remove_file(&path).ok();
+  assert_eq!((pcm.sample_rate, pcm.channels, pcm.frames()), (8000, 1, 4));
+  assert!((pcm.samples[1] - 0.5).abs() < 1e-3 && (pcm.samples[3] - 1.0).abs() < 1e-3);
+
+  assert!(valid_session_id("1760400000000"));
+  assert!(!valid_session_id("../settings"));
+  assert!(!valid_session_id(""));
+  // nothing in the native player, the webview plays
+  assert_eq!(take_latency(&AppState::default()).unwrap(), INPUT_LATENCY);
+}
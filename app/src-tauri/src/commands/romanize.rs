@@ -0,0 +1,78 @@
+use pinyin::ToPinyin;
+use wana_kana::ConvertJapanese;
+use tauri::State;
+
+use klok_core::language::detect_language;
+use klok_core::lyrics::LyricLine;
+
+use crate::commands::get_metadata::find_bilingual_lyrics;
+use crate::AppState;
+
+// Pinyin with tone marks for Han characters, one space between syllables; other text is kept.
+fn to_pinyin(text: &str) -> String {
+  let mut out = String::new();
+  // whether the last thing written was a pinyin syllable (needs a space before the next word)
+  let mut after_syllable = false;
+  for c in text.chars() {
+    match c.to_pinyin() {
+      Some(p) => {
+        if !out.is_empty() && !out.ends_with(' ') {
+          out.push(' ');
+        }
+        out.push_str(p.with_tone());
+        after_syllable = true;
+      }
+      None => {
+        if after_syllable && c.is_alphanumeric() {
+          out.push(' ');
+        }
+        out.push(c);
+        after_syllable = false;
+      }
+    }
+  }
+  out
+}
+
+/// Romanize lyric text: pinyin for Chinese (`zh`), romaji for Japanese (`ja`) kana.
+/// Japanese kanji have no reading without a dictionary, so they are left as written.
+/// Returns `None` for other languages or when nothing changed.
+pub fn romanize_text(text: &str, language: &str) -> Option<String> {
+  let romanized = match language {
+    "zh" => to_pinyin(text),
+    "ja" => text.to_romaji(),
+    _ => return None,
+  };
+  let romanized = romanized.trim().to_string();
+  (romanized != text.trim()).then_some(romanized)
+}
+
+/// Fill `romanization` for every line in `language`.
+pub fn romanize_lines(lines: &mut [LyricLine], language: &str) {
+  for line in lines.iter_mut() {
+    line.romanization = romanize_text(&line.text, language);
+  }
+}
+
+/// Lyrics next to `path` (as returned by `get_metadata`) with a `romanization` per line.
+/// `language` defaults to the detected lyrics language.
+#[tauri::command]
+pub fn romanize_lyrics(state: State<'_, AppState>, path: String, language: Option<String>) -> Result<Vec<LyricLine>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let mut lyrics = find_bilingual_lyrics(&state, &path, None)?.ok_or_else(|| format!("lyrics file not found for provided path: {}", path))?;
+  let Some(language) = language.or_else(|| detect_language(&lyrics)) else {
+    return Ok(lyrics);
+  };
+  romanize_lines(&mut lyrics, &language);
+  Ok(lyrics)
+}
+
+#[test]
+pub fn test_romanize_text() {
+  assert_eq!(romanize_text("我的朋友", "zh").as_deref(), Some("wǒ de péng yǒu"));
+  assert_eq!(romanize_text("你好 world", "zh").as_deref(), Some("nǐ hǎo world"));
+  assert_eq!(romanize_text("さくら", "ja").as_deref(), Some("sakura"));
+  assert_eq!(romanize_text("hello", "en"), None);
+}
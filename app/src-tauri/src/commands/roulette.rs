@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use tauri::{AppHandle, State};
+
+use crate::commands::load_playlist::{load_playlist, PlaylistItem};
+use crate::AppState;
+
+/// Filters for `pick_random`; every field is optional.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RandomConstraints {
+  /// lyrics language (ISO 639-1)
+  pub language: Option<String>,
+  /// highest difficulty score; songs without a rating are allowed
+  pub max_difficulty: Option<f64>,
+  /// skip songs marked with `mark_sung` this session
+  pub exclude_sung: bool,
+}
+
+impl Default for RandomConstraints {
+  fn default() -> Self {
+    RandomConstraints { language: None, max_difficulty: None, exclude_sung: true }
+  }
+}
+
+impl RandomConstraints {
+  fn allows(&self, item: &PlaylistItem, sung: &std::collections::BTreeSet<String>) -> bool {
+    self.language.as_ref().is_none_or(|l| item.language.as_ref() == Some(l))
+      && self.max_difficulty.is_none_or(|max| item.difficulty.is_none_or(|d| d <= max))
+      && !(self.exclude_sung && sung.contains(&item.url))
+  }
+}
+
+/// Pick a random playlist song matching `constraints`, for karaoke roulette. Returns `None` when
+/// no song matches.
+#[tauri::command]
+pub async fn pick_random(app: AppHandle, state: State<'_, AppState>, constraints: Option<RandomConstraints>) -> Result<Option<PlaylistItem>, String> {
+  let constraints = constraints.unwrap_or_default();
+  let playlist = load_playlist(app, state.clone(), None, None).await?;
+  let sung = state.sung_songs.lock().map_err(|e| format!("sung songs lock poisoned: {}", e))?.clone();
+  let mut candidates: Vec<PlaylistItem> = playlist.into_iter().filter(|item| constraints.allows(item, &sung)).collect();
+  if candidates.is_empty() {
+    return Ok(None);
+  }
+  // a freshly seeded std hasher is random enough for picking a song
+  let index = (RandomState::new().hash_one(candidates.len()) % candidates.len() as u64) as usize;
+  let picked = candidates.swap_remove(index);
+  info!(url = %picked.url, candidates = candidates.len() + 1, "picked random song");
+  Ok(Some(picked))
+}
+
+/// Record that a song was sung this session, so roulette skips it.
+#[tauri::command]
+pub fn mark_sung(state: State<'_, AppState>, path: String) -> Result<(), String> {
+  state.sung_songs.lock().map_err(|e| format!("sung songs lock poisoned: {}", e))?.insert(path);
+  Ok(())
+}
+
+#[test]
+pub fn test_random_constraints() {
+  let item = |url: &str, language: Option<&str>, difficulty: Option<f64>| PlaylistItem { title: url.to_string(), url: url.to_string(), artist: None, language: language.map(str::to_string), difficulty, midi_only: false };
+  let sung: std::collections::BTreeSet<String> = ["a.mp3".to_string()].into();
+  let constraints = RandomConstraints { language: Some("zh".to_string()), max_difficulty: Some(5.0), ..Default::default() };
+
+  assert!(constraints.allows(&item("b.mp3", Some("zh"), Some(4.0)), &sung));
+  assert!(constraints.allows(&item("b.mp3", Some("zh"), None), &sung));
+  assert!(!constraints.allows(&item("a.mp3", Some("zh"), Some(4.0)), &sung));
+  assert!(!constraints.allows(&item("b.mp3", Some("en"), Some(4.0)), &sung));
+  assert!(!constraints.allows(&item("b.mp3", Some("zh"), Some(7.5)), &sung));
+  assert!(RandomConstraints { exclude_sung: false, ..Default::default() }.allows(&item("a.mp3", None, None), &sung));
+}
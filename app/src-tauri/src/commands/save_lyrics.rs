@@ -0,0 +1,54 @@
+use std::path::Path;
+use tauri::State;
+
+use klok_core::lrc::format_lrc;
+use klok_core::lyrics::LyricLine;
+
+use crate::commands::get_metadata::get_duration_and_artist;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::sylt::embed_sylt;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// Write `lines` as an LRC file next to `path` (`song.mp3` -> `song.lrc`), replacing any existing one.
+/// Title, artist and length tags are filled from the path and the audio file when available.
+/// With `embed` the lines are also written into the MP3's `SYLT` frame.
+#[tauri::command]
+pub fn save_lyrics(state: State<'_, AppState>, path: String, lines: Vec<LyricLine>, embed: Option<bool>) -> Result<(), String> {
+  ensure_unlocked(&state, "save_lyrics")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  // lines from get_metadata are masked while the filter is on; saving them would lose the original text
+  if state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.profanity_filter.enabled {
+    return Err("disable the profanity filter before saving lyrics".to_string());
+  }
+  write_lrc(&state, &path, &lines)?;
+  if embed.unwrap_or(false) {
+    let audio = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+    embed_sylt(&audio, &lines)?;
+  }
+  Ok(())
+}
+
+/// Write `lines` to the LRC file for `path`, see [`save_lyrics`].
+pub(crate) fn write_lrc(state: &AppState, path: &str, lines: &[LyricLine]) -> Result<(), String> {
+  let title = Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+  let mut tags = vec![("ti", title)];
+  if let Some((duration, artist)) = state.resolve(path).and_then(get_duration_and_artist) {
+    tags.push(("ar", artist));
+    tags.push(("length", format_length(duration)));
+  }
+  tags.push(("re", "klok".to_string()));
+
+  let target = state.res_dir.join(with_extension(path, ".lrc"));
+  std::fs::write(&target, format_lrc(lines, &tags)).map_err(|e| format!("failed to write {}: {}", target.display(), e))?;
+  info!(path = %target.display(), lines = lines.len(), "saved lyrics");
+  Ok(())
+}
+
+fn format_length(duration: f64) -> String {
+  let secs = duration.max(0.0).round() as u64;
+  format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
@@ -0,0 +1,27 @@
+use tauri::State;
+
+use klok_core::midi::{encode_midi_with_tempo, Note};
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::VOCAL_MIDI_SUFFIXES;
+use crate::commands::with_extension;
+use crate::AppState;
+
+// tempo of the written file when none is given, the MIDI default
+const DEFAULT_TEMPO: f64 = 120.0;
+
+/// Write `notes` as the vocal MIDI of `path` (`song.mp3` -> `song_vocals_pitches.mid`), replacing
+/// any existing one, e.g. after correcting an auto-transcribed melody. `tempo` (bpm, default 120)
+/// only sets the beat grid of the file; note times are kept in seconds.
+#[tauri::command]
+pub fn save_midi(state: State<'_, AppState>, path: String, notes: Vec<Note>, tempo: Option<f64>) -> Result<(), String> {
+  ensure_unlocked(&state, "save_midi")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let bytes = encode_midi_with_tempo(&notes, tempo.unwrap_or(DEFAULT_TEMPO))?;
+  let target = state.res_dir.join(with_extension(&path, VOCAL_MIDI_SUFFIXES[0]));
+  std::fs::write(&target, bytes).map_err(|e| format!("failed to write {}: {}", target.display(), e))?;
+  info!(path = %target.display(), notes = notes.len(), "saved midi");
+  Ok(())
+}
@@ -0,0 +1,67 @@
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::settings::ScoringProfile;
+use crate::AppState;
+
+/// Return all scoring profiles stored in settings.
+#[tauri::command]
+pub fn list_scoring_profiles(state: State<'_, AppState>) -> Result<Vec<ScoringProfile>, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  Ok(settings.scoring_profiles.clone())
+}
+
+/// Create or replace (by name) a scoring profile and persist settings.
+#[tauri::command]
+pub fn save_scoring_profile(state: State<'_, AppState>, profile: ScoringProfile) -> Result<(), String> {
+  ensure_unlocked(&state, "save_scoring_profile")?;
+  if profile.name.trim().is_empty() {
+    return Err("profile name is empty".to_string());
+  }
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  match settings.scoring_profiles.iter_mut().find(|p| p.name == profile.name) {
+    Some(existing) => *existing = profile,
+    None => settings.scoring_profiles.push(profile),
+  }
+  settings.save(&state.config_dir)
+}
+
+/// Remove a scoring profile by name and persist settings. The default profile cannot be removed.
+#[tauri::command]
+pub fn delete_scoring_profile(state: State<'_, AppState>, name: String) -> Result<(), String> {
+  ensure_unlocked(&state, "delete_scoring_profile")?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  if settings.default_scoring_profile == name {
+    return Err(format!("cannot delete the default scoring profile: {}", name));
+  }
+  let before = settings.scoring_profiles.len();
+  settings.scoring_profiles.retain(|p| p.name != name);
+  if settings.scoring_profiles.len() == before {
+    return Err(format!("scoring profile not found: {}", name));
+  }
+  settings.save(&state.config_dir)
+}
+
+/// Select the scoring profile for the current session and return it.
+#[tauri::command]
+pub fn select_scoring_profile(state: State<'_, AppState>, name: String) -> Result<ScoringProfile, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  let profile = settings.scoring_profile(&name).cloned().ok_or_else(|| format!("scoring profile not found: {}", name))?;
+  let mut active = state.scoring_profile.lock().map_err(|e| format!("session lock poisoned: {}", e))?;
+  info!(%name, "selected scoring profile");
+  *active = Some(name);
+  Ok(profile)
+}
+
+/// Return the scoring profile selected for this session, or the default from settings.
+#[tauri::command]
+pub fn get_scoring_profile(state: State<'_, AppState>) -> Result<ScoringProfile, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  let active = state.scoring_profile.lock().map_err(|e| format!("session lock poisoned: {}", e))?;
+  let name = active.as_deref().unwrap_or(&settings.default_scoring_profile);
+  settings
+    .scoring_profile(name)
+    .or_else(|| settings.scoring_profiles.first())
+    .cloned()
+    .ok_or_else(|| "no scoring profiles configured".to_string())
+}
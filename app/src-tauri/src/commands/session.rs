@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::AppState;
+
+const SESSION_FILE: &str = "session.json";
+
+/// Volume and speed of the player.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MixerSettings {
+  /// 0..1
+  pub volume: f64,
+  pub playback_rate: f64,
+}
+
+impl Default for MixerSettings {
+  fn default() -> Self {
+    MixerSettings { volume: 1.0, playback_rate: 1.0 }
+  }
+}
+
+/// What the app was doing, saved with `save_session` so the next start can resume it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Session {
+  /// playlist url of the current song
+  pub song: Option<String>,
+  /// playback position in seconds
+  pub position: f64,
+  /// playlist urls of the songs queued after the current one
+  pub queue: Vec<String>,
+  /// key shift in semitones
+  pub transpose: i32,
+  pub mixer: MixerSettings,
+  /// scoring profile picked with `select_scoring_profile`; filled in by the backend
+  pub scoring_profile: Option<String>,
+  /// songs marked with `mark_sung`; filled in by the backend
+  pub sung_songs: Vec<String>,
+}
+
+fn session_path(dir: &Path) -> PathBuf {
+  dir.join(SESSION_FILE)
+}
+
+/// Save the session next to `settings.json`. The player state comes from the frontend; the
+/// scoring profile and sung songs are taken from the backend. The file is replaced atomically, so
+/// a crash while saving keeps the previous session.
+#[tauri::command]
+pub fn save_session(state: State<'_, AppState>, mut session: Session) -> Result<(), String> {
+  session.scoring_profile = state.scoring_profile.lock().map_err(|e| format!("session lock poisoned: {}", e))?.clone();
+  session.sung_songs = state.sung_songs.lock().map_err(|e| format!("sung songs lock poisoned: {}", e))?.iter().cloned().collect();
+
+  let path = session_path(&state.config_dir);
+  let s = serde_json::to_string_pretty(&session).map_err(|e| format!("failed to serialize session: {}", e))?;
+  let tmp = path.with_extension("json.tmp");
+  std::fs::write(&tmp, s).map_err(|e| format!("failed to write {}: {}", tmp.display(), e))?;
+  std::fs::rename(&tmp, &path).map_err(|e| format!("failed to replace {}: {}", path.display(), e))?;
+  debug!(song = ?session.song, position = session.position, "saved session");
+  Ok(())
+}
+
+/// Load the last saved session and restore its scoring profile and sung songs. Returns `None`
+/// when no session was saved; an unreadable session file is ignored with a warning.
+#[tauri::command]
+pub fn restore_session(state: State<'_, AppState>) -> Result<Option<Session>, String> {
+  let path = session_path(&state.config_dir);
+  let Ok(s) = std::fs::read_to_string(&path) else {
+    return Ok(None);
+  };
+  let session: Session = match serde_json::from_str(&s) {
+    Ok(session) => session,
+    Err(e) => {
+      warn!(path = %path.display(), error = %e, "invalid session file, ignoring");
+      return Ok(None);
+    }
+  };
+
+  *state.scoring_profile.lock().map_err(|e| format!("session lock poisoned: {}", e))? = session.scoring_profile.clone();
+  state.sung_songs.lock().map_err(|e| format!("sung songs lock poisoned: {}", e))?.extend(session.sung_songs.iter().cloned());
+  info!(song = ?session.song, position = session.position, queued = session.queue.len(), "restored session");
+  Ok(Some(session))
+}
+
+#[test]
+pub fn test_session_defaults() {
+  let session: Session = serde_json::from_str(r#"{"song": "a.mp3", "position": 12.5, "mixer": {"volume": 0.5}}"#).expect("failed to parse session");
+  assert_eq!(session.song.as_deref(), Some("a.mp3"));
+  assert_eq!(session.mixer, MixerSettings { volume: 0.5, playback_rate: 1.0 });
+  assert!(session.queue.is_empty() && session.transpose == 0);
+  assert_eq!(serde_json::from_str::<Session>("{}").expect("failed to parse empty session"), Session::default());
+}
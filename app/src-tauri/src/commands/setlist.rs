@@ -0,0 +1,114 @@
+use serde::Deserialize;
+use tauri::State;
+
+use klok_core::phrases::estimate_key;
+
+use crate::commands::get_metadata::get_duration_and_artist;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::AppState;
+
+/// One song of a setlist, usually a playlist item plus who sings it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SetlistEntry {
+  pub url: String,
+  pub title: Option<String>,
+  pub artist: Option<String>,
+  /// musical key; estimated from the vocal MIDI when omitted
+  pub key: Option<String>,
+  pub singers: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetlistFormat {
+  /// printable page (print to PDF from the webview)
+  #[default]
+  Html,
+  Csv,
+}
+
+// A setlist row with every column resolved.
+struct SetlistRow {
+  title: String,
+  artist: String,
+  key: String,
+  singers: String,
+}
+
+/// Render a setlist for event planning with titles, artists, keys and singer assignments.
+/// Missing artists are read from the audio tags and missing keys estimated from the vocal MIDI.
+/// Returns the document text; saving or printing it is left to the caller.
+#[tauri::command]
+pub fn export_setlist(state: State<'_, AppState>, songs: Vec<SetlistEntry>, format: Option<SetlistFormat>, title: Option<String>) -> Result<String, String> {
+  let rows: Vec<SetlistRow> = songs.into_iter().map(|song| resolve_row(&state, song)).collect();
+  let title = title.unwrap_or_else(|| "Setlist".to_string());
+  Ok(match format.unwrap_or_default() {
+    SetlistFormat::Html => format_html(&title, &rows),
+    SetlistFormat::Csv => format_csv(&rows),
+  })
+}
+
+fn resolve_row(state: &AppState, song: SetlistEntry) -> SetlistRow {
+  let title = song.title.unwrap_or_else(|| {
+    std::path::Path::new(&song.url).file_stem().and_then(|s| s.to_str()).unwrap_or(&song.url).to_string()
+  });
+  let artist = song
+    .artist
+    .or_else(|| state.resolve(&song.url).and_then(get_duration_and_artist).map(|(_, a)| a))
+    .unwrap_or_default();
+  let key = song.key.or_else(|| match find_vocal_notes(state, &song.url) {
+    Ok(notes) => notes.and_then(|n| estimate_key(&n)),
+    Err(e) => {
+      warn!(url = %song.url, error = %e, "failed to load vocal midi for key estimate");
+      None
+    }
+  });
+  SetlistRow { title, artist, key: key.unwrap_or_default(), singers: song.singers.join(", ") }
+}
+
+fn escape_html(s: &str) -> String {
+  s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn format_html(title: &str, rows: &[SetlistRow]) -> String {
+  let mut out = format!(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>\nbody {{ font-family: sans-serif; }}\ntable {{ border-collapse: collapse; width: 100%; }}\nth, td {{ border-bottom: 1px solid #ccc; padding: 4px 8px; text-align: left; }}\n</style>\n</head>\n<body>\n<h1>{0}</h1>\n<table>\n<tr><th>#</th><th>Title</th><th>Artist</th><th>Key</th><th>Singers</th></tr>\n",
+    escape_html(title)
+  );
+  for (i, row) in rows.iter().enumerate() {
+    out.push_str(&format!(
+      "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+      i + 1,
+      escape_html(&row.title),
+      escape_html(&row.artist),
+      escape_html(&row.key),
+      escape_html(&row.singers)
+    ));
+  }
+  out.push_str("</table>\n</body>\n</html>\n");
+  out
+}
+
+fn escape_csv(s: &str) -> String {
+  if s.contains([',', '"', '\n']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_string()
+  }
+}
+
+fn format_csv(rows: &[SetlistRow]) -> String {
+  let mut out = "#,title,artist,key,singers\n".to_string();
+  for (i, row) in rows.iter().enumerate() {
+    out.push_str(&format!("{},{},{},{},{}\n", i + 1, escape_csv(&row.title), escape_csv(&row.artist), escape_csv(&row.key), escape_csv(&row.singers)));
+  }
+  out
+}
+
+#[test]
+pub fn test_format_setlist() {
+  let rows = vec![SetlistRow { title: "A & B".to_string(), artist: "X".to_string(), key: "C major".to_string(), singers: "Ann, Bo".to_string() }];
+  assert!(format_html("Friday", &rows).contains("<tr><td>1</td><td>A &amp; B</td><td>X</td><td>C major</td><td>Ann, Bo</td></tr>"));
+  assert_eq!(format_csv(&rows), "#,title,artist,key,singers\n1,A & B,X,C major,\"Ann, Bo\"\n");
+}
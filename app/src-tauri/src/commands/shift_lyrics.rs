@@ -0,0 +1,28 @@
+use tauri::State;
+
+use klok_core::lyrics::{shift_timings, LyricLine};
+
+use crate::commands::get_metadata::find_lyrics;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::save_lyrics::write_lrc;
+use crate::AppState;
+
+/// Shift and scale every timing of the lyrics next to `path` (`time * scale + offset_secs`).
+/// Returns the adjusted lines; with `write` they are also saved as the song's `.lrc` file.
+#[tauri::command]
+pub fn shift_lyrics(state: State<'_, AppState>, path: String, offset_secs: f64, scale: Option<f64>, write: Option<bool>) -> Result<Vec<LyricLine>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let scale = scale.unwrap_or(1.0);
+  if !scale.is_finite() || scale <= 0.0 || !offset_secs.is_finite() {
+    return Err(format!("invalid shift: offset {} scale {}", offset_secs, scale));
+  }
+  let mut lyrics = find_lyrics(&state, &path)?.ok_or_else(|| format!("lyrics file not found for provided path: {}", path))?;
+  shift_timings(&mut lyrics, offset_secs, scale);
+  if write.unwrap_or(false) {
+    ensure_unlocked(&state, "shift_lyrics")?;
+    write_lrc(&state, &path, &lyrics)?;
+  }
+  Ok(lyrics)
+}
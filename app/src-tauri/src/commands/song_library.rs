@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+use klok_core::lyrics::LYRICS_FORMATS;
+
+use crate::commands::background::BACKGROUND_SUFFIXES;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::VOCAL_MIDI_SUFFIXES;
+use crate::commands::COMMON_EXT;
+use crate::settings::DeletedSong;
+use crate::AppState;
+
+/// Hide a song from `load_playlist` (or show it again with `hidden: false`) and persist settings.
+/// The files are left untouched.
+#[tauri::command]
+pub fn hide_song(state: State<'_, AppState>, path: String, hidden: Option<bool>) -> Result<(), String> {
+  ensure_unlocked(&state, "hide_song")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  if hidden.unwrap_or(true) {
+    settings.hidden_songs.insert(path);
+  } else {
+    settings.hidden_songs.remove(&path);
+  }
+  settings.save(&state.config_dir)
+}
+
+/// Delete a song and its companion files (lyrics, stems, pitch MIDI ...), either to the OS trash
+/// or permanently. The removed files are recorded in `settings.deleted_songs`, so a song sent to
+/// the trash can be put back with `restore_song`.
+/// Returns the paths that were removed.
+#[tauri::command]
+pub fn delete_song(state: State<'_, AppState>, path: String, to_trash: bool) -> Result<Vec<String>, String> {
+  ensure_unlocked(&state, "delete_song")?;
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+  let files = song_files(&resolved)?;
+
+  if to_trash {
+    trash::delete_all(&files).map_err(|e| format!("failed to move {} to trash: {}", path, e))?;
+  } else {
+    for file in &files {
+      let removed = if file.is_dir() { std::fs::remove_dir_all(file) } else { std::fs::remove_file(file) };
+      removed.map_err(|e| format!("failed to delete {}: {}", file.display(), e))?;
+    }
+  }
+  let files: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+  info!(%path, to_trash, count = files.len(), "deleted song");
+
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.hidden_songs.remove(&path);
+  let deleted_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+  settings.deleted_songs.push(DeletedSong { url: path, files: files.clone(), to_trash, deleted_at });
+  settings.save(&state.config_dir)?;
+  Ok(files)
+}
+
+/// Undo the last `delete_song` of `path` that went to the OS trash, putting its files back where
+/// they were, and drop it from `settings.deleted_songs`. Returns the paths restored; files no
+/// longer in the trash are skipped, and none being there is an error.
+#[tauri::command]
+pub fn restore_song(state: State<'_, AppState>, path: String) -> Result<Vec<String>, String> {
+  ensure_unlocked(&state, "restore_song")?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  let index = settings.deleted_songs.iter().rposition(|d| d.url == path).ok_or_else(|| format!("no deleted song to restore: {}", path))?;
+  if !settings.deleted_songs[index].to_trash {
+    return Err(format!("{} was deleted permanently", path));
+  }
+  let restored = restore_from_trash(&settings.deleted_songs[index].files)?;
+  info!(%path, count = restored.len(), "restored song");
+  settings.deleted_songs.remove(index);
+  settings.save(&state.config_dir)?;
+  Ok(restored)
+}
+
+// Put the trashed `files` back, the latest deletion of each; the ones found are returned.
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))))]
+fn restore_from_trash(files: &[String]) -> Result<Vec<String>, String> {
+  use std::collections::BTreeMap;
+
+  let mut latest: BTreeMap<String, trash::TrashItem> = BTreeMap::new();
+  for item in trash::os_limited::list().map_err(|e| format!("failed to list the trash: {}", e))? {
+    let original = item.original_path().display().to_string();
+    if files.contains(&original) && latest.get(&original).is_none_or(|l| l.time_deleted < item.time_deleted) {
+      latest.insert(original, item);
+    }
+  }
+  if latest.is_empty() {
+    return Err("the deleted files are no longer in the trash".to_string());
+  }
+  let restored = latest.keys().cloned().collect();
+  trash::os_limited::restore_all(latest.into_values()).map_err(|e| format!("failed to restore from the trash: {}", e))?;
+  Ok(restored)
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))))]
+fn restore_from_trash(_files: &[String]) -> Result<Vec<String>, String> {
+  Err("restoring from the trash is not supported on this platform".to_string())
+}
+
+// companions written by exports, after the song's stem
+const EXPORT_SUFFIXES: [&str; 3] = ["_click.mid", "_practice_melody.wav", "_practice_guide.wav"];
+
+// stems come as the library's audio formats, or wav from a separation tool
+fn is_stem_audio(ext: &str) -> bool {
+  ext.eq_ignore_ascii_case("wav") || COMMON_EXT.iter().any(|e| e[1..].eq_ignore_ascii_case(ext))
+}
+
+/// Whether `name` is a companion of the song whose file stem is `stem`: its lyrics (`song.lrc`,
+/// `song.zh.lrc`, `song.srt`, `song.txt`, ...), stems (`song_vocals.mp3`, `song_non_vocals.mp3`),
+/// melody (`song_vocals_pitches.mid`, `song.kar`, ...), background, lyric clips
+/// (`song_clip_83.50`) and exports (`song_click.mid`, `song_take_<id>.wav`, ...). Never another
+/// song such as `song_live.mp3` or `song.flac`.
+pub(crate) fn is_companion(stem: &str, name: &str) -> bool {
+  let Some(rest) = name.strip_prefix(stem) else {
+    return false;
+  };
+  let lower = rest.to_ascii_lowercase();
+  let is_lyrics = |ext: &str| LYRICS_FORMATS.iter().any(|(e, _)| e[1..] == *ext);
+  if let Some(ext) = lower.strip_prefix('.') {
+    return match ext.rsplit_once('.') {
+      // `song.zh.lrc`
+      Some((lang, ext)) => !lang.is_empty() && !lang.contains('.') && is_lyrics(ext),
+      None => is_lyrics(ext) || ext == "txt" || VOCAL_MIDI_SUFFIXES.iter().any(|s| s.strip_prefix('.') == Some(ext)),
+    };
+  }
+  if let Some(start) = lower.strip_prefix("_clip_") {
+    return start.parse::<f64>().is_ok();
+  }
+  if let Some(id) = lower.strip_prefix("_take_").and_then(|r| r.strip_suffix(".wav")) {
+    return !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+  }
+  match lower.rsplit_once('.') {
+    Some(("_vocals" | "_non_vocals", ext)) => is_stem_audio(ext),
+    _ => [&VOCAL_MIDI_SUFFIXES[..], &BACKGROUND_SUFFIXES[..], &EXPORT_SUFFIXES[..]].concat().contains(&lower.as_str()),
+  }
+}
+
+/// The song file plus its companions next to it (see [`is_companion`]), lyric clip folders
+/// included. `song.<x>.lrc` is left to the song `song.<x>.mp3` when the folder has one.
+pub(crate) fn song_files(song: &Path) -> Result<Vec<PathBuf>, String> {
+  let dir = song.parent().ok_or_else(|| format!("invalid song path: {}", song.display()))?;
+  let stem = song.file_stem().and_then(|s| s.to_str()).ok_or_else(|| format!("invalid song path: {}", song.display()))?;
+  let entries = std::fs::read_dir(dir).map_err(|e| format!("failed to read res_dir {}: {}", dir.display(), e))?;
+  let paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+  let names: Vec<&str> = paths.iter().filter_map(|p| p.file_name()?.to_str()).collect();
+  let of_another_song = |name: &str| {
+    let Some((base, _)) = name.rsplit_once('.').filter(|(base, _)| base.strip_prefix(stem).is_some_and(|x| x.starts_with('.'))) else {
+      return false;
+    };
+    names.iter().any(|n| n.strip_prefix(base).is_some_and(|ext| COMMON_EXT.iter().any(|e| ext.eq_ignore_ascii_case(e))))
+  };
+  let mut files: Vec<PathBuf> = paths
+    .iter()
+    .filter(|p| *p == song || p.file_name().and_then(|n| n.to_str()).is_some_and(|n| is_companion(stem, n) && !of_another_song(n)))
+    .cloned()
+    .collect();
+  files.sort();
+  Ok(files)
+}
+
+#[test]
+pub fn test_song_files() {
+  let dir = std::env::temp_dir().join(format!("klok_song_files_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  for name in ["song.mp3", "song.lrc", "song.zh.lrc", "song_vocals_pitches.mid", "song_vocals.mp3", "song_background.jpg", "song2.mp3", "other.lrc"] {
+    std::fs::write(dir.join(name), b"").unwrap();
+  }
+  // another song named with this one as a prefix, and its own companions
+  for name in ["song_live.mp3", "song_live.lrc", "song_live_vocals.mp3", "song.flac"] {
+    std::fs::write(dir.join(name), b"").unwrap();
+  }
+  std::fs::create_dir_all(dir.join("song_clip_83.50")).unwrap();
+  let files = song_files(&dir.join("song.mp3")).expect("failed to list song files");
+  let names: Vec<&str> = files.iter().filter_map(|f| f.file_name()?.to_str()).collect();
+  assert_eq!(names, vec!["song.lrc", "song.mp3", "song.zh.lrc", "song_background.jpg", "song_clip_83.50", "song_vocals.mp3", "song_vocals_pitches.mid"]);
+  let files = song_files(&dir.join("song_live.mp3")).expect("failed to list song files");
+  let names: Vec<&str> = files.iter().filter_map(|f| f.file_name()?.to_str()).collect();
+  assert_eq!(names, vec!["song_live.lrc", "song_live.mp3", "song_live_vocals.mp3"]);
+  std::fs::remove_dir_all(&dir).unwrap();
+
+  // `Live.2019.lrc` is the lyrics of `Live.2019.mp3`, `Live.en.lrc` still those of `Live.mp3`
+  std::fs::create_dir_all(&dir).unwrap();
+  for name in ["Live.mp3", "Live.en.lrc", "Live.2019.mp3", "Live.2019.lrc"] {
+    std::fs::write(dir.join(name), b"").unwrap();
+  }
+  let live = song_files(&dir.join("Live.mp3")).expect("failed to list song files");
+  let live_2019 = song_files(&dir.join("Live.2019.mp3")).expect("failed to list song files");
+  std::fs::remove_dir_all(&dir).unwrap();
+  assert_eq!(live, vec![dir.join("Live.en.lrc"), dir.join("Live.mp3")]);
+  assert_eq!(live_2019, vec![dir.join("Live.2019.lrc"), dir.join("Live.2019.mp3")]);
+
+  assert!(is_companion("Love", "Love_take_1760400000000.wav"));
+  assert!(!is_companion("Love", "Love_Story.mp3"));
+  assert!(!is_companion("Love", "Love.en.mp3"));
+}
@@ -0,0 +1,173 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::settings::{ExportTarget, StorageKind};
+use crate::AppState;
+
+// space left free on a target after a write, so an export never fills a drive completely
+const FREE_SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+// recordings are 16-bit PCM at this rate, for `check_recording_space`
+const RECORDING_SAMPLE_RATE: u64 = 48_000;
+const RECORDING_SAMPLE_BYTES: u64 = 2;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportTargetStatus {
+  #[serde(flatten)]
+  pub target: ExportTarget,
+  /// mounted or reachable (local and cloud sync folders are created on first use)
+  pub available: bool,
+  /// free bytes on the target, `None` when unknown
+  pub free_space: Option<u64>,
+}
+
+/// Bytes free for unprivileged writes on the file system holding `path`, `None` when unknown.
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+  use std::ffi::CString;
+  use std::os::unix::ffi::OsStrExt;
+
+  let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+  let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+  // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer
+  if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+    return None;
+  }
+  Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+  None
+}
+
+fn find_target(state: &AppState, name: &str) -> Result<ExportTarget, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.export_targets.iter().find(|t| t.name == name).cloned().ok_or_else(|| format!("unknown export target: {}", name))
+}
+
+// Root folder of `target`, created for kinds that live on this machine.
+fn target_root(target: &ExportTarget) -> Result<PathBuf, String> {
+  let root = PathBuf::from(&target.path);
+  match target.kind {
+    StorageKind::Local | StorageKind::CloudSync => {
+      std::fs::create_dir_all(&root).map_err(|e| format!("failed to create {}: {}", root.display(), e))?;
+    }
+    StorageKind::Usb if !root.is_dir() => return Err(format!("export target {} is not mounted: {}", target.name, root.display())),
+    StorageKind::NetworkShare if !root.is_dir() => return Err(format!("export target {} is not reachable: {}", target.name, root.display())),
+    _ => {}
+  }
+  Ok(root)
+}
+
+/// Where an export named `rel` (library-relative, e.g. `a/song_click.mid`) goes: next to the song in
+/// the library without a `target`, else under the root of the named export target. Returns the
+/// absolute path and the path reported back to the frontend (`rel` for the library).
+pub(crate) fn export_path(state: &AppState, target: Option<&str>, rel: &str) -> Result<(PathBuf, String), String> {
+  match target {
+    None => Ok((state.res_dir.join(rel), rel.to_string())),
+    Some(name) => {
+      let resolved = target_root(&find_target(state, name)?)?.join(rel);
+      let display = resolved.display().to_string();
+      Ok((resolved, display))
+    }
+  }
+}
+
+/// Fail when the file system holding `path` (or its closest existing parent) can't take `bytes`
+/// more while keeping a margin free. Passes when the free space is unknown.
+pub(crate) fn ensure_free_space(path: &Path, bytes: u64) -> Result<(), String> {
+  let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+    return Ok(());
+  };
+  match free_space(existing) {
+    Some(free) if free < bytes + FREE_SPACE_MARGIN => Err(format!("not enough space on {}: {} MB needed, {} MB free", existing.display(), (bytes + FREE_SPACE_MARGIN) / 1_000_000, free / 1_000_000)),
+    _ => Ok(()),
+  }
+}
+
+/// Write an export to `target` (see [`export_path`]) after checking for space, creating its
+/// folders. Returns the reported path.
+pub(crate) fn write_export(state: &AppState, target: Option<&str>, rel: &str, bytes: &[u8]) -> Result<String, String> {
+  let (resolved, display) = export_path(state, target, rel)?;
+  ensure_free_space(&resolved, bytes.len() as u64)?;
+  if let Some(dir) = resolved.parent() {
+    std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+  }
+  std::fs::write(&resolved, bytes).map_err(|e| format!("failed to write {}: {}", resolved.display(), e))?;
+  Ok(display)
+}
+
+/// The configured export targets with whether each can be written now and its free space.
+#[tauri::command]
+pub fn list_export_targets(state: State<'_, AppState>) -> Result<Vec<ExportTargetStatus>, String> {
+  let targets = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.export_targets.clone();
+  Ok(
+    targets
+      .into_iter()
+      .map(|target| {
+        let root = Path::new(&target.path);
+        let available = match target.kind {
+          StorageKind::Local | StorageKind::CloudSync => true,
+          StorageKind::Usb | StorageKind::NetworkShare => root.is_dir(),
+        };
+        let free_space = root.ancestors().find(|p| p.exists()).and_then(free_space);
+        ExportTargetStatus { target, available, free_space }
+      })
+      .collect(),
+  )
+}
+
+/// Replace the export targets and persist settings. Names must be unique and non-empty.
+#[tauri::command]
+pub fn set_export_targets(state: State<'_, AppState>, targets: Vec<ExportTarget>) -> Result<(), String> {
+  ensure_unlocked(&state, "set_export_targets")?;
+  validate_targets(&targets)?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.export_targets = targets;
+  settings.save(&state.config_dir)
+}
+
+fn validate_targets(targets: &[ExportTarget]) -> Result<(), String> {
+  for (i, target) in targets.iter().enumerate() {
+    if target.name.trim().is_empty() {
+      return Err("export target name is empty".to_string());
+    }
+    if !Path::new(&target.path).is_absolute() {
+      return Err(format!("export target {} needs an absolute path: {}", target.name, target.path));
+    }
+    if targets[..i].iter().any(|t| t.name == target.name) {
+      return Err(format!("duplicate export target: {}", target.name));
+    }
+  }
+  Ok(())
+}
+
+pub(crate) fn recording_bytes(seconds: f64, channels: u32) -> u64 {
+  (seconds.max(0.0) * (RECORDING_SAMPLE_RATE * RECORDING_SAMPLE_BYTES * channels.max(1) as u64) as f64).ceil() as u64
+}
+
+/// Check before a long recording that `target` (the library without one) can hold `seconds` of
+/// audio with `channels` channels (default mono). Returns the free bytes, when known.
+#[tauri::command]
+pub fn check_recording_space(state: State<'_, AppState>, target: Option<String>, seconds: f64, channels: Option<u32>) -> Result<Option<u64>, String> {
+  let (root, _) = export_path(&state, target.as_deref(), "")?;
+  ensure_free_space(&root, recording_bytes(seconds, channels.unwrap_or(1)))?;
+  Ok(root.ancestors().find(|p| p.exists()).and_then(free_space))
+}
+
+#[test]
+pub fn test_export_targets() {
+  assert_eq!(recording_bytes(60.0, 2), 11_520_000);
+  assert_eq!(recording_bytes(-1.0, 1), 0);
+
+  let target = |name: &str, path: &str| ExportTarget { name: name.to_string(), kind: StorageKind::Usb, path: path.to_string() };
+  let root = std::env::temp_dir().display().to_string();
+  assert!(validate_targets(&[target("usb", &root), target("share", &root)]).is_ok());
+  assert!(validate_targets(&[target("usb", &root), target("usb", &root)]).is_err());
+  assert!(validate_targets(&[target("usb", "relative/dir")]).is_err());
+  assert!(target_root(&target("usb", &format!("{}/klok_missing_drive", root))).is_err());
+  assert!(ensure_free_space(&std::env::temp_dir(), 0).is_ok());
+}
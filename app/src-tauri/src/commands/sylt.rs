@@ -0,0 +1,71 @@
+use lofty::id3::v2::{Frame, FrameFlags, FrameValue, ID3v2Tag, SyncTextContentType, SyncTextInformation, SynchronizedText, TimestampFormat};
+use lofty::mpeg::MPEGFile;
+use lofty::{AudioFile, ParseOptions, TagExt, TextEncoding};
+use std::fs::File;
+use std::path::Path;
+
+use klok_core::language::detect_language;
+use klok_core::lyrics::LyricLine;
+
+// ISO 639-2 codes for the languages `detect_language` reports; ID3 uses "XXX" when unknown
+const LANGUAGES: [(&str, &str); 8] = [("zh", "chi"), ("ja", "jpn"), ("ko", "kor"), ("en", "eng"), ("fr", "fre"), ("de", "ger"), ("es", "spa"), ("it", "ita")];
+
+/// SYLT entries (milliseconds, text): one per word for word-timed lines, else one per line.
+/// Every line after the first starts with a newline, as players expect.
+fn sylt_entries(lines: &[LyricLine]) -> Vec<(u32, String)> {
+  let ms = |t: f64| (t.max(0.0) * 1000.0).round() as u32;
+  let mut entries = Vec::new();
+  for line in lines {
+    let break_before = if entries.is_empty() { "" } else { "\n" };
+    if line.words.is_empty() {
+      entries.push((ms(line.time), format!("{}{}", break_before, line.text)));
+    } else {
+      for (i, word) in line.words.iter().enumerate() {
+        entries.push((ms(word.time), if i == 0 { format!("{}{}", break_before, word.text) } else { word.text.clone() }));
+      }
+    }
+  }
+  entries
+}
+
+/// Write `lines` into the ID3v2 `SYLT` frame of an MP3 file, replacing any synced lyrics it has,
+/// so they travel with the file to players that read them.
+pub(crate) fn embed_sylt(audio: &Path, lines: &[LyricLine]) -> Result<(), String> {
+  if !audio.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("mp3")) {
+    return Err(format!("synced lyrics can only be embedded in mp3 files: {}", audio.display()));
+  }
+  let mut file = File::open(audio).map_err(|e| format!("failed to open {}: {}", audio.display(), e))?;
+  let mpeg = MPEGFile::read_from(&mut file, ParseOptions::new()).map_err(|e| format!("failed to read tags of {}: {}", audio.display(), e))?;
+  let mut tag: ID3v2Tag = mpeg.id3v2().cloned().unwrap_or_default();
+
+  let language = detect_language(lines).and_then(|code| LANGUAGES.iter().find(|(iso1, _)| *iso1 == code)).map_or("XXX", |(_, iso2)| iso2);
+  let sylt = SynchronizedText {
+    information: SyncTextInformation {
+      encoding: TextEncoding::UTF8,
+      language: language.to_string(),
+      timestamp_format: TimestampFormat::MS,
+      content_type: SyncTextContentType::Lyrics,
+      description: None,
+    },
+    content: sylt_entries(lines),
+  };
+  let bytes = sylt.as_bytes().map_err(|e| format!("failed to encode synced lyrics: {}", e))?;
+  let frame = Frame::new("SYLT", FrameValue::Binary(bytes), FrameFlags::default()).map_err(|e| format!("failed to build SYLT frame: {}", e))?;
+  tag.remove("SYLT");
+  tag.insert(frame);
+  tag.save_to_path(audio).map_err(|e| format!("failed to write tags of {}: {}", audio.display(), e))?;
+  info!(path = %audio.display(), entries = sylt.content.len(), %language, "embedded synced lyrics");
+  Ok(())
+}
+
+#[test]
+pub fn test_sylt_entries() {
+  use klok_core::lyrics::LyricWord;
+
+  let lines = vec![
+    LyricLine { time: 1.0, text: "hello world".to_string(), ..Default::default() },
+    LyricLine { time: 2.5, text: "sing along".to_string(), words: vec![LyricWord { time: 2.5, text: "sing ".to_string() }, LyricWord { time: 3.0, text: "along".to_string() }], ..Default::default() },
+  ];
+  assert_eq!(sylt_entries(&lines), vec![(1000, "hello world".to_string()), (2500, "\nsing ".to_string()), (3000, "along".to_string())]);
+  assert!(sylt_entries(&[]).is_empty());
+}
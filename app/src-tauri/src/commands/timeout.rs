@@ -0,0 +1,119 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::commands::perf::record;
+use crate::AppState;
+
+/// Set once a command has run past its timeout. Blocking work can't be interrupted, so loops
+/// over many files should check it and stop early.
+#[derive(Clone, Debug, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed)
+  }
+}
+
+/// A command exceeded its configured timeout. It reaches the frontend as
+/// `timeout: <command> exceeded <n>s`, so it can be told apart from other errors.
+#[derive(Debug)]
+pub struct TimeoutError {
+  pub command: String,
+  pub seconds: f64,
+}
+
+impl fmt::Display for TimeoutError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "timeout: {} exceeded {}s", self.command, self.seconds)
+  }
+}
+
+fn limit(state: &AppState, command: &str) -> Result<Option<Duration>, String> {
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  Ok(settings.command_timeout(command))
+}
+
+// Count the call in the command's latency stats.
+fn finished<T>(state: &AppState, command: &str, started: Instant, result: Result<T, String>) -> Result<T, String> {
+  record(state, |perf| perf.record_command(command, started.elapsed(), result.is_ok()));
+  result
+}
+
+fn timed_out(command: &str, limit: Duration) -> String {
+  warn!(%command, seconds = limit.as_secs_f64(), "command timed out");
+  TimeoutError { command: command.to_string(), seconds: limit.as_secs_f64() }.to_string()
+}
+
+/// Run blocking command work on the blocking pool, failing with a [`TimeoutError`] when it takes
+/// longer than the command's timeout. The work is then told to stop through its [`Cancel`] and
+/// its result is discarded.
+pub(crate) async fn run_blocking<T, F>(state: &AppState, command: &str, work: F) -> Result<T, String>
+where
+  F: FnOnce(Cancel) -> Result<T, String> + Send + 'static,
+  T: Send + 'static,
+{
+  let limit = limit(state, command)?;
+  let started = Instant::now();
+  let cancel = Cancel::default();
+  let handle = tauri::async_runtime::spawn_blocking({
+    let cancel = cancel.clone();
+    move || work(cancel)
+  });
+  let joined = match limit {
+    Some(limit) => match tokio::time::timeout(limit, handle).await {
+      Ok(joined) => joined,
+      Err(_) => {
+        cancel.cancel();
+        return finished(state, command, started, Err(timed_out(command, limit)));
+      }
+    },
+    None => handle.await,
+  };
+  let result = joined.map_err(|e| format!("{} failed: {}", command, e)).and_then(|r| r);
+  finished(state, command, started, result)
+}
+
+/// Await async command work, failing with a [`TimeoutError`] when it takes longer than the
+/// command's timeout. The future is dropped, which cancels outstanding requests.
+pub(crate) async fn run_async<T>(state: &AppState, command: &str, work: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+  let limit = limit(state, command)?;
+  let started = Instant::now();
+  let result = match limit {
+    Some(limit) => tokio::time::timeout(limit, work).await.map_err(|_| timed_out(command, limit)).and_then(|r| r),
+    None => work.await,
+  };
+  finished(state, command, started, result)
+}
+
+#[test]
+pub fn test_run_blocking() {
+  use crate::settings::Settings;
+  use std::sync::Mutex;
+
+  let mut settings = Settings::default();
+  settings.command_timeouts.commands.insert("slow".to_string(), 0.05);
+  // too large for a Duration, as hand-edited in settings.json
+  settings.command_timeouts.commands.insert("huge".to_string(), 1e30);
+  assert_eq!(settings.command_timeout("huge"), None);
+  let state = AppState { settings: Arc::new(Mutex::new(settings)), ..Default::default() };
+
+  let slow = tauri::async_runtime::block_on(run_blocking(&state, "slow", |cancel| {
+    while !cancel.is_cancelled() {
+      std::thread::sleep(Duration::from_millis(5));
+    }
+    Ok(())
+  }));
+  assert_eq!(slow.unwrap_err(), "timeout: slow exceeded 0.05s");
+  assert_eq!(tauri::async_runtime::block_on(run_blocking(&state, "fast", |_| Ok(1))), Ok(1));
+  let stats = state.perf.lock().unwrap().snapshot();
+  assert_eq!((stats.commands["slow"].calls, stats.commands["slow"].failures), (1, 1));
+  assert_eq!((stats.commands["fast"].calls, stats.commands["fast"].failures), (1, 0));
+}
@@ -0,0 +1,93 @@
+use serde::Serialize;
+use std::path::Path;
+use tauri::State;
+
+use klok_core::phrases::estimate_tempo;
+
+use crate::commands::get_metadata::get_duration_and_artist;
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::load_midi::find_vocal_notes;
+use crate::settings::TransitionSettings;
+use crate::AppState;
+
+// count-in tempo when the next song has no vocal MIDI to estimate one from
+const DEFAULT_COUNT_IN_BPM: f64 = 100.0;
+
+/// One step of a song change, run by the frontend in order, each after the previous one finished.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TransitionStep {
+  /// fade the playing song out over `seconds`, then stop it
+  FadeOut { seconds: f64 },
+  /// read `text` out with text-to-speech
+  Announce { text: String },
+  /// play `beats` clicks at `bpm`
+  CountIn { beats: u32, bpm: f64 },
+  /// load and play `path`
+  Start { path: String },
+}
+
+/// Steps to change over to the song at `next`: fade out the playing song, announce the next song
+/// (and `singer`, when given), count in at the next song's tempo and start it. Which steps run is
+/// set per venue in the transition settings.
+#[tauri::command]
+pub fn plan_transition(state: State<'_, AppState>, next: String, singer: Option<String>) -> Result<Vec<TransitionStep>, String> {
+  if next.is_empty() {
+    return Err("next argument is empty".to_string());
+  }
+  let settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?.transition.clone();
+  let title = Path::new(&next).file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+  let artist = state.resolve(&next).and_then(get_duration_and_artist).map(|(_, a)| a).unwrap_or_default();
+  let bpm = if settings.count_in > 0 {
+    find_vocal_notes(&state, &next)?.as_deref().and_then(estimate_tempo).unwrap_or(DEFAULT_COUNT_IN_BPM)
+  } else {
+    DEFAULT_COUNT_IN_BPM
+  };
+  let steps = transition_steps(&settings, &next, &title, &artist, singer.as_deref(), bpm);
+  info!(%next, steps = steps.len(), "planned transition");
+  Ok(steps)
+}
+
+/// Replace the transition settings and persist settings.
+#[tauri::command]
+pub fn set_transition_settings(state: State<'_, AppState>, transition: TransitionSettings) -> Result<(), String> {
+  ensure_unlocked(&state, "set_transition_settings")?;
+  let mut settings = state.settings.lock().map_err(|e| format!("settings lock poisoned: {}", e))?;
+  settings.transition = transition;
+  settings.save(&state.config_dir)
+}
+
+fn transition_steps(settings: &TransitionSettings, next: &str, title: &str, artist: &str, singer: Option<&str>, bpm: f64) -> Vec<TransitionStep> {
+  let mut steps = Vec::new();
+  if settings.fade_out > 0.0 {
+    steps.push(TransitionStep::FadeOut { seconds: settings.fade_out });
+  }
+  if settings.announce {
+    let template = match singer {
+      Some(_) => &settings.singer_announcement,
+      None => &settings.announcement,
+    };
+    let text = template.replace("{singer}", singer.unwrap_or_default()).replace("{title}", title).replace("{artist}", artist);
+    steps.push(TransitionStep::Announce { text: text.trim().to_string() });
+  }
+  if settings.count_in > 0 {
+    steps.push(TransitionStep::CountIn { beats: settings.count_in, bpm });
+  }
+  steps.push(TransitionStep::Start { path: next.to_string() });
+  steps
+}
+
+#[test]
+pub fn test_transition_steps() {
+  let settings = TransitionSettings::default();
+  let steps = transition_steps(&settings, "a/晴天.mp3", "晴天", "周杰伦", Some("Ann"), 120.0);
+  assert_eq!(steps, vec![
+    TransitionStep::FadeOut { seconds: 3.0 },
+    TransitionStep::Announce { text: "Next up: Ann, singing 晴天".to_string() },
+    TransitionStep::CountIn { beats: 4, bpm: 120.0 },
+    TransitionStep::Start { path: "a/晴天.mp3".to_string() },
+  ]);
+
+  let quiet = TransitionSettings { fade_out: 0.0, announce: false, count_in: 0, ..settings };
+  assert_eq!(transition_steps(&quiet, "b.mp3", "b", "", None, 120.0), vec![TransitionStep::Start { path: "b.mp3".to_string() }]);
+}
@@ -0,0 +1,274 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use klok_core::encoding::decode_text;
+use klok_core::lrc::format_lrc;
+use klok_core::lyrics::{LyricLine, LyricWord};
+use klok_core::midi::{encode_midi, Note};
+
+use crate::commands::kiosk::ensure_unlocked;
+use crate::commands::sanitize_file_name;
+use crate::commands::song_library::is_companion;
+use crate::AppState;
+
+/// A parsed UltraStar song file (`.txt`), the format shared by UltraStar Deluxe, Performous and Vocaluxe.
+#[derive(Debug, Default)]
+pub struct UltraStarSong {
+  pub title: String,
+  pub artist: String,
+  /// audio file names relative to the song folder
+  pub audio: Option<String>,
+  pub vocals: Option<String>,
+  pub instrumental: Option<String>,
+  pub lyrics: Vec<LyricLine>,
+  /// sung notes; duet part P2 is on channel 1
+  pub notes: Vec<Note>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedSong {
+  pub title: String,
+  pub artist: String,
+  /// playlist url of the imported audio, `<Artist> - <Title>` numbered (`... (2).mp3`) when the
+  /// library already has a song of that name
+  pub url: String,
+}
+
+// UltraStar headers write decimals with either separator, e.g. `#BPM:300,5`.
+fn parse_number(value: &str) -> Option<f64> {
+  value.trim().replace(',', ".").parse::<f64>().ok()
+}
+
+// Split a note line `<beat> <length> <pitch> <syllable>` keeping the syllable's own spacing.
+fn parse_note_fields(rest: &str) -> Option<(i64, i64, i32, &str)> {
+  let mut rest = rest;
+  let mut fields = [0i64; 3];
+  for field in fields.iter_mut() {
+    rest = rest.trim_start();
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    *field = rest[..end].parse().ok()?;
+    rest = &rest[end..];
+  }
+  // a single separator space, anything after it belongs to the syllable
+  let text = rest.strip_prefix([' ', '\t']).unwrap_or(rest);
+  Some((fields[0], fields[1], fields[2] as i32, text))
+}
+
+/// Parse UltraStar `.txt` content. Beats are converted to seconds with `#BPM` (quarter beats)
+/// and `#GAP`; `#RELATIVE:yes` files and duet `P1`/`P2` sections are supported.
+pub fn parse_ultrastar(content: &str) -> Result<UltraStarSong, String> {
+  let mut song = UltraStarSong::default();
+  let mut bpm: Option<f64> = None;
+  let mut gap = 0.0;
+  let mut relative = false;
+
+  // beat offset for relative files, current duet part, and the line being collected
+  let mut offset = 0i64;
+  let mut channel = 0u8;
+  let mut line: Option<LyricLine> = None;
+
+  let flush = |line: &mut Option<LyricLine>, lyrics: &mut Vec<LyricLine>| {
+    if let Some(mut l) = line.take() {
+      l.text = l.text.trim().to_string();
+      lyrics.push(l);
+    }
+  };
+
+  for raw_line in content.trim_start_matches('\u{feff}').lines() {
+    let raw_line = raw_line.trim_end_matches('\r');
+    if let Some(header) = raw_line.strip_prefix('#') {
+      let Some((key, value)) = header.split_once(':') else {
+        continue;
+      };
+      let value = value.trim();
+      match key.trim().to_ascii_uppercase().as_str() {
+        "TITLE" => song.title = value.to_string(),
+        "ARTIST" => song.artist = value.to_string(),
+        "MP3" | "AUDIO" => song.audio = Some(value.to_string()),
+        "VOCALS" => song.vocals = Some(value.to_string()),
+        "INSTRUMENTAL" => song.instrumental = Some(value.to_string()),
+        "BPM" => bpm = parse_number(value),
+        "GAP" => gap = parse_number(value).unwrap_or(0.0) / 1000.0,
+        "RELATIVE" => relative = value.eq_ignore_ascii_case("yes"),
+        _ => {}
+      }
+      continue;
+    }
+
+    let trimmed = raw_line.trim_start();
+    let Some(kind) = trimmed.chars().next() else {
+      continue;
+    };
+    let bpm = bpm.filter(|b| *b > 0.0).ok_or_else(|| "missing or invalid #BPM header".to_string())?;
+    let seconds = |beat: i64| gap + beat as f64 * 60.0 / (bpm * 4.0);
+    let rest = &trimmed[kind.len_utf8()..];
+    match kind {
+      ':' | '*' | 'F' | 'R' | 'G' => {
+        let Some((beat, length, pitch, text)) = parse_note_fields(rest) else {
+          continue;
+        };
+        let (start, end) = (seconds(beat + offset), seconds(beat + offset + length));
+        let current = line.get_or_insert_with(|| LyricLine { time: start, ..Default::default() });
+        // `~` continues the previous syllable on a new pitch
+        let syllable = text.replace('~', "");
+        current.text.push_str(&syllable);
+        if !syllable.trim().is_empty() {
+          current.words.push(LyricWord { time: start, text: syllable });
+        }
+        current.end = Some(end);
+        // freestyle notes have no pitch to score against
+        if kind != 'F' {
+          song.notes.push(Note { note: 60 + pitch, start, duration: end - start, velocity: 100.0, channel, confidence: None, bend: Vec::new() });
+        }
+      }
+      '-' => {
+        flush(&mut line, &mut song.lyrics);
+        if relative {
+          let beats: Vec<i64> = rest.split_whitespace().filter_map(|b| b.parse().ok()).collect();
+          offset += beats.last().copied().unwrap_or(0);
+        }
+      }
+      'P' => {
+        flush(&mut line, &mut song.lyrics);
+        channel = if rest.trim() == "2" { 1 } else { 0 };
+        offset = 0;
+      }
+      'E' => break,
+      _ => {}
+    }
+  }
+  flush(&mut line, &mut song.lyrics);
+
+  if song.title.is_empty() {
+    return Err("missing #TITLE header".to_string());
+  }
+  song.lyrics.sort_by(|a, b| a.time.total_cmp(&b.time));
+  song.notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+  Ok(song)
+}
+
+// UltraStar song files are `.txt` files that start with `#` headers.
+fn find_song_files(dir: &Path, out: &mut Vec<PathBuf>) {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      find_song_files(&path, out);
+    } else if path.extension().and_then(|s| s.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("txt")) {
+      out.push(path);
+    }
+  }
+}
+
+fn copy_beside(folder: &Path, name: &str, target: &Path) -> Result<(), String> {
+  let source = folder.join(name);
+  std::fs::copy(&source, target).map(|_| ()).map_err(|e| format!("failed to copy {}: {}", source.display(), e))
+}
+
+// `stem`, or `stem (2)`, `stem (3)` ... while the `existing` file names have a song of that name
+// or its companions
+fn unique_stem(stem: &str, existing: &[String]) -> String {
+  let taken = |stem: &str| existing.iter().any(|n| n.strip_prefix(stem).is_some_and(|rest| rest.starts_with('.')) || is_companion(stem, n));
+  std::iter::once(stem.to_string()).chain((2..).map(|n| format!("{} ({})", stem, n))).find(|candidate| !taken(candidate)).unwrap_or_default()
+}
+
+fn import_song(state: &AppState, file: &Path) -> Result<Option<ImportedSong>, String> {
+  let bytes = std::fs::read(file).map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+  // UltraStar txt files are often CP1252 or a local codepage
+  let content = decode_text(&bytes);
+  if !content.trim_start_matches('\u{feff}').starts_with('#') {
+    return Ok(None);
+  }
+  let song = parse_ultrastar(&content).map_err(|e| format!("failed to parse {}: {}", file.display(), e))?;
+  let Some(audio) = song.audio.as_deref() else {
+    return Err(format!("{} has no #MP3/#AUDIO header", file.display()));
+  };
+
+  let folder = file.parent().unwrap_or(Path::new("."));
+  let ext = Path::new(audio).extension().and_then(|s| s.to_str()).unwrap_or("mp3");
+  let res = &state.res_dir;
+  let name = sanitize_file_name(&if song.artist.is_empty() { song.title.clone() } else { format!("{} - {}", song.artist, song.title) });
+  // never over another song or its lyrics, stems and melody
+  let existing: Vec<String> = std::fs::read_dir(res).map(|entries| entries.flatten().filter_map(|e| e.file_name().into_string().ok()).collect()).unwrap_or_default();
+  let stem = unique_stem(&name, &existing);
+  if stem != name {
+    info!(%name, %stem, "ultrastar song name taken, numbered");
+  }
+  let url = format!("{}.{}", stem, ext);
+
+  copy_beside(folder, audio, &res.join(&url))?;
+  for (name, suffix) in [(&song.vocals, "_vocals"), (&song.instrumental, "_non_vocals")] {
+    if let Some(name) = name {
+      let source_ext = Path::new(name).extension().and_then(|s| s.to_str()).unwrap_or(ext);
+      copy_beside(folder, name, &res.join(format!("{}{}.{}", stem, suffix, source_ext)))?;
+    }
+  }
+  let tags = [("ti", song.title.clone()), ("ar", song.artist.clone()), ("re", "klok ultrastar import".to_string())];
+  let lrc = res.join(format!("{}.lrc", stem));
+  std::fs::write(&lrc, format_lrc(&song.lyrics, &tags)).map_err(|e| format!("failed to write {}: {}", lrc.display(), e))?;
+  let midi = res.join(format!("{}_vocals_pitches.mid", stem));
+  std::fs::write(&midi, encode_midi(&song.notes)?).map_err(|e| format!("failed to write {}: {}", midi.display(), e))?;
+
+  info!(%url, notes = song.notes.len(), "imported ultrastar song");
+  Ok(Some(ImportedSong { title: song.title, artist: song.artist, url }))
+}
+
+/// Import every UltraStar-format song below `folder` (UltraStar Deluxe, Performous and Vocaluxe
+/// song folders) into the res directory: audio, `.lrc` lyrics with syllable timing, and the notes
+/// as `_vocals_pitches.mid`. Existing songs are never overwritten: a song whose name is taken is
+/// numbered, see `ImportedSong::url`. Songs that fail to import are logged and skipped.
+#[tauri::command]
+pub fn import_ultrastar(state: State<'_, AppState>, folder: String) -> Result<Vec<ImportedSong>, String> {
+  ensure_unlocked(&state, "import_ultrastar")?;
+  let folder = PathBuf::from(folder);
+  if !folder.is_dir() {
+    return Err(format!("not a directory: {}", folder.display()));
+  }
+  let mut files = Vec::new();
+  find_song_files(&folder, &mut files);
+  files.sort();
+
+  let mut imported = Vec::new();
+  for file in files {
+    match import_song(&state, &file) {
+      Ok(Some(song)) => imported.push(song),
+      Ok(None) => {}
+      Err(e) => warn!(file = %file.display(), error = %e, "skipping ultrastar song"),
+    }
+  }
+  Ok(imported)
+}
+
+#[test]
+pub fn test_parse_ultrastar() {
+  let txt = "#TITLE:Song\n#ARTIST:Band\n#MP3:song.mp3\n#BPM:150\n#GAP:1000,5\n: 0 4 5 Hel\n* 4 4 7 lo~\nF 8 2 0  world\n- 12\n: 16 4 0 Again\nE\n: 99 1 0 ignored\n";
+  let song = parse_ultrastar(txt).expect("failed to parse ultrastar");
+  assert_eq!(song.audio.as_deref(), Some("song.mp3"));
+  assert_eq!(song.lyrics.len(), 2);
+  assert_eq!(song.lyrics[0].text, "Hello world");
+  assert_eq!(song.lyrics[0].words.len(), 3);
+  assert_eq!(song.lyrics[0].time, 1.0005);
+  // 150 bpm in quarter beats -> 0.1s per beat
+  assert_eq!(song.lyrics[1].time, 1.0005 + 1.6);
+  // the freestyle note is not a scored note
+  assert_eq!(song.notes.len(), 3);
+  assert_eq!(song.notes[1].note, 67);
+
+  let relative = "#TITLE:R\n#BPM:150\n#RELATIVE:yes\n: 0 2 0 a\n- 4 10\n: 0 2 0 b\n";
+  let song = parse_ultrastar(relative).expect("failed to parse relative ultrastar");
+  assert!((song.lyrics[1].time - 1.0).abs() < 1e-9);
+
+  // blank lines among the headers, before `#BPM`
+  let spaced = "#TITLE:S\n\n#BPM:150\r\n\n: 0 2 0 a\n";
+  assert_eq!(parse_ultrastar(spaced).expect("failed to parse spaced ultrastar").lyrics.len(), 1);
+}
+
+#[test]
+pub fn test_unique_stem() {
+  let existing: Vec<String> = ["Band - Song.flac", "Band - Song (2)_vocals_pitches.mid", "Band - Songs.mp3"].map(String::from).to_vec();
+  assert_eq!(unique_stem("Band - Song", &existing), "Band - Song (3)");
+  assert_eq!(unique_stem("Band - Songs (live)", &existing), "Band - Songs (live)");
+}
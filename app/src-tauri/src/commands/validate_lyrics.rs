@@ -0,0 +1,28 @@
+use tauri::State;
+
+use klok_core::encoding::decode_text;
+use klok_core::lrc::{lint_lrc, LyricsWarning};
+
+use crate::commands::get_metadata::get_duration_and_artist;
+use crate::commands::with_extension;
+use crate::AppState;
+
+/// Report problems in the `.lrc` lyrics of a song. `path` is the song or the `.lrc` file itself;
+/// lines beyond the audio duration are only checked when given the song.
+#[tauri::command]
+pub fn validate_lyrics(state: State<'_, AppState>, path: String) -> Result<Vec<LyricsWarning>, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  let (lrc, duration) = if path.ends_with(".lrc") {
+    (path.clone(), None)
+  } else {
+    (with_extension(&path, ".lrc"), state.resolve(&path).and_then(get_duration_and_artist).map(|(d, _)| d))
+  };
+  let resolved = state.resolve(&lrc).ok_or_else(|| format!(".lrc file not found for provided path: {}", path))?;
+  let content = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+  let warnings = lint_lrc(&decode_text(&content), duration);
+  info!(path = %lrc, warnings = warnings.len(), "validated lyrics");
+  Ok(warnings)
+}
+
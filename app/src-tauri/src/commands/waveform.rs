@@ -0,0 +1,45 @@
+use sha2::{Digest, Sha256};
+use tauri::ipc::Response;
+use tauri::State;
+
+use klok_core::waveform::Waveform;
+
+use crate::commands::decode_audio::{decodable_extension, decode_file};
+use crate::commands::midi_cache::hex;
+use crate::commands::timeout::run_blocking;
+use crate::AppState;
+
+const CACHE_DIR: &str = "cache/waveform";
+// most buckets asked for, a few screen widths
+const MAX_BUCKETS: usize = 16384;
+
+/// Min/max peaks of the WAV file `path` in `buckets` slices, as `Waveform::to_bytes`, for drawing
+/// the seek-bar waveform. Peaks are cached by file content, so renamed songs keep theirs.
+#[tauri::command]
+pub async fn get_waveform(state: State<'_, AppState>, path: String, buckets: usize) -> Result<Response, String> {
+  if path.is_empty() {
+    return Err("path argument is empty".to_string());
+  }
+  if buckets == 0 || buckets > MAX_BUCKETS {
+    return Err(format!("buckets must be between 1 and {}: {}", MAX_BUCKETS, buckets));
+  }
+  let resolved = state.resolve(&path).ok_or_else(|| format!("resource not found: {}", path))?;
+  decodable_extension(&resolved)?;
+  let st = state.inner().clone();
+  let bytes = run_blocking(&state, "get_waveform", move |_| {
+    let content = std::fs::read(&resolved).map_err(|e| format!("failed to read {}: {}", resolved.display(), e))?;
+    let file = st.config_dir.join(CACHE_DIR).join(format!("{}-{}.bin", hex(&Sha256::digest(&content)), buckets));
+    if let Ok(bytes) = std::fs::read(&file) {
+      return Ok(bytes);
+    }
+    let pcm = decode_file(&st, &resolved)?;
+    let bytes = Waveform::new(&pcm, buckets)?.to_bytes();
+    let written = std::fs::create_dir_all(st.config_dir.join(CACHE_DIR)).and_then(|_| std::fs::write(&file, &bytes));
+    if let Err(e) = written {
+      warn!(path = %file.display(), error = %e, "failed to cache waveform");
+    }
+    Ok(bytes)
+  })
+  .await?;
+  Ok(Response::new(bytes))
+}
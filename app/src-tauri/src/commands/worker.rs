@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// A device served by its own thread (a MIDI port, the microphone) until dropped.
+#[derive(Debug)]
+pub struct Worker {
+  /// what the thread serves, e.g. the MIDI port id
+  pub(crate) label: String,
+  stop: Arc<AtomicBool>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+  /// Run `serve` on a thread named `name` until the handle is dropped; `serve` gets the stop flag.
+  pub(crate) fn spawn(name: &str, label: String, serve: impl FnOnce(Arc<AtomicBool>) + Send + 'static) -> Result<Worker, String> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    let thread = std::thread::Builder::new().name(name.to_string()).spawn(move || serve(flag)).map_err(|e| format!("failed to start {}: {}", name, e))?;
+    Ok(Worker { label, stop, thread: Some(thread) })
+  }
+
+  /// Whether `serve` is still running; it returns on its own when its device fails.
+  pub(crate) fn is_alive(&self) -> bool {
+    self.thread.as_ref().is_some_and(|t| !t.is_finished())
+  }
+}
+
+impl Drop for Worker {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::Relaxed);
+    if let Some(thread) = self.thread.take() {
+      thread.join().ok();
+    }
+  }
+}
+
+#[test]
+pub fn test_worker() {
+  let worker = Worker::spawn("test-worker", "loop".to_string(), |stop| {
+    while !stop.load(Ordering::Relaxed) {
+      std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+  })
+  .unwrap();
+  assert!(worker.is_alive());
+  drop(worker);
+
+  // a thread stopping by itself, as on a device error
+  let worker = Worker::spawn("test-worker", "once".to_string(), |_| {}).unwrap();
+  let started = std::time::Instant::now();
+  while worker.is_alive() && started.elapsed().as_secs() < 5 {
+    std::thread::sleep(std::time::Duration::from_millis(1));
+  }
+  assert!(!worker.is_alive());
+}
@@ -1,17 +1,44 @@
 #[macro_use]
 extern crate tracing;
 use serde::{Serialize, Deserialize};
-use tauri::{WindowEvent, Position, PhysicalPosition, LogicalPosition};
+use tauri::{WindowEvent, Position, PhysicalPosition, LogicalPosition, Manager};
 use std::env;
 use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
 
 // Simple application state exposed to Tauri commands/pages. Holds the resolved
 // path to the `res` directory so Rust-side code can reliably locate bundled
 // resources (audio, lyrics, etc.). Clone is derived so it can be cheaply
-// shared into the Tauri managed state.
-#[derive(Clone, Debug)]
+// shared into the Tauri managed state. Default is empty state without a library, for tests.
+#[derive(Clone, Debug, Default)]
 pub struct AppState {
   pub res_dir: PathBuf,
+  // directory holding `settings.json` (same place as `window_state.json`)
+  pub config_dir: PathBuf,
+  pub settings: Arc<Mutex<Settings>>,
+  // scoring profile selected for this session; `None` uses the settings default
+  pub scoring_profile: Arc<Mutex<Option<String>>>,
+  // result of the last library availability check, to emit changes only
+  pub library_available: Arc<Mutex<Option<bool>>>,
+  // songs sung this session (playlist urls), skipped by `pick_random`
+  pub sung_songs: Arc<Mutex<BTreeSet<String>>>,
+  // timings reported by `get_perf_stats`
+  pub perf: Arc<Mutex<PerfCounters>>,
+  // MIDI keyboard forwarded as `midi-input` events, see `start_midi_input`
+  pub midi_input: Arc<Mutex<Option<Worker>>>,
+  // reference melody played to a MIDI synth, see `start_midi_output`
+  pub midi_output: Arc<Mutex<Option<Worker>>>,
+  // native audio playback, see `player_load`
+  pub player: Arc<Mutex<Player>>,
+  // the shared microphone capture, see `commands::mic`
+  pub mic: Arc<Mutex<Mic>>,
+  // the microphone take being recorded, if any
+  pub recording: Arc<Mutex<Option<Recording>>>,
+  // microphone monitoring, see `commands::monitor`
+  pub monitoring: Arc<Mutex<Monitoring>>,
+  // recently decoded audio, see `commands::decode_audio`
+  pub pcm_cache: Arc<Mutex<PcmCache>>,
 }
 
 impl AppState {
@@ -26,10 +53,67 @@ impl AppState {
 }
 
 pub mod commands;
+pub mod protocol;
+pub mod settings;
+use settings::Settings;
+use commands::decode_audio::PcmCache;
+use commands::mic::Mic;
+use commands::monitor::Monitoring;
+use commands::perf::PerfCounters;
+use commands::player::Player;
+use commands::recording::Recording;
+use commands::worker::Worker;
+pub use commands::align::align_lyrics;
+pub use commands::assign_mic_turns::assign_mic_turns;
+pub use commands::attract::{get_attract_mode, set_attract_mode};
+pub use commands::audio_device::{list_audio_outputs, set_audio_output, set_second_output};
+pub use commands::background::set_song_background;
+pub use commands::backing::render_backing;
+pub use commands::click_track::export_click_track;
+pub use commands::consent::{get_consents, grant_consent, revoke_consent};
+pub use commands::convert_lyrics::convert_lyrics;
+pub use commands::countdown::{get_countdown_cues, set_countdown_settings};
+pub use commands::decode_audio::decode_audio;
+pub use commands::difficulty::midi_stats;
+pub use commands::fetch_lyrics::fetch_lyrics;
+pub use commands::gain::{set_gain_settings, set_song_loudness, song_gain};
 pub use commands::get_metadata::get_metadata;
+pub use commands::guide::render_guide_track;
+pub use commands::kiosk::{get_kiosk_mode, set_kiosk_mode};
+pub use commands::library::get_library_status;
 pub use commands::load_audio::load_audio;
-pub use commands::load_midi::load_midi;
+pub use commands::load_lyrics::load_lyrics;
+pub use commands::load_midi::{load_f0_curve, load_midi, load_midi_meta, load_midi_raw, load_midi_tracks, load_midi_warnings, load_notes_json, load_piano_roll};
 pub use commands::load_playlist::load_playlist;
+pub use commands::lyric_frames::export_lyric_frame;
+pub use commands::lyrics_provider::fetch_lyrics_auto;
+pub use commands::mic::set_mic_denoise;
+pub use commands::midi_input::{list_midi_inputs, start_midi_input, stop_midi_input};
+pub use commands::midi_output::{list_midi_outputs, start_midi_output, stop_midi_output};
+pub use commands::mixdown::export_take;
+pub use commands::monitor::set_monitoring;
+pub use commands::netease::fetch_lyrics_netease;
+pub use commands::organize_library::organize_library;
+pub use commands::perf::get_perf_stats;
+pub use commands::pitch_detection::{start_pitch_detection, stop_pitch_detection};
+pub use commands::player::{player_load, player_pause, player_play, player_seek, player_status, set_key_shift, set_song_channel_mode, set_tempo, set_vocal_volume};
+pub use commands::practice_mix::export_practice_mix;
+pub use commands::profanity::{get_profanity_filter, save_profanity_filter};
+pub use commands::recording::{list_recordings, load_take, start_recording, stop_recording};
+pub use commands::romanize::romanize_lyrics;
+pub use commands::roulette::{mark_sung, pick_random};
+pub use commands::save_lyrics::save_lyrics;
+pub use commands::save_midi::save_midi;
+pub use commands::scoring_profile::{delete_scoring_profile, get_scoring_profile, list_scoring_profiles, save_scoring_profile, select_scoring_profile};
+pub use commands::session::{restore_session, save_session};
+pub use commands::setlist::export_setlist;
+pub use commands::shift_lyrics::shift_lyrics;
+pub use commands::song_library::{delete_song, hide_song, restore_song};
+pub use commands::storage::{check_recording_space, list_export_targets, set_export_targets};
+pub use commands::transition::{plan_transition, set_transition_settings};
+pub use commands::ultrastar::import_ultrastar;
+pub use commands::validate_lyrics::validate_lyrics;
+pub use commands::waveform::get_waveform;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -54,10 +138,30 @@ pub fn run() {
       {
         let res_dir = env::current_dir().map(|d| d.join("../../res")).unwrap_or_else(|_| PathBuf::from("res"));
         info!(?res_dir, "resolved res directory");
-        AppState { res_dir }
+        let config_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let settings = Settings::load(&config_dir);
+        let mic = Mic::new(settings.mic_denoise);
+        AppState {
+          res_dir,
+          config_dir,
+          settings: Arc::new(Mutex::new(settings)),
+          scoring_profile: Arc::new(Mutex::new(None)),
+          library_available: Arc::new(Mutex::new(None)),
+          sung_songs: Arc::new(Mutex::new(BTreeSet::new())),
+          perf: Arc::new(Mutex::new(PerfCounters::default())),
+          midi_input: Arc::new(Mutex::new(None)),
+          midi_output: Arc::new(Mutex::new(None)),
+          player: Arc::new(Mutex::new(Player::default())),
+          mic: Arc::new(Mutex::new(mic)),
+          recording: Arc::new(Mutex::new(None)),
+          monitoring: Arc::new(Mutex::new(Monitoring::default())),
+          pcm_cache: Arc::new(Mutex::new(PcmCache::default())),
+        }
       }
     )
     .plugin(tauri_plugin_opener::init())
+    // library files (song backgrounds) for the webview
+    .register_uri_scheme_protocol(protocol::SCHEME, |ctx, request| protocol::handle(&ctx.app_handle().state::<AppState>(), &request))
     // restore saved window position when the page loads
     .on_page_load(|webview, _| {
       if let Ok(cwd) = env::current_dir() {
@@ -103,7 +207,104 @@ pub fn run() {
         _ => {}
       }
     })
-  .invoke_handler(tauri::generate_handler![greet, get_metadata, load_audio, load_midi, load_playlist])
+  .invoke_handler(tauri::generate_handler![
+    greet,
+    get_metadata,
+    load_audio,
+    load_midi,
+    load_midi_meta,
+    load_midi_raw,
+    load_midi_tracks,
+    load_midi_warnings,
+    load_notes_json,
+    load_piano_roll,
+    load_f0_curve,
+    load_playlist,
+    list_scoring_profiles,
+    save_scoring_profile,
+    delete_scoring_profile,
+    select_scoring_profile,
+    get_scoring_profile,
+    assign_mic_turns,
+    get_countdown_cues,
+    set_countdown_settings,
+    save_lyrics,
+    save_midi,
+    export_setlist,
+    shift_lyrics,
+    import_ultrastar,
+    fetch_lyrics,
+    fetch_lyrics_netease,
+    fetch_lyrics_auto,
+    hide_song,
+    delete_song,
+    romanize_lyrics,
+    organize_library,
+    align_lyrics,
+    get_library_status,
+    export_practice_mix,
+    convert_lyrics,
+    export_click_track,
+    pick_random,
+    mark_sung,
+    load_lyrics,
+    get_kiosk_mode,
+    set_kiosk_mode,
+    validate_lyrics,
+    set_song_background,
+    get_profanity_filter,
+    save_profanity_filter,
+    get_perf_stats,
+    save_session,
+    restore_session,
+    export_lyric_frame,
+    plan_transition,
+    set_transition_settings,
+    midi_stats,
+    render_backing,
+    song_gain,
+    set_song_loudness,
+    set_gain_settings,
+    list_export_targets,
+    set_export_targets,
+    check_recording_space,
+    get_consents,
+    grant_consent,
+    revoke_consent,
+    list_midi_inputs,
+    start_midi_input,
+    stop_midi_input,
+    list_midi_outputs,
+    start_midi_output,
+    stop_midi_output,
+    render_guide_track,
+    decode_audio,
+    get_waveform,
+    player_load,
+    player_play,
+    player_pause,
+    player_seek,
+    player_status,
+    set_vocal_volume,
+    set_key_shift,
+    set_tempo,
+    list_audio_outputs,
+    set_audio_output,
+    start_pitch_detection,
+    stop_pitch_detection,
+    start_recording,
+    stop_recording,
+    list_recordings,
+    load_take,
+    export_take,
+    set_mic_denoise,
+    set_monitoring,
+    get_attract_mode,
+    set_attract_mode,
+    set_second_output,
+    set_song_channel_mode,
+    restore_song,
+  ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
@@ -0,0 +1,169 @@
+use percent_encoding::percent_decode_str;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use tauri::http::{header, HeaderValue, Request, Response, StatusCode};
+
+use crate::AppState;
+
+/// URI scheme serving library files to the webview, e.g. song backgrounds and audio. The frontend
+/// builds URLs with `convertFileSrc(path, 'klok')`, where `path` is relative to the library root.
+/// Range requests are answered with partial content, so media elements stream and seek big files.
+pub const SCHEME: &str = "klok";
+
+// most bytes answered to an open-ended range (`bytes=N-`), media elements ask again for the rest
+const MAX_CHUNK: u64 = 4 << 20;
+
+fn content_type(path: &Path) -> &'static str {
+  let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+  match ext.as_str() {
+    "jpg" | "jpeg" => "image/jpeg",
+    "png" => "image/png",
+    "webp" => "image/webp",
+    "gif" => "image/gif",
+    "mp4" => "video/mp4",
+    "webm" => "video/webm",
+    "mp3" => "audio/mpeg",
+    "m4a" => "audio/mp4",
+    "flac" => "audio/flac",
+    "wav" => "audio/wav",
+    _ => "application/octet-stream",
+  }
+}
+
+/// Library-relative path of a request: the percent-decoded URI path, or `None` when it is empty or
+/// would leave the library (`..`, absolute paths).
+fn request_path(uri_path: &str) -> Option<PathBuf> {
+  let decoded = percent_decode_str(uri_path.trim_start_matches('/')).decode_utf8().ok()?;
+  let path = PathBuf::from(decoded.as_ref());
+  let inside = path.components().all(|c| matches!(c, Component::Normal(_)));
+  (inside && !decoded.is_empty()).then_some(path)
+}
+
+/// Byte range asked for by a `Range` header for a file of `len` bytes: `Some(None)` when the
+/// header can't be served (multiple ranges, past the end), `None` when it isn't a byte range at
+/// all, so the whole file is sent.
+fn byte_range(header: &str, len: u64) -> Option<Option<Range<u64>>> {
+  let spec = header.trim().strip_prefix("bytes=")?;
+  if spec.contains(',') {
+    return Some(None);
+  }
+  let (start, end) = spec.split_once('-')?;
+  let (start, end) = (start.trim(), end.trim());
+  let range = if start.is_empty() {
+    // suffix: the last `end` bytes
+    let suffix: u64 = end.parse().ok()?;
+    len.saturating_sub(suffix)..len
+  } else {
+    let start: u64 = start.parse().ok()?;
+    let end = match end {
+      "" => len.min(start.saturating_add(MAX_CHUNK)),
+      end => len.min(end.parse::<u64>().ok()?.saturating_add(1)),
+    };
+    start..end
+  };
+  Some((range.start < range.end).then_some(range))
+}
+
+fn read_range(path: &Path, range: &Range<u64>) -> std::io::Result<Vec<u8>> {
+  let mut file = File::open(path)?;
+  file.seek(SeekFrom::Start(range.start))?;
+  let mut bytes = Vec::with_capacity((range.end - range.start) as usize);
+  file.take(range.end - range.start).read_to_end(&mut bytes)?;
+  Ok(bytes)
+}
+
+fn partial(path: &Path, range: Range<u64>, len: u64) -> Response<Vec<u8>> {
+  match read_range(path, &range) {
+    // the file shrank since its length was taken
+    Ok(bytes) if bytes.is_empty() => not_satisfiable(len),
+    Ok(bytes) => {
+      let content_range = format!("bytes {}-{}/{}", range.start, range.start + bytes.len() as u64 - 1, len);
+      let mut response = Response::new(bytes);
+      *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+      let headers = response.headers_mut();
+      headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type(path)));
+      headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+      if let Ok(value) = HeaderValue::from_str(&content_range) {
+        headers.insert(header::CONTENT_RANGE, value);
+      }
+      response
+    }
+    Err(e) => {
+      warn!(path = %path.display(), error = %e, "failed to read asset range");
+      status(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+// 416 for a range outside a file of `len` bytes
+fn not_satisfiable(len: u64) -> Response<Vec<u8>> {
+  let mut response = status(StatusCode::RANGE_NOT_SATISFIABLE);
+  if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+    response.headers_mut().insert(header::CONTENT_RANGE, value);
+  }
+  response
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+  let mut response = Response::new(Vec::new());
+  *response.status_mut() = code;
+  response
+}
+
+/// Serve a file from the library root, or the part of it asked for by a `Range` header.
+pub fn handle(state: &AppState, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+  let Some(path) = request_path(request.uri().path()) else {
+    warn!(uri = %request.uri(), "refused asset request outside the library");
+    return status(StatusCode::FORBIDDEN);
+  };
+  let resolved = state.res_dir.join(path);
+  let range = request.headers().get(header::RANGE).and_then(|v| v.to_str().ok());
+  if let (Some(range), Ok(meta)) = (range, std::fs::metadata(&resolved)) {
+    match byte_range(range, meta.len()) {
+      Some(Some(range)) => return partial(&resolved, range, meta.len()),
+      Some(None) => return not_satisfiable(meta.len()),
+      None => {}
+    }
+  }
+  match std::fs::read(&resolved) {
+    Ok(bytes) => {
+      let mut response = Response::new(bytes);
+      let headers = response.headers_mut();
+      headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type(&resolved)));
+      headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+      response
+    }
+    Err(e) => {
+      debug!(path = %resolved.display(), error = %e, "asset not found");
+      status(StatusCode::NOT_FOUND)
+    }
+  }
+}
+
+#[test]
+pub fn test_request_path() {
+  assert_eq!(request_path("/Artist%2FTitle_background.jpg"), Some(PathBuf::from("Artist/Title_background.jpg")));
+  assert_eq!(request_path("/%E6%88%91.png"), Some(PathBuf::from("我.png")));
+  assert_eq!(request_path("/..%2Fsettings.json"), None);
+  assert_eq!(request_path("/%2Fetc%2Fpasswd"), None);
+  assert_eq!(request_path("/"), None);
+
+  assert_eq!(byte_range("bytes=0-99", 1000), Some(Some(0..100)));
+  assert_eq!(byte_range("bytes=900-2000", 1000), Some(Some(900..1000)));
+  assert_eq!(byte_range("bytes=-100", 1000), Some(Some(900..1000)));
+  assert_eq!(byte_range("bytes=0-", 1000), Some(Some(0..1000)));
+  assert_eq!(byte_range("bytes=0-", MAX_CHUNK * 3), Some(Some(0..MAX_CHUNK)));
+  assert_eq!(byte_range("bytes=1000-", 1000), Some(None));
+  assert_eq!(byte_range("bytes=0-1,5-9", 1000), Some(None));
+  assert_eq!(byte_range("items=0-1", 1000), None);
+
+  // a file emptied after its length was taken
+  let empty = std::env::temp_dir().join(format!("klok_protocol_{}", std::process::id()));
+  std::fs::write(&empty, b"").unwrap();
+  let response = partial(&empty, 0..10, 10);
+  std::fs::remove_file(&empty).ok();
+  assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+  assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+}
@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use klok_core::mix::ChannelMode;
+
+pub use klok_core::difficulty::SongDifficulty;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Options consumed by the frontend scoring engine (`scoreNotes` in `utils/pitch.ts`).
+/// Field names are camelCase on the wire so a profile can be passed straight through as `ScoreOptions`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoringProfile {
+  pub name: String,
+  /// semitones of error at which a pitch sample scores zero
+  pub tolerance: f64,
+  /// seconds added before/after each note when collecting pitch samples
+  pub margin: f64,
+  pub min_samples: u32,
+  pub weight_by_duration: bool,
+  /// compare pitch classes only, so singing an octave off still counts
+  #[serde(default)]
+  pub octave_agnostic: bool,
+}
+
+impl ScoringProfile {
+  fn builtin() -> Vec<ScoringProfile> {
+    vec![
+      ScoringProfile { name: "party".to_string(), tolerance: 3.0, margin: 0.15, min_samples: 1, weight_by_duration: true, octave_agnostic: false },
+      ScoringProfile { name: "training".to_string(), tolerance: 1.0, margin: 0.03, min_samples: 3, weight_by_duration: true, octave_agnostic: false },
+      ScoringProfile { name: "kids".to_string(), tolerance: 2.5, margin: 0.1, min_samples: 1, weight_by_duration: false, octave_agnostic: true },
+    ]
+  }
+}
+
+/// When and how to count singers back in after an instrumental gap.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CountdownSettings {
+  /// shortest silence (seconds) before a vocal entry that gets a countdown
+  pub min_gap: f64,
+  /// number of cues, e.g. 3 for "3-2-1"
+  pub count: u32,
+  /// seconds between cues
+  pub interval: f64,
+}
+
+impl Default for CountdownSettings {
+  fn default() -> Self {
+    CountdownSettings { min_gap: 8.0, count: 3, interval: 1.0 }
+  }
+}
+
+/// How the app moves from one song to the next, see `commands::transition`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TransitionSettings {
+  /// seconds to fade out the finishing song
+  pub fade_out: f64,
+  /// read out the next song before it starts
+  pub announce: bool,
+  /// announcement without a singer; `{title}` and `{artist}` are replaced
+  pub announcement: String,
+  /// announcement with a singer, who replaces `{singer}`
+  pub singer_announcement: String,
+  /// count-in beats before the next song starts, 0 for none
+  pub count_in: u32,
+}
+
+impl Default for TransitionSettings {
+  fn default() -> Self {
+    TransitionSettings {
+      fade_out: 3.0,
+      announce: true,
+      announcement: "Next up: {title} by {artist}".to_string(),
+      singer_announcement: "Next up: {singer}, singing {title}".to_string(),
+      count_in: 4,
+    }
+  }
+}
+
+/// Automatic level matching between songs, see `commands::gain`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GainSettings {
+  pub enabled: bool,
+  /// loudness (dBFS RMS of the full mix) every song is brought to
+  pub target: f64,
+  /// most a quiet song is turned up, in dB
+  pub max_boost: f64,
+  /// most a loud song is turned down, in dB
+  pub max_cut: f64,
+}
+
+impl Default for GainSettings {
+  fn default() -> Self {
+    GainSettings { enabled: true, target: -18.0, max_boost: 6.0, max_cut: 12.0 }
+  }
+}
+
+/// Background songs played while the app sits idle, see `commands::attract`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AttractSettings {
+  pub enabled: bool,
+  /// minutes without playback or queued songs before the playlist starts
+  pub idle_minutes: f64,
+  /// library paths of the songs, played in order and repeated
+  pub playlist: Vec<String>,
+  /// volume of the playlist relative to the song volume, 0 to 1
+  pub volume: f64,
+}
+
+impl Default for AttractSettings {
+  fn default() -> Self {
+    AttractSettings { enabled: false, idle_minutes: 5.0, playlist: Vec::new(), volume: 0.3 }
+  }
+}
+
+/// Another output playing the music along with `audio_output`, e.g. a monitor or another room.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SecondOutput {
+  /// from `list_audio_outputs`
+  pub device: String,
+  /// seconds this output is held back to line up with `audio_output`; negative holds back
+  /// `audio_output` instead
+  pub delay: f64,
+}
+
+/// Masks flagged words in lyrics for family or venue settings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProfanityFilter {
+  pub enabled: bool,
+  /// flagged words keyed by language code, e.g. `"en"`, `"zh"`; all lists apply
+  pub wordlists: BTreeMap<String, Vec<String>>,
+}
+
+/// An online lyrics source and whether `fetch_lyrics_auto` may query it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LyricsProviderSetting {
+  pub name: String,
+  pub enabled: bool,
+}
+
+impl LyricsProviderSetting {
+  fn builtin() -> Vec<LyricsProviderSetting> {
+    ["lrclib", "netease", "qq"].iter().map(|name| LyricsProviderSetting { name: name.to_string(), enabled: true }).collect()
+  }
+}
+
+/// Kind of place an [`ExportTarget`] writes to, see `commands::storage`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageKind {
+  /// a folder on this machine, created when missing
+  Local,
+  /// a removable drive, written only while it is mounted
+  Usb,
+  /// a mounted network share or UNC path, written only while it is reachable
+  NetworkShare,
+  /// the folder of a cloud sync client, which uploads whatever is written there
+  CloudSync,
+}
+
+/// A named place recordings and exports can be written to instead of next to the song.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportTarget {
+  pub name: String,
+  pub kind: StorageKind,
+  /// absolute path of the target's root folder
+  pub path: String,
+}
+
+/// Record of a deleted song, kept so the deletion can be undone (from the OS trash) or audited.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DeletedSong {
+  pub url: String,
+  /// absolute paths of the song and its companion files at deletion time
+  pub files: Vec<String>,
+  /// moved to the OS trash (restorable) rather than removed permanently
+  pub to_trash: bool,
+  /// seconds since the Unix epoch
+  pub deleted_at: u64,
+}
+
+/// Timeouts (seconds) for commands that probe files or the network; `0` disables the timeout.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CommandTimeouts {
+  pub default: f64,
+  /// per-command overrides keyed by command name, e.g. `"load_playlist"`
+  pub commands: BTreeMap<String, f64>,
+}
+
+impl Default for CommandTimeouts {
+  fn default() -> Self {
+    CommandTimeouts { default: 30.0, commands: BTreeMap::new() }
+  }
+}
+
+/// A privacy-sensitive feature that only runs once the user has granted it, see `commands::consent`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+  /// capturing the singer's voice, for pitch scoring and recordings
+  Microphone,
+  /// serving the app to other devices from an embedded server
+  Server,
+  /// outbound requests to online providers, e.g. lyrics sources
+  Network,
+}
+
+/// PIN lock of kiosk mode. The PIN itself is not stored, only a salted SHA-256 of it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct KioskLock {
+  pub salt: String,
+  pub pin_hash: String,
+}
+
+/// Persistent user settings, stored as `settings.json` next to `window_state.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+  #[serde(default = "ScoringProfile::builtin")]
+  pub scoring_profiles: Vec<ScoringProfile>,
+  /// profile used when a session does not select one
+  #[serde(default = "Settings::default_scoring_profile")]
+  pub default_scoring_profile: String,
+  #[serde(default)]
+  pub countdown: CountdownSettings,
+  /// per-song countdown overrides, keyed by song path
+  #[serde(default)]
+  pub song_countdowns: BTreeMap<String, CountdownSettings>,
+  #[serde(default)]
+  pub profanity_filter: ProfanityFilter,
+  /// lyrics providers in priority order
+  #[serde(default = "LyricsProviderSetting::builtin")]
+  pub lyrics_providers: Vec<LyricsProviderSetting>,
+  /// songs (playlist urls) excluded from `load_playlist`
+  #[serde(default)]
+  pub hidden_songs: BTreeSet<String>,
+  #[serde(default)]
+  pub deleted_songs: Vec<DeletedSong>,
+  #[serde(default)]
+  pub command_timeouts: CommandTimeouts,
+  /// cached difficulty ratings, keyed by song path
+  #[serde(default)]
+  pub song_difficulties: BTreeMap<String, SongDifficulty>,
+  /// whether the library root is on a network share; detected from the mount table when unset
+  #[serde(default)]
+  pub library_on_network: Option<bool>,
+  /// background image or video per song (both library-relative paths)
+  #[serde(default)]
+  pub song_backgrounds: BTreeMap<String, String>,
+  /// set while kiosk mode is on; see `commands::kiosk`
+  #[serde(default)]
+  pub kiosk: Option<KioskLock>,
+  #[serde(default)]
+  pub transition: TransitionSettings,
+  #[serde(default)]
+  pub gain: GainSettings,
+  /// measured loudness (dBFS) keyed by song path, see `commands::gain`
+  #[serde(default)]
+  pub song_loudness: BTreeMap<String, f64>,
+  /// where exports can be sent, selected by name per export
+  #[serde(default)]
+  pub export_targets: Vec<ExportTarget>,
+  /// features the user has allowed; everything else stays off
+  #[serde(default)]
+  pub consents: BTreeSet<Feature>,
+  /// device native playback goes to (see `list_audio_outputs`), the system default when `None`
+  #[serde(default)]
+  pub audio_output: Option<String>,
+  /// noise suppression on the microphone, before pitch detection, recording and monitoring
+  #[serde(default)]
+  pub mic_denoise: bool,
+  #[serde(default)]
+  pub attract: AttractSettings,
+  #[serde(default)]
+  pub second_output: Option<SecondOutput>,
+  /// channels played of songs not played in stereo, keyed by song path
+  #[serde(default)]
+  pub song_channel_modes: BTreeMap<String, ChannelMode>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      scoring_profiles: ScoringProfile::builtin(),
+      default_scoring_profile: Settings::default_scoring_profile(),
+      countdown: CountdownSettings::default(),
+      song_countdowns: BTreeMap::new(),
+      profanity_filter: ProfanityFilter::default(),
+      lyrics_providers: LyricsProviderSetting::builtin(),
+      hidden_songs: BTreeSet::new(),
+      deleted_songs: Vec::new(),
+      command_timeouts: CommandTimeouts::default(),
+      song_difficulties: BTreeMap::new(),
+      library_on_network: None,
+      song_backgrounds: BTreeMap::new(),
+      kiosk: None,
+      transition: TransitionSettings::default(),
+      gain: GainSettings::default(),
+      song_loudness: BTreeMap::new(),
+      export_targets: Vec::new(),
+      consents: BTreeSet::new(),
+      audio_output: None,
+      mic_denoise: false,
+      attract: AttractSettings::default(),
+      second_output: None,
+      song_channel_modes: BTreeMap::new(),
+    }
+  }
+}
+
+impl Settings {
+  fn default_scoring_profile() -> String {
+    "party".to_string()
+  }
+
+  pub fn path(dir: &Path) -> PathBuf {
+    dir.join(SETTINGS_FILE)
+  }
+
+  /// Load settings from `dir`, falling back to defaults when the file is missing or invalid.
+  pub fn load(dir: &Path) -> Settings {
+    let path = Settings::path(dir);
+    match std::fs::read_to_string(&path) {
+      Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+        warn!(path = %path.display(), error = %e, "invalid settings file, using defaults");
+        Settings::default()
+      }),
+      Err(_) => Settings::default(),
+    }
+  }
+
+  pub fn save(&self, dir: &Path) -> Result<(), String> {
+    let path = Settings::path(dir);
+    let s = serde_json::to_string_pretty(self).map_err(|e| format!("failed to serialize settings: {}", e))?;
+    std::fs::write(&path, s).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+  }
+
+  /// Countdown settings for a song: its override if any, otherwise the global default.
+  pub fn countdown(&self, path: &str) -> &CountdownSettings {
+    self.song_countdowns.get(path).unwrap_or(&self.countdown)
+  }
+
+  /// Timeout for a command, `None` when disabled.
+  pub fn command_timeout(&self, command: &str) -> Option<Duration> {
+    let seconds = self.command_timeouts.commands.get(command).copied().unwrap_or(self.command_timeouts.default);
+    if seconds > 0.0 { Duration::try_from_secs_f64(seconds).ok() } else { None }
+  }
+
+  pub fn scoring_profile(&self, name: &str) -> Option<&ScoringProfile> {
+    self.scoring_profiles.iter().find(|p| p.name == name)
+  }
+}
@@ -1,10 +1,20 @@
 
-type LyricLine = { time: number; text: string }
+type LyricWord = { time: number; text: string }
+
+type LinePacing = { chars_per_second: number; density: number }
+
+type LinePart = 'p1' | 'p2' | 'both'
+
+type LyricLine = { time: number; end?: number; text: string; words?: Array<LyricWord>; translation?: string; breaths?: Array<number>; romanization?: string; pacing?: LinePacing; part?: LinePart }
 
 type Metadata = {
   title: string
   artist: string
   url: string
   duration: number
+  language?: string
   lyrics: Array<LyricLine>
+  countdown?: Array<{ time: number; count: number; entry: number }>
+  // library-relative background image or video (served over the `klok` scheme)
+  background?: string
 }
@@ -1,4 +1,8 @@
 import { invoke } from "@tauri-apps/api/core"
+import { parseF0Curve } from "./f0Curve"
+import { parsePianoRoll } from "./pianoRoll"
+import { parseWaveform } from "./waveform"
+import type { MidiNote } from "./pitch"
 
 export function getAudioMimeType(url: string): string {
   const ext = url.split('.').pop()?.toLowerCase()
@@ -28,6 +32,222 @@ export async function loadAudioContent(url: string) {
   return URL.createObjectURL(blob)
 }
 
+// Chunk size for `loadAudioChunked`
+const AUDIO_CHUNK = 4 << 20
+
+// Like `loadAudioContent`, but reads the file `chunk` bytes at a time (see the `offset`/`length`
+// arguments of Rust `load_audio`), reporting the bytes loaded so far, for big lossless files
+export async function loadAudioChunked(url: string, onProgress?: (loaded: number) => void, chunk = AUDIO_CHUNK) {
+  const parts: ArrayBuffer[] = []
+  let offset = 0
+  for (;;) {
+    const data = await invoke('load_audio', { path: url, offset, length: chunk }) as ArrayBuffer
+    parts.push(data)
+    offset += data.byteLength
+    onProgress?.(offset)
+    if (data.byteLength < chunk) break
+  }
+  return URL.createObjectURL(new Blob(parts, { type: getAudioMimeType(url) }))
+}
+
+export type DecodedAudio = { sampleRate: number, channels: number, samples: Float32Array }
+
+// Raw PCM of `duration` seconds of a song's audio from `start`, decoded by the backend, see Rust `decode_audio`
+export async function decodeAudio(url: string, start?: number, duration?: number): Promise<DecodedAudio> {
+  const data = await invoke('decode_audio', { path: url, start, duration }) as ArrayBuffer
+  const view = new DataView(data)
+  return { sampleRate: view.getUint32(0, true), channels: view.getUint32(4, true), samples: new Float32Array(data.slice(8)) }
+}
+
+// Min/max peaks of a song's audio in `buckets` slices for the seek bar, see Rust `get_waveform`
+export async function getWaveform(url: string, buckets: number) {
+  const data = await invoke('get_waveform', { path: url, buckets }) as ArrayBuffer
+  return parseWaveform(data)
+}
+
+// Synthesized backing (WAV) of a song that only has MIDI, see Rust `render_backing`
+export async function loadBackingContent(url: string) {
+  const data = await invoke('render_backing', { path: url }) as ArrayBuffer
+  return URL.createObjectURL(new Blob([data], { type: 'audio/wav' }))
+}
+
+// Synthesized guide melody (WAV) of a song, mixed in while practising, see Rust `render_guide_track`
+export async function loadGuideContent(url: string, voice?: 'sine' | 'piano', transpose?: number) {
+  const data = await invoke('render_guide_track', { path: url, voice, transpose }) as ArrayBuffer
+  return URL.createObjectURL(new Blob([data], { type: 'audio/wav' }))
+}
+
+// Notes of a song's vocal MIDI rasterized by the backend, see Rust `load_piano_roll`
+export async function loadPianoRoll(url: string, bucket?: number) {
+  const data = await invoke('load_piano_roll', { path: url, bucket }) as ArrayBuffer
+  return parsePianoRoll(data)
+}
+
+// Melody of a song as a reference pitch curve (Hz every `hop` seconds), see Rust `load_f0_curve`
+export async function loadF0Curve(url: string, hop?: number, transpose?: number) {
+  const data = await invoke('load_f0_curve', { path: url, hop, transpose, simplify: {} }) as ArrayBuffer
+  return parseF0Curve(data)
+}
+
+// Note events written by the pitch pipeline as JSON/CSV, with confidences, see Rust `load_notes_json`
+export async function loadNotesJson(path: string) {
+  return await invoke('load_notes_json', { path }) as MidiNote[]
+}
+
+// Problems worked around while loading a damaged vocal MIDI, see Rust `load_midi_warnings`
+export async function loadMidiWarnings(url: string) {
+  return await invoke('load_midi_warnings', { path: url }) as string[]
+}
+
+// MIDI port, matches Rust `MidiPort`
+export type MidiPort = {
+  id: string
+  name: string
+}
+
+// payload of the `midi-input` event, see Rust `start_midi_input`
+export type MidiInputEvent = {
+  port: string
+  kind: 'note_on' | 'note_off'
+  channel: number
+  note: number
+  velocity?: number
+}
+
+export async function listMidiInputs() {
+  return await invoke('list_midi_inputs') as MidiPort[]
+}
+
+export async function listMidiOutputs() {
+  return await invoke('list_midi_outputs') as MidiPort[]
+}
+
+// matches Rust `PlayerStatus`
+export type PlayerStatus = { path: string | null, playing: boolean, position: number, duration: number }
+// payload of `player-position` events
+export type PlayerPosition = { position: number, playing: boolean }
+// payload of `player-output` events, when the output device failed while playing
+export type PlayerOutputFailure = { error: string, reopened: boolean, position: number }
+
+// Native playback in the backend, see Rust `player_load` and friends
+export async function playerLoad(path: string) {
+  return await invoke('player_load', { path }) as PlayerStatus
+}
+
+export async function playerPlay() {
+  return await invoke('player_play') as PlayerStatus
+}
+
+export async function playerPause() {
+  return await invoke('player_pause') as PlayerStatus
+}
+
+export async function playerSeek(position: number) {
+  return await invoke('player_seek', { position }) as PlayerStatus
+}
+
+// Vocal stem level in native playback, 0 (karaoke) to 1 (the original mix)
+export async function setVocalVolume(volume: number) {
+  await invoke('set_vocal_volume', { volume })
+}
+
+// Key of native playback in semitones from the original, see Rust `set_key_shift`
+export async function setKeyShift(semitones: number) {
+  await invoke('set_key_shift', { semitones })
+}
+
+// Speed of native playback, the pitch kept, see Rust `set_tempo`
+export async function setTempo(tempo: number) {
+  await invoke('set_tempo', { tempo })
+}
+
+// Channels of a song played natively, matches Rust `ChannelMode`
+export type ChannelMode = 'stereo' | 'left' | 'right' | 'mono'
+
+// Play one channel of the song at `path` (or a mono mix), or as it is with null
+export async function setSongChannelMode(path: string, mode: ChannelMode | null) {
+  await invoke('set_song_channel_mode', { path, mode })
+}
+
+// An audio output for native playback, matches Rust `AudioDevice`
+export type AudioDevice = { id: string, name: string }
+
+export async function listAudioOutputs() {
+  return await invoke('list_audio_outputs') as AudioDevice[]
+}
+
+// Route native playback to `deviceId`, or the system default with null
+export async function setAudioOutput(deviceId: string | null) {
+  await invoke('set_audio_output', { deviceId })
+}
+
+// A device playing along with the selected output, matches Rust `SecondOutput`
+export type SecondOutput = { device: string, delay: number }
+
+// Also play on `output.device`, held back by `output.delay` seconds (negative holds back the
+// selected output instead), or stop that with null
+export async function setSecondOutput(output: SecondOutput | null) {
+  await invoke('set_second_output', { output })
+}
+
+// A pitch detected on the microphone, matches Rust `PitchFrame`; f0 is null while unvoiced
+export type MicPitch = { time: number, f0: number | null, confidence: number }
+
+export async function startPitchDetection() {
+  await invoke('start_pitch_detection')
+}
+
+export async function stopPitchDetection() {
+  await invoke('stop_pitch_detection')
+}
+
+// Suppress steady noise (fans, hum) on the microphone before pitch detection and recording
+export async function setMicDenoise(enabled: boolean) {
+  await invoke('set_mic_denoise', { enabled })
+}
+
+// Play the microphone back on the audio output, `reverb` 0..1, `gain` relative to the microphone
+export async function setMonitoring(enabled: boolean, reverb: number, gain: number) {
+  await invoke('set_monitoring', { enabled, reverb, gain })
+}
+
+// A recorded take and what it was sung over, matches Rust `Session`
+export type RecordingSession = {
+  id: string
+  song: string
+  keyShift: number
+  songOffset: number
+  latency: number
+  sampleRate: number
+  duration: number
+  startedAt: number
+}
+
+// Record the microphone as a take over `song`, started at `position` seconds of it
+export async function startRecording(song: string, position: number, keyShift?: number) {
+  return await invoke('start_recording', { song, position, keyShift }) as RecordingSession
+}
+
+export async function stopRecording() {
+  return await invoke('stop_recording') as RecordingSession
+}
+
+export async function listRecordings() {
+  return await invoke('list_recordings') as RecordingSession[]
+}
+
+// The take of a session as an object URL to play back
+export async function loadTake(sessionId: string) {
+  const data = await invoke('load_take', { sessionId }) as ArrayBuffer
+  return URL.createObjectURL(new Blob([data], { type: 'audio/wav' }))
+}
+
+// Mix a take over the song's backing into a file next to the song (or on export `target`), the
+// written path. Only 'wav' works so far
+export async function exportTake(sessionId: string, format: 'wav' | 'mp3' | 'ogg' = 'wav', target?: string) {
+  return await invoke('export_take', { sessionId, format, target }) as string
+}
+
 export type pitchData = {
   pitch: number
   midi: number
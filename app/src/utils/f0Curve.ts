@@ -0,0 +1,30 @@
+// Reference pitch curve of the melody from the backend (`load_f0_curve`), to compare detected
+// mic pitch against frame by frame
+
+// matches Rust `F0Curve`
+export type F0Curve = {
+  // seconds between samples
+  hop: number
+  // Hz per sample, 0 where no note sounds
+  hz: Float32Array
+}
+
+const HEADER_LEN = 8
+
+// Parse the bytes of `F0Curve::to_bytes`
+export function parseF0Curve(data: ArrayBuffer): F0Curve {
+  const view = new DataView(data)
+  const hop = view.getFloat32(0, true)
+  const count = view.getUint32(4, true)
+  // copy, since the samples after the 8-byte header may not be 4-byte aligned for a view
+  const hz = new Float32Array(count)
+  for (let i = 0; i < count; i++) hz[i] = view.getFloat32(HEADER_LEN + i * 4, true)
+  return { hop, hz }
+}
+
+// Reference Hz at `time` seconds, 0 outside the melody
+export function referenceHz(curve: F0Curve | null, time: number): number {
+  if (!curve || time < 0) return 0
+  const i = Math.round(time / curve.hop)
+  return i < curve.hz.length ? curve.hz[i] : 0
+}
@@ -0,0 +1,90 @@
+// Render the lyric display as still frames, for exporting clips (see `exportLyricFrames` in state.ts)
+
+export type FrameOptions = {
+  width?: number
+  height?: number
+  fps?: number
+  background?: string
+  // colour of the sung part of the current line
+  highlight?: string
+  font?: string
+}
+
+const DEFAULT_FRAME: Required<FrameOptions> = {
+  width: 1280,
+  height: 720,
+  fps: 30,
+  background: '#111111',
+  highlight: '#ff6b6b',
+  font: 'sans-serif',
+}
+
+export function frameOptions(opts: FrameOptions = {}): Required<FrameOptions> {
+  return { ...DEFAULT_FRAME, ...opts }
+}
+
+// Index of the line shown at `t`: the last one that has started, or the first before any has
+export function lineAt(lyrics: LyricLine[], t: number): number {
+  for (let i = lyrics.length - 1; i >= 0; i--) {
+    if (t >= lyrics[i].time) return i
+  }
+  return 0
+}
+
+// Width of the part of `line` sung by `t`, by word timing when present, else spread evenly
+// over the line up to `end`
+function sungWidth(ctx: CanvasRenderingContext2D, line: LyricLine, end: number, t: number): number {
+  const progress = (from: number, to: number) => Math.min(1, Math.max(0, (t - from) / Math.max(to - from, 1e-3)))
+  const words = line.words ?? []
+  if (!words.length) return ctx.measureText(line.text).width * progress(line.time, end)
+  let width = 0
+  for (let i = 0; i < words.length; i++) {
+    const w = ctx.measureText(words[i].text).width
+    const p = progress(words[i].time, words[i + 1]?.time ?? end)
+    width += w * p
+    if (p < 1) break
+  }
+  return width
+}
+
+// Draw the frame at song time `t`: the current line with its sung part highlighted, and the next line
+export function drawLyricFrame(ctx: CanvasRenderingContext2D, lyrics: LyricLine[], t: number, opts: FrameOptions = {}) {
+  const { width, height, background, highlight, font } = frameOptions(opts)
+  ctx.fillStyle = background
+  ctx.fillRect(0, 0, width, height)
+  if (!lyrics.length) return
+
+  const index = lineAt(lyrics, t)
+  const line = lyrics[index]
+  const next = lyrics[index + 1]
+  const size = Math.round(height / 12)
+  ctx.textBaseline = 'middle'
+
+  ctx.font = `bold ${size}px ${font}`
+  const lineWidth = ctx.measureText(line.text).width
+  const x = (width - lineWidth) / 2
+  const y = height * 0.45
+  ctx.fillStyle = '#ffffff'
+  ctx.fillText(line.text, x, y)
+  if (t >= line.time) {
+    const end = line.end ?? next?.time ?? line.time + 5
+    ctx.save()
+    ctx.beginPath()
+    ctx.rect(x, y - size, sungWidth(ctx, line, end, t), size * 2)
+    ctx.clip()
+    ctx.fillStyle = highlight
+    ctx.fillText(line.text, x, y)
+    ctx.restore()
+  }
+  if (line.translation) {
+    ctx.font = `${Math.round(size * 0.5)}px ${font}`
+    ctx.fillStyle = '#bbbbbb'
+    ctx.fillText(line.translation, (width - ctx.measureText(line.translation).width) / 2, y + size)
+  }
+
+  if (next) {
+    ctx.font = `${Math.round(size * 0.75)}px ${font}`
+    ctx.fillStyle = '#888888'
+    ctx.fillText(next.text, (width - ctx.measureText(next.text).width) / 2, height * 0.7)
+  }
+}
@@ -0,0 +1,35 @@
+// Measure how loud a song plays, for gain staging between songs (see Rust `commands::gain`)
+
+// length of the blocks averaged, as in EBU R 128 momentary loudness
+const BLOCK_SECONDS = 0.4
+// blocks quieter than this (dBFS) are silence and don't count
+const SILENCE_DB = -70
+
+// Loudness (dBFS RMS over the non-silent blocks) of the sources played together, e.g. the
+// vocal and backing stems, given as object/blob urls. Null when all of it is silent.
+export async function measureLoudness(urls: string[]): Promise<number | null> {
+  const ctx = new OfflineAudioContext(1, 1, 44100)
+  const buffers = await Promise.all(urls.map(async url => ctx.decodeAudioData(await (await fetch(url)).arrayBuffer())))
+  if (!buffers.length) return null
+  const rate = buffers[0].sampleRate
+  const length = Math.max(...buffers.map(b => b.length))
+  const block = Math.round(BLOCK_SECONDS * rate)
+  const channels = buffers.flatMap(b => Array.from({ length: b.numberOfChannels }, (_, c) => ({ data: b.getChannelData(c), weight: 1 / b.numberOfChannels })))
+
+  let sum = 0
+  let blocks = 0
+  for (let start = 0; start < length; start += block) {
+    const end = Math.min(length, start + block)
+    let energy = 0
+    for (let i = start; i < end; i++) {
+      let s = 0
+      for (const ch of channels) s += (ch.data[i] ?? 0) * ch.weight
+      energy += s * s
+    }
+    const mean = energy / (end - start)
+    if (10 * Math.log10(mean) <= SILENCE_DB) continue
+    sum += mean
+    blocks++
+  }
+  return blocks ? 10 * Math.log10(sum / blocks) : null
+}
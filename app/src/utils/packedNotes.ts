@@ -0,0 +1,39 @@
+// Decoder of the binary note layout of `load_midi_raw` (Rust `klok_core::midi::pack_notes`)
+import type { MidiNote } from './pitch'
+
+const HEADER_LEN = 8
+const NOTE_LEN = 20
+const BEND_LEN = 8
+
+// Layout, little-endian:
+// - header: note count (u32), bend point count (u32)
+// - per note: start, duration, confidence (f32 each, confidence NaN when none),
+//   bend point count (u32), note, velocity, channel (u8 each), padding (u8)
+// - bend points of all notes in note order: time, semitones (f32 each)
+export function unpackNotes(data: ArrayBuffer): MidiNote[] {
+  const view = new DataView(data)
+  const count = view.getUint32(0, true)
+  const notes: MidiNote[] = new Array(count)
+  let point = HEADER_LEN + count * NOTE_LEN
+  for (let i = 0; i < count; i++) {
+    const at = HEADER_LEN + i * NOTE_LEN
+    const confidence = view.getFloat32(at + 8, true)
+    const bends = view.getUint32(at + 12, true)
+    const note: MidiNote = {
+      note: view.getUint8(at + 16),
+      start: view.getFloat32(at, true),
+      duration: view.getFloat32(at + 4, true),
+      velocity: view.getUint8(at + 17),
+      channel: view.getUint8(at + 18),
+      confidence: Number.isNaN(confidence) ? null : confidence,
+    }
+    if (bends) {
+      note.bend = []
+      for (let b = 0; b < bends; b++, point += BEND_LEN) {
+        note.bend.push([view.getFloat32(point, true), view.getFloat32(point + 4, true)])
+      }
+    }
+    notes[i] = note
+  }
+  return notes
+}
@@ -0,0 +1,55 @@
+// Piano roll raster from the backend (`load_piano_roll`), for drawing songs with many notes
+// as one image instead of note by note
+
+// matches Rust `PianoRoll`
+export type PianoRoll = {
+  // MIDI note of row 0
+  low: number
+  rows: number
+  columns: number
+  // seconds per column
+  bucket: number
+  // rows * columns velocities, row-major with the lowest pitch first; 0 when silent
+  cells: Uint8Array
+}
+
+const HEADER_LEN = 12
+
+// Parse the bytes of `PianoRoll::to_bytes`
+export function parsePianoRoll(data: ArrayBuffer): PianoRoll {
+  const view = new DataView(data)
+  const low = view.getUint8(0)
+  const rows = view.getUint8(1)
+  const columns = view.getUint32(4, true)
+  const bucket = view.getFloat32(8, true)
+  return { low, rows, columns, bucket, cells: new Uint8Array(data, HEADER_LEN, rows * columns) }
+}
+
+// Draw the roll over the whole canvas, one pixel per cell scaled to fit, with the highest pitch
+// at the top and louder notes brighter
+export function drawPianoRoll(canvas: HTMLCanvasElement | null, roll: PianoRoll | null, color: [number, number, number] = [110, 231, 183]) {
+  if (!canvas) return
+  const ctx = canvas.getContext('2d')
+  if (!ctx) return
+  canvas.width = canvas.clientWidth * devicePixelRatio
+  canvas.height = canvas.clientHeight * devicePixelRatio
+  ctx.clearRect(0, 0, canvas.width, canvas.height)
+  if (!roll || !roll.rows || !roll.columns) return
+
+  const image = new ImageData(roll.columns, roll.rows)
+  for (let row = 0; row < roll.rows; row++) {
+    const y = roll.rows - 1 - row
+    for (let col = 0; col < roll.columns; col++) {
+      const v = roll.cells[row * roll.columns + col]
+      if (!v) continue
+      const i = (y * roll.columns + col) * 4
+      image.data.set([color[0], color[1], color[2], 64 + Math.round(v * 191 / 127)], i)
+    }
+  }
+  const bitmap = document.createElement('canvas')
+  bitmap.width = roll.columns
+  bitmap.height = roll.rows
+  bitmap.getContext('2d')?.putImageData(image, 0, 0)
+  ctx.imageSmoothingEnabled = false
+  ctx.drawImage(bitmap, 0, 0, canvas.width, canvas.height)
+}
@@ -7,6 +7,18 @@ export type MidiNote = {
   velocity: number
   channel: number
   confidence?: number | null
+  // pitch bend as [time, semitones] points, each holding until the next
+  bend?: [number, number][]
+}
+
+// Pitch bend (semitones) of a note at `time`; 0 before its first bend point.
+export function bendAt(note: MidiNote, time: number): number {
+  let offset = 0
+  for (const [t, semitones] of note.bend || []) {
+    if (t > time) break
+    offset = semitones
+  }
+  return offset
 }
 
 type DrawOptions = {
@@ -17,10 +29,14 @@ type DrawOptions = {
   min_note?: number
   max_note?: number
   alpha_threshold?: number
+  // notes with `confidence` below this are drawn as uncertain (outlined only)
+  confidence_threshold?: number
   // current detected pitch in Hz (optional)
   current_pitch_data?:  pitchData| null
   // history of detected pitches as array of {t: number, hz: number}
   pitch_history?: pitchData[] | null
+  // bar start times (seconds) drawn as measure lines
+  bars?: number[] | null
 }
 
 function isValidNumber(v: any): v is number {
@@ -95,6 +111,17 @@ export function drawNotes(canvas: HTMLCanvasElement | null, notes: MidiNote[] |
     }
   }
 
+  // measure lines
+  ctx.strokeStyle = 'rgba(255,255,255,0.12)'
+  for (const bar of opts.bars || []) {
+    if (bar < viewStart || bar > viewEnd) continue
+    const x = timeToX(bar)
+    ctx.beginPath()
+    ctx.moveTo(x, 0)
+    ctx.lineTo(x, cssHeight)
+    ctx.stroke()
+  }
+
   // notes (cropped to view window)
   const visibleNotes = ns.filter(n => (n.start + n.duration) > viewStart && n.start < viewEnd)
   for (const n of visibleNotes) {
@@ -104,6 +131,16 @@ export function drawNotes(canvas: HTMLCanvasElement | null, notes: MidiNote[] |
     const h = Math.max(4, (cssHeight - 20) / noteRange)
     const alphaThreshold = opts.alpha_threshold || 0.3
     const alpha = Math.min(1, Math.max(n.velocity / 127 - alphaThreshold, 0) / (1 - alphaThreshold))
+    const uncertain = isValidNumber(n.confidence) && n.confidence < (opts.confidence_threshold ?? 0.5)
+    if (uncertain) {
+      ctx.fillStyle = `rgba(160,160,160,${alpha * 0.3})`
+      ctx.fillRect(x, y - h / 2, w, h)
+      ctx.setLineDash([3, 2])
+      ctx.strokeStyle = `rgba(160,160,160,0.8)`
+      ctx.strokeRect(x + 0.5, y - h / 2 + 0.5, w - 1, h - 1)
+      ctx.setLineDash([])
+      continue
+    }
     ctx.fillStyle = `rgba(40,200,255,${alpha})`
     ctx.fillRect(x, y - h / 2, w, h)
     ctx.strokeStyle = `rgba(0,0,0,0.25)`
@@ -189,6 +226,7 @@ export type ScoreResult = {
     margin: number
     minSamples: number
     weightByDuration: boolean
+    octaveAgnostic: boolean
   }
 }
 
@@ -201,6 +239,8 @@ export type ScoreOptions = {
   minSamples?: number
   // whether to weight per-note scores by their duration when computing overall score
   weightByDuration?: boolean
+  // compare pitch classes only (singing an octave above/below still counts)
+  octaveAgnostic?: boolean
 }
 
 /**
@@ -209,7 +249,7 @@ export type ScoreOptions = {
  *
  * Strategy (simple, robust):
  * - For each note, collect pitchHistory samples whose time falls in [start-margin, start+duration+margin]
- * - For each sample compute semitone error = abs(sample.midi - note)
+ * - For each sample compute semitone error = abs(sample.midi - note), following the note's pitch bend
  * - Convert sample error to sample score = clamp(1 - error / tolerance, 0, 1)
  * - Note score = average(sample scores). If no samples, score = 0.
  */
@@ -218,6 +258,13 @@ export function scoreNotes(notes: MidiNote[] | null, pitch_history: pitchData[]
   const margin = opts.margin ?? 0.06 // seconds
   const minSamples = opts.minSamples ?? 1
   const weightByDuration = opts.weightByDuration ?? true
+  const octaveAgnostic = opts.octaveAgnostic ?? false
+  const semitoneError = (sung: number, target: number) => {
+    const d = Math.abs(sung - target)
+    if (!octaveAgnostic) return d
+    const m = d % 12
+    return Math.min(m, 12 - m)
+  }
 
   const nh = notes || []
   const ph = pitch_history || []
@@ -237,7 +284,8 @@ export function scoreNotes(notes: MidiNote[] | null, pitch_history: pitchData[]
       continue
     }
 
-    const errors = samples.map(s => Math.abs(s.midi - n.note))
+    // compare against the bent pitch, so slides and vibrato in the reference are followed
+    const errors = samples.map(s => semitoneError(s.midi - bendAt(n, s.time), n.note))
     const meanError = errors.reduce((a, b) => a + b, 0) / errors.length
 
     const sampleScores = errors.map(e => Math.max(0, 1 - e / tolerance))
@@ -264,6 +312,139 @@ export function scoreNotes(notes: MidiNote[] | null, pitch_history: pitchData[]
   return {
     perNote,
     overall,
-    options: { tolerance, margin, minSamples, weightByDuration }
+    options: { tolerance, margin, minSamples, weightByDuration, octaveAgnostic }
+  }
+}
+
+export type NoteFeedback = {
+  index: number
+  // target midi note
+  target: number
+  // median detected midi pitch over the note window (null when nothing was sung)
+  sung: number | null
+  // signed deviation of `sung` from the target in cents (positive = sharp)
+  centsOff: number | null
+  // seconds between the note start and the first sample within tolerance (positive = late)
+  timingError: number | null
+  score: number
+}
+
+export type LineFeedback = {
+  // index into the lyrics array
+  index: number
+  time: number
+  text: string
+  // duration-weighted mean score of the notes starting within the line
+  score: number
+  notes: number
+}
+
+export type PerformanceReport = {
+  overall: number
+  notes: NoteFeedback[]
+  // lowest scoring lines first, for a "practice these lines" screen
+  worstLines: LineFeedback[]
+}
+
+function median(values: number[]): number {
+  const sorted = [...values].sort((a, b) => a - b)
+  const mid = Math.floor(sorted.length / 2)
+  return sorted.length % 2 === 1 ? sorted[mid] : (sorted[mid - 1] + sorted[mid]) / 2
+}
+
+/**
+ * Build a per-note and per-line feedback report for a finished run.
+ * Uses the same sample windows and options as `scoreNotes`, so `overall` matches the final score.
+ */
+export function performanceReport(
+  notes: MidiNote[] | null,
+  pitch_history: pitchData[] | null,
+  lyrics: LyricLine[] | null,
+  opts: ScoreOptions & { worstLines?: number } = {},
+): PerformanceReport {
+  const result = scoreNotes(notes, pitch_history, opts)
+  const { tolerance, margin, octaveAgnostic } = result.options
+  const nh = notes || []
+  const ph = (pitch_history || []).filter(p => typeof p.midi === 'number' && !Number.isNaN(p.midi))
+
+  const feedback: NoteFeedback[] = result.perNote.map(ns => {
+    const n = nh[ns.index]
+    const samples = ph
+      .filter(p => p.time >= Math.max(0, n.start - margin) && p.time <= n.start + n.duration + margin)
+      .map(p => ({ ...p, midi: p.midi - bendAt(n, p.time) }))
+    if (samples.length === 0) {
+      return { index: ns.index, target: n.note, sung: null, centsOff: null, timingError: null, score: ns.score }
+    }
+    // fold octave errors back near the target when scoring ignores octaves
+    const fold = (m: number) => octaveAgnostic ? m - 12 * Math.round((m - n.note) / 12) : m
+    const sung = median(samples.map(s => fold(s.midi)))
+    const hit = samples.find(s => Math.abs(fold(s.midi) - n.note) < tolerance)
+    return {
+      index: ns.index,
+      target: n.note,
+      sung,
+      centsOff: Math.round((sung - n.note) * 100),
+      timingError: hit ? hit.time - n.start : null,
+      score: ns.score,
+    }
+  })
+
+  const lines: LineFeedback[] = []
+  const ls = lyrics || []
+  for (let i = 0; i < ls.length; i++) {
+    const start = ls[i].time
+    const end = i + 1 < ls.length ? ls[i + 1].time : Infinity
+    let weight = 0
+    let sum = 0
+    let count = 0
+    for (const f of feedback) {
+      const n = nh[f.index]
+      if (n.start < start || n.start >= end) continue
+      weight += n.duration
+      sum += f.score * n.duration
+      count++
+    }
+    if (count > 0) {
+      lines.push({ index: i, time: start, text: ls[i].text, score: weight > 0 ? sum / weight : 0, notes: count })
+    }
+  }
+  lines.sort((a, b) => a.score - b.score)
+
+  return {
+    overall: result.overall,
+    notes: feedback,
+    worstLines: lines.slice(0, opts.worstLines ?? 3),
+  }
+}
+
+/**
+ * Pass-the-mic scoring: attribute each note to the player assigned to the lyric line it starts in
+ * (`assignments[i]` is the player for `lyrics[i]`, as returned by `assign_mic_turns`)
+ * and score every player separately. Notes before the first line go to that line's player.
+ */
+export function scoreByPlayer(
+  notes: MidiNote[] | null,
+  pitch_history: pitchData[] | null,
+  lyrics: LyricLine[] | null,
+  assignments: number[],
+  opts: ScoreOptions = {},
+): number[] {
+  const result = scoreNotes(notes, pitch_history, opts)
+  const weightByDuration = result.options.weightByDuration
+  const ls = lyrics || []
+  const players = assignments.reduce((m, p) => Math.max(m, p + 1), 0)
+  const weights = new Array<number>(players).fill(0)
+  const sums = new Array<number>(players).fill(0)
+
+  let line = 0
+  // perNote follows the order of `notes`, which `load_midi` returns sorted by start
+  for (const ns of result.perNote) {
+    while (line + 1 < ls.length && ns.start >= ls[line + 1].time) line++
+    const player = assignments[line]
+    if (player === undefined) continue
+    const w = weightByDuration ? ns.duration : 1
+    weights[player] += w
+    sums[player] += ns.score * w
   }
+  return sums.map((s, i) => weights[i] > 0 ? s / weights[i] : 0)
 }
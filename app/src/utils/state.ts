@@ -1,7 +1,16 @@
-import { invoke } from '@tauri-apps/api/core'
+import { convertFileSrc, invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { defineStore } from 'pinia'
 import { ref, computed, watch, nextTick } from 'vue'
-import { fetchCurrentPitch, loadAudioContent, pitchData} from './api'
+import { fetchCurrentPitch, getWaveform, loadBackingContent, loadF0Curve, loadGuideContent, loadMidiWarnings, MicPitch, MidiInputEvent, pitchData, playerLoad, playerPause, playerPlay, playerSeek, PlayerOutputFailure, PlayerPosition, setKeyShift, setTempo, setVocalVolume, startPitchDetection, stopPitchDetection } from './api'
+import type { F0Curve } from './f0Curve'
+import type { ScoreOptions } from './pitch'
+import type { Waveform } from './waveform'
+import { drawLyricFrame, frameOptions, FrameOptions } from './frames'
+import { runTransition, TransitionStep } from './transition'
+import { measureLoudness } from './loudness'
+import { unpackNotes } from './packedNotes'
+import { completePass, createSpeedTrainer, SpeedTrainer, SpeedTrainerOptions } from './trainer'
 
 
 // MIDI note representation (matches Rust `Note` returned from `load_midi`)
@@ -12,14 +21,118 @@ type MidiNote = {
   velocity: number
   channel: number
   confidence?: number | null
+  bend?: [number, number][]
+}
+
+// scoring profile stored in Rust settings (matches `settings::ScoringProfile`)
+export type ScoringProfile = ScoreOptions & { name: string }
+
+// countdown cue before a vocal entry (matches Rust `CountdownCue`)
+export type CountdownCue = {
+  time: number
+  count: number
+  entry: number
 }
 
 export type PlayListItem = {
   title: string
   artist?: string
   url: string
+  // detected lyrics language (ISO 639-1)
+  language?: string | null
+  // sing-along difficulty, 0 (easy) to 10 (hard)
+  difficulty?: number | null
+  // karaoke MIDI without audio, rehearsed with a synthesized backing
+  midi_only?: boolean
+}
+
+// note statistics of a song's vocal MIDI (matches Rust `MidiStats`)
+export type MidiStats = {
+  note_count: number
+  lowest: number
+  highest: number
+  mean_duration: number
+  longest_phrase: [number, number]
+  // notes per second in 10 s windows
+  density: number[]
+}
+
+// library root availability (matches Rust `LibraryStatus`)
+export type LibraryStatus = {
+  root: string
+  network: boolean
+  available: boolean
+}
+
+// matches Rust `Feature`
+export type ConsentFeature = 'microphone' | 'server' | 'network'
+
+// matches Rust `ExportTargetStatus`
+export type ExportTargetStatus = {
+  name: string
+  kind: 'local' | 'usb' | 'network_share' | 'cloud_sync'
+  path: string
+  available: boolean
+  freeSpace: number | null
+}
+
+// timing structure of a MIDI file (matches Rust `MidiMeta`), times in seconds
+export type MidiMeta = {
+  ticks_per_quarter: number
+  tempos: { time: number, bpm: number }[]
+  time_signatures: { time: number, numerator: number, denominator: number }[]
+  key_signatures: { time: number, sharps: number, minor: boolean }[]
+  bars: number[]
+}
+
+// error returned by load_lyrics (matches Rust `LyricsError`)
+export type LyricsError = {
+  kind: 'empty_path' | 'not_found' | 'unavailable' | 'failed'
+  message?: string
+}
+
+// warning returned by validate_lyrics (matches Rust `LyricsWarning`)
+export type LyricsWarning = {
+  kind: 'out_of_order' | 'duplicate_line' | 'missing_offset' | 'beyond_duration'
+  line?: number
+  message: string
+}
+
+// saved player state (matches Rust `Session`)
+export type Session = {
+  song: string | null
+  position: number
+  queue: string[]
+  transpose: number
+  mixer: { volume: number, playbackRate: number }
 }
 
+// idle background playlist (matches Rust `AttractSettings`)
+export type AttractSettings = {
+  enabled: boolean
+  idleMinutes: number
+  playlist: string[]
+  volume: number
+}
+
+// General MIDI percussion channel, never transposed
+const DRUM_CHANNEL = 9
+
+// seconds of playback between session saves
+const SESSION_SAVE_INTERVAL = 5
+
+// seconds between checks whether the app has been idle long enough for attract mode
+const ATTRACT_CHECK_INTERVAL = 10
+
+// seconds the audio may be off the MIDI output melody before it is restarted there (a seek)
+const MIDI_OUTPUT_DRIFT = 0.3
+
+// peaks drawn behind the seek bar, about a pixel each on a wide window
+const WAVEFORM_BUCKETS = 1024
+
+// names of the pitch classes, for pitch samples from MIDI keys
+const KEY_NAMES = ['C', 'C#', 'D', 'D#', 'E', 'F', 'F#', 'G', 'G#', 'A', 'A#', 'B']
+
 export const useAppState = defineStore('app', () => {
   const playList = ref<PlayListItem[]>([])
   const fileUrl = ref<string | null>(null)
@@ -30,12 +143,64 @@ export const useAppState = defineStore('app', () => {
   const duration = ref(1)
   const currentTime = ref(0)
   const volume = ref(1)
+  const playbackRate = ref(1)
+  // gain staging of the current song, applied on top of `volume` (see Rust `song_gain`)
+  const gain = ref(1)
+  // playlist urls to play after the current song
+  const queue = ref<string[]>([])
+  // key shift in semitones
+  const transpose = ref(0)
+  // active speed-trainer practice program (null when not practicing)
+  const speedTrainer = ref<SpeedTrainer | null>(null)
   const metadata = ref<Metadata | null>(null)
   const notes = ref<MidiNote[] | null>(null)
   // history of detected pitch data
   const pitchHistory = ref<pitchData[]>([])
+  // pass-the-mic player per lyric line (null = single player)
+  const micTurns = ref<number[] | null>(null)
+  // "entry in 3-2-1" cues for the current song
+  const countdownCues = ref<CountdownCue[]>([])
+  // tempo map, signatures and bar lines of the vocal MIDI
+  const midiMeta = ref<MidiMeta | null>(null)
+  // problems worked around while loading a damaged vocal MIDI, so the melody may be incomplete
+  const midiWarnings = ref<string[]>([])
+  // the melody as Hz every 10ms, for comparing with the detected pitch
+  const f0Curve = ref<F0Curve | null>(null)
+  // seek-bar peaks of the backing stem, null while unknown or undecodable
+  const waveform = ref<Waveform | null>(null)
+  // null until the first availability check
+  const libraryStatus = ref<LibraryStatus | null>(null)
+  // scoring profile selected for this session
+  const scoringProfile = ref<ScoringProfile | null>(null)
+  // export target (by name) exports are written to; null writes next to the song
+  const exportTarget = ref<string | null>(null)
   // polling handle
   let pitchPollTimer: number | null = null
+  // pitch detected by the backend on the microphone instead of the pitch endpoint
+  let unlistenMicPitch: (() => void) | null = null
+  // instrument mode: the held keys of a MIDI keyboard stand in for the sung pitch
+  const midiInputPort = ref<string | null>(null)
+  const heldKeys = new Set<number>()
+  let instrumentTimer: number | null = null
+  let unlistenMidiInput: (() => void) | null = null
+  // MIDI output playing the reference melody along with the audio, null when off
+  const midiOutputPort = ref<string | null>(null)
+  // where and when the output melody was started, to notice seeks
+  let midiOutputSync: { position: number, wall: number, rate: number } | null = null
+  // synthesized guide melody mixed in at `guideVolume` (0 = off, relative to the song volume)
+  const guideUrl = ref<string | null>(null)
+  const guideVolume = ref(0)
+  const guideVoice = ref<'sine' | 'piano'>('sine')
+  // native playback: the backend mixes the stems instead of the media elements playing them
+  const nativePlayback = ref(false)
+  // level of the vocal stem in native playback, 0 (karaoke) to 1 (the original mix)
+  const vocalVolume = ref(0)
+  let unlistenPlayer: (() => void) | null = null
+  let unlistenPlayerOutput: (() => void) | null = null
+  // position to seek to once the restored session's song has loaded
+  let restoredPosition: number | null = null
+  // playback position at the last session save
+  let lastSavedPosition = 0
 
   // Keep original lyrics exactly as provided by metadata
   const originalLyrics = computed(() => Array.from(metadata.value?.lyrics || []))
@@ -69,6 +234,7 @@ export const useAppState = defineStore('app', () => {
     currentTime.value = 0
     metadata.value = null
     pitchHistory.value = []
+    countdownCues.value = []
   }
 
   const loadPlaylist = async () => {
@@ -81,23 +247,352 @@ export const useAppState = defineStore('app', () => {
     }
   }
 
+  // Track library availability; reload the playlist when an offline library comes back
+  const watchLibraryStatus = async () => {
+    await listen<LibraryStatus>('library-availability', async (event) => {
+      const wasOffline = libraryStatus.value?.available === false
+      libraryStatus.value = event.payload
+      if (wasOffline && event.payload.available) {
+        await loadPlaylist()
+      }
+    })
+    try {
+      libraryStatus.value = await invoke('get_library_status') as LibraryStatus
+    } catch (e) {
+      console.warn('get_library_status failed', e)
+    }
+  }
+
+  // Note statistics per playlist url, fetched on demand by `loadMidiStats`
+  const midiStats = ref<Record<string, MidiStats>>({})
+  const loadMidiStats = async (url: string) => {
+    if (url in midiStats.value) return
+    try {
+      const stats = await invoke('midi_stats', { path: url }) as MidiStats
+      midiStats.value = { ...midiStats.value, [url]: stats }
+    } catch (e) {
+      console.warn('midi_stats failed', e)
+    }
+  }
+
+  // Hide a song from the playlist (files are kept)
+  const hideSong = async (url: string) => {
+    try {
+      await invoke('hide_song', { path: url })
+      await loadPlaylist()
+    } catch (e) {
+      console.warn('hide_song failed', e)
+    }
+  }
+
+  // Move a song and its companion files (lyrics, stems, MIDI) to the OS trash
+  const deleteSong = async (url: string) => {
+    try {
+      await invoke('delete_song', { path: url, toTrash: true })
+      await loadPlaylist()
+    } catch (e) {
+      console.warn('delete_song failed', e)
+    }
+  }
+
+  // Put the last song sent to the trash under `url` back
+  const restoreSong = async (url: string) => {
+    try {
+      await invoke('restore_song', { path: url })
+      await loadPlaylist()
+    } catch (e) {
+      console.warn('restore_song failed', e)
+    }
+  }
+
+  // Background image or video of the current song, as a URL the webview can load
+  const background = computed(() => {
+    const bg = metadata.value?.background
+    return bg ? { url: convertFileSrc(bg, 'klok'), video: /\.(mp4|webm)$/i.test(bg) } : null
+  })
+
+  // Assign a library-relative background to the current song (omit to clear), then reload metadata
+  const setSongBackground = async (bg?: string) => {
+    if (!fileUrl.value) return
+    try {
+      await invoke('set_song_background', { path: fileUrl.value, background: bg })
+      await loadMetadata(fileUrl.value)
+    } catch (e) {
+      console.warn('set_song_background failed', e)
+    }
+  }
+
+  // Kiosk mode: the backend refuses settings and library changes until unlocked with the PIN
+  const kiosk = ref(false)
+  const loadKioskMode = async () => {
+    try {
+      kiosk.value = await invoke('get_kiosk_mode') as boolean
+    } catch (e) {
+      console.warn('get_kiosk_mode failed', e)
+    }
+  }
+  const setKioskMode = async (enabled: boolean, pin: string) => {
+    try {
+      await invoke('set_kiosk_mode', { enabled, pin })
+      kiosk.value = enabled
+      return true
+    } catch (e) {
+      console.warn('set_kiosk_mode failed', e)
+      return false
+    }
+  }
+
+  // Privacy consents tracked by the backend; gated features stay off until granted
+  const consents = ref<Record<ConsentFeature, boolean>>({ microphone: false, server: false, network: false })
+  const loadConsents = async () => {
+    try {
+      consents.value = await invoke('get_consents') as Record<ConsentFeature, boolean>
+    } catch (e) {
+      console.warn('get_consents failed', e)
+    }
+  }
+  const setConsent = async (feature: ConsentFeature, granted: boolean) => {
+    try {
+      await invoke(granted ? 'grant_consent' : 'revoke_consent', { feature })
+      consents.value = { ...consents.value, [feature]: granted }
+      return true
+    } catch (e) {
+      console.warn('set consent failed', e)
+      return false
+    }
+  }
+  // True when `feature` is allowed, asking the user (with `reason`) when it hasn't been yet
+  const requestConsent = async (feature: ConsentFeature, reason: string) => {
+    if (consents.value[feature]) return true
+    if (!window.confirm(reason)) return false
+    return await setConsent(feature, true)
+  }
+
+  // Move songs into an Artist/Title layout based on their tags
+  const organizeLibrary = async (pattern?: string) => {
+    try {
+      const moves = await invoke('organize_library', { pattern }) as { from: string, to: string }[]
+      const moved = moves.find(m => m.from === fileUrl.value)
+      if (moved) {
+        fileUrl.value = moved.to
+      }
+      await loadPlaylist()
+    } catch (e) {
+      console.warn('organize_library failed', e)
+    }
+  }
+
+  // Configured export targets with availability and free space, for picking `exportTarget`
+  const listExportTargets = async () => {
+    try {
+      return await invoke('list_export_targets') as ExportTargetStatus[]
+    } catch (e) {
+      console.warn('list_export_targets failed', e)
+      return []
+    }
+  }
+
+  // Write a practice file next to the current song (or to `exportTarget`); returns its path
+  const exportPracticeMix = async (preset: 'melody_only' | 'instrumental_guide' = 'melody_only') => {
+    if (!fileUrl.value) return null
+    try {
+      return await invoke('export_practice_mix', { path: fileUrl.value, preset, target: exportTarget.value }) as string
+    } catch (e) {
+      console.warn('export_practice_mix failed', e)
+      return null
+    }
+  }
+
+  // Render [start, end) of the lyric display to PNG frames next to the song (or to `exportTarget`);
+  // returns the frame directory
+  const exportLyricFrames = async (start: number, end: number, opts: FrameOptions = {}) => {
+    if (!fileUrl.value || fileUrl.value.startsWith('blob:')) return null
+    const url = fileUrl.value
+    const { width, height, fps } = frameOptions(opts)
+    const canvas = document.createElement('canvas')
+    canvas.width = width
+    canvas.height = height
+    const ctx = canvas.getContext('2d')
+    if (!ctx) return null
+    let dir: string | null = null
+    try {
+      for (let frame = 0; frame < Math.ceil((end - start) * fps); frame++) {
+        drawLyricFrame(ctx, lyrics.value, start + frame / fps, opts)
+        const blob = await new Promise<Blob | null>(resolve => canvas.toBlob(resolve, 'image/png'))
+        if (!blob) throw new Error(`failed to encode frame ${frame}`)
+        const headers: Record<string, string> = { 'x-klok-path': encodeURIComponent(url), 'x-klok-start': start.toFixed(2), 'x-klok-frame': String(frame) }
+        if (exportTarget.value) headers['x-klok-target'] = encodeURIComponent(exportTarget.value)
+        dir = await invoke('export_lyric_frame', new Uint8Array(await blob.arrayBuffer()), { headers }) as string
+      }
+      return dir
+    } catch (e) {
+      console.warn('export_lyric_frame failed', e)
+      return null
+    }
+  }
+
+  // Karaoke roulette: switch to a random song matching the constraints
+  const pickRandom = async (constraints: { language?: string, maxDifficulty?: number, excludeSung?: boolean } = {}) => {
+    try {
+      const item = await invoke('pick_random', { constraints }) as PlayListItem | null
+      if (item) switchToSong(item.url)
+      return item
+    } catch (e) {
+      console.warn('pick_random failed', e)
+      return null
+    }
+  }
+
+  // Remember the current song as sung tonight so roulette skips it
+  const markSung = async () => {
+    if (!fileUrl.value || fileUrl.value.startsWith('blob:')) return
+    try {
+      await invoke('mark_sung', { path: fileUrl.value })
+    } catch (e) {
+      console.warn('mark_sung failed', e)
+    }
+  }
+
+  // Render the playlist as a setlist document (HTML for printing, CSV for sharing)
+  const exportSetlist = async (format: 'html' | 'csv' = 'html', singers: Record<string, string[]> = {}) => {
+    const songs = playList.value.map(item => ({ url: item.url, title: item.title, artist: item.artist, singers: singers[item.url] ?? [] }))
+    try {
+      return await invoke('export_setlist', { songs, format }) as string
+    } catch (e) {
+      console.warn('export_setlist failed', e)
+      return null
+    }
+  }
+
+  const loadScoringProfile = async () => {
+    try {
+      scoringProfile.value = await invoke('get_scoring_profile') as ScoringProfile
+    } catch (e) {
+      console.warn('get_scoring_profile failed', e)
+    }
+  }
+
+  const selectScoringProfile = async (name: string) => {
+    try {
+      scoringProfile.value = await invoke('select_scoring_profile', { name }) as ScoringProfile
+    } catch (e) {
+      console.warn('select_scoring_profile failed', e)
+    }
+  }
+
+  const loadMicTurns = async (players: number, linesPerTurn?: number) => {
+    if (!fileUrl.value || players < 2) {
+      micTurns.value = null
+      return
+    }
+    try {
+      micTurns.value = await invoke('assign_mic_turns', { path: fileUrl.value, players, linesPerTurn }) as number[]
+    } catch (e) {
+      console.warn('assign_mic_turns failed', e)
+      micTurns.value = null
+    }
+  }
+
   const loadMetadata = async (newUrl: string) => {
     try {
       // fetch metadata
       const md = await invoke('get_metadata', { path: newUrl })
       metadata.value = md as Metadata
+      // countdown cues come with the lyrics; `loadCountdownCues` refreshes them after a settings change
+      countdownCues.value = metadata.value?.countdown ?? []
       if (metadata.value?.duration) {
         duration.value = metadata.value.duration
       }
       if (metadata.value?.title) {
         _title.value = metadata.value.title
       }
+      if (metadata.value && !hasLocalLyrics(metadata.value)) {
+        await fetchLyrics(newUrl)
+      }
     } catch (e) {
       // ignore, optional
       console.warn('get_metadata failed', e)
     }
   }
 
+  // Re-read only the lyrics of the current song, e.g. after editing its lyrics file.
+  // Errors from load_lyrics are `{ kind, message }`; `unavailable` means the library may come back.
+  const reloadLyrics = async () => {
+    const md = metadata.value
+    if (!md || !fileUrl.value) return null
+    try {
+      const lines = await invoke('load_lyrics', { path: fileUrl.value }) as LyricLine[]
+      if (metadata.value === md) {
+        metadata.value = { ...md, lyrics: lines }
+      }
+      return null
+    } catch (e) {
+      console.warn('load_lyrics failed', e)
+      return e as LyricsError
+    }
+  }
+
+  // Problems found in the current song's .lrc file (matches Rust `LyricsWarning`)
+  const validateLyrics = async () => {
+    if (!fileUrl.value) return []
+    try {
+      return await invoke('validate_lyrics', { path: fileUrl.value }) as LyricsWarning[]
+    } catch (e) {
+      console.warn('validate_lyrics failed', e)
+      return []
+    }
+  }
+
+  // Backend timings for bug reports (matches Rust `PerfStats`), `null` when unavailable
+  const getPerfStats = async () => {
+    try {
+      return await invoke('get_perf_stats') as Record<string, unknown>
+    } catch (e) {
+      console.warn('get_perf_stats failed', e)
+      return null
+    }
+  }
+
+  // Add pinyin/romaji readings to the current lyrics (same lines and order as get_metadata)
+  const loadRomanization = async () => {
+    const md = metadata.value
+    if (!md || !fileUrl.value) return
+    try {
+      const lines = await invoke('romanize_lyrics', { path: fileUrl.value, language: md.language }) as LyricLine[]
+      if (metadata.value === md && lines.length === md.lyrics.length) {
+        metadata.value = { ...md, lyrics: md.lyrics.map((l, i) => ({ ...l, romanization: lines[i].romanization })) }
+      }
+    } catch (e) {
+      console.warn('romanize_lyrics failed', e)
+    }
+  }
+
+  // get_metadata falls back to a two-line placeholder when the song has no lyrics file
+  const hasLocalLyrics = (md: Metadata) => !(md.lyrics.length === 2 && md.lyrics[1].text === '暂无歌词')
+
+  // Look the song up with the lyrics providers enabled in settings; the result is cached as the song's .lrc
+  const fetchLyrics = async (url: string) => {
+    const md = metadata.value
+    if (!md) return
+    try {
+      const artist = md.artist === '未知' ? '' : md.artist
+      const query = { title: md.title, artist, duration: md.duration, path: url }
+      let fetched: LyricLine[] | null
+      try {
+        fetched = await invoke('fetch_lyrics_auto', query) as LyricLine[] | null
+      } catch (e) {
+        if (!String(e).startsWith('consent:')) throw e
+        if (!await requestConsent('network', `Look up lyrics for "${md.title}" online? klok stays offline until you allow this.`)) return
+        fetched = await invoke('fetch_lyrics_auto', query) as LyricLine[] | null
+      }
+      if (fetched && fetched.length > 0 && metadata.value === md) {
+        metadata.value = { ...md, lyrics: fetched }
+      }
+    } catch (e) {
+      console.warn('fetch_lyrics_auto failed', e)
+    }
+  }
+
   const setVocalUrl = (url: string | null) => {
     const oldVocalUrl = vocalUrl.value
     nextTick(() => {
@@ -118,17 +613,68 @@ export const useAppState = defineStore('app', () => {
     streamUrl.value = url
   }
 
+  // Urls of what plays for the song at `url`: its backing and vocal stems, or in rehearsal
+  // mode (no stems yet) the MIDI's accompaniment
+  const songSources = async (url: string) => {
+    if (playList.value.find(item => item.url === url)?.midi_only) {
+      return { stream: await loadBackingContent(url), vocal: null }
+    }
+    // stems are streamed from the library (Range requests), not loaded whole over IPC
+    const { stream, vocal } = stemPaths(url)
+    return { stream: convertFileSrc(stream, 'klok'), vocal: convertFileSrc(vocal, 'klok') }
+  }
+
+  // Library paths of the backing and vocal stems of the song at `url`
+  const stemPaths = (url: string) => {
+    const name = url.split(".")[0]
+    return { stream: `${name}_non_vocals.mp3`, vocal: `${name}_vocals.mp3` }
+  }
+
+  // Gain for the song at `url`; without a stored loudness the song is measured from `sources`
+  // (decoding it again from the backend when not given) and the result kept by the backend
+  const songGain = async (url: string, sources?: string[]) => {
+    const known = await invoke('song_gain', { path: url }) as number | null
+    if (known != null) return known
+    let urls = sources
+    if (!urls) {
+      const { stream, vocal } = await songSources(url)
+      urls = vocal ? [stream, vocal] : [stream]
+    }
+    try {
+      const loudness = await measureLoudness(urls)
+      return loudness == null ? 1 : await invoke('set_song_loudness', { path: url, loudness }) as number
+    } finally {
+      if (!sources) urls.filter(u => u.startsWith('blob:')).forEach(u => URL.revokeObjectURL(u))
+    }
+  }
+
+  // Measure a queued song ahead of playback so its gain is ready when it starts
+  const prepareGain = (url: string) => {
+    if (playList.value.find(item => item.url === url)?.midi_only) return
+    songGain(url).catch(e => console.warn('song gain analysis failed', e))
+  }
+
   const loadAudio = async (newUrl: string) => {
     // fetch audio content from rust backend as data URL for bundled resource
     // store it in `streamUrl` so we don't overwrite any user-selected `fileUrl`
     try {
-      const name = newUrl.split(".")[0]
-
-      const vocalUrl = await loadAudioContent(`${name}_vocals.mp3`)
-      const backgroundUrl = await loadAudioContent(`${name}_non_vocals.mp3`)
-
-      setVocalUrl(vocalUrl)
-      setStreamUrl(backgroundUrl)
+      if (nativePlayback.value) {
+        const status = await playerLoad(newUrl).catch(e => {
+          // the backend only decodes WAV songs, the media elements play the others
+          console.warn('player_load failed, playing in the webview', e)
+          return null
+        })
+        if (status) duration.value = status.duration
+        else await setNativePlayback(false)
+        return
+      }
+      const { stream, vocal } = await songSources(newUrl)
+      setVocalUrl(vocal)
+      setStreamUrl(stream)
+      const g = await songGain(newUrl, vocal ? [stream, vocal] : [stream])
+      if (fileUrl.value === newUrl) gain.value = g
+      const peaks = vocal ? await getWaveform(stemPaths(newUrl).stream, WAVEFORM_BUCKETS).catch(() => null) : null
+      if (fileUrl.value === newUrl) waveform.value = peaks
     } catch (e) {
       // fallback: keep using builtin file name
       console.warn('load_audio failed', e)
@@ -137,11 +683,33 @@ export const useAppState = defineStore('app', () => {
 
   const loadMidi = async (newUrl: string) => {
     try {
-      const res = await invoke('load_midi', { path: newUrl })
-      notes.value = res as MidiNote[]
+      // pipeline MIDI is written by basic-pitch, which encodes note amplitude as velocity;
+      // sustain only changes hand-made piano-style MIDI, pipeline output has no pedal;
+      // simplify (with the backend defaults) merges the fragments and blips of transcription
+      const res = await invoke('load_midi_raw', { path: newUrl, confidence: 'velocity', sustain: true, transpose: transpose.value, simplify: {} })
+      notes.value = unpackNotes(res as ArrayBuffer)
+      midiWarnings.value = await loadMidiWarnings(newUrl).catch(() => [])
+      f0Curve.value = await loadF0Curve(newUrl, undefined, transpose.value).catch(() => null)
     } catch (e) {
-      console.warn('load_midi failed', e)
+      console.warn('load_midi_raw failed', e)
       notes.value = null
+      midiWarnings.value = []
+      f0Curve.value = null
+    }
+    try {
+      midiMeta.value = await invoke('load_midi_meta', { path: newUrl }) as MidiMeta
+    } catch (e) {
+      console.warn('load_midi_meta failed', e)
+      midiMeta.value = null
+    }
+  }
+
+  const loadCountdownCues = async (newUrl: string) => {
+    try {
+      countdownCues.value = await invoke('get_countdown_cues', { path: newUrl }) as CountdownCue[]
+    } catch (e) {
+      console.warn('get_countdown_cues failed', e)
+      countdownCues.value = []
     }
   }
 
@@ -155,6 +723,20 @@ export const useAppState = defineStore('app', () => {
     await loadMetadata(newUrl)
     await loadAudio(newUrl)
     await loadMidi(newUrl)
+    if (restoredPosition !== null) {
+      seekTo(restoredPosition)
+      restoredPosition = null
+    }
+  })
+
+  // number to show right now ("3", "2", "1"), or null outside a countdown
+  const countdown = computed(() => {
+    const t = currentTime.value
+    let active: CountdownCue | null = null
+    for (const cue of countdownCues.value) {
+      if (cue.time <= t && t < cue.entry) active = cue
+    }
+    return active?.count ?? null
   })
 
   const activeIndex = computed(() => {
@@ -177,6 +759,8 @@ export const useAppState = defineStore('app', () => {
 
   const togglePlay = (b?: boolean) => { isPlaying.value = b !== undefined ? b : !isPlaying.value }
   const seekTo = (v: number) => {
+    // the backend reports its position itself, so this is a seek by the user
+    if (nativePlayback.value && v !== currentTime.value) playerSeek(v).catch(e => console.warn('player_seek failed', e))
     currentTime.value = v
     // remove pitch history if seeking backwards
     if (pitchHistory.value.length > 0 && v < pitchHistory.value[pitchHistory.value.length - 1].time) {
@@ -216,13 +800,210 @@ export const useAppState = defineStore('app', () => {
     }
   }
 
+  // Write the adjusted lyrics (with all time deltas applied) back as an LRC file, then reload.
+  // `embed` also writes them into the MP3's SYLT frame so they travel with the file.
+  const saveLyrics = async (embed = false) => {
+    if (!fileUrl.value) return
+    const url = fileUrl.value
+    try {
+      await invoke('save_lyrics', { path: url, lines: lyrics.value, embed })
+      clearLyricTimeDelta()
+      setLyricDelta(0)
+      await loadMetadata(url)
+    } catch (e) {
+      console.warn('save_lyrics failed', e)
+    }
+  }
+
+  // Write edited melody notes back as the song's vocal MIDI, keeping the file's tempo, then reload.
+  // Notes are saved in the original key, undoing `transpose`.
+  const saveMidi = async (edited: MidiNote[]) => {
+    if (!fileUrl.value) return
+    const url = fileUrl.value
+    const original = edited.map(n => n.channel === DRUM_CHANNEL ? n : { ...n, note: n.note - transpose.value })
+    try {
+      await invoke('save_midi', { path: url, notes: original, tempo: midiMeta.value?.tempos[0]?.bpm })
+      await loadMidi(url)
+    } catch (e) {
+      console.warn('save_midi failed', e)
+    }
+  }
+
+  // Loop [start, end) starting at a reduced tempo, speeding up after each successful pass
+  const startSpeedTrainer = (start: number, end: number, opts: SpeedTrainerOptions = {}) => {
+    speedTrainer.value = createSpeedTrainer(start, end, opts)
+    playbackRate.value = speedTrainer.value.rate
+    seekTo(start)
+    isPlaying.value = true
+  }
+
+  const stopSpeedTrainer = () => {
+    speedTrainer.value = null
+    playbackRate.value = 1
+  }
+
+  watch(currentTime, (t) => {
+    const trainer = speedTrainer.value
+    if (!trainer || t < trainer.end) return
+    const next = completePass(trainer, notes.value, pitchHistory.value, scoringProfile.value ?? {})
+    speedTrainer.value = next
+    playbackRate.value = next.rate
+    if (next.done) {
+      isPlaying.value = false
+      return
+    }
+    // drop this pass's samples so the next pass is scored on its own
+    pitchHistory.value = pitchHistory.value.filter(p => p.time < trainer.start)
+    seekTo(trainer.start)
+  })
+
+  // Persist the player state so the next start resumes here (see `restoreSession`)
+  const saveSession = async () => {
+    const song = fileUrl.value && !fileUrl.value.startsWith('blob:') ? fileUrl.value : null
+    lastSavedPosition = currentTime.value
+    try {
+      await invoke('save_session', {
+        session: {
+          song,
+          position: song ? currentTime.value : 0,
+          queue: queue.value,
+          transpose: transpose.value,
+          mixer: { volume: volume.value, playbackRate: playbackRate.value },
+        },
+      })
+    } catch (e) {
+      console.warn('save_session failed', e)
+    }
+  }
+
+  // Resume the last saved session; false when there is none or its song is gone from the playlist
+  const restoreSession = async () => {
+    try {
+      const session = await invoke('restore_session') as Session | null
+      if (!session) return false
+      queue.value = session.queue.filter(url => playList.value.some(item => item.url === url))
+      queue.value.forEach(prepareGain)
+      transpose.value = session.transpose
+      volume.value = session.mixer.volume
+      playbackRate.value = session.mixer.playbackRate
+      if (!session.song || !playList.value.some(item => item.url === session.song)) return false
+      restoredPosition = session.position
+      fileUrl.value = session.song
+      return true
+    } catch (e) {
+      console.warn('restore_session failed', e)
+      return false
+    }
+  }
+
+  // save on every change, and every few seconds of playback in case the app crashes
+  watch([fileUrl, transpose, volume, playbackRate], () => saveSession())
+  watch(queue, () => saveSession(), { deep: true })
+  // the reference melody follows the key of the backing track
+  watch(transpose, () => {
+    if (fileUrl.value) loadMidi(fileUrl.value)
+  })
+
+  watch(currentTime, (t) => {
+    if (Math.abs(t - lastSavedPosition) >= SESSION_SAVE_INTERVAL) saveSession()
+  })
+
+  // Add a song to the end of the queue; in attract mode it starts at once
+  const enqueue = (url: string) => {
+    queue.value = [...queue.value, url]
+    prepareGain(url)
+    if (attractActive.value) {
+      isPlaying.value = false
+      stopAttract()
+      playNext()
+    }
+  }
+
+  // Change over to the first queued song: fade out, announce, count in and start, as planned by
+  // the backend for this venue. False when the queue is empty.
+  const playNext = async (singer?: string) => {
+    const [next, ...rest] = queue.value
+    if (!next) return false
+    queue.value = rest
+    try {
+      let steps = await invoke('plan_transition', { next, singer }) as TransitionStep[]
+      // nothing to fade once the song has ended
+      if (!isPlaying.value) steps = steps.filter(s => s.kind !== 'fade_out')
+      await runTransition(steps, {
+        getVolume: () => volume.value,
+        setVolume,
+        stop: () => { isPlaying.value = false },
+        start: (path) => {
+          switchToSong(path)
+          isPlaying.value = true
+        },
+      })
+    } catch (e) {
+      console.warn('plan_transition failed', e)
+      switchToSong(next)
+    }
+    return true
+  }
+
+  // Attract mode: once nothing has played or been queued for `idleMinutes`, the attract playlist
+  // plays at reduced volume, until a song is queued or picked
+  const attract = ref<AttractSettings | null>(null)
+  const attractActive = ref(false)
+  let attractIndex = 0
+  let lastActivity = Date.now()
+  const loadAttractMode = async () => {
+    try {
+      attract.value = await invoke('get_attract_mode') as AttractSettings
+    } catch (e) {
+      console.warn('get_attract_mode failed', e)
+    }
+  }
+  const setAttractMode = async (settings: AttractSettings) => {
+    try {
+      await invoke('set_attract_mode', { attract: settings })
+      await loadAttractMode()
+      return true
+    } catch (e) {
+      console.warn('set_attract_mode failed', e)
+      return false
+    }
+  }
+  // Play the next song of the attract playlist, also when the previous one ends
+  const playAttract = async () => {
+    const playlist = attract.value?.playlist ?? []
+    if (!playlist.length) return stopAttract()
+    stopSpeedTrainer()
+    isPlaying.value = false
+    fileUrl.value = playlist[attractIndex++ % playlist.length]
+    // a change the player sees, also right after the previous song ended
+    await nextTick()
+    isPlaying.value = true
+  }
+  // Back to the song volume; what plays carries on until changed
+  const stopAttract = () => { attractActive.value = false }
+  watch([isPlaying, queue], () => {
+    if (!attractActive.value) lastActivity = Date.now()
+  })
+  setInterval(() => {
+    const settings = attract.value
+    if (!settings?.enabled || attractActive.value || isPlaying.value || queue.value.length) return
+    if (Date.now() - lastActivity < settings.idleMinutes * 60_000) return
+    attractActive.value = true
+    playAttract()
+  }, ATTRACT_CHECK_INTERVAL * 1000)
+  // volume the media elements play at
+  const playbackVolume = computed(() => attractActive.value ? volume.value * (attract.value?.volume ?? 1) : volume.value)
+
   const switchToSong = (url: string) => {
+    stopAttract()
+    stopSpeedTrainer()
     isPlaying.value = false
     fileUrl.value = url
   }
 
-  // Start polling pitch endpoint every 100ms. Will replace existing timer.
-  const startPitchPolling = () => {
+  // Start polling pitch endpoint every 100ms once the microphone is allowed. Will replace existing timer.
+  const startPitchPolling = async () => {
+    if (!await requestConsent('microphone', 'Use the microphone to score your singing?')) return
     if (pitchPollTimer) clearInterval(pitchPollTimer)
     const poll = async () => {
       if (!isPlaying.value) return
@@ -244,6 +1025,155 @@ export const useAppState = defineStore('app', () => {
     }
   }
 
+  // Score the pitch the backend detects on the microphone, ~100 times a second
+  const startMicPitch = async () => {
+    if (!await requestConsent('microphone', 'Use the microphone to score your singing?')) return
+    stopPitchPolling()
+    await stopMicPitch()
+    unlistenMicPitch = await listen<MicPitch>('pitch', (event) => {
+      const f0 = event.payload.f0
+      if (!isPlaying.value || f0 === null) return
+      const midi = Math.round(69 + 12 * Math.log2(f0 / 440))
+      const note = KEY_NAMES[((midi % 12) + 12) % 12] + (Math.floor(midi / 12) - 1)
+      pitchHistory.value.push({ pitch: f0, midi, note, time: currentTime.value })
+    })
+    try {
+      await startPitchDetection()
+    } catch (e) {
+      console.warn('start_pitch_detection failed', e)
+      await stopMicPitch()
+    }
+  }
+
+  const stopMicPitch = async () => {
+    if (!unlistenMicPitch) return
+    unlistenMicPitch()
+    unlistenMicPitch = null
+    await stopPitchDetection().catch(e => console.warn('stop_pitch_detection failed', e))
+  }
+
+  // Switch between native playback and the media elements, carrying over position and play state
+  const setNativePlayback = async (on: boolean) => {
+    if (on === nativePlayback.value) return
+    if (on) {
+      const url = fileUrl.value
+      if (!url) return
+      const status = await playerLoad(url)
+      unlistenPlayer = await listen<PlayerPosition>('player-position', (event) => {
+        currentTime.value = event.payload.position
+        if (!event.payload.playing) isPlaying.value = false
+      })
+      unlistenPlayerOutput = await listen<PlayerOutputFailure>('player-output', (event) => {
+        console.warn('player output failed', event.payload)
+      })
+      nativePlayback.value = true
+      setStreamUrl(null)
+      setVocalUrl(null)
+      duration.value = status.duration
+      await setVocalVolume(vocalVolume.value)
+      await setKeyShift(transpose.value)
+      await setTempo(playbackRate.value)
+      await playerSeek(currentTime.value)
+      if (isPlaying.value) await playerPlay()
+    } else {
+      nativePlayback.value = false
+      unlistenPlayer?.()
+      unlistenPlayer = null
+      unlistenPlayerOutput?.()
+      unlistenPlayerOutput = null
+      await playerPause().catch(e => console.warn('player_pause failed', e))
+      if (fileUrl.value) await loadAudio(fileUrl.value)
+    }
+  }
+  watch(vocalVolume, (v) => {
+    if (nativePlayback.value) setVocalVolume(v).catch(e => console.warn('set_vocal_volume failed', e))
+  })
+  // the backing follows `transpose`, which moves the MIDI reference too
+  watch(transpose, (semitones) => {
+    if (nativePlayback.value) setKeyShift(semitones).catch(e => console.warn('set_key_shift failed', e))
+  })
+  // practice speed (and the speed trainer) slow the backend down the same way
+  watch(playbackRate, (rate) => {
+    if (nativePlayback.value) setTempo(rate).catch(e => console.warn('set_tempo failed', e))
+  })
+  watch(isPlaying, (playing) => {
+    if (!nativePlayback.value) return
+    (playing ? playerPlay() : playerPause()).catch(e => console.warn('native playback failed', e))
+  })
+
+  // Render the guide when it is switched on or its song, voice or key changes
+  const loadGuide = async () => {
+    const url = fileUrl.value
+    const old = guideUrl.value
+    let next: string | null = null
+    if (guideVolume.value > 0 && url) {
+      next = await loadGuideContent(url, guideVoice.value, transpose.value).catch(e => {
+        console.warn('render_guide_track failed', e)
+        return null
+      })
+      // the song changed while rendering
+      if (url !== fileUrl.value) {
+        if (next) URL.revokeObjectURL(next)
+        return
+      }
+    }
+    guideUrl.value = next
+    if (old) nextTick(() => URL.revokeObjectURL(old))
+  }
+  watch([() => guideVolume.value > 0, guideVoice, fileUrl, transpose], loadGuide)
+
+  // (Re)start the melody on the MIDI output at the current position, or stop it when paused
+  const syncMidiOutput = async () => {
+    const port = midiOutputPort.value
+    if (!port || !isPlaying.value || !fileUrl.value) {
+      if (midiOutputSync) {
+        midiOutputSync = null
+        await invoke('stop_midi_output').catch(e => console.warn('stop_midi_output failed', e))
+      }
+      return
+    }
+    midiOutputSync = { position: currentTime.value, wall: performance.now(), rate: playbackRate.value }
+    await invoke('start_midi_output', { id: port, path: fileUrl.value, position: currentTime.value, rate: playbackRate.value, transpose: transpose.value })
+      .catch(e => console.warn('start_midi_output failed', e))
+  }
+  watch([midiOutputPort, isPlaying, playbackRate, transpose, fileUrl], syncMidiOutput)
+  watch(currentTime, (t) => {
+    if (!midiOutputSync) return
+    const expected = midiOutputSync.position + (performance.now() - midiOutputSync.wall) / 1000 * midiOutputSync.rate
+    if (Math.abs(t - expected) > MIDI_OUTPUT_DRIFT) syncMidiOutput()
+  })
+
+  // Score the keys played on MIDI input `port` instead of the microphone: the highest held key
+  // goes into the pitch history every 100ms, like a detected pitch
+  const startInstrumentMode = async (port: string) => {
+    stopPitchPolling()
+    await stopInstrumentMode()
+    await invoke('start_midi_input', { id: port })
+    midiInputPort.value = port
+    unlistenMidiInput = await listen<MidiInputEvent>('midi-input', (event) => {
+      if (event.payload.kind === 'note_on') heldKeys.add(event.payload.note)
+      else heldKeys.delete(event.payload.note)
+    })
+    instrumentTimer = setInterval(() => {
+      if (!isPlaying.value || heldKeys.size === 0) return
+      const midi = Math.max(...heldKeys)
+      const note = KEY_NAMES[midi % 12] + (Math.floor(midi / 12) - 1)
+      pitchHistory.value.push({ pitch: 440 * Math.pow(2, (midi - 69) / 12), midi, note, time: currentTime.value })
+    }, 100)
+  }
+
+  const stopInstrumentMode = async () => {
+    if (instrumentTimer) clearInterval(instrumentTimer)
+    instrumentTimer = null
+    unlistenMidiInput?.()
+    unlistenMidiInput = null
+    heldKeys.clear()
+    if (midiInputPort.value !== null) {
+      midiInputPort.value = null
+      await invoke('stop_midi_input').catch(e => console.warn('stop_midi_input failed', e))
+    }
+  }
+
   return {
     playList,
     title,
@@ -254,6 +1184,11 @@ export const useAppState = defineStore('app', () => {
     duration,
     currentTime,
     volume,
+    gain,
+    playbackRate,
+    speedTrainer,
+    startSpeedTrainer,
+    stopSpeedTrainer,
     metadata,
     notes,
     lyrics,
@@ -266,7 +1201,48 @@ export const useAppState = defineStore('app', () => {
     activeRightTime,
     setTitle,
     loadPlaylist,
+    hideSong,
+    deleteSong,
+    restoreSong,
+    midiMeta,
+    midiWarnings,
+    f0Curve,
+    waveform,
+    background,
+    setSongBackground,
+    kiosk,
+    loadKioskMode,
+    setKioskMode,
+    consents,
+    loadConsents,
+    setConsent,
+    requestConsent,
+    organizeLibrary,
+    exportPracticeMix,
+    exportLyricFrames,
+    midiStats,
+    loadMidiStats,
+    pickRandom,
+    markSung,
+    libraryStatus,
+    watchLibraryStatus,
+    exportSetlist,
+    scoringProfile,
+    exportTarget,
+    listExportTargets,
+    loadScoringProfile,
+    selectScoringProfile,
+    micTurns,
+    loadMicTurns,
+    countdownCues,
+    countdown,
+    loadCountdownCues,
     loadMetadata,
+    fetchLyrics,
+    loadRomanization,
+    reloadLyrics,
+    validateLyrics,
+    getPerfStats,
     loadAudio,
     loadMidi,
     togglePlay,
@@ -276,10 +1252,36 @@ export const useAppState = defineStore('app', () => {
     setLyricLineDelta,
     setLyricDelta,
     clearLyricTimeDelta,
+    saveLyrics,
+    saveMidi,
+    queue,
+    transpose,
+    saveSession,
+    restoreSession,
+    enqueue,
+    playNext,
+    attract,
+    attractActive,
+    loadAttractMode,
+    setAttractMode,
+    playAttract,
+    playbackVolume,
     switchToSong,
     // realtime pitch controls
     pitchHistory,
     startPitchPolling,
     stopPitchPolling,
+    startMicPitch,
+    stopMicPitch,
+    midiInputPort,
+    midiOutputPort,
+    guideUrl,
+    guideVolume,
+    guideVoice,
+    nativePlayback,
+    setNativePlayback,
+    vocalVolume,
+    startInstrumentMode,
+    stopInstrumentMode,
   }
 })
@@ -0,0 +1,61 @@
+import { pitchData } from './api'
+import { MidiNote, scoreNotes, ScoreOptions } from './pitch'
+
+export type SpeedTrainerOptions = {
+  // playback rate of the first pass
+  initialRate?: number
+  // rate increase after each successful pass
+  step?: number
+  // rate at which the program is complete
+  targetRate?: number
+  // minimum section score (0..1) for a pass to count as successful
+  passScore?: number
+}
+
+export type SpeedTrainer = {
+  // looped section, in song seconds
+  start: number
+  end: number
+  rate: number
+  step: number
+  targetRate: number
+  passScore: number
+  // score of every finished pass at the rate it was sung
+  passes: { rate: number; score: number }[]
+  done: boolean
+}
+
+export function createSpeedTrainer(start: number, end: number, opts: SpeedTrainerOptions = {}): SpeedTrainer {
+  const targetRate = opts.targetRate ?? 1
+  return {
+    start,
+    end: Math.max(end, start + 0.5),
+    rate: Math.min(opts.initialRate ?? 0.7, targetRate),
+    step: opts.step ?? 0.05,
+    targetRate,
+    passScore: opts.passScore ?? 0.7,
+    passes: [],
+    done: false,
+  }
+}
+
+/**
+ * Score one pass over the trainer section and return the updated trainer.
+ * Only notes starting inside the section count. A successful pass raises the rate by `step`
+ * (capped at `targetRate`); a successful pass at the target rate completes the program.
+ */
+export function completePass(trainer: SpeedTrainer, notes: MidiNote[] | null, pitch_history: pitchData[] | null, opts: ScoreOptions = {}): SpeedTrainer {
+  const sectionNotes = (notes || []).filter(n => n.start >= trainer.start && n.start < trainer.end)
+  const samples = (pitch_history || []).filter(p => p.time >= trainer.start && p.time <= trainer.end)
+  const score = sectionNotes.length > 0 ? scoreNotes(sectionNotes, samples, opts).overall : 0
+
+  const passed = score >= trainer.passScore
+  const done = passed && trainer.rate >= trainer.targetRate
+  const rate = passed ? Math.min(trainer.targetRate, Math.round((trainer.rate + trainer.step) * 100) / 100) : trainer.rate
+  return {
+    ...trainer,
+    rate,
+    passes: [...trainer.passes, { rate: trainer.rate, score }],
+    done,
+  }
+}
@@ -0,0 +1,76 @@
+// Run the song change steps planned by the backend (`plan_transition`)
+
+// matches Rust `TransitionStep`
+export type TransitionStep =
+  | { kind: 'fade_out', seconds: number }
+  | { kind: 'announce', text: string }
+  | { kind: 'count_in', beats: number, bpm: number }
+  | { kind: 'start', path: string }
+
+export type TransitionHooks = {
+  getVolume: () => number
+  setVolume: (v: number) => void
+  stop: () => void
+  start: (path: string) => void
+}
+
+// longest wait for speech, in case the engine never reports the end
+const ANNOUNCE_TIMEOUT_MS = 15_000
+
+const sleep = (ms: number) => new Promise(resolve => setTimeout(resolve, ms))
+
+// Lower the volume to 0 over `seconds`, stop, then restore the volume for the next song
+async function fadeOut(seconds: number, hooks: TransitionHooks) {
+  const from = hooks.getVolume()
+  const steps = Math.max(1, Math.round(seconds * 20))
+  for (let i = 1; i <= steps; i++) {
+    hooks.setVolume(from * (1 - i / steps))
+    await sleep(seconds * 1000 / steps)
+  }
+  hooks.stop()
+  hooks.setVolume(from)
+}
+
+// Read `text` out; resolves when done, or right away without speech synthesis
+function announce(text: string) {
+  if (!text || typeof speechSynthesis === 'undefined') return Promise.resolve()
+  return new Promise<void>(resolve => {
+    const utterance = new SpeechSynthesisUtterance(text)
+    const timer = setTimeout(resolve, ANNOUNCE_TIMEOUT_MS)
+    utterance.onend = utterance.onerror = () => {
+      clearTimeout(timer)
+      resolve()
+    }
+    speechSynthesis.speak(utterance)
+  })
+}
+
+// Short clicks on every beat, the first one higher
+async function countIn(beats: number, bpm: number) {
+  const ctx = new AudioContext()
+  const beat = 60 / bpm
+  const t0 = ctx.currentTime + 0.05
+  for (let i = 0; i < beats; i++) {
+    const osc = ctx.createOscillator()
+    const gain = ctx.createGain()
+    osc.frequency.value = i === 0 ? 1760 : 880
+    gain.gain.setValueAtTime(0.5, t0 + i * beat)
+    gain.gain.exponentialRampToValueAtTime(0.001, t0 + i * beat + 0.08)
+    osc.connect(gain).connect(ctx.destination)
+    osc.start(t0 + i * beat)
+    osc.stop(t0 + i * beat + 0.1)
+  }
+  await sleep((0.05 + beats * beat) * 1000)
+  await ctx.close()
+}
+
+export async function runTransition(steps: TransitionStep[], hooks: TransitionHooks) {
+  for (const step of steps) {
+    switch (step.kind) {
+      case 'fade_out': await fadeOut(step.seconds, hooks); break
+      case 'announce': await announce(step.text); break
+      case 'count_in': await countIn(step.beats, step.bpm); break
+      case 'start': hooks.start(step.path); break
+    }
+  }
+}
@@ -0,0 +1,40 @@
+// Seek-bar waveform peaks from the backend (`get_waveform`)
+
+// matches Rust `Waveform`
+export type Waveform = {
+  // seconds of audio covered
+  duration: number
+  // min, max per bucket, interleaved
+  peaks: Float32Array
+}
+
+const HEADER_LEN = 8
+
+// Parse the bytes of `Waveform::to_bytes`
+export function parseWaveform(data: ArrayBuffer): Waveform {
+  const view = new DataView(data)
+  const buckets = view.getUint32(0, true)
+  const duration = view.getFloat32(4, true)
+  return { duration, peaks: new Float32Array(data.slice(HEADER_LEN, HEADER_LEN + buckets * 8)) }
+}
+
+// Draw the peaks over the whole canvas as one vertical bar per bucket, centered on the middle line
+export function drawWaveform(canvas: HTMLCanvasElement | null, waveform: Waveform | null, color = 'rgba(255, 255, 255, 0.5)') {
+  if (!canvas) return
+  const ctx = canvas.getContext('2d')
+  if (!ctx) return
+  canvas.width = canvas.clientWidth * devicePixelRatio
+  canvas.height = canvas.clientHeight * devicePixelRatio
+  ctx.clearRect(0, 0, canvas.width, canvas.height)
+  const buckets = (waveform?.peaks.length ?? 0) / 2
+  if (!waveform || !buckets) return
+
+  const mid = canvas.height / 2
+  const width = canvas.width / buckets
+  ctx.fillStyle = color
+  for (let b = 0; b < buckets; b++) {
+    const lo = waveform.peaks[b * 2]
+    const hi = waveform.peaks[b * 2 + 1]
+    ctx.fillRect(b * width, mid - hi * mid, Math.max(width, 1), Math.max((hi - lo) * mid, 1))
+  }
+}
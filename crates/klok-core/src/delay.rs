@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+
+/// A fixed delay on interleaved samples: what goes in comes out `frames` frames later, after
+/// silence (feed it silence to play out the rest). Lines up outputs whose sound reaches the
+/// listener at different times.
+#[derive(Clone, Debug)]
+pub struct Delay {
+  queue: VecDeque<f32>,
+}
+
+impl Delay {
+  pub fn new(frames: usize, channels: u16) -> Delay {
+    Delay { queue: vec![0.0; frames * channels.max(1) as usize].into() }
+  }
+
+  /// Delay `samples` in place.
+  pub fn process(&mut self, samples: &mut [f32]) {
+    if self.queue.is_empty() {
+      return;
+    }
+    for sample in samples {
+      self.queue.push_back(*sample);
+      *sample = self.queue.pop_front().unwrap_or_default();
+    }
+  }
+}
+
+#[test]
+pub fn test_delay() {
+  // two stereo frames late
+  let mut delay = Delay::new(2, 2);
+  let mut first = [1.0, -1.0, 2.0, -2.0, 3.0, -3.0];
+  delay.process(&mut first);
+  assert_eq!(first, [0.0, 0.0, 0.0, 0.0, 1.0, -1.0]);
+  let mut second = [4.0, -4.0];
+  delay.process(&mut second);
+  assert_eq!(second, [2.0, -2.0]);
+
+  let mut none = Delay::new(0, 2);
+  let mut samples = [1.0, 2.0];
+  none.process(&mut samples);
+  assert_eq!(samples, [1.0, 2.0]);
+}
@@ -0,0 +1,118 @@
+use std::f32::consts::PI;
+
+// the voice is split at these frequencies into bands suppressed separately
+const CROSSOVERS: [f32; 3] = [300.0, 1500.0, 5000.0];
+// seconds over which a band's level is measured
+const LEVEL_TIME: f32 = 0.01;
+// seconds per window of the minimum tracking: a band's noise floor is its lowest level over the
+// current and the previous window, long enough that a held note isn't taken for noise
+const NOISE_WINDOW: f32 = 1.5;
+// noise is assumed this much louder than its floor (power), so it is suppressed fully
+const OVERSUBTRACTION: f32 = 2.0;
+// gain of a band taken for noise, -20 dB, less than silence so the voice doesn't sound gated
+const MIN_GAIN: f32 = 0.1;
+// seconds for a band to open and to close again
+const ATTACK: f32 = 0.005;
+const RELEASE: f32 = 0.05;
+
+// one-pole smoothing coefficient for a time constant of `seconds`
+fn smoothing(seconds: f32, sample_rate: u32) -> f32 {
+  1.0 - (-1.0 / (seconds * sample_rate.max(1) as f32)).exp()
+}
+
+#[derive(Debug, Clone)]
+struct Band {
+  level: f32,
+  // lowest level of the current and the previous window, MAX when unknown
+  min: f32,
+  previous_min: f32,
+  gain: f32,
+}
+
+/// Noise suppressor for a mono microphone stream: splits it into a few bands, tracks the steady
+/// noise floor of each (fans, hum, hiss) and turns a band down while it is near its floor, so
+/// pauses go quiet and the voice passes. Keeps its state between calls, so a stream can be
+/// processed chunk by chunk.
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+  // one-pole low-pass coefficients and states at each crossover
+  lowpass: [f32; 3],
+  lowpass_state: [f32; 3],
+  bands: [Band; 4],
+  level: f32,
+  window: usize,
+  // samples processed, levels before `settle` are not taken for the floor
+  processed: usize,
+  settle: usize,
+  attack: f32,
+  release: f32,
+}
+
+impl Denoiser {
+  pub fn new(sample_rate: u32) -> Denoiser {
+    let lowpass = CROSSOVERS.map(|hz| 1.0 - (-2.0 * PI * hz / sample_rate.max(1) as f32).exp());
+    Denoiser {
+      lowpass,
+      lowpass_state: [0.0; 3],
+      bands: std::array::from_fn(|_| Band { level: 0.0, min: f32::MAX, previous_min: f32::MAX, gain: 1.0 }),
+      level: smoothing(LEVEL_TIME, sample_rate),
+      window: ((NOISE_WINDOW * sample_rate as f32) as usize).max(1),
+      processed: 0,
+      settle: (5.0 * LEVEL_TIME * sample_rate as f32) as usize,
+      attack: smoothing(ATTACK, sample_rate),
+      release: smoothing(RELEASE, sample_rate),
+    }
+  }
+
+  /// Suppress the noise of mono `samples` in place.
+  pub fn process(&mut self, samples: &mut [f32]) {
+    for s in samples {
+      if self.processed.is_multiple_of(self.window) {
+        for band in &mut self.bands {
+          band.previous_min = std::mem::replace(&mut band.min, f32::MAX);
+        }
+      }
+      let settled = self.processed >= self.settle;
+      self.processed += 1;
+      for (state, coefficient) in self.lowpass_state.iter_mut().zip(self.lowpass) {
+        *state += coefficient * (*s - *state);
+      }
+      // differences of the low-passes, summing back to the input
+      let [a, b, c] = self.lowpass_state;
+      let split = [a, b - a, c - b, *s - c];
+      let mut out = 0.0;
+      for (band, x) in self.bands.iter_mut().zip(split) {
+        band.level += self.level * (x * x - band.level);
+        if settled {
+          band.min = band.min.min(band.level);
+        }
+        let floor = band.min.min(band.previous_min);
+        let target = if floor == f32::MAX { 1.0 } else { (1.0 - OVERSUBTRACTION * floor / band.level.max(1e-12)).max(0.0).sqrt().max(MIN_GAIN) };
+        band.gain += if target > band.gain { self.attack } else { self.release } * (target - band.gain);
+        out += band.gain * x;
+      }
+      *s = out;
+    }
+  }
+}
+
+#[test]
+pub fn test_denoise() {
+  let rate = 16000;
+  let energy = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>();
+  let mut seed = 1u32;
+  let mut noise = || {
+    seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+    0.01 * ((seed >> 8) as f32 / (1 << 23) as f32 - 1.0)
+  };
+  // two seconds of fan noise, then a sung tone over it
+  let hiss: Vec<f32> = (0..2 * rate).map(|_| noise()).collect();
+  let voice: Vec<f32> = (0..rate).map(|i| 0.3 * (2.0 * PI * 440.0 * i as f32 / rate as f32).sin() + noise()).collect();
+  let mut denoiser = Denoiser::new(rate as u32);
+  let (mut quiet, mut sung) = (hiss.clone(), voice.clone());
+  denoiser.process(&mut quiet);
+  denoiser.process(&mut sung);
+  let second = rate as usize;
+  assert!(energy(&quiet[second..]) < 0.1 * energy(&hiss[second..]), "noise kept {}", energy(&quiet[second..]) / energy(&hiss[second..]));
+  assert!(energy(&sung[second / 10..]) > 0.8 * energy(&voice[second / 10..]));
+}
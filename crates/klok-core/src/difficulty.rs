@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::midi::Note;
+use crate::phrases::{estimate_tempo, phrase_gaps};
+
+/// Sing-along difficulty of a song, computed from its vocal MIDI by `rate_difficulty`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SongDifficulty {
+  /// 0 (easy) to 10 (hard)
+  pub score: f64,
+  /// semitones between the 5th and 95th percentile pitch
+  pub range: i32,
+  pub notes_per_second: f64,
+  /// mean interval (semitones) between consecutive notes in a phrase
+  pub mean_jump: f64,
+  pub tempo: Option<f64>,
+  /// modification time (seconds since the Unix epoch) of the MIDI it was computed from
+  pub midi_modified: u64,
+}
+
+// silences at least this long (seconds) split phrases; they don't count as sung time
+const PHRASE_GAP: f64 = 1.0;
+
+// (easy, hard) bounds of each measure, mapped linearly onto 0..1
+const RANGE_BOUNDS: (f64, f64) = (8.0, 24.0);
+const DENSITY_BOUNDS: (f64, f64) = (1.5, 5.0);
+const JUMP_BOUNDS: (f64, f64) = (1.5, 5.0);
+const TEMPO_BOUNDS: (f64, f64) = (70.0, 160.0);
+
+fn scaled(value: f64, (easy, hard): (f64, f64)) -> f64 {
+  ((value - easy) / (hard - easy)).clamp(0.0, 1.0)
+}
+
+/// Rate how hard a vocal line is to sing along to, from 0 (easy) to 10 (hard): pitch range
+/// (5th to 95th percentile, so stray notes don't count), notes per second of sung time, mean
+/// interval between consecutive notes within a phrase and tempo. `None` without notes.
+pub fn rate_difficulty(notes: &[Note], midi_modified: u64) -> Option<SongDifficulty> {
+  if notes.is_empty() {
+    return None;
+  }
+  let mut pitches: Vec<i32> = notes.iter().map(|n| n.note).collect();
+  pitches.sort_unstable();
+  let percentile = |p: f64| pitches[((pitches.len() - 1) as f64 * p).round() as usize];
+  let range = percentile(0.95) - percentile(0.05);
+
+  let mut sorted: Vec<&Note> = notes.iter().collect();
+  sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+  let first = sorted[0].start;
+  let last = sorted.iter().map(|n| n.start + n.duration).fold(first, f64::max);
+  let gaps = phrase_gaps(notes, PHRASE_GAP);
+  let sung_time = (last - first - gaps.iter().map(|g| g.1 - g.0).sum::<f64>()).max(1.0);
+  let notes_per_second = notes.len() as f64 / sung_time;
+
+  // intervals that cross a phrase gap are a fresh start, not a jump
+  let jumps: Vec<f64> = sorted
+    .windows(2)
+    .filter(|w| !gaps.iter().any(|g| w[0].start < g.1 && w[1].start >= g.1))
+    .map(|w| (w[1].note - w[0].note).abs() as f64)
+    .collect();
+  let mean_jump = if jumps.is_empty() { 0.0 } else { jumps.iter().sum::<f64>() / jumps.len() as f64 };
+  let tempo = estimate_tempo(notes);
+
+  let score = 0.35 * scaled(range as f64, RANGE_BOUNDS)
+    + 0.3 * scaled(notes_per_second, DENSITY_BOUNDS)
+    + 0.25 * scaled(mean_jump, JUMP_BOUNDS)
+    + 0.1 * tempo.map_or(0.0, |t| scaled(t, TEMPO_BOUNDS));
+  Some(SongDifficulty { score: (score * 100.0).round() / 10.0, range, notes_per_second, mean_jump, tempo, midi_modified })
+}
+
+// width (seconds) of the windows `MidiStats::density` counts notes in
+const DENSITY_WINDOW: f64 = 10.0;
+
+/// Note statistics of a vocal line, shown in the library next to its difficulty.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct MidiStats {
+  pub note_count: usize,
+  /// lowest and highest MIDI note
+  pub lowest: i32,
+  pub highest: i32,
+  /// mean note duration in seconds
+  pub mean_duration: f64,
+  /// `(start, end)` of the longest run of notes without a breath, in seconds
+  pub longest_phrase: (f64, f64),
+  /// notes per second starting in each 10 second window from the song start
+  pub density: Vec<f64>,
+}
+
+/// Statistics of `notes`, see [`MidiStats`]. Phrases are split at silences of `PHRASE_GAP`, as for
+/// `rate_difficulty`. `None` without notes.
+pub fn note_stats(notes: &[Note]) -> Option<MidiStats> {
+  let first = notes.iter().map(|n| n.start).reduce(f64::min)?;
+  let last = notes.iter().map(|n| n.start + n.duration).fold(first, f64::max);
+
+  // phrases run from the end of one gap to the start of the next
+  let gaps = phrase_gaps(notes, PHRASE_GAP);
+  let starts = std::iter::once(first).chain(gaps.iter().map(|g| g.1));
+  let ends = gaps.iter().map(|g| g.0).chain(std::iter::once(last));
+  let longest_phrase = starts.zip(ends).fold((first, first), |best, p| if p.1 - p.0 > best.1 - best.0 { p } else { best });
+
+  let mut counts = vec![0usize; (last / DENSITY_WINDOW).floor() as usize + 1];
+  for n in notes {
+    counts[(n.start.max(0.0) / DENSITY_WINDOW) as usize] += 1;
+  }
+  Some(MidiStats {
+    note_count: notes.len(),
+    lowest: notes.iter().map(|n| n.note).min()?,
+    highest: notes.iter().map(|n| n.note).max()?,
+    mean_duration: notes.iter().map(|n| n.duration).sum::<f64>() / notes.len() as f64,
+    longest_phrase,
+    density: counts.iter().map(|&c| c as f64 / DENSITY_WINDOW).collect(),
+  })
+}
+
+#[test]
+pub fn test_rate_difficulty() {
+  let note = |note: i32, start: f64, duration: f64| Note { note, start, duration, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() };
+  // a slow nursery tune: stepwise, within a sixth
+  let easy: Vec<Note> = [60, 62, 64, 60, 64, 65, 67, 67, 65, 64, 62, 60].iter().enumerate().map(|(i, &n)| note(n, i as f64 * 0.8, 0.7)).collect();
+  // fast leaps over two octaves
+  let hard: Vec<Note> = (0..40).map(|i| note(if i % 2 == 0 { 52 } else { 72 + i % 5 }, i as f64 * 0.2, 0.15)).collect();
+
+  let easy = rate_difficulty(&easy, 0).expect("easy rating");
+  let hard = rate_difficulty(&hard, 0).expect("hard rating");
+  assert_eq!(easy.range, 7);
+  assert!(easy.score < 2.0, "easy score {}", easy.score);
+  assert!(hard.score > 7.0, "hard score {}", hard.score);
+  assert!(hard.mean_jump > 15.0);
+  assert_eq!(rate_difficulty(&[], 0), None);
+}
+
+#[test]
+pub fn test_note_stats() {
+  let note = |note: i32, start: f64, duration: f64| Note { note, start, duration, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() };
+  // a 2 s phrase, a breath, then a 4 s phrase crossing the 10 s window
+  let mut notes: Vec<Note> = (0..4).map(|i| note(60 + i, 1.0 + i as f64 * 0.5, 0.5)).collect();
+  notes.extend((0..8).map(|i| note(55 + i % 3, 8.0 + i as f64 * 0.5, 0.25)));
+
+  let stats = note_stats(&notes).expect("stats");
+  assert_eq!((stats.note_count, stats.lowest, stats.highest), (12, 55, 63));
+  assert!((stats.mean_duration - 4.0 / 12.0).abs() < 1e-9);
+  assert_eq!(stats.longest_phrase, (8.0, 11.75));
+  assert_eq!(stats.density, vec![0.8, 0.4]);
+  assert_eq!(note_stats(&[]), None);
+}
@@ -0,0 +1,40 @@
+use std::borrow::Cow;
+
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+
+/// Decode a text file of unknown encoding. A byte order mark wins, valid UTF-8 is used as is, and
+/// anything else (GBK, Big5, Shift-JIS, ...) is guessed from the content and transcoded.
+pub fn decode_text(content: &[u8]) -> Cow<'_, str> {
+  if let Some((encoding, bom_len)) = Encoding::for_bom(content) {
+    return encoding.decode_without_bom_handling(&content[bom_len..]).0;
+  }
+  if let Ok(text) = std::str::from_utf8(content) {
+    return Cow::Borrowed(text);
+  }
+  let mut detector = EncodingDetector::new();
+  detector.feed(content, true);
+  let encoding = detector.guess(None, true);
+  let (text, had_errors) = encoding.decode_without_bom_handling(content);
+  if had_errors {
+    warn!(encoding = encoding.name(), "lyrics contain bytes invalid in the detected encoding");
+  } else {
+    debug!(encoding = encoding.name(), "transcoded lyrics to UTF-8");
+  }
+  text
+}
+
+#[test]
+pub fn test_decode_text() {
+  let cases = [
+    (encoding_rs::GBK, "[00:01.00]我的一个道姑朋友\n[00:05.00]她说人间不值得\n"),
+    (encoding_rs::BIG5, "[00:01.00]我的一個道姑朋友\n[00:05.00]她說人間不值得\n"),
+    (encoding_rs::SHIFT_JIS, "[00:01.00]さくらさくら やよいの空は\n[00:05.00]見わたす限り かすみか雲か\n[00:09.00]匂いぞ出ずる いざやいざや 見にゆかん\n"),
+  ];
+  for (encoding, text) in cases {
+    let (bytes, _, _) = encoding.encode(text);
+    assert_eq!(decode_text(&bytes), text, "{}", encoding.name());
+  }
+  assert_eq!(decode_text(b"\xef\xbb\xbf[00:01.00]hi"), "[00:01.00]hi");
+  assert_eq!(decode_text("[00:01.00]héllo".as_bytes()), "[00:01.00]héllo");
+}
@@ -0,0 +1,71 @@
+use crate::midi::Note;
+
+/// seconds between samples of a reference curve unless asked otherwise (100 Hz)
+pub const DEFAULT_HOP: f64 = 0.01;
+
+/// Frequency in Hz of a (fractional) MIDI note, A4 = 69 = 440 Hz.
+pub fn note_hz(note: f64) -> f64 {
+  440.0 * 2f64.powf((note - 69.0) / 12.0)
+}
+
+/// The melody as a fundamental frequency curve sampled every `hop` seconds, to compare with the
+/// pitch detected from the microphone frame by frame.
+#[derive(Debug)]
+pub struct F0Curve {
+  pub hop: f32,
+  /// Hz at `i * hop` seconds, 0 where no note sounds
+  pub hz: Vec<f32>,
+}
+
+impl F0Curve {
+  /// Sample `notes` with their pitch bends. Where notes overlap the later-starting one is taken,
+  /// as the singer moves on to it.
+  pub fn new(notes: &[Note], hop: f64) -> Result<F0Curve, String> {
+    if !(hop.is_finite() && hop > 0.0) {
+      return Err(format!("invalid f0 hop: {}", hop));
+    }
+    let end = notes.iter().map(|n| n.start + n.duration).fold(0.0, f64::max);
+    let mut hz = vec![0.0f32; (end / hop).ceil() as usize];
+    let mut order: Vec<&Note> = notes.iter().filter(|n| n.duration > 0.0).collect();
+    order.sort_by(|a, b| a.start.total_cmp(&b.start));
+    for note in order {
+      let first = (note.start.max(0.0) / hop).ceil() as usize;
+      let last = (((note.start + note.duration) / hop).ceil() as usize).min(hz.len());
+      let mut bend = 0;
+      for (i, sample) in hz.iter_mut().enumerate().take(last).skip(first) {
+        let t = i as f64 * hop;
+        // bend points hold until the next one
+        while bend < note.bend.len() && note.bend[bend].0 <= t {
+          bend += 1;
+        }
+        let offset = if bend > 0 { note.bend[bend - 1].1 } else { 0.0 };
+        *sample = note_hz(note.note as f64 + offset) as f32;
+      }
+    }
+    Ok(F0Curve { hop: hop as f32, hz })
+  }
+
+  /// Little-endian layout for the frontend: hop (f32), sample count (u32), then the samples (f32).
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + self.hz.len() * 4);
+    out.extend_from_slice(&self.hop.to_le_bytes());
+    out.extend_from_slice(&(self.hz.len() as u32).to_le_bytes());
+    for hz in &self.hz {
+      out.extend_from_slice(&hz.to_le_bytes());
+    }
+    out
+  }
+}
+
+#[test]
+pub fn test_f0_curve() {
+  let notes = vec![
+    Note { note: 69, start: 0.0, duration: 0.05, velocity: 100.0, channel: 0, confidence: None, bend: vec![(0.02, 12.0)] },
+    Note { note: 57, start: 0.08, duration: 0.02, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() },
+  ];
+  let curve = F0Curve::new(&notes, 0.01).expect("f0 curve");
+  let hz: Vec<f32> = curve.hz.iter().map(|h| h.round()).collect();
+  assert_eq!(hz, vec![440.0, 440.0, 880.0, 880.0, 880.0, 0.0, 0.0, 0.0, 220.0, 220.0]);
+  assert_eq!(curve.to_bytes().len(), 8 + 10 * 4);
+  assert!(F0Curve::new(&notes, 0.0).is_err());
+}
@@ -0,0 +1,97 @@
+use crate::encoding::decode_text;
+use crate::lyrics::{LyricLine, LyricWord};
+use crate::midi::TempoMap;
+
+/// Parse the lyrics of a karaoke MIDI (`.kar`) file into lines with per-syllable timing. Lyric meta
+/// events are used when the file has any, otherwise Text events as written by Soft Karaoke, whose
+/// `@` header fields (title, language, ...) are skipped. A syllable starting with `/` or `\` (new
+/// line / new verse) or ending in a line break ends the current line.
+pub fn parse_kar(content: &[u8]) -> Result<Vec<LyricLine>, String> {
+  use midly::MetaMessage;
+
+  let smf = midly::Smf::parse(content).map_err(|e| format!("failed to parse midi: {}", e))?;
+  let tempo_map = TempoMap::new(&smf)?;
+
+  let mut lyrics: Vec<(u64, &[u8])> = Vec::new();
+  let mut texts: Vec<(u64, &[u8])> = Vec::new();
+  for track in &smf.tracks {
+    let mut abs: u64 = 0;
+    for ev in track {
+      abs = abs.wrapping_add(ev.delta.as_int() as u64);
+      match ev.kind {
+        midly::TrackEventKind::Meta(MetaMessage::Lyric(raw)) => lyrics.push((abs, raw)),
+        midly::TrackEventKind::Meta(MetaMessage::Text(raw)) if !raw.starts_with(b"@") => texts.push((abs, raw)),
+        _ => {}
+      }
+    }
+  }
+  let mut syllables = if lyrics.is_empty() { texts } else { lyrics };
+  syllables.sort_by_key(|(tick, _)| *tick);
+
+  // decode all syllables together, single syllables are too short to guess their encoding
+  let joined: Vec<u8> = syllables.iter().map(|(_, raw)| *raw).collect::<Vec<_>>().join(&0u8);
+  let decoded = decode_text(&joined);
+
+  let mut lines: Vec<LyricLine> = Vec::new();
+  let mut words: Vec<LyricWord> = Vec::new();
+  for ((tick, _), text) in syllables.iter().zip(decoded.split('\0')) {
+    let breaks_before = text.starts_with(['/', '\\']);
+    let breaks_after = text.ends_with(['\r', '\n']);
+    let text = text.trim_start_matches(['/', '\\']).trim_end_matches(['\r', '\n']);
+    if breaks_before {
+      finish_line(&mut words, &mut lines);
+    }
+    if !text.is_empty() {
+      words.push(LyricWord { time: tempo_map.seconds(*tick), text: text.to_string() });
+    }
+    if breaks_after {
+      finish_line(&mut words, &mut lines);
+    }
+  }
+  finish_line(&mut words, &mut lines);
+  Ok(lines)
+}
+
+// Turn the syllables collected so far into a line, unless they are all blank.
+fn finish_line(words: &mut Vec<LyricWord>, lines: &mut Vec<LyricLine>) {
+  let words = std::mem::take(words);
+  let text: String = words.iter().map(|w| w.text.as_str()).collect();
+  if let (Some(time), false) = (words.first().map(|w| w.time), text.trim().is_empty()) {
+    lines.push(LyricLine { time, text: text.trim().to_string(), words, ..Default::default() });
+  }
+}
+
+#[test]
+pub fn test_parse_kar() {
+  use midly::num::{u24, u28};
+  use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+  let meta = |delta: u32, message: MetaMessage<'static>| TrackEvent { delta: u28::new(delta), kind: TrackEventKind::Meta(message) };
+  // 480 ticks per quarter at 60 bpm: one second per 480 ticks
+  let conductor = vec![meta(0, MetaMessage::Tempo(u24::new(1_000_000)))];
+  let words = vec![
+    meta(0, MetaMessage::Text(b"@KMIDI KARAOKE FILE")),
+    meta(0, MetaMessage::Text(b"@TTwinkle")),
+    meta(480, MetaMessage::Text(b"\\Twin")),
+    meta(240, MetaMessage::Text(b"kle ")),
+    meta(240, MetaMessage::Text(b"twin")),
+    meta(240, MetaMessage::Text(b"kle")),
+    meta(720, MetaMessage::Text(b"/Lit")),
+    meta(240, MetaMessage::Text(b"tle star")),
+  ];
+  let smf = midly::Smf { header: midly::Header::new(midly::Format::Parallel, midly::Timing::Metrical(midly::num::u15::new(480))), tracks: vec![conductor, words] };
+  let mut bytes = Vec::new();
+  smf.write_std(&mut bytes).expect("failed to write midi");
+
+  let lines = parse_kar(&bytes).expect("failed to parse kar");
+  assert_eq!(lines.iter().map(|l| (l.time, l.text.as_str())).collect::<Vec<_>>(), vec![(1.0, "Twinkle twinkle"), (4.0, "Little star")]);
+  assert_eq!(lines[0].words.iter().map(|w| w.time).collect::<Vec<_>>(), vec![1.0, 1.5, 2.0, 2.5]);
+
+  // Lyric events win over Text events, and line breaks inside them end lines
+  let lyric_track = vec![meta(0, MetaMessage::Text(b"ignored")), meta(480, MetaMessage::Lyric(b"Hel")), meta(480, MetaMessage::Lyric(b"lo\r")), meta(480, MetaMessage::Lyric(b"world"))];
+  let smf = midly::Smf { header: midly::Header::new(midly::Format::SingleTrack, midly::Timing::Metrical(midly::num::u15::new(480))), tracks: vec![lyric_track] };
+  let mut bytes = Vec::new();
+  smf.write_std(&mut bytes).expect("failed to write midi");
+  let lines = parse_kar(&bytes).expect("failed to parse kar");
+  assert_eq!(lines.iter().map(|l| (l.time, l.text.as_str())).collect::<Vec<_>>(), vec![(0.5, "Hello"), (1.5, "world")]);
+}
@@ -0,0 +1,88 @@
+use std::io::Read;
+
+use crate::lyrics::{LyricLine, LyricWord};
+
+/// Kugou KRC files start with this magic, followed by the XOR'd zlib stream.
+const KRC_MAGIC: &[u8] = b"krc1";
+const KRC_KEY: [u8; 16] = [0x40, 0x47, 0x61, 0x77, 0x5e, 0x32, 0x74, 0x47, 0x51, 0x36, 0x31, 0x2d, 0xce, 0xd2, 0x6e, 0x69];
+
+/// Decode a raw `.krc` file (magic + XOR + zlib) into its UTF-8 text.
+pub fn decode_krc(content: &[u8]) -> Result<String, String> {
+  let body = content.strip_prefix(KRC_MAGIC).ok_or_else(|| "not a krc file (missing krc1 header)".to_string())?;
+  let unmasked: Vec<u8> = body.iter().enumerate().map(|(i, b)| b ^ KRC_KEY[i % KRC_KEY.len()]).collect();
+
+  let mut text = String::new();
+  flate2::read::ZlibDecoder::new(unmasked.as_slice())
+    .read_to_string(&mut text)
+    .map_err(|e| format!("failed to inflate krc: {}", e))?;
+  Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+// Parse `start,duration` millisecond pairs as used by `[..]` line and `<..>` word tags.
+fn parse_ms_pair(tag: &str) -> Option<(f64, f64)> {
+  let mut parts = tag.split(',');
+  let start = parts.next()?.trim().parse::<f64>().ok()?;
+  let duration = parts.next()?.trim().parse::<f64>().ok()?;
+  Some((start / 1000.0, duration / 1000.0))
+}
+
+/// Parse decoded KRC text into lyric lines. Lines look like
+/// `[line_start_ms,line_duration_ms]<word_offset_ms,word_duration_ms,0>word...`
+/// where word offsets are relative to the line start. Metadata tags are skipped.
+pub fn parse_krc(content: &str) -> Vec<LyricLine> {
+  let mut lyrics: Vec<LyricLine> = Vec::new();
+
+  for raw_line in content.lines() {
+    let line = raw_line.trim();
+    let Some(rest) = line.strip_prefix('[') else {
+      continue;
+    };
+    let Some(close) = rest.find(']') else {
+      continue;
+    };
+    let Some((start, duration)) = parse_ms_pair(&rest[..close]) else {
+      // [ti:..], [ar:..], [language:..] etc.
+      continue;
+    };
+
+    let mut text = String::new();
+    let mut words: Vec<LyricWord> = Vec::new();
+    let mut body = &rest[close + 1..];
+    while let Some(open) = body.find('<') {
+      text.push_str(&body[..open]);
+      let Some(end) = body[open..].find('>').map(|i| open + i) else {
+        break;
+      };
+      let next = body[end + 1..].find('<').map(|i| end + 1 + i).unwrap_or(body.len());
+      let word = &body[end + 1..next];
+      match parse_ms_pair(&body[open + 1..end]) {
+        Some((offset, _)) => {
+          if !word.is_empty() {
+            words.push(LyricWord { time: start + offset, text: word.to_string() });
+          }
+        }
+        None => text.push_str(&body[open..=end]),
+      }
+      text.push_str(word);
+      body = &body[next..];
+    }
+    text.push_str(body);
+
+    lyrics.push(LyricLine { time: start, end: Some(start + duration), text: text.trim().to_string(), words, ..Default::default() });
+  }
+
+  lyrics.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+  lyrics
+}
+
+#[test]
+pub fn test_parse_krc() {
+  let content = "[ti:Song]\n[language:e30=]\n[1000,1500]<0,300,0>Hel<300,200,0>lo<500,1000,0> world\n[3000,500]<0,500,0>end\n";
+
+  let lyrics = parse_krc(content);
+  assert_eq!(lyrics.len(), 2);
+  assert_eq!(lyrics[0].text, "Hello world");
+  assert_eq!(lyrics[0].words.len(), 3);
+  assert_eq!(lyrics[0].words[1].time, 1.3);
+  assert_eq!(lyrics[1].time, 3.0);
+}
@@ -0,0 +1,77 @@
+use crate::lyrics::LyricLine;
+
+// Common words of Latin-script languages, used when the text is mostly Latin letters.
+const STOPWORDS: [(&str, &[&str]); 6] = [
+  ("en", &["the", "and", "you", "i", "to", "a", "my", "me", "is", "it", "in", "love", "your", "of"]),
+  ("es", &["el", "la", "de", "que", "y", "en", "mi", "te", "tu", "no", "me", "amor", "los", "es"]),
+  ("fr", &["le", "la", "de", "et", "je", "tu", "les", "des", "est", "pas", "que", "moi", "un", "une"]),
+  ("de", &["der", "die", "und", "ich", "du", "das", "nicht", "ist", "mein", "dich", "mich", "ein", "zu", "wir"]),
+  ("pt", &["o", "a", "de", "que", "e", "eu", "não", "você", "meu", "um", "uma", "do", "da", "amor"]),
+  ("it", &["il", "di", "che", "e", "la", "io", "non", "sei", "mi", "ti", "un", "per", "amore", "sono"]),
+];
+
+#[derive(Default)]
+struct ScriptCounts {
+  han: usize,
+  kana: usize,
+  hangul: usize,
+  latin: usize,
+  cyrillic: usize,
+  thai: usize,
+  arabic: usize,
+}
+
+/// Guess the main language of some lyrics as an ISO 639-1 code (`zh`, `ja`, `ko`, `en`, ...),
+/// from the scripts used and, for Latin script, common words. Translations are ignored.
+pub fn detect_language(lines: &[LyricLine]) -> Option<String> {
+  let text: String = lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n");
+  detect_text_language(&text).map(str::to_string)
+}
+
+pub fn detect_text_language(text: &str) -> Option<&'static str> {
+  let mut counts = ScriptCounts::default();
+  for c in text.chars() {
+    match c as u32 {
+      0x3040..=0x30ff => counts.kana += 1,
+      0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xf900..=0xfaff => counts.han += 1,
+      0xac00..=0xd7af | 0x1100..=0x11ff => counts.hangul += 1,
+      0x0400..=0x04ff => counts.cyrillic += 1,
+      0x0e00..=0x0e7f => counts.thai += 1,
+      0x0600..=0x06ff => counts.arabic += 1,
+      _ if c.is_alphabetic() => counts.latin += 1,
+      _ => {}
+    }
+  }
+
+  // Japanese mixes kana with kanji, so a modest share of kana is enough
+  let cjk = counts.han + counts.kana;
+  if counts.kana > 0 && counts.kana * 5 >= cjk {
+    return Some("ja");
+  }
+  let scripts = [("zh", cjk), ("ko", counts.hangul), ("ru", counts.cyrillic), ("th", counts.thai), ("ar", counts.arabic), ("latin", counts.latin)];
+  let (script, count) = scripts.iter().copied().max_by_key(|(_, n)| *n)?;
+  if count == 0 {
+    return None;
+  }
+  if script != "latin" {
+    return Some(script);
+  }
+
+  let words: Vec<String> = text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).map(str::to_lowercase).collect();
+  STOPWORDS
+    .iter()
+    .map(|(lang, stop)| (*lang, words.iter().filter(|w| stop.contains(&w.as_str())).count()))
+    .max_by_key(|(_, hits)| *hits)
+    .filter(|(_, hits)| *hits > 0)
+    .map(|(lang, _)| lang)
+}
+
+#[test]
+pub fn test_detect_language() {
+  assert_eq!(detect_text_language("我的一个道姑朋友"), Some("zh"));
+  assert_eq!(detect_text_language("君の名前を呼んだ"), Some("ja"));
+  assert_eq!(detect_text_language("사랑해요"), Some("ko"));
+  assert_eq!(detect_text_language("I will always love you"), Some("en"));
+  assert_eq!(detect_text_language("No me puedo olvidar de tu amor"), Some("es"));
+  assert_eq!(detect_text_language("123 !!"), None);
+}
@@ -0,0 +1,35 @@
+//! Parsing and analysis behind klok: lyrics formats, MIDI, phrase and melody analysis, the
+//! guide-tone synth and WAV decoding. Nothing here depends on Tauri, so it can be tested on its
+//! own and built for other targets such as WASM.
+#[macro_use]
+extern crate tracing;
+
+pub mod delay;
+pub mod denoise;
+pub mod difficulty;
+pub mod encoding;
+pub mod f0;
+pub mod kar;
+pub mod karaoke;
+pub mod krc;
+pub mod language;
+pub mod live_midi;
+pub mod lrc;
+pub mod lyrics;
+pub mod melody;
+pub mod midi;
+pub mod mix;
+pub mod musicxml;
+pub mod note_events;
+pub mod pcm;
+pub mod phrases;
+pub mod pitch_shift;
+pub mod piano_roll;
+pub mod qrc;
+pub mod reverb;
+pub mod simd;
+pub mod simplify;
+pub mod stretch;
+pub mod synth;
+pub mod waveform;
+pub mod yin;
@@ -0,0 +1,87 @@
+use serde::Serialize;
+
+use crate::midi::Note;
+
+/// A key pressed or released on a connected MIDI instrument.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KeyEvent {
+  NoteOn { channel: u8, note: u8, velocity: u8 },
+  NoteOff { channel: u8, note: u8 },
+}
+
+impl KeyEvent {
+  /// The MIDI message, for sending to an output port.
+  pub fn to_bytes(self) -> [u8; 3] {
+    match self {
+      KeyEvent::NoteOn { channel, note, velocity } => [0x90 | (channel & 0x0f), note & 0x7f, velocity.clamp(1, 127)],
+      KeyEvent::NoteOff { channel, note } => [0x80 | (channel & 0x0f), note & 0x7f, 0],
+    }
+  }
+}
+
+/// "All notes off" (CC123) for `channel`, sent when output stops so nothing keeps sounding.
+pub fn all_notes_off(channel: u8) -> [u8; 3] {
+  [0xb0 | (channel & 0x0f), 123, 0]
+}
+
+/// Key events that play `notes` from `from` seconds on, at song times sorted with note-offs first
+/// on a tie. Notes still sounding at `from` start there.
+pub fn key_schedule(notes: &[Note], from: f64) -> Vec<(f64, KeyEvent)> {
+  let mut events = Vec::new();
+  for note in notes.iter().filter(|n| n.start + n.duration > from && n.duration > 0.0) {
+    let (channel, key) = (note.channel & 0x0f, note.note.clamp(0, 127) as u8);
+    events.push((note.start.max(from), KeyEvent::NoteOn { channel, note: key, velocity: note.velocity.round().clamp(1.0, 127.0) as u8 }));
+    events.push((note.start + note.duration, KeyEvent::NoteOff { channel, note: key }));
+  }
+  events.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| matches!(a.1, KeyEvent::NoteOn { .. }).cmp(&matches!(b.1, KeyEvent::NoteOn { .. }))));
+  events
+}
+
+/// Turns the raw bytes read from a MIDI input port into key events. Messages may be split across
+/// reads and use running status; everything but note on/off (clock, controllers, sysex) is skipped.
+#[derive(Debug, Default)]
+pub struct KeyStream {
+  stream: midly::stream::MidiStream,
+}
+
+impl KeyStream {
+  pub fn new() -> KeyStream {
+    KeyStream::default()
+  }
+
+  pub fn feed(&mut self, bytes: &[u8]) -> Vec<KeyEvent> {
+    let mut out = Vec::new();
+    self.stream.feed(bytes, |event| {
+      let midly::live::LiveEvent::Midi { channel, message } = event else {
+        return;
+      };
+      let channel = channel.as_int();
+      match message {
+        // velocity 0 note_on == note_off
+        midly::MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => out.push(KeyEvent::NoteOn { channel, note: key.as_int(), velocity: vel.as_int() }),
+        midly::MidiMessage::NoteOn { key, .. } | midly::MidiMessage::NoteOff { key, .. } => out.push(KeyEvent::NoteOff { channel, note: key.as_int() }),
+        _ => {}
+      }
+    });
+    out
+  }
+}
+
+#[test]
+pub fn test_key_stream() {
+  let mut stream = KeyStream::new();
+  // note on split across reads, a clock tick in between, then running status
+  assert_eq!(stream.feed(&[0x91, 60]), vec![]);
+  assert_eq!(stream.feed(&[0xf8, 100, 64, 90]), vec![KeyEvent::NoteOn { channel: 1, note: 60, velocity: 100 }, KeyEvent::NoteOn { channel: 1, note: 64, velocity: 90 }]);
+  assert_eq!(stream.feed(&[60, 0, 0xb1, 64, 127, 0x81, 64, 0]), vec![KeyEvent::NoteOff { channel: 1, note: 60 }, KeyEvent::NoteOff { channel: 1, note: 64 }]);
+
+  let note = |key: i32, start: f64, duration: f64| Note { note: key, start, duration, velocity: 100.0, channel: 1, confidence: None, bend: Vec::new() };
+  let schedule = key_schedule(&[note(60, 0.0, 1.0), note(62, 1.0, 1.0), note(64, 3.0, 1.0)], 0.5);
+  let times: Vec<f64> = schedule.iter().map(|e| e.0).collect();
+  assert_eq!(times, vec![0.5, 1.0, 1.0, 2.0, 3.0, 4.0]);
+  assert_eq!(schedule[1].1, KeyEvent::NoteOff { channel: 1, note: 60 });
+  // played back through an input stream, the bytes give the same events
+  let bytes: Vec<u8> = schedule.iter().flat_map(|e| e.1.to_bytes()).collect();
+  assert_eq!(KeyStream::new().feed(&bytes), schedule.iter().map(|e| e.1).collect::<Vec<_>>());
+}
@@ -0,0 +1,297 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::lyrics::{LinePart, LyricLine, LyricWord};
+
+/// Parse a `mm:ss.xx` timestamp (seconds may have decimals) into seconds.
+pub fn parse_timestamp(stamp: &str) -> Option<f64> {
+  let (mm, ss) = stamp.trim().split_once(':')?;
+  let mmv: f64 = mm.parse::<f64>().ok()?;
+  let ssv: f64 = ss.parse::<f64>().ok()?;
+  Some(mmv * 60.0 + ssv)
+}
+
+// Split Enhanced LRC text like `<00:01.00>Hello <00:01.50>world` into words.
+// Text before the first tag only goes into the line text.
+fn parse_words(text: &str) -> (String, Vec<LyricWord>) {
+  let mut plain = String::new();
+  let mut words: Vec<LyricWord> = Vec::new();
+  let mut current: Option<f64> = None;
+  let mut segment = String::new();
+  let mut rest = text;
+  while let Some(open) = rest.find('<') {
+    let Some(close) = rest[open..].find('>').map(|i| open + i) else {
+      break;
+    };
+    segment.push_str(&rest[..open]);
+    match parse_timestamp(&rest[open + 1..close]) {
+      Some(t) => {
+        push_word(&mut plain, &mut words, current, &segment);
+        segment.clear();
+        current = Some(t);
+      }
+      // not a timestamp, keep it as text
+      None => segment.push_str(&rest[open..=close]),
+    }
+    rest = &rest[close + 1..];
+  }
+  segment.push_str(rest);
+  push_word(&mut plain, &mut words, current, &segment);
+  (plain.trim().to_string(), words)
+}
+
+fn push_word(plain: &mut String, words: &mut Vec<LyricWord>, time: Option<f64>, segment: &str) {
+  plain.push_str(segment);
+  if let Some(time) = time {
+    // a trailing tag with no text only marks where the previous word ends
+    if !segment.trim().is_empty() {
+      words.push(LyricWord { time, text: segment.to_string() });
+    }
+  }
+}
+
+/// Parse LRC content into a vector of LyricLine. Handles multiple timestamps per line
+/// and Enhanced LRC inline `<mm:ss.xx>` word timestamps, and duet part markers.
+#[instrument(level = "debug", skip(content))]
+pub fn parse_lrc(content: &str) -> Vec<LyricLine> {
+  let mut lyrics: Vec<LyricLine> = Vec::new();
+  // current duet part, carried over until the next marker
+  let mut part: Option<LinePart> = None;
+
+  for raw_line in content.lines() {
+    let line = raw_line.trim();
+    if line.is_empty() {
+      continue;
+    }
+
+    // collect timestamps at start like [mm:ss.xx][mm:ss.xx]Text
+    // (metadata tags such as [ar:Artist] are skipped)
+    let mut times: Vec<f64> = Vec::new();
+    let mut rest = line;
+    while rest.starts_with('[') {
+      if let Some(idx) = rest.find(']') {
+        let stamp = &rest[1..idx];
+        if let Some(total) = parse_timestamp(stamp) {
+          times.push(total);
+        } else if let Some(tagged) = LinePart::from_tag(stamp) {
+          part = Some(tagged);
+        }
+        // advance rest past this timestamp
+        rest = &rest[idx + 1..];
+      } else {
+        break;
+      }
+    }
+
+    let rest = match LinePart::strip_prefix(rest) {
+      Some((marked, text)) => {
+        part = Some(marked);
+        text
+      }
+      None => rest,
+    };
+    let (text, words) = parse_words(rest);
+    let first = times.first().copied().unwrap_or(0.0);
+    for t in times {
+      // word times are absolute for the first timestamp; shift them for repeated lines
+      let words = words.iter().map(|w| LyricWord { time: w.time + (t - first), text: w.text.clone() }).collect();
+      lyrics.push(LyricLine { time: t, text: text.clone(), words, part, ..Default::default() });
+    }
+  }
+
+  lyrics.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+  lyrics
+}
+
+// Gaps shorter than this between a line's end and the next line don't get an empty break line.
+const BREAK_EPSILON: f64 = 0.05;
+
+// `[mm:ss.xx]` with centisecond precision
+fn format_timestamp(time: f64) -> String {
+  let cs = (time.max(0.0) * 100.0).round() as u64;
+  format!("{:02}:{:02}.{:02}", cs / 6000, cs / 100 % 60, cs % 100)
+}
+
+// Line text with Enhanced LRC `<mm:ss.xx>` word tags, or plain text when the words don't spell out the line.
+fn format_text(line: &LyricLine) -> String {
+  let joined: String = line.words.iter().map(|w| w.text.as_str()).collect();
+  let text = line.text.trim_end();
+  let Some(prefix) = text.strip_suffix(joined.trim_end()).filter(|_| !line.words.is_empty()) else {
+    return line.text.clone();
+  };
+  let mut out = prefix.to_string();
+  for word in &line.words {
+    out.push_str(&format!("<{}>{}", format_timestamp(word.time), word.text));
+  }
+  out.trim_end().to_string()
+}
+
+/// Serialize lyric lines as LRC. Translations are written as a second line with the same
+/// timestamp (bilingual LRC) and line end times as empty break lines, so `parse_lrc` reads them back.
+pub fn format_lrc(lines: &[LyricLine], tags: &[(&str, String)]) -> String {
+  let mut out = String::new();
+  for (key, value) in tags {
+    out.push_str(&format!("[{}:{}]\n", key, value));
+  }
+
+  let mut sorted: Vec<&LyricLine> = lines.iter().collect();
+  sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+  // duet markers are only written where the part changes
+  let mut part = None;
+  for (i, line) in sorted.iter().enumerate() {
+    let stamp = format_timestamp(line.time);
+    let marker = line.part.filter(|_| line.part != part).map_or("", |p| p.marker());
+    part = line.part.or(part);
+    out.push_str(&format!("[{}]{}{}\n", stamp, marker, format_text(line)));
+    if let Some(translation) = &line.translation {
+      out.push_str(&format!("[{}]{}\n", stamp, translation));
+    }
+    if let Some(end) = line.end {
+      let next = sorted.get(i + 1).map(|l| l.time).unwrap_or(f64::INFINITY);
+      if next - end > BREAK_EPSILON {
+        out.push_str(&format!("[{}]\n", format_timestamp(end)));
+      }
+    }
+  }
+  out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsWarningKind {
+  /// a line starts before the line above it
+  OutOfOrder,
+  /// same timestamp and text as an earlier line
+  DuplicateLine,
+  /// no `[offset:]` tag, so the file can't be shifted without rewriting every timestamp
+  MissingOffset,
+  /// a line starts after the end of the audio
+  BeyondDuration,
+}
+
+/// A problem found by `validate_lyrics`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LyricsWarning {
+  pub kind: LyricsWarningKind,
+  /// 1-based line in the file, absent for file-wide warnings
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub line: Option<usize>,
+  pub message: String,
+}
+
+/// Check LRC content for common problems of files collected from the internet. `duration` is the
+/// audio length in seconds, when known.
+pub fn lint_lrc(content: &str, duration: Option<f64>) -> Vec<LyricsWarning> {
+  let mut warnings = Vec::new();
+  let mut has_offset = false;
+  let mut last: Option<f64> = None;
+  let mut seen: HashSet<(u64, String)> = HashSet::new();
+
+  for (i, raw_line) in content.lines().enumerate() {
+    let number = Some(i + 1);
+    let mut times = Vec::new();
+    let mut rest = raw_line.trim();
+    while let Some(close) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+      let tag = &rest[1..close + 1];
+      match parse_timestamp(tag) {
+        Some(t) => times.push(t),
+        None => has_offset |= tag.trim().to_ascii_lowercase().starts_with("offset:"),
+      }
+      rest = &rest[close + 2..];
+    }
+    let text = rest.trim();
+
+    // only the first timestamp of a line is expected in order; repeats list later choruses
+    if let (Some(&first), Some(prev)) = (times.first(), last) {
+      if first < prev {
+        warnings.push(LyricsWarning { kind: LyricsWarningKind::OutOfOrder, line: number, message: format!("starts at {:.2}s, before the previous line at {:.2}s", first, prev) });
+      }
+    }
+    for &t in &times {
+      if !seen.insert((t.to_bits(), text.to_string())) {
+        warnings.push(LyricsWarning { kind: LyricsWarningKind::DuplicateLine, line: number, message: format!("\"{}\" at {:.2}s appears more than once", text, t) });
+      }
+      if let Some(duration) = duration.filter(|&d| t > d) {
+        warnings.push(LyricsWarning { kind: LyricsWarningKind::BeyondDuration, line: number, message: format!("starts at {:.2}s, after the audio ends at {:.2}s", t, duration) });
+      }
+    }
+    if let Some(&first) = times.first() {
+      last = Some(last.map_or(first, |prev: f64| prev.max(first)));
+    }
+  }
+
+  if !has_offset && last.is_some() {
+    warnings.push(LyricsWarning { kind: LyricsWarningKind::MissingOffset, line: None, message: "no [offset:] tag".to_string() });
+  }
+  warnings
+}
+
+#[test]
+pub fn test_parse_lrc_words() {
+  let content = "[ti:Song]\n[00:01.00]<00:01.00>Hello <00:01.50>world<00:02.00>\n[00:03.00][00:10.00]plain line\n";
+
+  let lyrics = parse_lrc(content);
+  assert_eq!(lyrics.len(), 3);
+  assert_eq!(lyrics[0].text, "Hello world");
+  assert_eq!(lyrics[0].words.len(), 2);
+  assert_eq!(lyrics[0].words[1].time, 1.5);
+  assert_eq!(lyrics[0].words[1].text, "world");
+  assert!(lyrics[1].words.is_empty());
+  assert_eq!(lyrics[2].time, 10.0);
+  assert!(lyrics.iter().all(|l| l.part.is_none()));
+
+  let duet = parse_lrc("[00:01.00]M: Hello\n[00:02.00]again\n[00:03.00][2]<00:03.00>world\n[00:04.00]合：一起\n[00:05.00]Dear: no marker\n");
+  let parts: Vec<Option<LinePart>> = duet.iter().map(|l| l.part).collect();
+  assert_eq!(parts, vec![Some(LinePart::P1), Some(LinePart::P1), Some(LinePart::P2), Some(LinePart::Both), Some(LinePart::Both)]);
+  assert_eq!(duet[0].text, "Hello");
+  assert_eq!(duet[2].words[0].text, "world");
+  assert_eq!(duet[3].text, "一起");
+  assert_eq!(duet[4].text, "Dear: no marker");
+}
+
+#[test]
+pub fn test_format_lrc() {
+  use crate::lyrics::merge_duplicate_timestamps;
+
+  let lines = vec![
+    LyricLine { time: 61.5, text: "world".to_string(), end: Some(63.0), ..Default::default() },
+    LyricLine {
+      time: 1.0,
+      text: "Hello there".to_string(),
+      words: vec![LyricWord { time: 1.0, text: "Hello ".to_string() }, LyricWord { time: 1.5, text: "there".to_string() }],
+      translation: Some("你好".to_string()),
+      ..Default::default()
+    },
+  ];
+  let lrc = format_lrc(&lines, &[("ti", "Song".to_string())]);
+  assert_eq!(lrc, "[ti:Song]\n[00:01.00]<00:01.00>Hello <00:01.50>there\n[00:01.00]你好\n[01:01.50]world\n[01:03.00]\n");
+
+  let parsed = merge_duplicate_timestamps(parse_lrc(&lrc));
+  assert_eq!(parsed.len(), 3);
+  assert_eq!(parsed[0].text, "Hello there");
+  assert_eq!(parsed[0].words.len(), 2);
+  assert_eq!(parsed[0].translation.as_deref(), Some("你好"));
+  assert_eq!(parsed[2].time, 63.0);
+
+  let duet = |time: f64, part: LinePart| LyricLine { time, text: "la".to_string(), part: Some(part), ..Default::default() };
+  let lrc = format_lrc(&[duet(1.0, LinePart::P1), duet(2.0, LinePart::P1), duet(3.0, LinePart::Both)], &[]);
+  assert_eq!(lrc, "[00:01.00]M: la\n[00:02.00]la\n[00:03.00]D: la\n");
+}
+
+#[test]
+pub fn test_lint_lrc() {
+  let content = "[ti:Song]\n[00:01.00]one\n[00:05.00]three\n[00:03.00]two\n[00:05.00]three\n[00:08.00][00:12.00]chorus\n[00:20.00]end\n";
+  let warnings = lint_lrc(content, Some(15.0));
+  let found: Vec<(LyricsWarningKind, Option<usize>)> = warnings.iter().map(|w| (w.kind, w.line)).collect();
+  assert_eq!(
+    found,
+    vec![
+      (LyricsWarningKind::OutOfOrder, Some(4)),
+      (LyricsWarningKind::DuplicateLine, Some(5)),
+      (LyricsWarningKind::BeyondDuration, Some(7)),
+      (LyricsWarningKind::MissingOffset, None),
+    ]
+  );
+  assert!(lint_lrc("[offset:+200]\n[00:01.00]a\n[00:02.00]b\n", None).is_empty());
+  assert!(lint_lrc("", None).is_empty());
+}
@@ -0,0 +1,481 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::decode_text;
+use crate::kar::parse_kar;
+use crate::krc::{decode_krc, parse_krc};
+use crate::lrc::parse_lrc;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LyricLine {
+  pub time: f64,
+  /// end of the line in seconds, from the source format or estimated by `get_metadata`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub end: Option<f64>,
+  pub text: String,
+  /// per-word timing from Enhanced LRC `<mm:ss.xx>` tags, empty for line-level lyrics
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub words: Vec<LyricWord>,
+  /// translated text from a bilingual LRC or a `song.<lang>.lrc` companion file
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub translation: Option<String>,
+  /// suggested breath points (seconds) from gaps in the vocal MIDI
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub breaths: Vec<f64>,
+  /// pinyin/romaji reading of `text`, from `romanize_lyrics`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub romanization: Option<String>,
+  /// prompter pacing, absent for empty (instrumental) lines
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub pacing: Option<LinePacing>,
+  /// duet part from `M:`/`F:`/`D:` or `[1]`/`[2]` markers, absent for solo songs
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub part: Option<LinePart>,
+}
+
+/// Who sings a line in a duet. A marker applies until the next one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinePart {
+  /// `M:`, `男：` or `[1]`
+  P1,
+  /// `F:`, `女：` or `[2]`
+  P2,
+  /// `D:` or `合：`, sung together
+  Both,
+}
+
+impl LinePart {
+  /// Part and remaining text for a line starting with a speaker prefix like `M: ` or `女：`.
+  pub(crate) fn strip_prefix(text: &str) -> Option<(LinePart, &str)> {
+    const PREFIXES: [(&str, LinePart); 6] = [("M", LinePart::P1), ("男", LinePart::P1), ("F", LinePart::P2), ("女", LinePart::P2), ("D", LinePart::Both), ("合", LinePart::Both)];
+    PREFIXES.iter().find_map(|(prefix, part)| {
+      let rest = text.strip_prefix(prefix)?;
+      let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('：'))?;
+      Some((*part, rest.trim_start()))
+    })
+  }
+
+  pub(crate) fn from_tag(tag: &str) -> Option<LinePart> {
+    match tag.trim() {
+      "1" => Some(LinePart::P1),
+      "2" => Some(LinePart::P2),
+      _ => None,
+    }
+  }
+
+  /// Marker written in front of the line text by `format_lrc`.
+  pub fn marker(self) -> &'static str {
+    match self {
+      LinePart::P1 => "M: ",
+      LinePart::P2 => "F: ",
+      LinePart::Both => "D: ",
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LyricWord {
+  pub time: f64,
+  pub text: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinePacing {
+  /// visible (non-whitespace) characters per second of line time
+  pub chars_per_second: f64,
+  /// `chars_per_second` relative to the song's median line, so rapid-fire lines are > 1
+  pub density: f64,
+}
+
+pub type LyricsParser = fn(&[u8]) -> Result<Vec<LyricLine>, String>;
+
+/// Supported lyrics files, in lookup order. Text formats may be in any encoding (see `decode_text`).
+/// QQ Music `.qrc` files are left out: they are stored encrypted, and `crate::qrc` only reads
+/// decrypted content.
+pub const LYRICS_FORMATS: [(&str, LyricsParser); 7] = [
+  (".lrc", |c| Ok(merge_duplicate_timestamps(parse_lrc(&decode_text(c))))),
+  (".krc", |c| Ok(parse_krc(&decode_krc(c)?))),
+  (".srt", |c| Ok(parse_srt(&decode_text(c)))),
+  (".vtt", |c| Ok(parse_vtt(&decode_text(c)))),
+  (".ass", |c| Ok(parse_ass(&decode_text(c)))),
+  (".ssa", |c| Ok(parse_ass(&decode_text(c)))),
+  (".kar", parse_kar),
+];
+
+/// Parse a subtitle timestamp: `hh:mm:ss,mmm` (SRT), `hh:mm:ss.mmm` or `mm:ss.mmm` (VTT).
+fn parse_cue_time(stamp: &str) -> Option<f64> {
+  let stamp = stamp.trim().replace(',', ".");
+  let parts: Vec<&str> = stamp.split(':').collect();
+  let (h, m, s) = match parts.as_slice() {
+    [h, m, s] => (h.parse::<f64>().ok()?, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+    [m, s] => (0.0, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+    _ => return None,
+  };
+  Some(h * 3600.0 + m * 60.0 + s)
+}
+
+// Parse a `start --> end [cue settings]` timing line.
+fn parse_cue_timing(line: &str) -> Option<(f64, f64)> {
+  let (start, rest) = line.split_once("-->")?;
+  let end = rest.split_whitespace().next()?;
+  Some((parse_cue_time(start)?, parse_cue_time(end)?))
+}
+
+// Strip markup tags (`<i>`, `<c.color>`, `{\an8}` ...) from cue text. VTT inline
+// timestamps (`<00:00:01.500>`) become word boundaries.
+fn parse_cue_text(text: &str) -> (String, Vec<LyricWord>) {
+  let mut plain = String::new();
+  let mut words: Vec<LyricWord> = Vec::new();
+  let mut current: Option<f64> = None;
+  let mut segment = String::new();
+  let mut rest = text;
+  loop {
+    let next = rest.find(['<', '{']);
+    let Some(open) = next else {
+      segment.push_str(rest);
+      break;
+    };
+    let closing = if rest[open..].starts_with('<') { '>' } else { '}' };
+    let Some(close) = rest[open..].find(closing).map(|i| open + i) else {
+      segment.push_str(rest);
+      break;
+    };
+    segment.push_str(&rest[..open]);
+    if let Some(t) = parse_cue_time(&rest[open + 1..close]).filter(|_| closing == '>') {
+      flush_word(&mut plain, &mut words, current, &segment);
+      segment.clear();
+      current = Some(t);
+    }
+    rest = &rest[close + 1..];
+  }
+  flush_word(&mut plain, &mut words, current, &segment);
+  (plain.trim().to_string(), words)
+}
+
+fn flush_word(plain: &mut String, words: &mut Vec<LyricWord>, time: Option<f64>, segment: &str) {
+  plain.push_str(segment);
+  if let (Some(time), false) = (time, segment.trim().is_empty()) {
+    words.push(LyricWord { time, text: segment.to_string() });
+  }
+}
+
+// Tags opening the translation line of a cue written by `format_srt` and `format_vtt`, so it
+// reads back as the translation rather than more of the lyric.
+const SRT_TRANSLATION_TAG: &str = "<font class=\"translation\">";
+const VTT_TRANSLATION_TAG: &str = "<c.translation>";
+
+// Shared block parser for SRT and VTT: cues are separated by blank lines and
+// contain an optional identifier line, a timing line and one or more text lines.
+fn parse_cues(content: &str) -> Vec<LyricLine> {
+  let mut lyrics: Vec<LyricLine> = Vec::new();
+  let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+
+  for block in content.split("\n\n") {
+    let mut lines = block.lines().map(str::trim).filter(|l| !l.is_empty());
+    let mut timing = None;
+    for line in lines.by_ref() {
+      if let Some(t) = parse_cue_timing(line) {
+        timing = Some(t);
+        break;
+      }
+    }
+    // header, NOTE/STYLE/REGION blocks and malformed cues have no timing line
+    let Some((start, end)) = timing else {
+      continue;
+    };
+
+    let (translated, sung): (Vec<&str>, Vec<&str>) = lines.partition(|l| l.starts_with(SRT_TRANSLATION_TAG) || l.starts_with(VTT_TRANSLATION_TAG));
+    let (text, words) = parse_cue_text(&sung.join(" "));
+    let translation = Some(parse_cue_text(&translated.join(" ")).0).filter(|t| !t.is_empty());
+    lyrics.push(LyricLine { time: start, end: Some(end), text, words, translation, ..Default::default() });
+  }
+
+  lyrics.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+  lyrics
+}
+
+/// Parse SubRip (`.srt`) subtitles into lyric lines with start and end times.
+pub fn parse_srt(content: &str) -> Vec<LyricLine> {
+  parse_cues(content)
+}
+
+/// Parse WebVTT (`.vtt`) subtitles into lyric lines with start and end times.
+/// Inline cue timestamps are returned as word timings.
+pub fn parse_vtt(content: &str) -> Vec<LyricLine> {
+  parse_cues(content)
+}
+
+// Karaoke duration in centiseconds from an override block like `{\k50}`, `{\kf100\b1}` or `{\K20}`.
+fn parse_karaoke_tag(block: &str) -> Option<f64> {
+  for tag in block.split('\\').skip(1) {
+    let Some(rest) = tag.strip_prefix('k').or_else(|| tag.strip_prefix('K')) else {
+      continue;
+    };
+    let digits = rest.trim_start_matches(['f', 'o']);
+    let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    if end > 0 && end == digits.trim_end().len() {
+      return digits[..end].parse::<f64>().ok().map(|cs| cs / 100.0);
+    }
+  }
+  None
+}
+
+// Convert ASS dialogue text into plain text and syllables, dropping other override tags.
+fn parse_ass_text(text: &str, start: f64) -> (String, Vec<LyricWord>) {
+  let text = text.replace("\\N", " ").replace("\\n", " ").replace("\\h", " ");
+  let mut plain = String::new();
+  let mut words: Vec<LyricWord> = Vec::new();
+  // time of the syllable currently being collected, and where the next one starts
+  let mut current: Option<f64> = None;
+  let mut next_start = start;
+  let mut segment = String::new();
+  let mut rest = text.as_str();
+  while let Some(open) = rest.find('{') {
+    let Some(close) = rest[open..].find('}').map(|i| open + i) else {
+      break;
+    };
+    segment.push_str(&rest[..open]);
+    if let Some(duration) = parse_karaoke_tag(&rest[open + 1..close]) {
+      flush_word(&mut plain, &mut words, current, &segment);
+      segment.clear();
+      current = Some(next_start);
+      next_start += duration;
+    }
+    rest = &rest[close + 1..];
+  }
+  segment.push_str(rest);
+  flush_word(&mut plain, &mut words, current, &segment);
+  (plain.trim().to_string(), words)
+}
+
+/// Parse Advanced SubStation Alpha (`.ass`/`.ssa`) dialogue events into lyric lines.
+/// Karaoke `\k`/`\K`/`\kf`/`\ko` tags become per-syllable word timings.
+pub fn parse_ass(content: &str) -> Vec<LyricLine> {
+  let mut lyrics: Vec<LyricLine> = Vec::new();
+  let mut in_events = false;
+  // field positions from the `Format:` line, defaulting to the standard ASS layout
+  let (mut start_idx, mut end_idx, mut text_idx, mut fields) = (1usize, 2usize, 9usize, 10usize);
+
+  for raw_line in content.trim_start_matches('\u{feff}').lines() {
+    let line = raw_line.trim();
+    if line.starts_with('[') {
+      in_events = line.eq_ignore_ascii_case("[events]");
+      continue;
+    }
+    if !in_events {
+      continue;
+    }
+    if let Some(format) = line.strip_prefix("Format:") {
+      let names: Vec<String> = format.split(',').map(|f| f.trim().to_ascii_lowercase()).collect();
+      let find = |name: &str, default: usize| names.iter().position(|n| n == name).unwrap_or(default);
+      start_idx = find("start", start_idx);
+      end_idx = find("end", end_idx);
+      text_idx = find("text", text_idx);
+      fields = names.len();
+      continue;
+    }
+    let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+      continue;
+    };
+    // Text is the last field and may itself contain commas
+    let values: Vec<&str> = dialogue.splitn(fields, ',').collect();
+    let (Some(start), Some(end), Some(text)) = (
+      values.get(start_idx).and_then(|v| parse_cue_time(v)),
+      values.get(end_idx).and_then(|v| parse_cue_time(v)),
+      values.get(text_idx),
+    ) else {
+      continue;
+    };
+    let (text, words) = parse_ass_text(text, start);
+    lyrics.push(LyricLine { time: start, end: Some(end), text, words, ..Default::default() });
+  }
+
+  lyrics.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+  lyrics
+}
+
+// Two timestamps closer than this are treated as the same line.
+const SAME_TIME_EPSILON: f64 = 0.05;
+
+/// Merge lines that share a timestamp: bilingual LRC files write the translation as a
+/// second line with the same time. Expects lines sorted by time (stable, in file order).
+pub fn merge_duplicate_timestamps(lines: Vec<LyricLine>) -> Vec<LyricLine> {
+  let mut merged: Vec<LyricLine> = Vec::with_capacity(lines.len());
+  for line in lines {
+    if let Some(prev) = merged.last_mut() {
+      let same_time = (line.time - prev.time).abs() < SAME_TIME_EPSILON;
+      if same_time && prev.translation.is_none() && !prev.text.is_empty() && !line.text.is_empty() && line.text != prev.text {
+        prev.translation = Some(line.text);
+        continue;
+      }
+    }
+    merged.push(line);
+  }
+  merged
+}
+
+/// Attach the text of `translated` lines to the `lines` they share a timestamp with.
+pub fn merge_translation(lines: &mut [LyricLine], translated: &[LyricLine]) {
+  for line in lines.iter_mut().filter(|l| !l.text.is_empty()) {
+    let found = translated
+      .iter()
+      .filter(|t| !t.text.is_empty() && (t.time - line.time).abs() < SAME_TIME_EPSILON)
+      .min_by(|a, b| (a.time - line.time).abs().total_cmp(&(b.time - line.time).abs()));
+    if let Some(t) = found {
+      line.translation = Some(t.text.clone());
+    }
+  }
+}
+
+/// Remap every timing as `time * scale + offset`, e.g. to fix a globally offset file
+/// (`scale` 1) or one timed against a different tempo.
+pub fn shift_timings(lines: &mut [LyricLine], offset: f64, scale: f64) {
+  let remap = |t: f64| (t * scale + offset).max(0.0);
+  for line in lines.iter_mut() {
+    line.time = remap(line.time);
+    line.end = line.end.map(remap);
+    for word in line.words.iter_mut() {
+      word.time = remap(word.time);
+    }
+    for breath in line.breaths.iter_mut() {
+      *breath = remap(*breath);
+    }
+  }
+}
+
+// cues without an end time last until the next line, but no longer than this
+const MAX_CUE_DURATION: f64 = 8.0;
+
+// `hh:mm:ss,mmm` for SRT (`separator` ','), `hh:mm:ss.mmm` for VTT
+fn format_cue_time(time: f64, separator: char) -> String {
+  let ms = (time.max(0.0) * 1000.0).round() as u64;
+  format!("{:02}:{:02}:{:02}{}{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, separator, ms % 1000)
+}
+
+// Sung lines as `(start, end, line)` cues; instrumental (empty) lines only end the cue before them.
+fn cues(lines: &[LyricLine]) -> Vec<(f64, f64, &LyricLine)> {
+  let mut sorted: Vec<&LyricLine> = lines.iter().collect();
+  sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+  (0..sorted.len())
+    .filter(|&i| !sorted[i].text.trim().is_empty())
+    .map(|i| {
+      let line = sorted[i];
+      let next = sorted.get(i + 1).map_or(f64::INFINITY, |l| l.time);
+      let end = line.end.unwrap_or_else(|| next.min(line.time + MAX_CUE_DURATION));
+      (line.time, end.max(line.time), line)
+    })
+    .collect()
+}
+
+/// Format lyric lines as SubRip (`.srt`) subtitles. Translations become a second cue line, tagged
+/// so `parse_srt` reads them back.
+pub fn format_srt(lines: &[LyricLine]) -> String {
+  let mut out = String::new();
+  for (i, (start, end, line)) in cues(lines).into_iter().enumerate() {
+    out.push_str(&format!("{}\n{} --> {}\n{}\n", i + 1, format_cue_time(start, ','), format_cue_time(end, ','), line.text));
+    if let Some(translation) = &line.translation {
+      out.push_str(&format!("{}{}</font>\n", SRT_TRANSLATION_TAG, translation));
+    }
+    out.push('\n');
+  }
+  out
+}
+
+/// Format lyric lines as WebVTT (`.vtt`) subtitles, with word timings as inline cue timestamps and
+/// translations as a second cue line in the `translation` class.
+pub fn format_vtt(lines: &[LyricLine]) -> String {
+  let mut out = "WEBVTT\n\n".to_string();
+  for (start, end, line) in cues(lines) {
+    let joined: String = line.words.iter().map(|w| w.text.as_str()).collect();
+    let text = if !line.words.is_empty() && joined.trim() == line.text.trim() {
+      line.words.iter().map(|w| format!("<{}>{}", format_cue_time(w.time, '.'), w.text)).collect()
+    } else {
+      line.text.clone()
+    };
+    out.push_str(&format!("{} --> {}\n{}\n", format_cue_time(start, '.'), format_cue_time(end, '.'), text.trim_end()));
+    if let Some(translation) = &line.translation {
+      out.push_str(&format!("{}{}</c>\n", VTT_TRANSLATION_TAG, translation));
+    }
+    out.push('\n');
+  }
+  out
+}
+
+#[test]
+pub fn test_parse_subtitles() {
+  let srt = "1\r\n00:00:01,000 --> 00:00:03,500\r\n<i>Hello</i>\r\nworld\r\n\r\n2\r\n00:00:04,000 --> 00:00:05,000\r\nagain\r\n";
+  let lyrics = parse_srt(srt);
+  assert_eq!(lyrics.len(), 2);
+  assert_eq!(lyrics[0].text, "Hello world");
+  assert_eq!(lyrics[0].end, Some(3.5));
+
+  let vtt = "WEBVTT\n\nNOTE a comment\n\nintro\n00:01.000 --> 00:02.000 align:start\n<00:01.000>one <00:01.500>two\n";
+  let lyrics = parse_vtt(vtt);
+  assert_eq!(lyrics.len(), 1);
+  assert_eq!(lyrics[0].text, "one two");
+  assert_eq!(lyrics[0].words.len(), 2);
+  assert_eq!(lyrics[0].words[1].time, 1.5);
+}
+
+#[test]
+pub fn test_parse_ass() {
+  let ass = "[Script Info]\nTitle: x\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nComment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,skip\nDialogue: 0,0:00:01.00,0:00:04.00,Default,,0,0,0,,{\\k50}Hel{\\k30\\b1}lo, {\\kf100}world\n";
+  let lyrics = parse_ass(ass);
+  assert_eq!(lyrics.len(), 1);
+  assert_eq!(lyrics[0].text, "Hello, world");
+  assert_eq!(lyrics[0].end, Some(4.0));
+  assert_eq!(lyrics[0].words.len(), 3);
+  assert_eq!(lyrics[0].words[1].time, 1.5);
+  assert_eq!(lyrics[0].words[2].time, 1.8);
+}
+
+#[test]
+pub fn test_merge_translations() {
+  let line = |time: f64, text: &str| LyricLine { time, text: text.to_string(), ..Default::default() };
+
+  let merged = merge_duplicate_timestamps(vec![line(1.0, "你好"), line(1.0, "Hello"), line(2.0, "世界")]);
+  assert_eq!(merged.len(), 2);
+  assert_eq!(merged[0].translation.as_deref(), Some("Hello"));
+
+  let mut lines = vec![line(1.0, "你好"), line(2.0, "世界")];
+  merge_translation(&mut lines, &[line(2.01, "world")]);
+  assert_eq!(lines[0].translation, None);
+  assert_eq!(lines[1].translation.as_deref(), Some("world"));
+}
+
+#[test]
+pub fn test_shift_timings() {
+  let mut lines = vec![LyricLine { time: 1.0, end: Some(2.0), text: "a".to_string(), words: vec![LyricWord { time: 1.5, text: "a".to_string() }], ..Default::default() }];
+  shift_timings(&mut lines, -0.5, 2.0);
+  assert_eq!(lines[0].time, 1.5);
+  assert_eq!(lines[0].end, Some(3.5));
+  assert_eq!(lines[0].words[0].time, 2.5);
+
+  shift_timings(&mut lines, -10.0, 1.0);
+  assert_eq!(lines[0].time, 0.0);
+}
+
+#[test]
+pub fn test_format_subtitles() {
+  let words = vec![LyricWord { time: 1.0, text: "one ".to_string() }, LyricWord { time: 1.5, text: "two".to_string() }];
+  let lines = vec![
+    LyricLine { time: 1.0, text: "one two".to_string(), words, translation: Some("一 二".to_string()), ..Default::default() },
+    LyricLine { time: 3.0, text: String::new(), ..Default::default() },
+    LyricLine { time: 3661.25, end: Some(3662.0), text: "late".to_string(), ..Default::default() },
+  ];
+
+  let srt = format_srt(&lines);
+  assert_eq!(srt, "1\n00:00:01,000 --> 00:00:03,000\none two\n<font class=\"translation\">一 二</font>\n\n2\n01:01:01,250 --> 01:01:02,000\nlate\n\n");
+  let parsed = parse_srt(&srt);
+  assert_eq!((parsed[0].text.as_str(), parsed[0].translation.as_deref()), ("one two", Some("一 二")));
+  assert_eq!((parsed[1].time, parsed[1].end), (3661.25, Some(3662.0)));
+  assert_eq!(parsed[1].translation, None);
+
+  let vtt = format_vtt(&lines);
+  assert!(vtt.starts_with("WEBVTT\n\n00:00:01.000 --> 00:00:03.000\n<00:00:01.000>one <00:00:01.500>two\n"));
+  let parsed = parse_vtt(&vtt);
+  assert_eq!(parsed[0].words.len(), 2);
+  assert_eq!(parsed[0].words[1].time, 1.5);
+  assert_eq!((parsed[0].text.as_str(), parsed[0].translation.as_deref()), ("one two", Some("一 二")));
+  assert_eq!(parsed[1].text, "late");
+}
@@ -0,0 +1,66 @@
+use crate::midi::{MidiTrack, Note};
+
+// General MIDI percussion channel, never a melody
+pub(crate) const DRUM_CHANNEL: u8 = 9;
+// typical sung range, E2 to C6
+const VOCAL_RANGE: (i32, i32) = (40, 84);
+// notes per second a singer can manage
+const DENSITY_RANGE: (f64, f64) = (0.5, 6.0);
+// tracks with fewer notes are less likely to carry the melody
+const MIN_NOTES: usize = 16;
+// notes may overlap the previous one by this much (seconds) and still count as monophonic
+const LEGATO_OVERLAP: f64 = 0.05;
+const NAME_HINTS: [&str; 9] = ["vocal", "voice", "vox", "melody", "lead", "sing", "人声", "主旋律", "ボーカル"];
+
+/// How much a track looks like a sung melody, 0 to 1 (plus up to 0.5 for a telling track name):
+/// mostly one note at a time, within vocal range, at a singable density.
+fn melody_score(track: &MidiTrack) -> f64 {
+  let notes: Vec<&Note> = track.notes.iter().filter(|n| n.channel != DRUM_CHANNEL).collect();
+  if notes.len() < 2 || notes.len() * 2 < track.notes.len() {
+    return 0.0;
+  }
+  let overlapping = notes.windows(2).filter(|w| w[1].start < w[0].start + w[0].duration - LEGATO_OVERLAP).count();
+  let monophony = 1.0 - overlapping as f64 / (notes.len() - 1) as f64;
+  let in_range = notes.iter().filter(|n| (VOCAL_RANGE.0..=VOCAL_RANGE.1).contains(&n.note)).count() as f64 / notes.len() as f64;
+
+  let first = notes[0].start;
+  let last = notes.iter().map(|n| n.start + n.duration).fold(first, f64::max);
+  let density = notes.len() as f64 / (last - first).max(1.0);
+  let density_fit = (density / DENSITY_RANGE.0).min(1.0) * (DENSITY_RANGE.1 / density).min(1.0);
+
+  let mut score = 0.45 * monophony + 0.3 * in_range + 0.25 * density_fit;
+  score *= (notes.len() as f64 / MIN_NOTES as f64).min(1.0);
+  let names = [track.name.as_deref(), track.instrument.as_deref()];
+  if names.iter().flatten().any(|name| NAME_HINTS.iter().any(|hint| name.to_lowercase().contains(hint))) {
+    score += 0.5;
+  }
+  score
+}
+
+/// Index of the track most likely to hold the vocal melody, or `None` when no track has notes
+/// that could be one.
+pub fn detect_melody_track(tracks: &[MidiTrack]) -> Option<usize> {
+  tracks
+    .iter()
+    .map(|t| (t.index, melody_score(t)))
+    .filter(|(_, score)| *score > 0.0)
+    .max_by(|a, b| a.1.total_cmp(&b.1))
+    .map(|(index, _)| index)
+}
+
+#[test]
+pub fn test_detect_melody_track() {
+  let note = |note: i32, start: f64, duration: f64, channel: u8| Note { note, start, duration, velocity: 100.0, channel, confidence: None, bend: Vec::new() };
+  let track = |index: usize, name: Option<&str>, notes: Vec<Note>| MidiTrack { index, name: name.map(str::to_string), instrument: None, notes, sustain: Vec::new() };
+  let tune: Vec<Note> = (0..32).map(|i| note(60 + i % 7, i as f64 * 0.5, 0.45, 0)).collect();
+  let chords: Vec<Note> = (0..32).flat_map(|i| [48, 52, 55].map(|n| note(n, (i / 4) as f64 * 2.0, 2.0, 1))).collect();
+  let drums: Vec<Note> = (0..64).map(|i| note(36, i as f64 * 0.25, 0.1, DRUM_CHANNEL)).collect();
+
+  let tracks = vec![track(0, None, Vec::new()), track(1, None, chords), track(2, None, drums), track(3, None, tune)];
+  assert_eq!(detect_melody_track(&tracks), Some(3));
+
+  // a telling name wins over a slightly better shape
+  let named = vec![track(0, None, (0..32).map(|i| note(62, i as f64 * 0.5, 0.45, 0)).collect()), track(1, Some("Lead Vocal"), (0..20).map(|i| note(64, i as f64, 0.9, 0)).collect())];
+  assert_eq!(detect_melody_track(&named), Some(1));
+  assert_eq!(detect_melody_track(&tracks[..1]), None);
+}
@@ -0,0 +1,1028 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::encoding::decode_text;
+use crate::melody::{detect_melody_track, DRUM_CHANNEL};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Note {
+  pub note: i32,
+  /// start time in seconds
+  pub start: f64,
+  /// duration in seconds
+  pub duration: f64,
+  pub velocity: f64,
+  /// MIDI channel (0-15)
+  pub channel: u8,
+  pub confidence: Option<f64>,
+  /// pitch bend as (time in seconds, offset in semitones) points, each holding until the next;
+  /// empty when the note is never bent
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub bend: Vec<(f64, f64)>,
+}
+
+/// Where to read per-note confidence from when loading a MIDI file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfidenceSource {
+  /// leave `Note.confidence` empty
+  #[default]
+  None,
+  /// basic-pitch writes `velocity = round(127 * amplitude)`, so map it back to 0..1
+  Velocity,
+  /// text/marker meta events `klok:confidence=<0..1>` written by the pipeline, or the same text
+  /// in a non-commercial SysEx message (`F0 7D klok:confidence=<0..1> F7`), applied to the next
+  /// note-on at or after the marker
+  Meta,
+}
+
+/// What to do with a note-on for a key that is already sounding on the same channel, as
+/// auto-transcribed MIDI often has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+  /// end the sounding note where the new one starts
+  #[default]
+  TruncatePrevious,
+  /// keep both; each note-off ends the earliest of the sounding notes
+  Layer,
+  /// drop the new note; the sounding note ends at the next note-off
+  Ignore,
+}
+
+const CONFIDENCE_META_PREFIX: &str = "klok:confidence=";
+// SysEx manufacturer id reserved for non-commercial use, and the end of a SysEx message
+const SYSEX_NON_COMMERCIAL: u8 = 0x7d;
+const SYSEX_END: u8 = 0xf7;
+
+fn parse_confidence_meta(raw: &[u8]) -> Option<f64> {
+  let text = std::str::from_utf8(raw).ok()?.trim();
+  let value = text.strip_prefix(CONFIDENCE_META_PREFIX)?.trim().parse::<f64>().ok()?;
+  value.is_finite().then(|| value.clamp(0.0, 1.0))
+}
+
+/// Notes of one track of a MIDI file, with its names from meta events.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MidiTrack {
+  /// position of the track in the file, as used by the `tracks` filter
+  pub index: usize,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub instrument: Option<String>,
+  pub notes: Vec<Note>,
+  /// spans the sustain pedal (CC64) is held, sorted by start time
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub sustain: Vec<SustainSpan>,
+}
+
+/// A span during which the sustain pedal of one channel is held down.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SustainSpan {
+  pub channel: u8,
+  pub start: f64,
+  pub end: f64,
+}
+
+// controller number of the sustain (damper) pedal; values of 64 and up mean down
+const SUSTAIN_CONTROLLER: u8 = 64;
+
+/// Extend notes released while the sustain pedal of their channel is held to the pedal release,
+/// or to the next note-on of the same key when that comes first.
+pub fn apply_sustain(track: &mut MidiTrack) {
+  let starts: Vec<(u8, i32, f64)> = track.notes.iter().map(|n| (n.channel, n.note, n.start)).collect();
+  for note in &mut track.notes {
+    let release = note.start + note.duration;
+    let Some(span) = track.sustain.iter().find(|s| s.channel == note.channel && s.start <= release && release < s.end) else {
+      continue;
+    };
+    let retrigger = starts
+      .iter()
+      .filter(|&&(ch, key, start)| ch == note.channel && key == note.note && start > note.start)
+      .map(|s| s.2)
+      .fold(f64::INFINITY, f64::min);
+    note.duration = span.end.min(retrigger) - note.start;
+  }
+}
+
+/// Shift the notes of `track` by `semitones` (12 for an octave up), e.g. to follow a key-shifted
+/// backing track. Percussion keeps its keys, which select drums rather than pitches.
+pub fn transpose(track: &mut MidiTrack, semitones: i32) {
+  for note in track.notes.iter_mut().filter(|n| n.channel != DRUM_CHANNEL) {
+    note.note = (note.note + semitones).clamp(0, 127);
+  }
+}
+
+/// Keep the tracks listed in `filter` (all tracks without one).
+pub fn select_tracks(tracks: Vec<MidiTrack>, filter: Option<&[usize]>) -> Vec<MidiTrack> {
+  match filter {
+    Some(filter) => tracks.into_iter().filter(|t| filter.contains(&t.index)).collect(),
+    None => tracks,
+  }
+}
+
+/// Like [`select_tracks`], but without a filter keeps only the detected melody track of a
+/// multi-track file.
+pub fn melody_tracks(tracks: Vec<MidiTrack>, filter: Option<&[usize]>) -> Vec<MidiTrack> {
+  if filter.is_some() || tracks.iter().filter(|t| !t.notes.is_empty()).count() < 2 {
+    return select_tracks(tracks, filter);
+  }
+  match detect_melody_track(&tracks) {
+    Some(melody) => select_tracks(tracks, Some(&[melody])),
+    None => tracks,
+  }
+}
+
+/// All notes of `tracks` sorted by start time.
+pub fn flatten_tracks(tracks: Vec<MidiTrack>) -> Vec<Note> {
+  let mut notes: Vec<Note> = tracks.into_iter().flat_map(|t| t.notes).collect();
+  notes.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
+  notes
+}
+
+/// Parse MIDI content from any reader and return a list of notes.
+pub fn load_midi_from_memory(content: &[u8]) -> Result<Vec<Note>, String> {
+  load_midi_from_memory_with(content, ConfidenceSource::None)
+}
+
+/// Same as [`load_midi_from_memory`], populating `Note.confidence` from `confidence`.
+pub fn load_midi_from_memory_with(content: &[u8], confidence: ConfidenceSource) -> Result<Vec<Note>, String> {
+  load_midi_tracks_from_memory(content, confidence, OverlapPolicy::default()).map(flatten_tracks)
+}
+
+// a note waiting for its note-off
+struct OngoingNote {
+  start: f64,
+  velocity: u8,
+  confidence: Option<f64>,
+  bend: Vec<(f64, f64)>,
+}
+
+impl OngoingNote {
+  fn finish(self, note: u8, channel: u8, end: f64) -> Note {
+    let OngoingNote { start, velocity, confidence, bend } = self;
+    Note { note: note as i32, start, duration: end - start, velocity: velocity as f64, channel, confidence, bend }
+  }
+}
+
+// The earliest sounding note of `key`, for its note-off.
+fn end_note(ongoing: &mut HashMap<(usize, u8, u8), Vec<OngoingNote>>, key: (usize, u8, u8)) -> Option<OngoingNote> {
+  let sounding = ongoing.get_mut(&key)?;
+  (!sounding.is_empty()).then(|| sounding.remove(0))
+}
+
+// pitch bend range (semitones) of a channel unless changed with RPN 0
+const DEFAULT_BEND_RANGE: f64 = 2.0;
+
+// pitch bend state of one channel
+struct ChannelBend {
+  /// current offset in semitones
+  offset: f64,
+  /// semitones at full bend
+  range: f64,
+  /// registered parameter selected by CC101/CC100, `(msb, lsb)`
+  rpn: (u8, u8),
+}
+
+impl Default for ChannelBend {
+  fn default() -> Self {
+    ChannelBend { offset: 0.0, range: DEFAULT_BEND_RANGE, rpn: (127, 127) }
+  }
+}
+
+/// Parse MIDI content into one [`MidiTrack`] per track of the file, notes sorted by start time.
+/// `overlap` resolves repeated note-ons of a sounding key.
+pub fn load_midi_tracks_from_memory(content: &[u8], confidence: ConfidenceSource, overlap: OverlapPolicy) -> Result<Vec<MidiTrack>, String> {
+  // Parse MIDI using midly
+  let smf = midly::Smf::parse(content).map_err(|e| format!("failed to parse midi: {}", e))?;
+  let ticks_per_quarter = ticks_per_quarter(smf.header.timing)?;
+  Ok(parse_tracks(ticks_per_quarter, &smf.tracks, confidence, overlap, false).0)
+}
+
+// Only support metrical timing (ticks per quarter-note)
+fn ticks_per_quarter(timing: midly::Timing) -> Result<u32, String> {
+  match timing {
+    midly::Timing::Metrical(t) => Ok(t.as_int() as u32),
+    _ => Err("SMPTE time formats are not supported".to_string()),
+  }
+}
+
+// Notes of each track of `smf_tracks`. With `close_ongoing`, notes still sounding at the last
+// event (a truncated track) end there instead of being dropped; returns how many did.
+fn parse_tracks(ticks_per_quarter: u32, smf_tracks: &[Vec<midly::TrackEvent<'_>>], confidence: ConfidenceSource, overlap: OverlapPolicy, close_ongoing: bool) -> (Vec<MidiTrack>, usize) {
+  // Collect all events with absolute tick and track index
+  let mut events: Vec<(u64, usize, midly::TrackEventKind)> = Vec::new();
+  for (index, track) in smf_tracks.iter().enumerate() {
+    let mut abs: u64 = 0;
+    for ev in track {
+      abs = abs.wrapping_add(ev.delta.as_int() as u64);
+      events.push((abs, index, ev.kind));
+    }
+  }
+
+  // Sort by absolute tick
+  events.sort_by_key(|(t, _, _)| *t);
+
+  let mut tracks: Vec<MidiTrack> = (0..smf_tracks.len()).map(|index| MidiTrack { index, name: None, instrument: None, notes: Vec::new(), sustain: Vec::new() }).collect();
+
+  // State while iterating events
+  let mut last_tick: u64 = 0;
+  let mut seconds: f64 = 0.0;
+  let mut tempo_micro: u32 = 500_000; // default microseconds per quarter-note
+
+  // ongoing notes keyed by (track, channel, note), earliest first
+  let mut ongoing: HashMap<(usize, u8, u8), Vec<OngoingNote>> = HashMap::new();
+  // pedal-down time keyed by (track, channel)
+  let mut pedals: HashMap<(usize, u8), f64> = HashMap::new();
+  let mut bends: HashMap<(usize, u8), ChannelBend> = HashMap::new();
+  // confidence marker waiting for the next note-on (ConfidenceSource::Meta)
+  let mut pending_confidence: Option<f64> = None;
+
+  for (abs_tick, track, kind) in events {
+    let delta_ticks = abs_tick.saturating_sub(last_tick);
+    if delta_ticks != 0 {
+      // convert ticks to seconds using current tempo
+      seconds += (delta_ticks as f64) * (tempo_micro as f64) / (ticks_per_quarter as f64) / 1_000_000.0;
+      last_tick = abs_tick;
+    }
+
+    match kind {
+      midly::TrackEventKind::Meta(midly::MetaMessage::Tempo(t)) => {
+        tempo_micro = t.into();
+      }
+      midly::TrackEventKind::Meta(midly::MetaMessage::TrackName(raw)) if tracks[track].name.is_none() => {
+        tracks[track].name = Some(decode_text(raw).trim().to_string()).filter(|n| !n.is_empty());
+      }
+      midly::TrackEventKind::Meta(midly::MetaMessage::InstrumentName(raw)) if tracks[track].instrument.is_none() => {
+        tracks[track].instrument = Some(decode_text(raw).trim().to_string()).filter(|n| !n.is_empty());
+      }
+      midly::TrackEventKind::Meta(midly::MetaMessage::Text(raw) | midly::MetaMessage::Marker(raw)) if confidence == ConfidenceSource::Meta => {
+        if let Some(c) = parse_confidence_meta(raw) {
+          pending_confidence = Some(c);
+        }
+      }
+      midly::TrackEventKind::SysEx(data) if confidence == ConfidenceSource::Meta => {
+        let data = data.strip_suffix(&[SYSEX_END]).unwrap_or(data);
+        if let Some(c) = data.strip_prefix(&[SYSEX_NON_COMMERCIAL]).and_then(parse_confidence_meta) {
+          pending_confidence = Some(c);
+        }
+      }
+      midly::TrackEventKind::Midi { channel, message } => {
+        match message {
+          midly::MidiMessage::NoteOn { key, vel } => {
+            let k = key.as_int();
+            let v = vel.as_int();
+            let ch = channel.as_int();
+            if v > 0 {
+              let c = match confidence {
+                ConfidenceSource::None => None,
+                ConfidenceSource::Velocity => Some(v as f64 / 127.0),
+                ConfidenceSource::Meta => pending_confidence.take(),
+              };
+              // a note struck while the wheel is off-centre starts bent
+              let offset = bends.get(&(track, ch)).map_or(0.0, |b| b.offset);
+              let bend = if offset != 0.0 { vec![(seconds, offset)] } else { Vec::new() };
+              let sounding = ongoing.entry((track, ch, k)).or_default();
+              match overlap {
+                OverlapPolicy::Ignore if !sounding.is_empty() => continue,
+                // a duplicate note-on on the same tick replaces the note instead of leaving an empty one
+                OverlapPolicy::TruncatePrevious => tracks[track].notes.extend(sounding.drain(..).filter(|n| n.start < seconds).map(|n| n.finish(k, ch, seconds))),
+                _ => {}
+              }
+              sounding.push(OngoingNote { start: seconds, velocity: v, confidence: c, bend });
+            } else {
+              // velocity 0 note_on == note_off
+              if let Some(note) = end_note(&mut ongoing, (track, ch, k)) {
+                tracks[track].notes.push(note.finish(k, ch, seconds));
+              }
+            }
+          }
+          midly::MidiMessage::NoteOff { key, vel: _ } => {
+            let k = key.as_int();
+            let ch = channel.as_int();
+            if let Some(note) = end_note(&mut ongoing, (track, ch, k)) {
+              tracks[track].notes.push(note.finish(k, ch, seconds));
+            }
+          }
+          midly::MidiMessage::PitchBend { bend } => {
+            let ch = channel.as_int();
+            let state = bends.entry((track, ch)).or_default();
+            state.offset = bend.as_f64() * state.range;
+            for ((t, c, _), notes) in ongoing.iter_mut() {
+              if (*t, *c) == (track, ch) {
+                notes.iter_mut().for_each(|note| note.bend.push((seconds, state.offset)));
+              }
+            }
+          }
+          midly::MidiMessage::Controller { controller, value } if matches!(controller.as_int(), 100 | 101 | 6) => {
+            let state = bends.entry((track, channel.as_int())).or_default();
+            match controller.as_int() {
+              101 => state.rpn.0 = value.as_int(),
+              100 => state.rpn.1 = value.as_int(),
+              // data entry for RPN 0 sets the bend range in semitones
+              _ if state.rpn == (0, 0) => state.range = value.as_int() as f64,
+              _ => {}
+            }
+          }
+          midly::MidiMessage::Controller { controller, value } if controller.as_int() == SUSTAIN_CONTROLLER => {
+            let ch = channel.as_int();
+            if value.as_int() >= 64 {
+              pedals.entry((track, ch)).or_insert(seconds);
+            } else if let Some(start) = pedals.remove(&(track, ch)) {
+              tracks[track].sustain.push(SustainSpan { channel: ch, start, end: seconds });
+            }
+          }
+          _ => {}
+        }
+      }
+      _ => {}
+    }
+  }
+
+  // a pedal still down at the end of the file holds until then
+  for ((track, channel), start) in pedals {
+    tracks[track].sustain.push(SustainSpan { channel, start, end: seconds });
+  }
+
+  let mut closed = 0;
+  if close_ongoing {
+    for ((track, ch, k), sounding) in ongoing {
+      for note in sounding.into_iter().filter(|n| n.start < seconds) {
+        tracks[track].notes.push(note.finish(k, ch, seconds));
+        closed += 1;
+      }
+    }
+  }
+
+  // notes and pedal spans sorted by start time within each track
+  for track in &mut tracks {
+    track.notes.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
+    track.sustain.sort_by(|a, b| a.start.total_cmp(&b.start));
+  }
+  (tracks, closed)
+}
+
+// Problems in the chunk layout after the header: track chunks whose declared length runs past the
+// end of the file, and a track count different from the header's.
+fn chunk_warnings(raw: &[u8]) -> Vec<String> {
+  let mut warnings = Vec::new();
+  let (mut at, mut declared, mut found) = (0, None, 0);
+  while at + 8 <= raw.len() {
+    let id = &raw[at..at + 4];
+    let len = u32::from_be_bytes([raw[at + 4], raw[at + 5], raw[at + 6], raw[at + 7]]) as usize;
+    let data = &raw[at + 8..raw.len().min(at + 8 + len)];
+    match id {
+      b"MThd" if declared.is_none() && data.len() >= 4 => declared = Some(u16::from_be_bytes([data[2], data[3]]) as usize),
+      b"MTrk" => {
+        if data.len() < len {
+          warnings.push(format!("track {} is truncated: {} of {} bytes", found, data.len(), len));
+        }
+        found += 1;
+      }
+      _ => {}
+    }
+    at += 8 + len;
+  }
+  if let Some(declared) = declared.filter(|d| *d != found) {
+    warnings.push(format!("header declares {} tracks, found {}", declared, found));
+  }
+  warnings
+}
+
+/// Like [`load_midi_tracks_from_memory`] for damaged files (truncated downloads, bogus events from
+/// transcription tools): bytes before the `MThd` header are skipped, each track keeps its events up
+/// to the first one that can't be decoded, and notes left without a note-off end at the last event.
+/// Returns the tracks with one warning per problem worked around; fails only without a usable header.
+pub fn load_midi_tracks_lenient(content: &[u8], confidence: ConfidenceSource, overlap: OverlapPolicy) -> Result<(Vec<MidiTrack>, Vec<String>), String> {
+  let mut warnings = Vec::new();
+  let start = if content.starts_with(b"RIFF") { Some(0) } else { content.windows(4).position(|w| w == b"MThd") };
+  let raw = &content[start.ok_or("failed to parse midi: no MThd header")?..];
+  if raw.len() < content.len() {
+    warnings.push(format!("skipped {} bytes before the midi header", content.len() - raw.len()));
+  }
+  let (header, track_iter) = midly::parse(raw).map_err(|e| format!("failed to parse midi: {}", e))?;
+  let ticks_per_quarter = ticks_per_quarter(header.timing)?;
+  if raw.starts_with(b"MThd") {
+    warnings.extend(chunk_warnings(raw));
+  }
+
+  let mut smf_tracks = Vec::new();
+  for (index, events) in track_iter.enumerate() {
+    let Ok(mut events) = events else {
+      warnings.push(format!("track {} could not be read", index));
+      continue;
+    };
+    let total = events.unread().len();
+    let mut track = Vec::new();
+    loop {
+      let left = events.unread().len();
+      match events.next() {
+        Some(Ok(event)) => track.push(event),
+        // midly stops a track silently when an event can't be decoded, leaving bytes unread
+        None if left > 0 => {
+          warnings.push(format!("track {}: invalid event at byte {}, dropped the last {} bytes", index, total - left, left));
+          break;
+        }
+        Some(Err(e)) => {
+          warnings.push(format!("track {}: invalid event at byte {}: {}", index, total - left, e));
+          break;
+        }
+        None => break,
+      }
+    }
+    smf_tracks.push(track);
+  }
+
+  let (tracks, closed) = parse_tracks(ticks_per_quarter, &smf_tracks, confidence, overlap, true);
+  if closed > 0 {
+    warnings.push(format!("{} notes had no note-off and end at the last event", closed));
+  }
+  Ok((tracks, warnings))
+}
+
+/// From `time` (seconds) on, the tempo is `bpm` quarter notes per minute.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TempoChange {
+  pub time: f64,
+  pub bpm: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TimeSignature {
+  pub time: f64,
+  pub numerator: u8,
+  pub denominator: u32,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct KeySignatureChange {
+  pub time: f64,
+  /// number of sharps, negative for flats
+  pub sharps: i8,
+  pub minor: bool,
+}
+
+/// Timing structure of a MIDI file, times in seconds.
+#[derive(Debug, Serialize)]
+pub struct MidiMeta {
+  pub ticks_per_quarter: u16,
+  /// starts with the tempo in effect at 0s (120 bpm when the file doesn't set one)
+  pub tempos: Vec<TempoChange>,
+  /// time signature events; bars are 4/4 until the first one
+  pub time_signatures: Vec<TimeSignature>,
+  pub key_signatures: Vec<KeySignatureChange>,
+  /// start of every bar up to the last event, for drawing measure lines
+  pub bars: Vec<f64>,
+}
+/// Tick to seconds conversion following the tempo changes of a MIDI file.
+pub struct TempoMap {
+  ticks_per_quarter: u16,
+  // (tick, seconds at tick, microseconds per quarter) for each tempo in effect
+  segments: Vec<(u64, f64, u32)>,
+}
+
+impl TempoMap {
+  pub fn new(smf: &midly::Smf) -> Result<TempoMap, String> {
+    let ticks_per_quarter = match smf.header.timing {
+      midly::Timing::Metrical(t) => t.as_int(),
+      _ => return Err("SMPTE time formats are not supported".to_string()),
+    };
+    let mut tempos: Vec<(u64, u32)> = Vec::new();
+    for track in &smf.tracks {
+      let mut abs: u64 = 0;
+      for ev in track {
+        abs = abs.wrapping_add(ev.delta.as_int() as u64);
+        if let midly::TrackEventKind::Meta(midly::MetaMessage::Tempo(t)) = ev.kind {
+          tempos.push((abs, t.as_int()));
+        }
+      }
+    }
+    tempos.sort_by_key(|(t, _)| *t);
+
+    let mut map = TempoMap { ticks_per_quarter, segments: vec![(0, 0.0, 500_000)] };
+    for (tick, tempo) in tempos {
+      let seconds = map.seconds(tick);
+      // a later tempo at the same tick replaces the earlier one
+      if map.segments.last().is_some_and(|s| s.0 == tick) {
+        map.segments.pop();
+      }
+      map.segments.push((tick, seconds, tempo));
+    }
+    Ok(map)
+  }
+
+  pub fn seconds(&self, tick: u64) -> f64 {
+    let (start, seconds, micros) = *self.segments.iter().rev().find(|s| s.0 <= tick).unwrap_or(&self.segments[0]);
+    seconds + (tick - start) as f64 * micros as f64 / self.ticks_per_quarter as f64 / 1_000_000.0
+  }
+}
+
+/// Parse the timing structure of MIDI content, see [`MidiMeta`].
+pub fn load_midi_meta_from_memory(content: &[u8]) -> Result<MidiMeta, String> {
+  use midly::MetaMessage;
+
+  let smf = midly::Smf::parse(content).map_err(|e| format!("failed to parse midi: {}", e))?;
+  let tempo_map = TempoMap::new(&smf)?;
+  let ticks_per_quarter = tempo_map.ticks_per_quarter;
+
+  let mut end_tick = 0u64;
+  let mut events: Vec<(u64, MetaMessage)> = Vec::new();
+  for track in &smf.tracks {
+    let mut abs: u64 = 0;
+    for ev in track {
+      abs = abs.wrapping_add(ev.delta.as_int() as u64);
+      end_tick = end_tick.max(abs);
+      if let midly::TrackEventKind::Meta(meta) = ev.kind {
+        events.push((abs, meta));
+      }
+    }
+  }
+  events.sort_by_key(|(t, _)| *t);
+  let to_seconds = |tick: u64| tempo_map.seconds(tick);
+
+  let mut signatures: Vec<(u64, u8, u32)> = Vec::new();
+  let mut key_signatures = Vec::new();
+  for (tick, meta) in &events {
+    match meta {
+      MetaMessage::TimeSignature(numerator, power, _, _) if *numerator > 0 => signatures.push((*tick, *numerator, 1u32 << power.min(&6))),
+      MetaMessage::KeySignature(sharps, minor) => key_signatures.push(KeySignatureChange { time: to_seconds(*tick), sharps: *sharps, minor: *minor }),
+      _ => {}
+    }
+  }
+
+  let mut bars = Vec::new();
+  let mut sections = signatures.clone();
+  if sections.first().is_none_or(|s| s.0 > 0) {
+    sections.insert(0, (0, 4, 4));
+  }
+  for (i, &(start, numerator, denominator)) in sections.iter().enumerate() {
+    let until = sections.get(i + 1).map_or(end_tick, |s| s.0);
+    let bar_ticks = (numerator as u64 * ticks_per_quarter as u64 * 4 / denominator as u64).max(1);
+    bars.extend((start..until).step_by(bar_ticks as usize).map(to_seconds));
+  }
+
+  Ok(MidiMeta {
+    ticks_per_quarter,
+    tempos: tempo_map.segments.iter().map(|&(_, time, micros)| TempoChange { time, bpm: 60_000_000.0 / micros as f64 }).collect(),
+    time_signatures: signatures.into_iter().map(|(tick, numerator, denominator)| TimeSignature { time: to_seconds(tick), numerator, denominator }).collect(),
+    key_signatures,
+    bars,
+  })
+}
+
+// Resolution used by `encode_midi`: 480 ticks per quarter at the default 120 bpm.
+const ENCODE_TICKS_PER_QUARTER: u16 = 480;
+const ENCODE_TICKS_PER_SECOND: f64 = ENCODE_TICKS_PER_QUARTER as f64 * 2.0;
+
+// Note on/off and pitch bend events for `notes`, ending with end-of-track, at `ticks_per_second`
+// resolution. Channels bent further than the default range get an RPN 0 bend range first.
+fn note_track(notes: &[Note], ticks_per_second: f64) -> Result<Vec<midly::TrackEvent<'static>>, String> {
+  use midly::num::{u28, u4, u7};
+  use midly::{MetaMessage, MidiMessage, PitchBend, TrackEvent, TrackEventKind};
+
+  let ticks = |seconds: f64| (seconds.max(0.0) * ticks_per_second).round() as u64;
+  let mut ranges = [DEFAULT_BEND_RANGE; 16];
+  for n in notes {
+    let range = &mut ranges[n.channel.min(15) as usize];
+    for (_, offset) in &n.bend {
+      *range = range.max(offset.abs().ceil().min(24.0));
+    }
+  }
+
+  // (tick, order, channel, message) sorted so that on the same tick note-offs come first, then
+  // bend resets of ended notes, then new bends, then note-ons
+  let mut events: Vec<(u64, u8, u8, MidiMessage)> = Vec::with_capacity(notes.len() * 2);
+  for (channel, range) in ranges.iter().enumerate().filter(|(_, r)| **r != DEFAULT_BEND_RANGE) {
+    for (controller, value) in [(101, 0), (100, 0), (6, *range as u8)] {
+      events.push((0, 0, channel as u8, MidiMessage::Controller { controller: u7::new(controller), value: u7::new(value) }));
+    }
+  }
+  for n in notes {
+    let channel = n.channel.min(15);
+    let key = u7::new(n.note.clamp(0, 127) as u8);
+    let (on, off) = (ticks(n.start), ticks(n.start + n.duration).max(ticks(n.start) + 1));
+    events.push((on, 3, channel, MidiMessage::NoteOn { key, vel: u7::new(n.velocity.round().clamp(1.0, 127.0) as u8) }));
+    events.push((off, 0, channel, MidiMessage::NoteOff { key, vel: u7::new(0) }));
+    let range = ranges[channel as usize];
+    for (time, offset) in &n.bend {
+      events.push((ticks(*time).clamp(on, off), 2, channel, MidiMessage::PitchBend { bend: PitchBend::from_f64((offset / range).clamp(-1.0, 1.0)) }));
+    }
+    if n.bend.last().is_some_and(|(_, offset)| *offset != 0.0) {
+      events.push((off, 1, channel, MidiMessage::PitchBend { bend: PitchBend::mid_raw_value() }));
+    }
+  }
+  events.sort_by_key(|(t, order, _, _)| (*t, *order));
+
+  let mut track = Vec::with_capacity(events.len() + 1);
+  let mut last = 0u64;
+  for (tick, _, channel, message) in events {
+    let delta = u28::try_from((tick - last) as u32).ok_or_else(|| "note time out of range for midi".to_string())?;
+    track.push(TrackEvent { delta, kind: TrackEventKind::Midi { channel: u4::new(channel), message } });
+    last = tick;
+  }
+  track.push(TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
+  Ok(track)
+}
+
+fn write_smf(format: midly::Format, tracks: Vec<Vec<midly::TrackEvent<'_>>>) -> Result<Vec<u8>, String> {
+  let smf = midly::Smf { header: midly::Header::new(format, midly::Timing::Metrical(midly::num::u15::new(ENCODE_TICKS_PER_QUARTER))), tracks };
+  let mut out = Vec::new();
+  smf.write_std(&mut out).map_err(|e| format!("failed to write midi: {}", e))?;
+  Ok(out)
+}
+
+/// Encode notes as a single-track standard MIDI file, readable by [`load_midi_from_memory`].
+pub fn encode_midi(notes: &[Note]) -> Result<Vec<u8>, String> {
+  write_smf(midly::Format::SingleTrack, vec![note_track(notes, ENCODE_TICKS_PER_SECOND)?])
+}
+
+/// Like [`encode_midi`] with a tempo event, so the notes line up with the beats of a song at `bpm`
+/// when opened in a MIDI editor.
+pub fn encode_midi_with_tempo(notes: &[Note], bpm: f64) -> Result<Vec<u8>, String> {
+  use midly::num::{u24, u28};
+  use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+  if !(bpm.is_finite() && bpm > 0.0) {
+    return Err(format!("invalid tempo: {}", bpm));
+  }
+  let mut track = vec![TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new((60_000_000.0 / bpm).round() as u32))) }];
+  track.extend(note_track(notes, ENCODE_TICKS_PER_QUARTER as f64 * bpm / 60.0)?);
+  write_smf(midly::Format::SingleTrack, vec![track])
+}
+
+// General MIDI percussion channel and the wood blocks used for clicks
+const CLICK_CHANNEL: u8 = 9;
+const CLICK_ACCENT: i32 = 76;
+const CLICK_BEAT: i32 = 77;
+
+/// Key of a click track, for the key signature: tonic pitch class (0 = C) and minor.
+pub type KeySignature = (usize, bool);
+
+/// Encode a 4/4 click track at `bpm` covering `duration` seconds, for importing into a DAW.
+/// The first track holds tempo, time and key signature and the clicks (accented downbeats on the
+/// percussion channel); `melody` notes go on a second track.
+pub fn encode_click_midi(bpm: f64, duration: f64, key: Option<KeySignature>, melody: Option<&[Note]>) -> Result<Vec<u8>, String> {
+  use midly::num::{u24, u28};
+  use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+  if !(bpm.is_finite() && bpm > 0.0) {
+    return Err(format!("invalid tempo: {}", bpm));
+  }
+  let beat = 60.0 / bpm;
+  let clicks: Vec<Note> = (0..(duration.max(0.0) / beat).ceil() as usize)
+    .map(|i| {
+      let (note, velocity) = if i % 4 == 0 { (CLICK_ACCENT, 120.0) } else { (CLICK_BEAT, 90.0) };
+      Note { note, start: i as f64 * beat, duration: beat / 4.0, velocity, channel: CLICK_CHANNEL, confidence: None, bend: Vec::new() }
+    })
+    .collect();
+
+  let at_start = |kind| TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(kind) };
+  let mut conductor = vec![
+    at_start(MetaMessage::Tempo(u24::new((60_000_000.0 / bpm).round() as u32))),
+    at_start(MetaMessage::TimeSignature(4, 2, 24, 8)),
+  ];
+  if let Some((tonic, minor)) = key {
+    // circle of fifths position of the (relative) major key, in -5..=6 sharps
+    let major = if minor { (tonic + 3) % 12 } else { tonic % 12 };
+    let fifths = (major * 7 % 12) as i8;
+    conductor.push(at_start(MetaMessage::KeySignature(if fifths > 6 { fifths - 12 } else { fifths }, minor)));
+  }
+  let ticks_per_second = ENCODE_TICKS_PER_QUARTER as f64 / beat;
+  conductor.extend(note_track(&clicks, ticks_per_second)?);
+
+  let mut tracks = vec![conductor];
+  if let Some(melody) = melody {
+    tracks.push(note_track(melody, ticks_per_second)?);
+  }
+  write_smf(midly::Format::Parallel, tracks)
+}
+
+// bytes of `pack_notes` before the note records, and per record and bend point
+const PACKED_HEADER_LEN: usize = 8;
+const PACKED_NOTE_LEN: usize = 20;
+const PACKED_BEND_LEN: usize = 8;
+
+/// Pack notes into a compact little-endian binary layout, much faster to send to the frontend
+/// than JSON for long songs:
+/// - header: `note_count: u32, bend_count: u32`
+/// - per note: `start: f32, duration: f32, confidence: f32` (NaN when none), `bends: u32`
+///   (its number of bend points), `note: u8, velocity: u8, channel: u8, 0u8`
+/// - then the bend points of all notes in note order: `time: f32, semitones: f32`
+pub fn pack_notes(notes: &[Note]) -> Vec<u8> {
+  let bends: usize = notes.iter().map(|n| n.bend.len()).sum();
+  let mut out = Vec::with_capacity(PACKED_HEADER_LEN + notes.len() * PACKED_NOTE_LEN + bends * PACKED_BEND_LEN);
+  out.extend_from_slice(&(notes.len() as u32).to_le_bytes());
+  out.extend_from_slice(&(bends as u32).to_le_bytes());
+  for n in notes {
+    out.extend_from_slice(&(n.start as f32).to_le_bytes());
+    out.extend_from_slice(&(n.duration as f32).to_le_bytes());
+    out.extend_from_slice(&n.confidence.map_or(f32::NAN, |c| c as f32).to_le_bytes());
+    out.extend_from_slice(&(n.bend.len() as u32).to_le_bytes());
+    out.extend_from_slice(&[n.note.clamp(0, 127) as u8, n.velocity.round().clamp(0.0, 127.0) as u8, n.channel, 0]);
+  }
+  for (time, semitones) in notes.iter().flat_map(|n| &n.bend) {
+    out.extend_from_slice(&(*time as f32).to_le_bytes());
+    out.extend_from_slice(&(*semitones as f32).to_le_bytes());
+  }
+  out
+}
+
+#[test]
+pub fn test_midi() {
+  let content = include_bytes!("../../../res/我的一个道姑朋友_vocals_pitches.mid");
+
+  let notes = load_midi_from_memory(content).expect("failed to parse midi from memory");
+  println!("{:?}", notes.iter().take(10).collect::<Vec<_>>());
+  assert_eq!(notes.len(), 768);
+}
+
+#[test]
+pub fn test_load_midi_lenient() {
+  let notes = vec![
+    Note { note: 60, start: 0.5, duration: 1.0, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() },
+    Note { note: 64, start: 1.0, duration: 1.0, velocity: 90.0, channel: 0, confidence: None, bend: Vec::new() },
+  ];
+  let bytes = encode_midi(&notes).expect("failed to encode midi");
+  let (tracks, warnings) = load_midi_tracks_lenient(&bytes, ConfidenceSource::None, OverlapPolicy::default()).expect("parse intact file");
+  assert_eq!((flatten_tracks(tracks).len(), warnings.len()), (2, 0));
+
+  // garbage before the header, cut off before the last note-off
+  let mut damaged = b"<html>".to_vec();
+  damaged.extend(&bytes[..bytes.len() - 8]);
+  assert!(load_midi_tracks_from_memory(&damaged, ConfidenceSource::None, OverlapPolicy::default()).is_err());
+  let (tracks, warnings) = load_midi_tracks_lenient(&damaged, ConfidenceSource::None, OverlapPolicy::default()).expect("salvage damaged file");
+  let salvaged = flatten_tracks(tracks);
+  assert_eq!(salvaged.iter().map(|n| (n.note, n.start, n.duration)).collect::<Vec<_>>(), vec![(60, 0.5, 1.0), (64, 1.0, 0.5)]);
+  assert!(warnings.iter().any(|w| w.starts_with("1 notes had no note-off")));
+  assert!(warnings.iter().any(|w| w.starts_with("skipped 6 bytes")));
+  assert!(warnings.iter().any(|w| w.contains("truncated")));
+  assert!(load_midi_tracks_lenient(b"not midi", ConfidenceSource::None, OverlapPolicy::default()).is_err());
+}
+
+#[test]
+pub fn test_encode_midi() {
+  let notes = vec![
+    Note { note: 60, start: 0.5, duration: 1.0, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() },
+    Note { note: 64, start: 1.5, duration: 0.25, velocity: 90.0, channel: 1, confidence: None, bend: Vec::new() },
+  ];
+  let decoded = load_midi_from_memory(&encode_midi(&notes).expect("failed to encode midi")).expect("failed to decode midi");
+  assert_eq!(decoded.len(), 2);
+  assert_eq!((decoded[0].note, decoded[0].start, decoded[0].duration), (60, 0.5, 1.0));
+  assert_eq!((decoded[1].note, decoded[1].channel, decoded[1].velocity), (64, 1, 90.0));
+}
+
+#[test]
+pub fn test_pack_notes() {
+  let notes = vec![
+    Note { note: 60, start: 0.5, duration: 1.0, velocity: 100.0, channel: 0, confidence: Some(0.75), bend: vec![(0.75, 1.0), (1.0, -0.5)] },
+    Note { note: 64, start: 1.5, duration: 0.25, velocity: 90.0, channel: 1, confidence: None, bend: Vec::new() },
+  ];
+  let packed = pack_notes(&notes);
+  assert_eq!(packed.len(), PACKED_HEADER_LEN + 2 * PACKED_NOTE_LEN + 2 * PACKED_BEND_LEN);
+  let f32_at = |i: usize| f32::from_le_bytes(packed[i..i + 4].try_into().unwrap());
+  let u32_at = |i: usize| u32::from_le_bytes(packed[i..i + 4].try_into().unwrap());
+  assert_eq!((u32_at(0), u32_at(4)), (2, 2));
+  assert_eq!((f32_at(8), f32_at(12), f32_at(16), u32_at(20)), (0.5, 1.0, 0.75, 2));
+  assert_eq!(&packed[24..28], &[60, 100, 0, 0]);
+  let second = PACKED_HEADER_LEN + PACKED_NOTE_LEN;
+  assert!(f32_at(second + 8).is_nan());
+  assert_eq!(&packed[second + 16..second + 20], &[64, 90, 1, 0]);
+  let points = PACKED_HEADER_LEN + 2 * PACKED_NOTE_LEN;
+  assert_eq!((f32_at(points), f32_at(points + 4), f32_at(points + 12)), (0.75, 1.0, -0.5));
+}
+
+#[test]
+pub fn test_encode_midi_with_tempo() {
+  let notes = vec![
+    Note { note: 62, start: 0.5, duration: 1.0, velocity: 80.0, channel: 0, confidence: None, bend: vec![(0.75, 1.0), (1.0, -3.5)] },
+    Note { note: 65, start: 1.5, duration: 0.5, velocity: 80.0, channel: 0, confidence: None, bend: Vec::new() },
+  ];
+  let bytes = encode_midi_with_tempo(&notes, 90.0).expect("failed to encode midi");
+  let meta = load_midi_meta_from_memory(&bytes).expect("failed to decode midi meta");
+  assert!((meta.tempos[0].bpm - 90.0).abs() < 1e-3);
+
+  let decoded = load_midi_from_memory(&bytes).expect("failed to decode midi");
+  assert_eq!(decoded.len(), 2);
+  assert!((decoded[0].start - 0.5).abs() < 1e-3 && (decoded[0].duration - 1.0).abs() < 1e-3);
+  // the bend range is widened to 4 semitones for the -3.5 bend
+  assert_eq!(decoded[0].bend.len(), 2);
+  assert!((decoded[0].bend[0].1 - 1.0).abs() < 1e-3 && (decoded[0].bend[1].1 + 3.5).abs() < 1e-3);
+  // the wheel is reset when the bent note ends
+  assert!(decoded[1].bend.is_empty());
+  assert!(encode_midi_with_tempo(&notes, f64::NAN).is_err());
+}
+
+#[test]
+pub fn test_encode_click_midi() {
+  let melody = vec![Note { note: 67, start: 1.0, duration: 0.5, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() }];
+  let bytes = encode_click_midi(90.0, 4.0, Some((9, true)), Some(&melody)).expect("failed to encode click midi");
+  let smf = midly::Smf::parse(&bytes).expect("failed to parse click midi");
+  assert_eq!(smf.tracks.len(), 2);
+  assert!(smf.tracks[0].iter().any(|e| matches!(e.kind, midly::TrackEventKind::Meta(midly::MetaMessage::KeySignature(0, true)))));
+
+  let decoded = load_midi_from_memory(&bytes).expect("failed to decode click midi");
+  let clicks: Vec<&Note> = decoded.iter().filter(|n| n.channel == CLICK_CHANNEL).collect();
+  // 4s at 90 bpm is 6 beats
+  assert_eq!(clicks.len(), 6);
+  assert!((clicks[1].start - 60.0 / 90.0).abs() < 1e-3);
+  assert_eq!((clicks[0].note, clicks[4].note), (CLICK_ACCENT, CLICK_ACCENT));
+  let sung = decoded.iter().find(|n| n.channel == 0).expect("melody note");
+  assert!((sung.start - 1.0).abs() < 1e-3 && (sung.duration - 0.5).abs() < 1e-3);
+  assert!(encode_click_midi(0.0, 4.0, None, None).is_err());
+}
+
+#[test]
+pub fn test_load_midi_meta() {
+  use midly::num::{u24, u28};
+  use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+  let meta = |delta: u32, kind| TrackEvent { delta: u28::new(delta), kind: TrackEventKind::Meta(kind) };
+  // two bars of 4/4 at 120 bpm, then 3/4 at 60 bpm for two bars
+  let track = vec![
+    meta(0, MetaMessage::Tempo(u24::new(500_000))),
+    meta(0, MetaMessage::KeySignature(-1, false)),
+    meta(3840, MetaMessage::Tempo(u24::new(1_000_000))),
+    meta(0, MetaMessage::TimeSignature(3, 2, 24, 8)),
+    meta(2880, MetaMessage::EndOfTrack),
+  ];
+  let bytes = write_smf(midly::Format::SingleTrack, vec![track]).expect("failed to write midi");
+  let parsed = load_midi_meta_from_memory(&bytes).expect("failed to parse midi meta");
+
+  assert_eq!(parsed.tempos, vec![TempoChange { time: 0.0, bpm: 120.0 }, TempoChange { time: 4.0, bpm: 60.0 }]);
+  assert_eq!(parsed.time_signatures, vec![TimeSignature { time: 4.0, numerator: 3, denominator: 4 }]);
+  assert_eq!(parsed.key_signatures, vec![KeySignatureChange { time: 0.0, sharps: -1, minor: false }]);
+  assert_eq!(parsed.bars, vec![0.0, 2.0, 4.0, 7.0]);
+}
+
+#[test]
+pub fn test_load_midi_tracks() {
+  use midly::num::u28;
+  use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+  let melody = vec![Note { note: 67, start: 0.5, duration: 0.5, velocity: 100.0, channel: 0, confidence: None, bend: Vec::new() }];
+  let bass = vec![Note { note: 40, start: 0.0, duration: 1.0, velocity: 80.0, channel: 0, confidence: None, bend: Vec::new() }];
+  let named = |name: &'static [u8], notes: &[Note]| {
+    let mut track = vec![TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::TrackName(name)) }];
+    track.extend(note_track(notes, ENCODE_TICKS_PER_SECOND).expect("failed to encode track"));
+    track
+  };
+  let bytes = write_smf(midly::Format::Parallel, vec![named(b"Vocals", &melody), named(b"Bass", &bass)]).expect("failed to write midi");
+
+  let tracks = load_midi_tracks_from_memory(&bytes, ConfidenceSource::None, OverlapPolicy::default()).expect("failed to parse tracks");
+  assert_eq!(tracks.iter().map(|t| (t.index, t.name.as_deref(), t.notes.len())).collect::<Vec<_>>(), vec![(0, Some("Vocals"), 1), (1, Some("Bass"), 1)]);
+  assert_eq!(tracks[0].notes[0].note, 67);
+
+  let only_melody = flatten_tracks(select_tracks(tracks, Some(&[0])));
+  assert_eq!(only_melody.iter().map(|n| n.note).collect::<Vec<_>>(), vec![67]);
+  // both channel 0: notes of different tracks don't end each other
+  let all = load_midi_from_memory(&bytes).expect("failed to parse notes");
+  assert_eq!(all.iter().map(|n| (n.note, n.duration)).collect::<Vec<_>>(), vec![(40, 1.0), (67, 0.5)]);
+}
+
+#[test]
+pub fn test_apply_sustain() {
+  use midly::num::{u28, u4, u7};
+  use midly::{MidiMessage, TrackEvent, TrackEventKind};
+
+  let quarter = (ENCODE_TICKS_PER_SECOND / 4.0) as u32;
+  let midi = |message: MidiMessage| TrackEventKind::Midi { channel: u4::new(0), message };
+  let key = |note: u8, on: bool| midi(if on { MidiMessage::NoteOn { key: u7::new(note), vel: u7::new(100) } } else { MidiMessage::NoteOff { key: u7::new(note), vel: u7::new(0) } });
+  let pedal = |down: bool| midi(MidiMessage::Controller { controller: u7::new(SUSTAIN_CONTROLLER), value: u7::new(if down { 127 } else { 0 }) });
+  // (time in quarter seconds, event): pedal held from 0.25s to 2.5s
+  let events = [
+    (0, key(60, true)),
+    (1, pedal(true)),
+    (2, key(60, false)),
+    (2, key(64, true)),
+    (3, key(64, false)),
+    (6, key(60, true)),
+    (7, key(60, false)),
+    (10, pedal(false)),
+    (12, key(67, true)),
+    (14, key(67, false)),
+  ];
+  let mut last = 0;
+  let track: Vec<TrackEvent> = events
+    .into_iter()
+    .map(|(time, kind)| {
+      let delta = (time - last) * quarter;
+      last = time;
+      TrackEvent { delta: u28::new(delta), kind }
+    })
+    .collect();
+  let bytes = write_smf(midly::Format::SingleTrack, vec![track]).expect("failed to write midi");
+
+  let mut tracks = load_midi_tracks_from_memory(&bytes, ConfidenceSource::None, OverlapPolicy::default()).expect("failed to parse tracks");
+  assert_eq!(tracks[0].sustain, vec![SustainSpan { channel: 0, start: 0.25, end: 2.5 }]);
+  apply_sustain(&mut tracks[0]);
+  let durations: Vec<(i32, f64)> = tracks[0].notes.iter().map(|n| (n.note, n.duration)).collect();
+  // the first C is cut by its retrigger, the E and second C ring to the pedal release, the G comes after it
+  assert_eq!(durations, vec![(60, 1.5), (64, 2.0), (60, 1.0), (67, 0.5)]);
+}
+
+#[test]
+pub fn test_overlap_policy() {
+  use midly::num::{u28, u4, u7};
+  use midly::{MetaMessage, MidiMessage, TrackEvent, TrackEventKind};
+
+  // 480 ticks per quarter at 120 bpm: 960 ticks per second
+  let event = |delta: u32, on: bool| {
+    let (key, vel) = (u7::new(60), u7::new(if on { 100 } else { 0 }));
+    let message = if on { MidiMessage::NoteOn { key, vel } } else { MidiMessage::NoteOff { key, vel } };
+    TrackEvent { delta: u28::new(delta), kind: TrackEventKind::Midi { channel: u4::new(0), message } }
+  };
+  // on at 0s, on again at 1s, off at 2s and 3s
+  let mut track = vec![event(0, true), event(960, true), event(960, false), event(960, false)];
+  track.push(TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
+  let bytes = write_smf(midly::Format::SingleTrack, vec![track]).expect("failed to write midi");
+
+  let spans = |overlap| {
+    let tracks = load_midi_tracks_from_memory(&bytes, ConfidenceSource::None, overlap).expect("failed to parse tracks");
+    flatten_tracks(tracks).iter().map(|n| (n.start, n.start + n.duration)).collect::<Vec<_>>()
+  };
+  assert_eq!(spans(OverlapPolicy::TruncatePrevious), vec![(0.0, 1.0), (1.0, 2.0)]);
+  assert_eq!(spans(OverlapPolicy::Layer), vec![(0.0, 2.0), (1.0, 3.0)]);
+  assert_eq!(spans(OverlapPolicy::Ignore), vec![(0.0, 2.0)]);
+}
+
+#[test]
+pub fn test_transpose() {
+  let note = |note, channel| Note { note, start: 0.0, duration: 1.0, velocity: 100.0, channel, confidence: None, bend: Vec::new() };
+  let mut track = MidiTrack { index: 0, name: None, instrument: None, notes: vec![note(60, 0), note(125, 0), note(36, DRUM_CHANNEL)], sustain: Vec::new() };
+  transpose(&mut track, 3);
+  assert_eq!(track.notes.iter().map(|n| n.note).collect::<Vec<_>>(), vec![63, 127, 36]);
+  transpose(&mut track, -12);
+  assert_eq!(track.notes[0].note, 51);
+}
+
+#[test]
+pub fn test_pitch_bend() {
+  use midly::num::{u14, u28, u4, u7};
+  use midly::{MidiMessage, PitchBend, TrackEvent, TrackEventKind};
+
+  let quarter = (ENCODE_TICKS_PER_SECOND / 4.0) as u32;
+  let midi = |message: MidiMessage| TrackEventKind::Midi { channel: u4::new(0), message };
+  let key = |note: u8, vel: u8| midi(MidiMessage::NoteOn { key: u7::new(note), vel: u7::new(vel) });
+  let bend = |semitones: f64, range: f64| midi(MidiMessage::PitchBend { bend: PitchBend::from_f64(semitones / range) });
+  let cc = |controller: u8, value: u8| midi(MidiMessage::Controller { controller: u7::new(controller), value: u7::new(value) });
+  // (time in quarter seconds, event): a slide up a whole tone, then a note started bent down
+  // after the range is set to 12 semitones through RPN 0
+  let events = [
+    (0, key(60, 100)),
+    (1, bend(1.0, DEFAULT_BEND_RANGE)),
+    (2, bend(-0.5 * DEFAULT_BEND_RANGE, DEFAULT_BEND_RANGE)),
+    (4, key(60, 0)),
+    (4, cc(101, 0)),
+    (4, cc(100, 0)),
+    (4, cc(6, 12)),
+    (4, bend(-6.0, 12.0)),
+    (5, key(64, 100)),
+    (6, midi(MidiMessage::PitchBend { bend: PitchBend(u14::new(0x2000)) })),
+    (8, key(64, 0)),
+  ];
+  let mut last = 0;
+  let track: Vec<TrackEvent> = events
+    .into_iter()
+    .map(|(time, kind)| {
+      let delta = (time - last) * quarter;
+      last = time;
+      TrackEvent { delta: u28::new(delta), kind }
+    })
+    .collect();
+  let bytes = write_smf(midly::Format::SingleTrack, vec![track]).expect("failed to write midi");
+
+  let notes = load_midi_from_memory(&bytes).expect("failed to parse notes");
+  assert_eq!(notes.len(), 2);
+  assert_eq!(notes[0].bend, vec![(0.25, 1.0), (0.5, -1.0)]);
+  assert_eq!(notes[1].bend, vec![(1.25, -6.0), (1.5, 0.0)]);
+}
+
+#[test]
+pub fn test_confidence_markers() {
+  use midly::num::{u28, u4, u7};
+  use midly::{MetaMessage, MidiMessage, TrackEvent, TrackEventKind};
+
+  let at = |delta: u32, kind| TrackEvent { delta: u28::new(delta), kind };
+  let key = |note: u8, vel: u8| TrackEventKind::Midi { channel: u4::new(0), message: MidiMessage::NoteOn { key: u7::new(note), vel: u7::new(vel) } };
+  // a text marker, a SysEx marker, a foreign SysEx and an unmarked note
+  let track = vec![
+    at(0, TrackEventKind::Meta(MetaMessage::Text(b"klok:confidence=0.25"))),
+    at(0, key(60, 127)),
+    at(240, key(60, 0)),
+    at(0, TrackEventKind::SysEx(b"\x7dklok:confidence=0.75\xf7")),
+    at(0, key(62, 127)),
+    at(240, key(62, 0)),
+    at(0, TrackEventKind::SysEx(b"\x41klok:confidence=0.5\xf7")),
+    at(0, key(64, 127)),
+    at(240, key(64, 0)),
+    at(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+  ];
+  let bytes = write_smf(midly::Format::SingleTrack, vec![track]).expect("failed to write midi");
+
+  let notes = load_midi_from_memory_with(&bytes, ConfidenceSource::Meta).expect("failed to parse notes");
+  assert_eq!(notes.iter().map(|n| n.confidence).collect::<Vec<_>>(), vec![Some(0.25), Some(0.75), None]);
+  let notes = load_midi_from_memory_with(&bytes, ConfidenceSource::Velocity).expect("failed to parse notes");
+  assert!(notes.iter().all(|n| n.confidence == Some(1.0)));
+  let notes = load_midi_from_memory_with(&bytes, ConfidenceSource::None).expect("failed to parse notes");
+  assert!(notes.iter().all(|n| n.confidence.is_none()));
+}
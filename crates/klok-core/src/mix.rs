@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pcm::Pcm;
+
+/// Which channels of a stereo song are played, for karaoke files with the instrumental on one
+/// channel and a guide vocal on the other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMode {
+  /// the channels as they are
+  #[default]
+  Stereo,
+  /// the left channel on every channel
+  Left,
+  Right,
+  /// the channels averaged
+  Mono,
+}
+
+/// `pcm` at `sample_rate` (linear interpolation) with `channels` channels: mono is spread to every
+/// channel, several channels mixed down to mono are averaged, others are taken in turn.
+pub fn conform(pcm: &Pcm, sample_rate: u32, channels: u16) -> Pcm {
+  let (from, to) = (pcm.channels.max(1) as usize, channels.max(1) as usize);
+  let frames = pcm.frames();
+  let sample = |frame: usize, channel: usize| -> f32 {
+    if to == 1 && from > 1 {
+      pcm.samples[frame * from..(frame + 1) * from].iter().sum::<f32>() / from as f32
+    } else {
+      pcm.samples[frame * from + channel % from]
+    }
+  };
+  let out_frames = if pcm.sample_rate == sample_rate { frames } else { (frames as u64 * sample_rate as u64 / pcm.sample_rate.max(1) as u64) as usize };
+  let step = pcm.sample_rate as f64 / sample_rate.max(1) as f64;
+  let mut samples = Vec::with_capacity(out_frames * to);
+  for i in 0..out_frames {
+    let at = i as f64 * step;
+    let (frame, frac) = (at as usize, at.fract() as f32);
+    for c in 0..to {
+      let a = sample(frame.min(frames - 1), c);
+      let b = sample((frame + 1).min(frames - 1), c);
+      samples.push(a + (b - a) * frac);
+    }
+  }
+  Pcm { sample_rate, channels: to as u16, samples }
+}
+
+/// The backing stem with the vocal stem added at `vocal_volume` (0 = karaoke, 1 = the original
+/// mix), sample by sample; a vocal shorter than the backing is silent past its end.
+pub fn mix_stems(backing: &[f32], vocal: Option<&[f32]>, vocal_volume: f32) -> Vec<f32> {
+  let mut out = backing.to_vec();
+  if let Some(vocal) = vocal.filter(|_| vocal_volume > 0.0) {
+    out.iter_mut().zip(vocal).for_each(|(o, v)| *o += v * vocal_volume);
+  }
+  out
+}
+
+/// Apply `mode` to interleaved `samples` with `channels` channels in place; mono stays as it is.
+pub fn select_channels(samples: &mut [f32], channels: u16, mode: ChannelMode) {
+  let channels = channels.max(1) as usize;
+  if channels < 2 || mode == ChannelMode::Stereo {
+    return;
+  }
+  for frame in samples.chunks_exact_mut(channels) {
+    let value = match mode {
+      ChannelMode::Left => frame[0],
+      ChannelMode::Right => frame[1],
+      _ => frame.iter().sum::<f32>() / channels as f32,
+    };
+    frame.fill(value);
+  }
+}
+
+/// Add `voice` (conformed to `out`'s rate and channels) to `out` at `gain`, its first sample at
+/// `at` seconds of `out`; a negative `at` drops the start of `voice`, parts past `out`'s end too.
+pub fn overlay(out: &mut Pcm, voice: &Pcm, at: f64, gain: f32) {
+  let voice = conform(voice, out.sample_rate, out.channels);
+  let channels = out.channels.max(1) as usize;
+  let offset = (at * out.sample_rate as f64).round() as isize * channels as isize;
+  let (skip, start) = if offset < 0 { ((-offset) as usize, 0) } else { (0, offset as usize) };
+  for (o, v) in out.samples.iter_mut().skip(start).zip(voice.samples.iter().skip(skip)) {
+    *o += v * gain;
+  }
+}
+
+#[test]
+pub fn test_mix() {
+  let stereo = Pcm { sample_rate: 4, channels: 2, samples: vec![0.25, 0.75, 0.5, 1.0] };
+  assert_eq!(conform(&stereo, 4, 1).samples, vec![0.5f32, 0.75]);
+  let mono = Pcm { sample_rate: 2, channels: 1, samples: vec![0.0, 1.0] };
+  // doubled rate: halfway samples interpolated, the last one held
+  assert_eq!(conform(&mono, 4, 2).samples, vec![0.0, 0.0, 0.5, 0.5, 1.0, 1.0, 1.0, 1.0]);
+  assert_eq!(conform(&stereo, 4, 2), stereo);
+
+  assert_eq!(mix_stems(&[0.5, 0.5, 0.5], Some(&[0.5, -0.5]), 0.5), vec![0.75, 0.25, 0.5]);
+  assert_eq!(mix_stems(&[0.5], Some(&[0.5]), 0.0), vec![0.5]);
+
+  let frames = [0.5, 1.0, 0.0, -1.0];
+  let selected = |mode: ChannelMode| {
+    let mut samples = frames;
+    select_channels(&mut samples, 2, mode);
+    samples
+  };
+  assert_eq!(selected(ChannelMode::Stereo), frames);
+  assert_eq!(selected(ChannelMode::Left), [0.5, 0.5, 0.0, 0.0]);
+  assert_eq!(selected(ChannelMode::Right), [1.0, 1.0, -1.0, -1.0]);
+  assert_eq!(selected(ChannelMode::Mono), [0.75, 0.75, -0.5, -0.5]);
+
+  let mut out = Pcm { sample_rate: 4, channels: 1, samples: vec![0.0; 4] };
+  overlay(&mut out, &Pcm { sample_rate: 4, channels: 1, samples: vec![1.0, 0.5] }, 0.75, 0.5);
+  assert_eq!(out.samples, vec![0.0, 0.0, 0.0, 0.5]);
+  overlay(&mut out, &Pcm { sample_rate: 4, channels: 1, samples: vec![1.0, 0.5] }, -0.25, 1.0);
+  assert_eq!(out.samples, vec![0.5, 0.0, 0.0, 0.5]);
+}
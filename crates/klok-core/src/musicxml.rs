@@ -0,0 +1,402 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::midi::Note;
+
+// tempo until the score gives one, as MusicXML assumes
+const DEFAULT_BPM: f64 = 120.0;
+const DEFAULT_VELOCITY: f64 = 90.0;
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+#[derive(Debug, Default)]
+struct ScoreNote {
+  pitch: i32,
+  /// quarter notes from the start of the measure
+  offset: f64,
+  duration: f64,
+  tie_start: bool,
+  tie_stop: bool,
+}
+
+#[derive(Debug, Default)]
+struct Measure {
+  notes: Vec<ScoreNote>,
+  /// (quarters from the start of the measure, quarter notes per minute)
+  tempos: Vec<(f64, f64)>,
+  /// in quarter notes
+  length: f64,
+  repeat_forward: bool,
+  /// times the repeated section is played, for a backward repeat
+  repeat_backward: Option<u32>,
+  /// passes of a volta bracket this measure is played on
+  ending: Option<Vec<u32>>,
+}
+
+// the `<note>` being read
+#[derive(Debug, Default)]
+struct NoteState {
+  step: Option<i32>,
+  alter: f64,
+  octave: i32,
+  duration: f64,
+  rest: bool,
+  chord: bool,
+  grace: bool,
+  tie_start: bool,
+  tie_stop: bool,
+}
+
+fn attribute(e: &BytesStart<'_>, name: &str) -> Option<String> {
+  e.try_get_attribute(name).ok().flatten().map(|a| a.value.into_owned())
+}
+
+fn step_semitone(step: &str) -> Option<i32> {
+  Some(match step.trim() {
+    "C" => 0,
+    "D" => 2,
+    "E" => 4,
+    "F" => 5,
+    "G" => 7,
+    "A" => 9,
+    "B" => 11,
+    _ => return None,
+  })
+}
+
+// length in quarter notes of a metronome `<beat-unit>`
+fn beat_unit_quarters(unit: &str) -> Option<f64> {
+  Some(match unit.trim() {
+    "whole" => 4.0,
+    "half" => 2.0,
+    "quarter" => 1.0,
+    "eighth" => 0.5,
+    "16th" => 0.25,
+    _ => return None,
+  })
+}
+
+/// Measures of the first part of a `score-partwise` document.
+fn parse_measures(xml: &str) -> Result<Vec<Measure>, String> {
+  let mut reader = Reader::from_str(xml);
+  reader.config_mut().trim_text(true);
+  let mut stack: Vec<String> = Vec::new();
+  let mut measures = Vec::new();
+  let mut measure = Measure::default();
+  let mut note = NoteState::default();
+  let mut divisions = 1.0;
+  let mut cursor = 0.0;
+  let mut last_start = 0.0;
+  // backup/forward duration, metronome beat unit (with dots) and rate
+  let mut shift = 0.0;
+  let (mut beat_unit, mut beat_dots, mut per_minute) = (None, 0, None);
+  let mut ending: Option<Vec<u32>> = None;
+  let mut ending_stops = false;
+
+  loop {
+    let event = reader.read_event().map_err(|e| format!("invalid musicxml at {}: {}", reader.buffer_position(), e))?;
+    match event {
+      Event::Start(ref e) | Event::Empty(ref e) => {
+        let name = e.local_name().as_ref().to_string();
+        match name.as_str() {
+          "measure" => {
+            measure = Measure { ending: ending.clone(), ..Default::default() };
+            cursor = 0.0;
+          }
+          "note" => note = NoteState::default(),
+          "rest" | "unpitched" | "cue" => note.rest = true,
+          "chord" => note.chord = true,
+          "grace" => note.grace = true,
+          "tie" | "tied" => match attribute(e, "type").as_deref() {
+            Some("start") => note.tie_start = true,
+            Some("stop") => note.tie_stop = true,
+            _ => {}
+          },
+          "repeat" => match attribute(e, "direction").as_deref() {
+            Some("forward") => measure.repeat_forward = true,
+            Some("backward") => measure.repeat_backward = Some(attribute(e, "times").and_then(|t| t.parse().ok()).unwrap_or(2)),
+            _ => {}
+          },
+          "ending" => match attribute(e, "type").as_deref() {
+            Some("start") => {
+              let numbers: Vec<u32> = attribute(e, "number").unwrap_or_default().split([',', ' ']).filter_map(|n| n.trim().parse().ok()).collect();
+              ending = Some(numbers);
+              measure.ending = ending.clone();
+            }
+            Some("stop") | Some("discontinue") => ending_stops = true,
+            _ => {}
+          },
+          "sound" => {
+            if let Some(bpm) = attribute(e, "tempo").and_then(|t| t.parse::<f64>().ok()).filter(|t| *t > 0.0) {
+              measure.tempos.push((cursor, bpm));
+            }
+          }
+          "metronome" => (beat_unit, beat_dots, per_minute) = (None, 0, None),
+          "beat-unit-dot" => beat_dots += 1,
+          _ => {}
+        }
+        if matches!(event, Event::Start(_)) {
+          stack.push(name);
+        }
+      }
+      Event::Text(ref t) => {
+        let text = t.trim();
+        let parent = stack.len().checked_sub(2).map(|i| stack[i].as_str());
+        match (stack.last().map(String::as_str), parent) {
+          (Some("divisions"), _) => divisions = text.parse::<f64>().ok().filter(|d| *d > 0.0).unwrap_or(divisions),
+          (Some("step"), Some("pitch")) => note.step = step_semitone(text),
+          (Some("alter"), Some("pitch")) => note.alter = text.parse().unwrap_or(0.0),
+          (Some("octave"), Some("pitch")) => note.octave = text.parse().unwrap_or(4),
+          (Some("duration"), Some("note")) => note.duration = text.parse::<f64>().unwrap_or(0.0) / divisions,
+          (Some("duration"), Some("backup" | "forward")) => shift = text.parse::<f64>().unwrap_or(0.0) / divisions,
+          (Some("beat-unit"), _) => beat_unit = beat_unit_quarters(text),
+          (Some("per-minute"), _) => per_minute = text.parse::<f64>().ok().filter(|p| *p > 0.0),
+          _ => {}
+        }
+      }
+      Event::End(ref e) => {
+        match e.local_name().as_ref() {
+          "note" if !note.grace => {
+            let start = if note.chord { last_start } else { cursor };
+            if let (false, Some(step)) = (note.rest, note.step) {
+              let pitch = (note.octave + 1) * 12 + step + note.alter.round() as i32;
+              measure.notes.push(ScoreNote { pitch, offset: start, duration: note.duration, tie_start: note.tie_start, tie_stop: note.tie_stop });
+            }
+            if !note.chord {
+              last_start = cursor;
+              cursor += note.duration;
+            }
+            measure.length = measure.length.max(cursor);
+          }
+          "backup" => cursor = (cursor - shift).max(0.0),
+          "forward" => {
+            cursor += shift;
+            measure.length = measure.length.max(cursor);
+          }
+          "metronome" => {
+            if let (Some(unit), Some(rate)) = (beat_unit, per_minute) {
+              let dotted = unit * (2.0 - 0.5f64.powi(beat_dots));
+              measure.tempos.push((cursor, rate * dotted));
+            }
+          }
+          "measure" => {
+            measures.push(std::mem::take(&mut measure));
+            if ending_stops {
+              ending = None;
+              ending_stops = false;
+            }
+          }
+          "part" => break,
+          _ => {}
+        }
+        stack.pop();
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+  Ok(measures)
+}
+
+/// Measure indices in playing order, with repeats taken and volta brackets played on their pass.
+fn playing_order(measures: &[Measure]) -> Vec<usize> {
+  let mut order = Vec::new();
+  let mut taken: HashMap<usize, u32> = HashMap::new();
+  let (mut i, mut section_start, mut pass, mut jumped) = (0, 0, 1, false);
+  while i < measures.len() {
+    let m = &measures[i];
+    if m.repeat_forward && !jumped {
+      section_start = i;
+      pass = 1;
+    }
+    jumped = false;
+    if let Some(ending) = &m.ending {
+      if !ending.contains(&pass) {
+        i += 1;
+        continue;
+      }
+    } else if i > 0 && measures[i - 1].ending.is_some() {
+      // left a volta bracket
+      pass = 1;
+    }
+    order.push(i);
+    if let Some(times) = m.repeat_backward {
+      let count = taken.entry(i).or_insert(0);
+      if *count + 1 < times {
+        *count += 1;
+        pass += 1;
+        i = section_start;
+        jumped = true;
+        continue;
+      }
+      if m.ending.is_none() {
+        pass = 1;
+      }
+    }
+    i += 1;
+  }
+  order
+}
+
+// Seconds at `quarters` under `tempos` (sorted quarter positions and quarter notes per minute).
+fn seconds_at(tempos: &[(f64, f64)], quarters: f64) -> f64 {
+  let (mut seconds, mut pos, mut bpm) = (0.0, 0.0, DEFAULT_BPM);
+  for &(at, next) in tempos.iter().take_while(|(at, _)| *at <= quarters) {
+    seconds += (at - pos) * 60.0 / bpm;
+    (pos, bpm) = (at, next);
+  }
+  seconds + (quarters - pos) * 60.0 / bpm
+}
+
+/// Parse the melody of an uncompressed MusicXML (`score-partwise`) score: the notes of its first
+/// part, with repeats and volta brackets played out, tied notes merged and times from the tempo
+/// marks (`<sound tempo>` or a metronome mark; 120 quarter notes per minute until the first).
+pub fn load_musicxml(xml: &str) -> Result<Vec<Note>, String> {
+  let measures = parse_measures(xml)?;
+  // offsets from the start of the song
+  let mut notes: Vec<ScoreNote> = Vec::new();
+  let mut tempos = Vec::new();
+  let mut position = 0.0;
+  for i in playing_order(&measures) {
+    let m = &measures[i];
+    tempos.extend(m.tempos.iter().map(|(offset, bpm)| (position + offset, *bpm)));
+    notes.extend(m.notes.iter().map(|n| ScoreNote { offset: position + n.offset, ..*n }));
+    position += m.length;
+  }
+  tempos.sort_by(|a, b| a.0.total_cmp(&b.0));
+  notes.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+
+  // (start, end) in quarters, merging a tied note into the sounding one of the same pitch
+  let mut merged: Vec<(i32, f64, f64)> = Vec::new();
+  let mut open_ties: HashMap<i32, usize> = HashMap::new();
+  for n in notes {
+    let (start, end) = (n.offset, n.offset + n.duration);
+    let index = match (n.tie_stop, open_ties.get(&n.pitch)) {
+      (true, Some(&index)) => {
+        merged[index].2 = merged[index].2.max(end);
+        index
+      }
+      _ => {
+        merged.push((n.pitch, start, end));
+        merged.len() - 1
+      }
+    };
+    if n.tie_start {
+      open_ties.insert(n.pitch, index);
+    } else {
+      open_ties.remove(&n.pitch);
+    }
+  }
+
+  Ok(
+    merged
+      .into_iter()
+      .filter(|(_, start, end)| end > start)
+      .map(|(pitch, start, end)| {
+        let start_seconds = seconds_at(&tempos, start);
+        Note { note: pitch, start: start_seconds, duration: seconds_at(&tempos, end) - start_seconds, velocity: DEFAULT_VELOCITY, channel: 0, confidence: None, bend: Vec::new() }
+      })
+      .collect(),
+  )
+}
+
+// Files of a zip archive by name, inflated, as found in its central directory.
+fn unzip(content: &[u8]) -> Result<HashMap<String, Vec<u8>>, String> {
+  let u16_at = |i: usize| content.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+  let u32_at = |i: usize| content.get(i..i + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+  let truncated = || "truncated mxl archive".to_string();
+  let eocd = (0..content.len().saturating_sub(21)).rev().find(|&i| content[i..].starts_with(b"PK\x05\x06")).ok_or("mxl archive has no central directory")?;
+  let count = u16_at(eocd + 10).ok_or_else(truncated)?;
+  let mut at = u32_at(eocd + 16).ok_or_else(truncated)?;
+  let mut files = HashMap::new();
+  for _ in 0..count {
+    if !content.get(at..).is_some_and(|c| c.starts_with(b"PK\x01\x02")) {
+      return Err("invalid mxl central directory".to_string());
+    }
+    let method = u16_at(at + 10).ok_or_else(truncated)?;
+    let size = u32_at(at + 20).ok_or_else(truncated)?;
+    let (name_len, extra_len, comment_len) = (u16_at(at + 28).ok_or_else(truncated)?, u16_at(at + 30).ok_or_else(truncated)?, u16_at(at + 32).ok_or_else(truncated)?);
+    let local = u32_at(at + 42).ok_or_else(truncated)?;
+    let name = String::from_utf8_lossy(content.get(at + 46..at + 46 + name_len).ok_or_else(truncated)?).into_owned();
+    at += 46 + name_len + extra_len + comment_len;
+
+    let data_start = local + 30 + u16_at(local + 26).ok_or_else(truncated)? + u16_at(local + 28).ok_or_else(truncated)?;
+    let data = content.get(data_start..data_start + size).ok_or_else(truncated)?;
+    let bytes = match method {
+      0 => data.to_vec(),
+      8 => {
+        let mut out = Vec::new();
+        flate2::read::DeflateDecoder::new(data).read_to_end(&mut out).map_err(|e| format!("failed to inflate {} in mxl archive: {}", name, e))?;
+        out
+      }
+      _ => return Err(format!("unsupported compression {} for {} in mxl archive", method, name)),
+    };
+    files.insert(name, bytes);
+  }
+  Ok(files)
+}
+
+/// Parse a compressed MusicXML (`.mxl`) score, see [`load_musicxml`]. The score is the root file
+/// named by `META-INF/container.xml`, else the first MusicXML file in the archive.
+pub fn load_mxl(content: &[u8]) -> Result<Vec<Note>, String> {
+  let files = unzip(content)?;
+  let container = files.get("META-INF/container.xml").map(|c| String::from_utf8_lossy(c).into_owned());
+  let root = container.and_then(|c| {
+    let mut reader = Reader::from_str(&c);
+    loop {
+      match reader.read_event() {
+        Ok(Event::Start(e) | Event::Empty(e)) if e.local_name().as_ref() == "rootfile" => break attribute(&e, "full-path"),
+        Ok(Event::Eof) | Err(_) => break None,
+        _ => {}
+      }
+    }
+  });
+  let mut names: Vec<&String> = files.keys().filter(|n| !n.starts_with("META-INF/") && (n.ends_with(".xml") || n.ends_with(".musicxml"))).collect();
+  names.sort();
+  let name = root.as_ref().filter(|r| files.contains_key(*r)).or(names.first().copied()).ok_or("no musicxml score in mxl archive")?;
+  load_musicxml(&String::from_utf8_lossy(&files[name]))
+}
+
+/// Parse a MusicXML score, compressed (`.mxl`) or not.
+pub fn load_score(content: &[u8]) -> Result<Vec<Note>, String> {
+  if content.starts_with(ZIP_SIGNATURE) {
+    load_mxl(content)
+  } else {
+    load_musicxml(&String::from_utf8_lossy(content))
+  }
+}
+
+#[test]
+pub fn test_load_musicxml() {
+  let note = |step: &str, octave: i32, duration: u32, extra: &str| format!("<note><pitch><step>{}</step><octave>{}</octave></pitch><duration>{}</duration>{}</note>", step, octave, duration, extra);
+  // 2 divisions per quarter at 60 bpm: a tied C4 over the bar line, then a repeated measure
+  // with two voltas
+  let xml = format!(
+    r#"<?xml version="1.0"?><score-partwise><part-list/><part id="P1">
+<measure number="1"><attributes><divisions>2</divisions></attributes><direction><sound tempo="60"/></direction>{}{}{}</measure>
+<measure number="2"><barline location="left"><repeat direction="forward"/></barline>{}{}</measure>
+<measure number="3"><barline location="left"><ending number="1" type="start"/></barline>{}<barline location="right"><ending number="1" type="stop"/><repeat direction="backward"/></barline></measure>
+<measure number="4"><barline location="left"><ending number="2" type="start"/></barline>{}<barline location="right"><ending number="2" type="discontinue"/></barline></measure>
+</part><part id="P2"><measure number="1">{}</measure></part></score-partwise>"#,
+    note("E", 4, 4, ""),
+    note("C", 4, 4, r#"<tie type="start"/>"#),
+    note("G", 4, 4, "<chord/>"),
+    note("C", 4, 4, r#"<tie type="stop"/>"#),
+    "<note><rest/><duration>4</duration></note>",
+    note("D", 4, 8, ""),
+    note("F", 4, 8, r#"<accidental>sharp</accidental>"#).replace("<octave>", "<alter>1</alter><octave>"),
+    note("A", 5, 8, ""),
+  );
+  let notes = load_musicxml(&xml).expect("parse musicxml");
+  let summary: Vec<(i32, f64, f64)> = notes.iter().map(|n| (n.note, n.start, n.duration)).collect();
+  assert_eq!(summary, vec![
+    (64, 0.0, 2.0),
+    (60, 2.0, 4.0),
+    (67, 2.0, 2.0),
+    (62, 8.0, 4.0),
+    // measure 2 again on the second pass; its C4 tie has nothing to continue
+    (60, 12.0, 2.0),
+    (66, 16.0, 4.0),
+  ]);
+}
@@ -0,0 +1,101 @@
+use serde_json::Value;
+
+use crate::midi::Note;
+
+// basic-pitch writes `velocity = round(127 * amplitude)` and pitch bends in thirds of a semitone
+const VELOCITY_SCALE: f64 = 127.0;
+const BEND_BINS_PER_SEMITONE: f64 = 3.0;
+
+// One note event of the pipeline: times in seconds, amplitude 0..1, bends in contour bins
+// spread evenly over the note.
+fn note_event(start: f64, end: f64, pitch: i64, amplitude: f64, bends: &[f64]) -> Option<Note> {
+  if !(start.is_finite() && end > start && (0..=127).contains(&pitch)) {
+    return None;
+  }
+  let amplitude = amplitude.clamp(0.0, 1.0);
+  let bend = if bends.iter().any(|b| *b != 0.0) {
+    let step = if bends.len() > 1 { (end - start) / (bends.len() - 1) as f64 } else { 0.0 };
+    bends.iter().enumerate().map(|(i, b)| (start + i as f64 * step, b / BEND_BINS_PER_SEMITONE)).collect()
+  } else {
+    Vec::new()
+  };
+  Some(Note { note: pitch as i32, start, duration: end - start, velocity: (amplitude * VELOCITY_SCALE).round(), channel: 0, confidence: Some(amplitude), bend })
+}
+
+fn numbers(value: Option<&Value>) -> Vec<f64> {
+  value.and_then(Value::as_array).map(|a| a.iter().filter_map(Value::as_f64).collect()).unwrap_or_default()
+}
+
+// `[start, end, pitch, amplitude, bends]` as in basic-pitch's `estimated_notes`, or an object with
+// the CSV column names (`start_time_s`, `end_time_s`, `pitch_midi`, `velocity`/`amplitude`/`confidence`).
+fn json_note(value: &Value) -> Option<Note> {
+  if let Some(fields) = value.as_array() {
+    let amplitude = fields.get(3).and_then(Value::as_f64).unwrap_or(1.0);
+    return note_event(fields.first()?.as_f64()?, fields.get(1)?.as_f64()?, fields.get(2)?.as_f64()?.round() as i64, amplitude, &numbers(fields.get(4)));
+  }
+  let field = |names: &[&str]| names.iter().find_map(|n| value.get(*n)).and_then(Value::as_f64);
+  let amplitude = field(&["confidence", "amplitude"]).or_else(|| field(&["velocity"]).map(|v| v / VELOCITY_SCALE)).unwrap_or(1.0);
+  let bends = numbers(value.get("pitch_bends").or_else(|| value.get("pitch_bend")));
+  note_event(field(&["start_time_s", "start"])?, field(&["end_time_s", "end"])?, field(&["pitch_midi", "pitch"])?.round() as i64, amplitude, &bends)
+}
+
+fn load_json(text: &str) -> Result<Vec<Note>, String> {
+  let root: Value = serde_json::from_str(text).map_err(|e| format!("invalid note events json: {}", e))?;
+  let events = match &root {
+    Value::Array(events) => events,
+    Value::Object(_) => ["estimated_notes", "note_events", "notes"].iter().find_map(|k| root.get(*k)?.as_array()).ok_or("note events json has no list of notes")?,
+    _ => return Err("note events json must be a list of notes".to_string()),
+  };
+  Ok(events.iter().filter_map(json_note).collect())
+}
+
+// basic-pitch's `save_note_events` layout: `start_time_s,end_time_s,pitch_midi,velocity,pitch_bend`
+// with the bends filling the remaining columns. A header row may rename or reorder the columns.
+fn load_csv(text: &str) -> Result<Vec<Note>, String> {
+  let mut columns = ["start_time_s", "end_time_s", "pitch_midi", "velocity", "pitch_bend"].map(String::from).to_vec();
+  let mut notes = Vec::new();
+  for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let values: Vec<Option<f64>> = fields.iter().map(|f| f.parse().ok()).collect();
+    if values.first().is_some_and(Option::is_none) {
+      columns = fields.iter().map(|f| f.to_ascii_lowercase()).collect();
+      continue;
+    }
+    let index = |names: &[&str]| columns.iter().position(|c| names.contains(&c.as_str()));
+    let get = |names: &[&str]| index(names).and_then(|i| values.get(i).copied().flatten());
+    let (Some(start), Some(end), Some(pitch)) = (get(&["start_time_s", "start"]), get(&["end_time_s", "end"]), get(&["pitch_midi", "pitch"])) else {
+      return Err(format!("invalid note events csv line: {}", line));
+    };
+    let amplitude = get(&["confidence", "amplitude"]).or_else(|| get(&["velocity"]).map(|v| v / VELOCITY_SCALE)).unwrap_or(1.0);
+    let bends: Vec<f64> = index(&["pitch_bend", "pitch_bends"]).map(|i| values.iter().skip(i).flatten().copied().collect()).unwrap_or_default();
+    notes.extend(note_event(start, end, pitch.round() as i64, amplitude, &bends));
+  }
+  Ok(notes)
+}
+
+/// Parse note events written by the Python pipeline (basic-pitch), as JSON or CSV, straight into
+/// notes sorted by start, with `confidence` set from the note amplitude and pitch bends kept.
+pub fn load_note_events(content: &[u8]) -> Result<Vec<Note>, String> {
+  let text = String::from_utf8_lossy(content);
+  let text = text.trim_start_matches('\u{feff}').trim();
+  let mut notes = if text.starts_with('[') || text.starts_with('{') { load_json(text)? } else { load_csv(text)? };
+  notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.note.cmp(&b.note)));
+  Ok(notes)
+}
+
+#[test]
+pub fn test_load_note_events() {
+  let json = br#"{"estimated_notes": [[1.5, 2.0, 64, 0.5, null], [0.5, 1.0, 60, 0.8, [0, 3, 6]]]}"#;
+  let notes = load_note_events(json).expect("parse json");
+  assert_eq!(notes.len(), 2);
+  assert_eq!((notes[0].note, notes[0].start, notes[0].duration, notes[0].confidence), (60, 0.5, 0.5, Some(0.8)));
+  assert_eq!(notes[0].velocity, 102.0);
+  assert_eq!(notes[0].bend, vec![(0.5, 0.0), (0.75, 1.0), (1.0, 2.0)]);
+  assert!(notes[1].bend.is_empty());
+
+  let csv = b"start_time_s,end_time_s,pitch_midi,velocity,pitch_bend\n0.5,1.0,60,127,0,-3\n1.5,2.0,64,0\n";
+  let notes = load_note_events(csv).expect("parse csv");
+  assert_eq!(notes.iter().map(|n| (n.note, n.confidence)).collect::<Vec<_>>(), vec![(60, Some(1.0)), (64, Some(0.0))]);
+  assert_eq!(notes[0].bend, vec![(0.5, 0.0), (1.0, -1.0)]);
+  assert!(load_note_events(b"0.5,oops,60,100\n").is_err());
+}
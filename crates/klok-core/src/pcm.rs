@@ -0,0 +1,191 @@
+/// Decoded audio: interleaved samples in -1.0..1.0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pcm {
+  pub sample_rate: u32,
+  pub channels: u16,
+  pub samples: Vec<f32>,
+}
+
+impl Pcm {
+  /// Length in seconds.
+  pub fn duration(&self) -> f64 {
+    self.frames() as f64 / self.sample_rate as f64
+  }
+
+  pub fn frames(&self) -> usize {
+    self.samples.len() / self.channels.max(1) as usize
+  }
+
+  /// The part from `start` seconds on, `duration` seconds long (to the end when `None`).
+  pub fn slice(&self, start: f64, duration: Option<f64>) -> Pcm {
+    let frame = |t: f64| ((t.max(0.0) * self.sample_rate as f64) as usize).min(self.frames());
+    let from = frame(start);
+    let to = duration.map_or(self.frames(), |d| frame(start + d)).max(from);
+    let channels = self.channels.max(1) as usize;
+    Pcm { sample_rate: self.sample_rate, channels: self.channels, samples: self.samples[from * channels..to * channels].to_vec() }
+  }
+
+  /// The channels averaged into one, for analysis.
+  pub fn mono(&self) -> Vec<f32> {
+    let channels = self.channels.max(1) as usize;
+    self.samples.chunks_exact(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect()
+  }
+
+  /// Binary form for the frontend: sample rate (u32), channel count (u32), then the interleaved
+  /// samples as f32, all little-endian.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + self.samples.len() * 4);
+    out.extend_from_slice(&self.sample_rate.to_le_bytes());
+    out.extend_from_slice(&(self.channels as u32).to_le_bytes());
+    for s in &self.samples {
+      out.extend_from_slice(&s.to_le_bytes());
+    }
+    out
+  }
+
+  /// 16-bit PCM WAV file of the samples, clipped to -1.0..1.0.
+  pub fn to_wav(&self) -> Vec<u8> {
+    let channels = self.channels.max(1);
+    let data_len = (self.samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&WAV_PCM.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&self.sample_rate.to_le_bytes());
+    out.extend_from_slice(&(self.sample_rate * channels as u32 * 2).to_le_bytes());
+    // block align, bits per sample
+    out.extend_from_slice(&(channels * 2).to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for s in &self.samples {
+      out.extend_from_slice(&((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    out
+  }
+}
+
+// WAVE_FORMAT_PCM, WAVE_FORMAT_IEEE_FLOAT and WAVE_FORMAT_EXTENSIBLE
+const WAV_PCM: u16 = 1;
+const WAV_FLOAT: u16 = 3;
+const WAV_EXTENSIBLE: u16 = 0xfffe;
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn wav_sample(bytes: &[u8], format: u16, bits: u16) -> Option<f32> {
+  Some(match (format, bits) {
+    (WAV_PCM, 8) => (bytes[0] as f32 - 128.0) / 128.0,
+    (WAV_PCM, 16) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+    (WAV_PCM, 24) => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8_388_608.0,
+    (WAV_PCM, 32) => i32::from_le_bytes(bytes.try_into().ok()?) as f32 / 2_147_483_648.0,
+    (WAV_FLOAT, 32) => f32::from_le_bytes(bytes.try_into().ok()?),
+    (WAV_FLOAT, 64) => f64::from_le_bytes(bytes.try_into().ok()?) as f32,
+    _ => return None,
+  })
+}
+
+/// Decode a RIFF WAVE file: integer PCM (8 to 32 bit) or float samples, plain or extensible.
+pub fn decode_wav(content: &[u8]) -> Result<Pcm, String> {
+  if content.get(..4) != Some(b"RIFF") || content.get(8..12) != Some(b"WAVE") {
+    return Err("not a RIFF WAVE file".to_string());
+  }
+  let mut format = None;
+  let mut at = 12;
+  while at + 8 <= content.len() {
+    let id = &content[at..at + 4];
+    // streamed files leave the size unset, the data then runs to the end
+    let size = (u32_at(content, at + 4).unwrap_or(0) as usize).min(content.len() - at - 8);
+    let body = &content[at + 8..at + 8 + size];
+    match id {
+      b"fmt " => {
+        let tag = u16_at(body, 0).ok_or("wav fmt chunk is too short")?;
+        // extensible formats keep the real tag at the start of the subformat GUID
+        let tag = if tag == WAV_EXTENSIBLE { u16_at(body, 24).ok_or("wav fmt chunk is too short")? } else { tag };
+        let channels = u16_at(body, 2).ok_or("wav fmt chunk is too short")?;
+        let sample_rate = u32_at(body, 4).ok_or("wav fmt chunk is too short")?;
+        let bits = u16_at(body, 14).ok_or("wav fmt chunk is too short")?;
+        if channels == 0 || sample_rate == 0 {
+          return Err("wav has no channels or no sample rate".to_string());
+        }
+        if wav_sample(&vec![0; bits as usize / 8], tag, bits).is_none() {
+          return Err(format!("unsupported wav sample format {} with {} bits", tag, bits));
+        }
+        format = Some((tag, channels, sample_rate, bits));
+      }
+      b"data" => {
+        let (tag, channels, sample_rate, bits) = format.ok_or("wav data comes before its fmt chunk")?;
+        let width = bits as usize / 8;
+        let frames = body.len() / (width * channels as usize);
+        let samples = body[..frames * width * channels as usize].chunks_exact(width).filter_map(|s| wav_sample(s, tag, bits)).collect();
+        return Ok(Pcm { sample_rate, channels, samples });
+      }
+      _ => {}
+    }
+    // chunks are padded to an even size
+    at += 8 + size + (size & 1);
+  }
+  Err("wav has no data chunk".to_string())
+}
+
+/// Extensions of the audio files `decode_audio` can decode. There is no decoder for compressed
+/// formats (mp3, m4a, flac, ...), so songs in those can't be decoded.
+pub const DECODABLE_EXTENSIONS: [&str; 1] = ["wav"];
+
+/// Decode an audio file, `ext` being its extension. Only WAV can be decoded; compressed formats
+/// are reported as unsupported.
+pub fn decode_audio(content: &[u8], ext: &str) -> Result<Pcm, String> {
+  if content.starts_with(b"RIFF") {
+    return decode_wav(content);
+  }
+  Err(format!("{} audio can't be decoded, only wav", if ext.is_empty() { "this" } else { ext }))
+}
+
+#[test]
+pub fn test_decode_wav() {
+  let samples = vec![0.0, 0.5, -0.5, 0.25];
+  let pcm = decode_audio(&crate::synth::encode_wav(&samples, 8000), "wav").unwrap();
+  assert_eq!((pcm.sample_rate, pcm.channels, pcm.frames()), (8000, 1, 4));
+  assert!(pcm.samples.iter().zip(&samples).all(|(a, b)| (a - b).abs() < 1e-3));
+  assert_eq!(pcm.slice(1.0 / 8000.0, Some(2.0 / 8000.0)).samples.len(), 2);
+
+  // stereo 24-bit extensible, with an odd-sized chunk before the data
+  let mut fmt = Vec::new();
+  fmt.extend_from_slice(&WAV_EXTENSIBLE.to_le_bytes());
+  fmt.extend_from_slice(&2u16.to_le_bytes());
+  fmt.extend_from_slice(&48000u32.to_le_bytes());
+  fmt.extend_from_slice(&(48000u32 * 6).to_le_bytes());
+  fmt.extend_from_slice(&6u16.to_le_bytes());
+  fmt.extend_from_slice(&24u16.to_le_bytes());
+  fmt.extend_from_slice(&22u16.to_le_bytes());
+  fmt.extend_from_slice(&24u16.to_le_bytes());
+  fmt.extend_from_slice(&0u32.to_le_bytes());
+  fmt.extend_from_slice(&WAV_PCM.to_le_bytes());
+  fmt.extend_from_slice(&[0; 14]);
+  let data = [0x00, 0x00, 0x40, 0x00, 0x00, 0xc0];
+  let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+  for (id, body) in [(b"fmt ", &fmt[..]), (b"LIST", &[1, 2, 3][..]), (b"data", &data[..])] {
+    wav.extend_from_slice(id);
+    wav.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    wav.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+      wav.push(0);
+    }
+  }
+  let pcm = decode_wav(&wav).unwrap();
+  assert_eq!((pcm.sample_rate, pcm.channels), (48000, 2));
+  assert_eq!(pcm.samples, vec![0.5, -0.5]);
+  assert_eq!(pcm.mono(), vec![0.0]);
+  let again = decode_wav(&pcm.to_wav()).unwrap();
+  assert_eq!((again.sample_rate, again.channels), (48000, 2));
+  assert!(again.samples.iter().zip(&pcm.samples).all(|(a, b)| (a - b).abs() < 1e-3));
+  assert!(decode_audio(b"ID3\x04", "mp3").is_err());
+}
@@ -0,0 +1,228 @@
+use crate::lyrics::{LinePacing, LyricLine};
+use crate::midi::Note;
+
+/// Silent gaps of at least `min_gap` seconds between consecutive notes, as `(start, end)` pairs.
+/// Overlapping notes are merged before looking for gaps.
+pub fn phrase_gaps(notes: &[Note], min_gap: f64) -> Vec<(f64, f64)> {
+  let mut sorted: Vec<(f64, f64)> = notes.iter().map(|n| (n.start, n.start + n.duration)).collect();
+  sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+  let mut gaps = Vec::new();
+  let mut sounding_until: Option<f64> = None;
+  for (start, end) in sorted {
+    if let Some(until) = sounding_until {
+      if start - until >= min_gap {
+        gaps.push((until, start));
+      }
+    }
+    sounding_until = Some(sounding_until.map_or(end, |u| u.max(end)));
+  }
+  gaps
+}
+
+/// Silent gaps of at least `min_gap` seconds between sung lyric lines, as `(start, end)` pairs.
+/// The song start counts as silence. A line ends at its `end` time, or at the next empty
+/// (instrumental) line when it has none; otherwise it runs into the next line.
+pub fn lyric_gaps(lyrics: &[LyricLine], min_gap: f64) -> Vec<(f64, f64)> {
+  let mut gaps = Vec::new();
+  // `None` while a line without an end time is still being sung
+  let mut silent_since: Option<f64> = Some(0.0);
+  for line in lyrics {
+    if line.text.trim().is_empty() {
+      silent_since.get_or_insert(line.time);
+      continue;
+    }
+    if let Some(since) = silent_since {
+      if line.time - since >= min_gap {
+        gaps.push((since, line.time));
+      }
+    }
+    silent_since = line.end;
+  }
+  gaps
+}
+
+/// Suggest breath marks: every phrase gap that starts while a line is active (between its start
+/// and the next line's start) adds the gap start time to that line's `breaths`.
+pub fn mark_breaths(lyrics: &mut [LyricLine], gaps: &[(f64, f64)]) {
+  for i in 0..lyrics.len() {
+    let start = lyrics[i].time;
+    let next = lyrics.get(i + 1).map(|l| l.time).unwrap_or(f64::INFINITY);
+    lyrics[i].breaths = gaps.iter().map(|g| g.0).filter(|&t| t > start && t < next).collect();
+  }
+}
+
+// a phrase gap ending this close to the next line leads into it
+const LEAD_IN_TOLERANCE: f64 = 1.0;
+
+/// Fill missing `end` times. A line ends where the vocal falls silent before the next line (the
+/// last phrase gap inside the line that runs up to the next line's start), otherwise at the next
+/// line's start; the last line ends at `song_end`.
+pub fn fill_line_ends(lyrics: &mut [LyricLine], gaps: &[(f64, f64)], song_end: f64) {
+  for i in 0..lyrics.len() {
+    if lyrics[i].end.is_some() {
+      continue;
+    }
+    let start = lyrics[i].time;
+    let next = lyrics.get(i + 1).map_or(song_end, |l| l.time).max(start);
+    let silent_from = gaps.iter().filter(|g| g.0 > start && g.0 < next && g.1 >= next - LEAD_IN_TOLERANCE).map(|g| g.0).reduce(f64::max);
+    lyrics[i].end = Some(silent_from.unwrap_or(next));
+  }
+}
+
+// Lines shorter than this are treated as this long, so a mistimed line doesn't blow up its pace.
+const MIN_LINE_DURATION: f64 = 0.25;
+
+/// Fill `pacing` for every sung line. A line lasts until its `end`, or the next line, or `song_end`.
+pub fn annotate_pacing(lyrics: &mut [LyricLine], song_end: f64) {
+  let mut rates: Vec<Option<f64>> = Vec::with_capacity(lyrics.len());
+  for (i, line) in lyrics.iter().enumerate() {
+    let chars = line.text.chars().filter(|c| !c.is_whitespace()).count();
+    if chars == 0 {
+      rates.push(None);
+      continue;
+    }
+    let until = line.end.or_else(|| lyrics.get(i + 1).map(|l| l.time)).unwrap_or(song_end);
+    rates.push(Some(chars as f64 / (until - line.time).max(MIN_LINE_DURATION)));
+  }
+
+  let mut sorted: Vec<f64> = rates.iter().flatten().copied().collect();
+  sorted.sort_by(f64::total_cmp);
+  let median = sorted.get(sorted.len() / 2).copied().unwrap_or(1.0);
+
+  for (line, rate) in lyrics.iter_mut().zip(rates) {
+    line.pacing = rate.map(|chars_per_second| LinePacing { chars_per_second, density: chars_per_second / median });
+  }
+}
+
+// Krumhansl-Kessler key profiles, indexed by semitones above the tonic.
+const MAJOR_PROFILE: [f64; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f64; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+const PITCH_NAMES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+fn correlation(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+  let mean = |v: &[f64; 12]| v.iter().sum::<f64>() / 12.0;
+  let (ma, mb) = (mean(a), mean(b));
+  let cov: f64 = a.iter().zip(b).map(|(x, y)| (x - ma) * (y - mb)).sum();
+  let var = |v: &[f64; 12], m: f64| v.iter().map(|x| (x - m).powi(2)).sum::<f64>();
+  cov / (var(a, ma) * var(b, mb)).sqrt()
+}
+
+/// Estimate the key of a melody (e.g. `"A minor"`) from its duration-weighted pitch classes.
+pub fn estimate_key(notes: &[Note]) -> Option<String> {
+  estimate_tonic(notes).map(|(tonic, minor)| format!("{} {}", PITCH_NAMES[tonic], if minor { "minor" } else { "major" }))
+}
+
+/// Like [`estimate_key`], as the tonic pitch class (0 = C) and whether the key is minor.
+pub fn estimate_tonic(notes: &[Note]) -> Option<(usize, bool)> {
+  let mut histogram = [0.0f64; 12];
+  for n in notes {
+    histogram[n.note.rem_euclid(12) as usize] += n.duration;
+  }
+  if histogram.iter().all(|&w| w == 0.0) {
+    return None;
+  }
+
+  let mut best: Option<(f64, usize, bool)> = None;
+  for tonic in 0..12 {
+    let rotated: [f64; 12] = std::array::from_fn(|i| histogram[(i + tonic) % 12]);
+    for (minor, profile) in [(false, &MAJOR_PROFILE), (true, &MINOR_PROFILE)] {
+      let r = correlation(&rotated, profile);
+      if best.is_none_or(|(b, _, _)| r > b) {
+        best = Some((r, tonic, minor));
+      }
+    }
+  }
+  best.map(|(_, tonic, minor)| (tonic, minor))
+}
+
+// tempo range searched by `estimate_tempo`, one octave wide so half/double tempo can't both fit
+const MIN_BPM: f64 = 70.0;
+const MAX_BPM: f64 = 160.0;
+// onsets further apart than this (seconds) are not compared
+const TEMPO_WINDOW: f64 = 4.0;
+// timing slack (seconds) when matching an interval to the beat grid
+const TEMPO_TOLERANCE: f64 = 0.03;
+
+/// Rough tempo (bpm) of a vocal line: the beat period that the most intervals between nearby note
+/// onsets are whole multiples of. Scores are scaled by the period, because short periods match
+/// more intervals by chance. `None` with fewer than 4 notes.
+pub fn estimate_tempo(notes: &[Note]) -> Option<f64> {
+  if notes.len() < 4 {
+    return None;
+  }
+  let mut onsets: Vec<f64> = notes.iter().map(|n| n.start).collect();
+  onsets.sort_by(f64::total_cmp);
+  let mut intervals = Vec::new();
+  for (i, a) in onsets.iter().enumerate() {
+    intervals.extend(onsets[i + 1..].iter().map(|b| b - a).take_while(|&d| d <= TEMPO_WINDOW).filter(|&d| d > TEMPO_TOLERANCE));
+  }
+
+  let score = |bpm: f64| {
+    let period = 60.0 / bpm;
+    let fit: f64 = intervals
+      .iter()
+      .map(|d| {
+        let off = d - (d / period).round().max(1.0) * period;
+        (-(off * off) / (2.0 * TEMPO_TOLERANCE * TEMPO_TOLERANCE)).exp()
+      })
+      .sum();
+    fit * period
+  };
+  let steps = ((MAX_BPM - MIN_BPM) * 2.0) as usize;
+  (0..=steps).map(|i| MIN_BPM + i as f64 * 0.5).max_by(|a, b| score(*a).total_cmp(&score(*b)))
+}
+
+#[test]
+pub fn test_mark_breaths() {
+  let note = |start: f64, duration: f64| Note { note: 60, start, duration, velocity: 1.0, channel: 0, confidence: None, bend: Vec::new() };
+  let notes = vec![note(1.0, 1.0), note(1.5, 0.8), note(2.4, 0.5), note(4.0, 1.0), note(6.0, 0.5)];
+  let gaps = phrase_gaps(&notes, 0.3);
+  assert_eq!(gaps, vec![(2.9, 4.0), (5.0, 6.0)]);
+
+  let line = |time: f64| LyricLine { time, text: "la".to_string(), ..Default::default() };
+  let mut lyrics = vec![line(1.0), line(5.5)];
+  mark_breaths(&mut lyrics, &gaps);
+  assert_eq!(lyrics[0].breaths, vec![2.9, 5.0]);
+  assert!(lyrics[1].breaths.is_empty());
+}
+
+#[test]
+pub fn test_fill_line_ends() {
+  let line = |time: f64| LyricLine { time, text: "la".to_string(), ..Default::default() };
+  let mut lyrics = vec![line(1.0), line(4.0), LyricLine { end: Some(6.5), ..line(5.5) }, line(8.0)];
+  fill_line_ends(&mut lyrics, &[(2.0, 2.4), (2.9, 4.0), (7.0, 7.5)], 10.0);
+  let ends: Vec<Option<f64>> = lyrics.iter().map(|l| l.end).collect();
+  assert_eq!(ends, vec![Some(2.9), Some(5.5), Some(6.5), Some(10.0)]);
+}
+
+#[test]
+pub fn test_annotate_pacing() {
+  let line = |time: f64, text: &str| LyricLine { time, text: text.to_string(), ..Default::default() };
+  let mut lyrics = vec![line(0.0, "abcd"), line(2.0, "ab cd"), line(4.0, ""), line(6.0, "abcdefgh")];
+  annotate_pacing(&mut lyrics, 8.0);
+  assert_eq!(lyrics[0].pacing.as_ref().map(|p| p.chars_per_second), Some(2.0));
+  assert!(lyrics[2].pacing.is_none());
+  assert_eq!(lyrics[3].pacing.as_ref().map(|p| p.density), Some(2.0));
+}
+
+#[test]
+pub fn test_estimate_key() {
+  let note = |note: i32, duration: f64| Note { note, start: 0.0, duration, velocity: 1.0, channel: 0, confidence: None, bend: Vec::new() };
+  // C major scale with a long tonic and dominant
+  let notes: Vec<Note> = [(60, 2.0), (62, 1.0), (64, 1.0), (65, 1.0), (67, 2.0), (69, 1.0), (71, 1.0), (72, 2.0)].iter().map(|&(n, d)| note(n, d)).collect();
+  assert_eq!(estimate_key(&notes).as_deref(), Some("C major"));
+  assert_eq!(estimate_key(&[]), None);
+  assert_eq!(estimate_tonic(&notes), Some((0, false)));
+}
+
+#[test]
+pub fn test_estimate_tempo() {
+  let note = |start: f64| Note { note: 60, start, duration: 0.2, velocity: 1.0, channel: 0, confidence: None, bend: Vec::new() };
+  // quarter notes at 100 bpm with a few eighths and a rest
+  let beat = 0.6;
+  let beats = [0.0, 1.0, 1.5, 2.0, 3.0, 4.0, 4.5, 5.0, 8.0, 9.0, 10.0, 10.5, 11.0, 12.0];
+  let notes: Vec<Note> = beats.iter().map(|b| note(2.0 + b * beat)).collect();
+  assert_eq!(estimate_tempo(&notes), Some(100.0));
+  assert_eq!(estimate_tempo(&notes[..3]), None);
+}
@@ -0,0 +1,82 @@
+use crate::midi::Note;
+
+// bytes before the cells in `PianoRoll::to_bytes`
+const HEADER_LEN: usize = 12;
+
+/// Notes rasterized into a grid of pitches by time buckets, small enough to hand to the frontend
+/// as one binary blob for songs with tens of thousands of notes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PianoRoll {
+  /// MIDI note of the first row
+  pub low: u8,
+  pub rows: u8,
+  pub columns: u32,
+  /// seconds per column
+  pub bucket: f32,
+  /// row-major, lowest pitch first; each cell holds the loudest velocity sounding in it, 0 for none
+  pub cells: Vec<u8>,
+}
+
+impl PianoRoll {
+  /// Rasterize `notes` into columns of `bucket` seconds from time 0, with rows from the lowest to
+  /// the highest note. A note marks every column it overlaps.
+  pub fn new(notes: &[Note], bucket: f64) -> Result<PianoRoll, String> {
+    if !(bucket.is_finite() && bucket > 0.0) {
+      return Err(format!("invalid bucket size: {}", bucket));
+    }
+    let key = |n: &Note| n.note.clamp(0, 127) as u8;
+    let low = notes.iter().map(key).min().unwrap_or(0);
+    let high = notes.iter().map(key).max().unwrap_or(0);
+    // columns a note covers; zero-length notes still mark the one they start in
+    let span = |n: &Note| {
+      let first = (n.start.max(0.0) / bucket) as usize;
+      (first, (((n.start + n.duration) / bucket).ceil() as usize).max(first + 1))
+    };
+    let rows = if notes.is_empty() { 0 } else { high - low + 1 };
+    let columns = notes.iter().map(|n| span(n).1).max().unwrap_or(0) as u32;
+
+    let mut cells = vec![0u8; rows as usize * columns as usize];
+    for n in notes {
+      let row = (key(n) - low) as usize;
+      let (first, last) = span(n);
+      let velocity = n.velocity.round().clamp(1.0, 127.0) as u8;
+      for cell in &mut cells[row * columns as usize + first..row * columns as usize + last] {
+        *cell = (*cell).max(velocity);
+      }
+    }
+    Ok(PianoRoll { low, rows, columns, bucket: bucket as f32, cells })
+  }
+
+  /// Little-endian layout: `low: u8, rows: u8, 0u16, columns: u32, bucket: f32`, then the cells.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + self.cells.len());
+    out.extend_from_slice(&[self.low, self.rows, 0, 0]);
+    out.extend_from_slice(&self.columns.to_le_bytes());
+    out.extend_from_slice(&self.bucket.to_le_bytes());
+    out.extend_from_slice(&self.cells);
+    out
+  }
+}
+
+#[test]
+pub fn test_piano_roll() {
+  let note = |note, start, duration, velocity| Note { note, start, duration, velocity, channel: 0, confidence: None, bend: Vec::new() };
+  let notes = vec![note(60, 0.0, 0.25, 100.0), note(62, 0.1, 0.4, 80.0), note(60, 0.2, 0.05, 120.0)];
+  let roll = PianoRoll::new(&notes, 0.1).expect("piano roll");
+  assert_eq!((roll.low, roll.rows, roll.columns), (60, 3, 5));
+  // C4 sounds in 0..0.3, the louder repeat wins its column
+  assert_eq!(&roll.cells[..5], &[100, 100, 120, 0, 0]);
+  assert_eq!(&roll.cells[5..10], &[0; 5]);
+  assert_eq!(&roll.cells[10..], &[0, 80, 80, 80, 80]);
+
+  let bytes = roll.to_bytes();
+  assert_eq!(bytes.len(), HEADER_LEN + 15);
+  assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 5);
+  assert_eq!(PianoRoll::new(&[], 0.1).expect("empty roll").to_bytes().len(), HEADER_LEN);
+  assert!(PianoRoll::new(&notes, 0.0).is_err());
+
+  // a zero-length note ending last, on a column boundary, gets its own column
+  let roll = PianoRoll::new(&[note(60, 0.0, 0.25, 100.0), note(60, 0.5, 0.0, 90.0)], 0.1).expect("piano roll");
+  assert_eq!(roll.columns, 6);
+  assert_eq!(roll.cells, vec![100, 100, 100, 0, 0, 90]);
+}
@@ -0,0 +1,84 @@
+use std::f32::consts::PI;
+
+// seconds of the delay line each read head sweeps; longer is smoother but smears transients
+const WINDOW: f32 = 0.05;
+// widest shift handled, in semitones either way
+pub const MAX_SEMITONES: f32 = 12.0;
+
+/// Real-time pitch shifter for a stream of interleaved samples, keeping its tempo: two read heads
+/// sweep a short delay line at the shifted speed, crossfading so one is always away from the
+/// jump back. Keeps its state between calls, so a stream can be processed chunk by chunk.
+#[derive(Debug, Clone)]
+pub struct PitchShifter {
+  channels: usize,
+  window: usize,
+  // per channel delay lines, interleaved
+  buffer: Vec<f32>,
+  write: usize,
+  // read head phase in 0..1 of the window
+  phase: f32,
+  ratio: f32,
+}
+
+impl PitchShifter {
+  pub fn new(channels: u16, sample_rate: u32) -> PitchShifter {
+    let channels = channels.max(1) as usize;
+    let window = ((WINDOW * sample_rate as f32) as usize).max(4);
+    PitchShifter { channels, window, buffer: vec![0.0; (window + 3) * channels], write: 0, phase: 0.0, ratio: 1.0 }
+  }
+
+  /// Shift by `semitones` (clamped to `MAX_SEMITONES` either way) from the next sample on.
+  pub fn set_semitones(&mut self, semitones: f32) {
+    self.ratio = 2f32.powf(semitones.clamp(-MAX_SEMITONES, MAX_SEMITONES) / 12.0);
+  }
+
+  // sample of `channel` `delay` frames (fractional, up to the window) before the last one written
+  fn delayed(&self, channel: usize, delay: f32) -> f32 {
+    let len = self.window + 3;
+    let at = (self.write + len - 1) as f32 - delay;
+    let (i, frac) = (at.floor() as usize, at.fract());
+    let a = self.buffer[(i % len) * self.channels + channel];
+    let b = self.buffer[((i + 1) % len) * self.channels + channel];
+    a + (b - a) * frac
+  }
+
+  /// Shift interleaved `samples` in place. Unshifted audio passes through unchanged.
+  pub fn process(&mut self, samples: &mut [f32]) {
+    let len = self.window + 3;
+    let bypass = self.ratio == 1.0;
+    // the heads' delay shrinks (higher pitch) or grows (lower) by this much per sample
+    let step = (1.0 - self.ratio) / self.window as f32;
+    for frame in samples.chunks_exact_mut(self.channels) {
+      self.buffer[self.write * self.channels..(self.write + 1) * self.channels].copy_from_slice(frame);
+      self.write = (self.write + 1) % len;
+      if bypass {
+        continue;
+      }
+      self.phase = (self.phase + step).rem_euclid(1.0);
+      let heads = [self.phase, (self.phase + 0.5) % 1.0];
+      for (c, out) in frame.iter_mut().enumerate() {
+        // sin² fades of heads half a window apart sum to one
+        *out = heads.iter().map(|&p| (PI * p).sin().powi(2) * self.delayed(c, 1.0 + p * self.window as f32)).sum();
+      }
+    }
+  }
+}
+
+#[test]
+pub fn test_pitch_shift() {
+  let rate = 44100;
+  let samples: Vec<f32> = (0..rate as usize).map(|i| (2.0 * PI * 440.0 * i as f32 / rate as f32).sin()).collect();
+  let crossings = |s: &[f32]| s.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count() as f32;
+
+  let mut out = samples.clone();
+  let mut shifter = PitchShifter::new(1, rate);
+  shifter.process(&mut out);
+  assert_eq!(out, samples);
+
+  // an octave up doubles the frequency, within the crossfade's blur
+  shifter.set_semitones(12.0);
+  shifter.process(&mut out);
+  let ratio = crossings(&out[rate as usize / 2..]) / crossings(&samples[rate as usize / 2..]);
+  assert!((ratio - 2.0).abs() < 0.15, "ratio {}", ratio);
+  assert!(out.iter().all(|s| s.abs() <= 1.01));
+}
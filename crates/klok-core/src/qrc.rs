@@ -0,0 +1,132 @@
+use crate::lyrics::{LyricLine, LyricWord};
+
+/// Extract the lyric text from a decrypted QQ Music QRC file.
+/// Accepts both the XML wrapper (`<QrcInfos>...<Lyric_1 LyricContent="..."/>`) and bare QRC content.
+/// Encrypted QRC payloads (QQ's modified triple-DES) are not supported and are reported as such.
+pub fn decode_qrc(content: &[u8]) -> Result<String, String> {
+  let text = std::str::from_utf8(content)
+    .map_err(|_| "qrc file is not UTF-8 text (encrypted qrc is not supported, decrypt it first)".to_string())?
+    .trim_start_matches('\u{feff}');
+
+  if !text.trim_start().starts_with('<') {
+    return Ok(text.to_string());
+  }
+
+  const ATTR: &str = "LyricContent=\"";
+  let start = text.find(ATTR).ok_or_else(|| "qrc xml has no LyricContent attribute".to_string())? + ATTR.len();
+  // LyricContent is not always escaped properly, so prefer the `"/>` that closes the element
+  let end = text[start..]
+    .find("\"/>")
+    .or_else(|| text[start..].find('"'))
+    .map(|i| start + i)
+    .ok_or_else(|| "unterminated LyricContent attribute".to_string())?;
+  Ok(unescape_xml(&text[start..end]))
+}
+
+/// Decode XML/HTML character entities (`&amp;`, `&#58;`, `&#x3a;`).
+pub fn unescape_xml(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  let mut rest = s;
+  while let Some(amp) = rest.find('&') {
+    out.push_str(&rest[..amp]);
+    rest = &rest[amp..];
+    let Some(semi) = rest.find(';') else {
+      break;
+    };
+    let entity = &rest[1..semi];
+    let decoded = match entity {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ => entity
+        .strip_prefix("#x")
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse::<u32>().ok()))
+        .and_then(char::from_u32),
+    };
+    match decoded {
+      Some(c) => {
+        out.push(c);
+        rest = &rest[semi + 1..];
+      }
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+// Parse `start,duration` millisecond pairs used by `[..]` line and `(..)` word tags.
+fn parse_ms_pair(tag: &str) -> Option<(f64, f64)> {
+  let (start, duration) = tag.split_once(',')?;
+  Some((start.trim().parse::<f64>().ok()? / 1000.0, duration.trim().parse::<f64>().ok()? / 1000.0))
+}
+
+/// Parse QRC lyric content into lyric lines. Lines look like
+/// `[line_start_ms,line_duration_ms]word(start_ms,duration_ms)word(start_ms,duration_ms)`
+/// where word starts are absolute. Metadata tags are skipped.
+pub fn parse_qrc(content: &str) -> Vec<LyricLine> {
+  let mut lyrics: Vec<LyricLine> = Vec::new();
+
+  for raw_line in content.lines() {
+    let line = raw_line.trim();
+    let Some(rest) = line.strip_prefix('[') else {
+      continue;
+    };
+    let Some(close) = rest.find(']') else {
+      continue;
+    };
+    let Some((start, duration)) = parse_ms_pair(&rest[..close]) else {
+      // [ti:..], [ar:..], [offset:..] etc.
+      continue;
+    };
+
+    let mut text = String::new();
+    let mut words: Vec<LyricWord> = Vec::new();
+    let mut body = &rest[close + 1..];
+    // each word is the text before a `(start,duration)` tag; parentheses that aren't timing stay as text
+    let mut word = String::new();
+    while let Some(open) = body.find('(') {
+      let Some(end) = body[open..].find(')').map(|i| open + i) else {
+        break;
+      };
+      word.push_str(&body[..open]);
+      match parse_ms_pair(&body[open + 1..end]) {
+        Some((time, _)) => {
+          if !word.is_empty() {
+            words.push(LyricWord { time, text: word.clone() });
+          }
+          text.push_str(&word);
+          word.clear();
+        }
+        None => word.push_str(&body[open..=end]),
+      }
+      body = &body[end + 1..];
+    }
+    text.push_str(&word);
+    text.push_str(body);
+
+    lyrics.push(LyricLine { time: start, end: Some(start + duration), text: text.trim().to_string(), words, ..Default::default() });
+  }
+
+  lyrics.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+  lyrics
+}
+
+#[test]
+pub fn test_parse_qrc() {
+  let xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<QrcInfos>\n<LyricInfo LyricCount=\"1\">\n<Lyric_1 LyricType=\"1\" LyricContent=\"[ti:Song]\n[1000,1500]Hello (1000,500)world(1500,1000)\n[3000,500]a &amp; b(3000,500)\n\"/>\n</LyricInfo>\n</QrcInfos>\n";
+
+  let content = decode_qrc(xml.as_bytes()).expect("failed to decode qrc xml");
+  let lyrics = parse_qrc(&content);
+  assert_eq!(lyrics.len(), 2);
+  assert_eq!(lyrics[0].text, "Hello world");
+  assert_eq!(lyrics[0].words.len(), 2);
+  assert_eq!(lyrics[0].words[1].time, 1.5);
+  assert_eq!(lyrics[1].text, "a & b");
+}
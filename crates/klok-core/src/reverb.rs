@@ -0,0 +1,88 @@
+// comb and allpass delays of Freeverb, in samples at 44.1 kHz; scaled to the sample rate
+const COMBS: [usize; 4] = [1116, 1188, 1277, 1356];
+const ALLPASSES: [usize; 2] = [556, 441];
+// how long the tail rings, and how quickly its highs fade
+const FEEDBACK: f32 = 0.84;
+const DAMPING: f32 = 0.2;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+// level of the tail at full amount, next to the dry signal
+const WET: f32 = 0.25;
+
+#[derive(Debug, Clone)]
+struct Comb {
+  buffer: Vec<f32>,
+  at: usize,
+  // low-passed feedback
+  filtered: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Allpass {
+  buffer: Vec<f32>,
+  at: usize,
+}
+
+/// Room reverb for a mono stream (Schroeder/Freeverb: parallel damped combs into allpasses), e.g.
+/// on the monitored microphone. Keeps its tail between calls, so a stream can be processed chunk
+/// by chunk.
+#[derive(Debug, Clone)]
+pub struct Reverb {
+  combs: Vec<Comb>,
+  allpasses: Vec<Allpass>,
+}
+
+impl Reverb {
+  pub fn new(sample_rate: u32) -> Reverb {
+    let scale = |delay: usize| ((delay as u64 * sample_rate as u64 / 44100) as usize).max(1);
+    Reverb {
+      combs: COMBS.iter().map(|&d| Comb { buffer: vec![0.0; scale(d)], at: 0, filtered: 0.0 }).collect(),
+      allpasses: ALLPASSES.iter().map(|&d| Allpass { buffer: vec![0.0; scale(d)], at: 0 }).collect(),
+    }
+  }
+
+  /// Add the reverb of mono `samples` to them in place, `amount` from 0 (dry) to 1.
+  pub fn process(&mut self, samples: &mut [f32], amount: f32) {
+    let amount = amount.clamp(0.0, 1.0);
+    for s in samples {
+      let input = *s / self.combs.len() as f32;
+      let mut wet = 0.0;
+      for comb in &mut self.combs {
+        let delayed = comb.buffer[comb.at];
+        comb.filtered = delayed * (1.0 - DAMPING) + comb.filtered * DAMPING;
+        comb.buffer[comb.at] = input + comb.filtered * FEEDBACK;
+        comb.at = (comb.at + 1) % comb.buffer.len();
+        wet += delayed;
+      }
+      for allpass in &mut self.allpasses {
+        let delayed = allpass.buffer[allpass.at];
+        allpass.buffer[allpass.at] = wet + delayed * ALLPASS_FEEDBACK;
+        allpass.at = (allpass.at + 1) % allpass.buffer.len();
+        wet = delayed - wet;
+      }
+      *s += amount * WET * wet;
+    }
+  }
+}
+
+#[test]
+pub fn test_reverb() {
+  let rate = 16000;
+  let energy = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>();
+  let mut impulse = vec![0.0; 2 * rate];
+  impulse[0] = 1.0;
+
+  let mut dry = impulse.clone();
+  Reverb::new(rate as u32).process(&mut dry, 0.0);
+  assert_eq!(dry, impulse);
+
+  // a tail after the impulse, dying away
+  let mut wet = impulse.clone();
+  Reverb::new(rate as u32).process(&mut wet, 1.0);
+  let (early, late) = (energy(&wet[rate / 20..rate / 2]), energy(&wet[3 * rate / 2..]));
+  assert!(early > 1e-3, "no tail {}", early);
+  assert!(late < early / 100.0);
+
+  let mut loud: Vec<f32> = (0..rate).map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate as f32).sin()).collect();
+  Reverb::new(rate as u32).process(&mut loud, 1.0);
+  assert!(loud.iter().all(|s| s.abs() < 2.0));
+}
@@ -0,0 +1,41 @@
+// independent sums kept by `dot`, enough for the compiler to fill 256-bit vector registers
+const LANES: usize = 8;
+
+/// Sum of `a[i] * b[i]` over the shorter of the two. Accumulated in `LANES` independent sums, so
+/// the inner loop vectorizes on stable Rust (a single float sum has to stay in order).
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+  let len = a.len().min(b.len());
+  let (a, b) = (a[..len].chunks_exact(LANES), b[..len].chunks_exact(LANES));
+  let tail: f32 = a.remainder().iter().zip(b.remainder()).map(|(x, y)| x * y).sum();
+  let mut lanes = [0.0f32; LANES];
+  for (x, y) in a.zip(b) {
+    for ((lane, x), y) in lanes.iter_mut().zip(x).zip(y) {
+      *lane += x * y;
+    }
+  }
+  lanes.iter().sum::<f32>() + tail
+}
+
+/// Running sums of squares of `samples`: element `i` is the energy of `samples[..i]`, so any
+/// window's energy is a difference of two. Kept in f64, as the differences cancel.
+pub fn energy_prefix(samples: &[f32]) -> Vec<f64> {
+  let mut sums = Vec::with_capacity(samples.len() + 1);
+  sums.push(0.0);
+  let mut sum = 0.0;
+  for s in samples {
+    sum += (*s as f64).powi(2);
+    sums.push(sum);
+  }
+  sums
+}
+
+#[test]
+pub fn test_dot() {
+  let a: Vec<f32> = (0..21).map(|i| i as f32 * 0.5).collect();
+  let b: Vec<f32> = (0..21).map(|i| 1.0 - i as f32 * 0.25).collect();
+  let expected: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+  assert!((dot(&a, &b) - expected).abs() < 1e-4);
+  // the shorter one counts
+  assert_eq!(dot(&[1.0, 2.0, 3.0], &[1.0, 1.0]), 3.0);
+  assert_eq!(energy_prefix(&[1.0, -2.0, 0.5]), vec![0.0, 1.0, 5.0, 5.25]);
+}
@@ -0,0 +1,98 @@
+use serde::Deserialize;
+
+use crate::midi::{MidiTrack, TempoChange};
+
+// beats per minute before the first tempo change, as in MIDI without a tempo event
+const DEFAULT_BPM: f64 = 120.0;
+
+/// Clean-up of auto-transcribed notes, which come split into fragments with tiny gaps, sprinkled
+/// with blips and slightly off the beat.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SimplifyOptions {
+  /// gaps shorter than this (seconds) are closed: notes of the same key are merged into one,
+  /// otherwise the earlier note is held until the next starts
+  pub merge_gap: f64,
+  /// notes shorter than this (seconds) after merging are dropped
+  pub min_duration: f64,
+  /// grid steps per beat that starts snap to (4 for sixteenths in 4/4), 0 to keep starts
+  pub grid: u32,
+}
+
+impl Default for SimplifyOptions {
+  fn default() -> Self {
+    SimplifyOptions { merge_gap: 0.05, min_duration: 0.06, grid: 4 }
+  }
+}
+
+// The grid point nearest to `time`, counting steps from the tempo change in effect.
+fn snap(time: f64, tempos: &[TempoChange], grid: u32) -> f64 {
+  let (origin, bpm) = tempos.iter().rev().find(|t| t.time <= time).map_or((0.0, DEFAULT_BPM), |t| (t.time, t.bpm));
+  let step = 60.0 / bpm / grid as f64;
+  origin + ((time - origin) / step).round() * step
+}
+
+/// Simplify the notes of `track` following `options`. Starts snap to the beat grid of `tempos` (see
+/// `MidiMeta::tempos`); without a tempo map (note events, scores) they are kept. Notes stay sorted.
+pub fn simplify_track(track: &mut MidiTrack, tempos: Option<&[TempoChange]>, options: &SimplifyOptions) {
+  let mut notes = std::mem::take(&mut track.notes);
+  notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+  for note in notes {
+    let merged = track.notes.iter_mut().rev().take_while(|n| note.start - (n.start + n.duration) < options.merge_gap).find(|n| n.note == note.note && n.channel == note.channel);
+    if let Some(previous) = merged {
+      // one sung note split by the transcription
+      let (a, b) = (previous.duration, note.duration);
+      previous.confidence = match (previous.confidence, note.confidence) {
+        (Some(x), Some(y)) => Some((x * a + y * b) / (a + b)),
+        (x, y) => x.or(y),
+      };
+      // the longer fragment sets the loudness
+      if b > a {
+        previous.velocity = note.velocity;
+      }
+      previous.duration = previous.duration.max(note.start + note.duration - previous.start);
+      previous.bend.extend(note.bend);
+    } else {
+      track.notes.push(note);
+    }
+  }
+  track.notes.retain(|n| n.duration >= options.min_duration);
+  // legato across the gaps left, once the blips between notes are gone
+  for i in 1..track.notes.len() {
+    let (start, channel) = (track.notes[i].start, track.notes[i].channel);
+    let previous = &mut track.notes[i - 1];
+    let gap = start - (previous.start + previous.duration);
+    if previous.channel == channel && gap > 0.0 && gap < options.merge_gap {
+      previous.duration += gap;
+    }
+  }
+
+  if let Some(tempos) = tempos.filter(|_| options.grid > 0) {
+    for note in &mut track.notes {
+      let end = note.start + note.duration;
+      let start = snap(note.start, tempos, options.grid);
+      if start < end {
+        note.start = start;
+        note.duration = end - start;
+      }
+    }
+    track.notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+  }
+}
+
+#[test]
+pub fn test_simplify_track() {
+  use crate::midi::Note;
+
+  let note = |key: i32, start: f64, duration: f64| Note { note: key, start, duration, velocity: 100.0, channel: 0, confidence: Some(0.5), bend: Vec::new() };
+  let mut track = MidiTrack { index: 0, name: None, instrument: None, notes: vec![note(60, 0.0, 0.48), note(60, 0.5, 0.5), note(62, 1.02, 0.03), note(64, 1.26, 0.5), note(65, 1.78, 0.4)], sustain: Vec::new() };
+  let tempos = [TempoChange { time: 0.0, bpm: 120.0 }];
+  simplify_track(&mut track, Some(&tempos), &SimplifyOptions::default());
+  let got: Vec<(i32, f64, f64)> = track.notes.iter().map(|n| (n.note, (n.start * 1000.0).round(), (n.duration * 1000.0).round())).collect();
+  // the split C is merged, the short D dropped, E snapped to 1.25s and held until F
+  assert_eq!(got, vec![(60, 0.0, 1000.0), (64, 1250.0, 530.0), (65, 1750.0, 430.0)]);
+
+  let mut unsnapped = MidiTrack { index: 0, name: None, instrument: None, notes: vec![note(64, 1.26, 0.5)], sustain: Vec::new() };
+  simplify_track(&mut unsnapped, None, &SimplifyOptions::default());
+  assert_eq!(unsnapped.notes[0].start, 1.26);
+}
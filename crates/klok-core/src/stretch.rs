@@ -0,0 +1,119 @@
+use std::f32::consts::PI;
+
+use crate::simd::{dot, energy_prefix};
+
+// seconds per grain; grains overlap by half
+const WINDOW: f64 = 0.046;
+// seconds a grain may move from its nominal place to line up with the previous one
+const TOLERANCE: f64 = 0.01;
+// samples skipped between those compared when lining up, for speed
+const SEARCH_STRIDE: usize = 4;
+/// Slowest and fastest tempo handled.
+pub const MIN_TEMPO: f64 = 0.25;
+pub const MAX_TEMPO: f64 = 2.0;
+
+/// WSOLA time-stretcher: plays a source at another tempo without changing its pitch, by
+/// overlap-adding grains taken at the source position, each moved a little to line up with the
+/// waveform continuing the previous one. The source is read at random through a callback, so a
+/// tempo can change from one step to the next.
+#[derive(Debug, Clone)]
+pub struct Wsola {
+  channels: usize,
+  window: usize,
+  tolerance: usize,
+  // the overlap-add buffer, `window` frames
+  out: Vec<f32>,
+  // source frame of the previous grain
+  last: Option<isize>,
+}
+
+impl Wsola {
+  pub fn new(channels: u16, sample_rate: u32) -> Wsola {
+    let channels = channels.max(1) as usize;
+    // even, so two half-overlapping grains sum to one
+    let window = ((WINDOW * sample_rate as f64) as usize / 2 * 2).max(4);
+    Wsola { channels, window, tolerance: (TOLERANCE * sample_rate as f64) as usize, out: vec![0.0; window * channels], last: None }
+  }
+
+  /// Output frames produced by each `step`; the source position should advance by `hop() * tempo`.
+  pub fn hop(&self) -> usize {
+    self.window / 2
+  }
+
+  /// Forget the previous grain, e.g. after a seek.
+  pub fn reset(&mut self) {
+    self.out.iter_mut().for_each(|s| *s = 0.0);
+    self.last = None;
+  }
+
+  // The offset (from -tolerance) of the `hop()` frames of `region`, read from `tolerance` before
+  // the nominal place, continuing most like `target`: the normalized correlation of every
+  // `SEARCH_STRIDE`th channel-summed frame. The region is split by stride phase, so the frames of
+  // each candidate are contiguous and its energy a difference of running sums.
+  fn best_offset(&self, region: &[f32], target: &[f32]) -> isize {
+    let mono = |samples: &[f32]| samples.chunks_exact(self.channels).map(|f| f.iter().sum()).collect::<Vec<f32>>();
+    let target: Vec<f32> = mono(target).into_iter().step_by(SEARCH_STRIDE).collect();
+    let region = mono(region);
+    let phases: Vec<Vec<f32>> = (0..SEARCH_STRIDE).map(|p| region.iter().skip(p).step_by(SEARCH_STRIDE).copied().collect()).collect();
+    let energies: Vec<Vec<f64>> = phases.iter().map(|p| energy_prefix(p)).collect();
+    let compared = target.len();
+    (0..=2 * self.tolerance)
+      .map(|at| {
+        let (phase, from) = (at % SEARCH_STRIDE, at / SEARCH_STRIDE);
+        let energy = energies[phase][from + compared] - energies[phase][from];
+        (at, dot(&phases[phase][from..from + compared], &target) / (energy as f32 + 1e-9).sqrt())
+      })
+      .max_by(|a, b| a.1.total_cmp(&b.1))
+      .map_or(0, |(at, _)| at as isize - self.tolerance as isize)
+  }
+
+  /// The next `hop()` interleaved output frames, with a grain from around source frame `position`.
+  /// `read(start, frames)` returns `frames` interleaved source frames from `start`, silence
+  /// outside the source.
+  pub fn step(&mut self, position: f64, read: impl Fn(isize, usize) -> Vec<f32>) -> Vec<f32> {
+    let (hop, channels) = (self.hop(), self.channels);
+    let nominal = position as isize;
+    let start = match self.last {
+      None => nominal,
+      Some(last) => {
+        // what would have followed the previous grain, and the candidates around the nominal place
+        let target = read(last + hop as isize, hop);
+        let region = read(nominal - self.tolerance as isize, hop + 2 * self.tolerance);
+        nominal + self.best_offset(&region, &target)
+      }
+    };
+    let grain = read(start, self.window);
+    for (i, frame) in grain.chunks_exact(channels).enumerate() {
+      let weight = 0.5 - 0.5 * (2.0 * PI * i as f32 / self.window as f32).cos();
+      for (c, s) in frame.iter().enumerate() {
+        self.out[i * channels + c] += weight * s;
+      }
+    }
+    self.last = Some(start);
+    let emitted: Vec<f32> = self.out.drain(..hop * channels).collect();
+    self.out.resize(self.window * channels, 0.0);
+    emitted
+  }
+}
+
+#[test]
+pub fn test_wsola() {
+  let rate = 44100;
+  let source: Vec<f32> = (0..rate as usize).map(|i| (2.0 * PI * 440.0 * i as f32 / rate as f32).sin()).collect();
+  let read = |start: isize, frames: usize| (start..start + frames as isize).map(|i| if i >= 0 { source.get(i as usize).copied().unwrap_or(0.0) } else { 0.0 }).collect::<Vec<_>>();
+  let crossings = |s: &[f32]| s.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count() as f64 / s.len() as f64;
+
+  // half speed: twice as long, same pitch
+  let mut wsola = Wsola::new(1, rate);
+  let (mut out, mut position) = (Vec::new(), 0.0);
+  while position < source.len() as f64 {
+    out.extend(wsola.step(position, read));
+    position += wsola.hop() as f64 * 0.5;
+  }
+  assert!((out.len() as f64 / source.len() as f64 - 2.0).abs() < 0.01);
+  let steady = &out[rate as usize / 4..out.len() - rate as usize / 4];
+  assert!((crossings(steady) / crossings(&source) - 1.0).abs() < 0.02);
+  // aligned grains keep the level of the tone
+  let peak = steady.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+  assert!(peak > 0.9 && peak < 1.1, "peak {}", peak);
+}
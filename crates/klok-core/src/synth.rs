@@ -0,0 +1,225 @@
+use serde::Deserialize;
+
+use crate::f0::note_hz;
+use crate::melody::DRUM_CHANNEL;
+use crate::midi::{MidiTrack, Note};
+
+pub const SAMPLE_RATE: u32 = 22050;
+
+// seconds of fade in/out per note, so notes don't click
+const ATTACK: f64 = 0.01;
+const RELEASE: f64 = 0.05;
+// peak level of a full-velocity note, leaving headroom for overlapping notes
+const NOTE_GAIN: f64 = 0.3;
+
+// seconds a drum hit rings
+const DRUM_DECAY: f64 = 0.12;
+// General MIDI bass drum keys, rendered as a falling low sine instead of noise
+const KICK_KEYS: [i32; 2] = [35, 36];
+
+fn silence(end: f64, tail: f64) -> Vec<f32> {
+  vec![0.0f32; ((end + tail.max(0.0)) * SAMPLE_RATE as f64).ceil() as usize]
+}
+
+// Add `note` to `samples`: a sine with two soft overtones, scaled by velocity (0-127).
+fn add_tone(samples: &mut [f32], note: &Note) {
+  let rate = SAMPLE_RATE as f64;
+  let freq = note_hz(note.note as f64);
+  let gain = NOTE_GAIN * (note.velocity / 127.0).clamp(0.0, 1.0);
+  let first = (note.start.max(0.0) * rate) as usize;
+  let length = ((note.duration + RELEASE) * rate) as usize;
+  for (i, sample) in samples.iter_mut().skip(first).take(length).enumerate() {
+    let t = i as f64 / rate;
+    let envelope = (t / ATTACK).min(1.0) * ((note.duration + RELEASE - t) / RELEASE).clamp(0.0, 1.0);
+    let phase = std::f64::consts::TAU * freq * t;
+    let tone = phase.sin() + 0.3 * (2.0 * phase).sin() + 0.1 * (3.0 * phase).sin();
+    *sample += (gain * envelope * tone / 1.4) as f32;
+  }
+}
+
+// Add a percussion hit: a decaying noise burst, or a pitch-dropping thump for bass drums.
+// `seed` keeps the noise deterministic.
+fn add_drum(samples: &mut [f32], note: &Note, seed: &mut u32) {
+  let rate = SAMPLE_RATE as f64;
+  let gain = NOTE_GAIN * (note.velocity / 127.0).clamp(0.0, 1.0);
+  let first = (note.start.max(0.0) * rate) as usize;
+  let kick = KICK_KEYS.contains(&note.note);
+  for (i, sample) in samples.iter_mut().skip(first).take((DRUM_DECAY * 2.0 * rate) as usize).enumerate() {
+    let t = i as f64 / rate;
+    let envelope = (-t / (DRUM_DECAY / 3.0)).exp();
+    let tone = if kick {
+      (std::f64::consts::TAU * (50.0 * t + 60.0 * DRUM_DECAY * (1.0 - (-t / DRUM_DECAY).exp()))).sin()
+    } else {
+      // xorshift noise
+      *seed ^= *seed << 13;
+      *seed ^= *seed >> 17;
+      *seed ^= *seed << 5;
+      *seed as f64 / u32::MAX as f64 * 2.0 - 1.0
+    };
+    *sample += (gain * envelope * tone * 0.6) as f32;
+  }
+}
+
+/// Render notes as a mono melody: a sine with two soft overtones per note, scaled by velocity
+/// (0-127). `tail` seconds of silence are kept after the last note.
+pub fn render_notes(notes: &[Note], tail: f64) -> Vec<f32> {
+  let end = notes.iter().map(|n| n.start + n.duration + RELEASE).fold(0.0, f64::max);
+  let mut samples = silence(end, tail);
+  for note in notes {
+    add_tone(&mut samples, note);
+  }
+  samples
+}
+
+/// Sound of a guide melody, see [`render_guide`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuideVoice {
+  /// a pure sine, the clearest pitch reference
+  #[default]
+  Sine,
+  /// struck and decaying with a few harmonics, easier to sing over for long notes
+  Piano,
+}
+
+// seconds for the piano voice to fall by 1/e, and how much faster each harmonic fades
+const PIANO_DECAY: f64 = 0.8;
+const PIANO_HARMONICS: [f64; 4] = [1.0, 0.5, 0.25, 0.12];
+
+// Add `note` in `voice`, following its pitch bend.
+fn add_guide_tone(samples: &mut [f32], note: &Note, voice: GuideVoice) {
+  let rate = SAMPLE_RATE as f64;
+  let gain = NOTE_GAIN * (note.velocity / 127.0).clamp(0.0, 1.0);
+  let first = (note.start.max(0.0) * rate) as usize;
+  let length = ((note.duration + RELEASE) * rate) as usize;
+  let (mut phase, mut bend) = (0.0, 0);
+  for (i, sample) in samples.iter_mut().skip(first).take(length).enumerate() {
+    let t = i as f64 / rate;
+    // bend points hold until the next one
+    while bend < note.bend.len() && note.bend[bend].0 <= note.start + t {
+      bend += 1;
+    }
+    let offset = if bend > 0 { note.bend[bend - 1].1 } else { 0.0 };
+    phase += std::f64::consts::TAU * note_hz(note.note as f64 + offset) / rate;
+    let release = ((note.duration + RELEASE - t) / RELEASE).clamp(0.0, 1.0);
+    let tone = match voice {
+      GuideVoice::Sine => (t / ATTACK).min(1.0) * phase.sin(),
+      GuideVoice::Piano => {
+        let harmonics: f64 = PIANO_HARMONICS.iter().enumerate().map(|(h, a)| a * ((h + 1) as f64 * phase).sin() * (-t * (h + 1) as f64 / PIANO_DECAY).exp()).sum();
+        (t / 0.002).min(1.0) * harmonics / 1.3
+      }
+    };
+    *sample += (gain * release * tone) as f32;
+  }
+}
+
+/// Render notes as a guide melody to sing along with, following pitch bends, in `voice`. Notes
+/// play at the same level as [`render_notes`]; `tail` seconds of silence follow the last one.
+pub fn render_guide(notes: &[Note], voice: GuideVoice, tail: f64) -> Vec<f32> {
+  let end = notes.iter().map(|n| n.start + n.duration + RELEASE).fold(0.0, f64::max);
+  let mut samples = silence(end, tail);
+  for note in notes {
+    add_guide_tone(&mut samples, note, voice);
+  }
+  let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+  if peak > 1.0 {
+    samples.iter_mut().for_each(|s| *s /= peak);
+  }
+  samples
+}
+
+/// Render the tracks of a MIDI file, except the `melody` track, as a mono backing to rehearse with:
+/// pitched notes as in [`render_notes`], percussion (channel 10) as noise bursts and thumps.
+/// The mix is scaled down when it would clip. `tail` seconds of silence follow the last note.
+pub fn render_backing(tracks: &[MidiTrack], melody: Option<usize>, tail: f64) -> Vec<f32> {
+  let notes: Vec<&Note> = tracks.iter().filter(|t| Some(t.index) != melody).flat_map(|t| &t.notes).collect();
+  let end = notes.iter().map(|n| n.start + n.duration.max(DRUM_DECAY * 2.0) + RELEASE).fold(0.0, f64::max);
+  let mut samples = silence(end, tail);
+  let mut seed = 0x9e37_79b9;
+  for note in notes {
+    if note.channel == DRUM_CHANNEL {
+      add_drum(&mut samples, note, &mut seed);
+    } else {
+      add_tone(&mut samples, note);
+    }
+  }
+  let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+  if peak > 1.0 {
+    samples.iter_mut().for_each(|s| *s /= peak);
+  }
+  samples
+}
+
+/// Encode mono samples (-1.0..1.0) as a 16-bit PCM WAV file.
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+  let data_len = (samples.len() * 2) as u32;
+  let mut out = Vec::with_capacity(44 + data_len as usize);
+  out.extend_from_slice(b"RIFF");
+  out.extend_from_slice(&(36 + data_len).to_le_bytes());
+  out.extend_from_slice(b"WAVEfmt ");
+  out.extend_from_slice(&16u32.to_le_bytes());
+  // PCM, mono
+  out.extend_from_slice(&1u16.to_le_bytes());
+  out.extend_from_slice(&1u16.to_le_bytes());
+  out.extend_from_slice(&sample_rate.to_le_bytes());
+  out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+  // block align, bits per sample
+  out.extend_from_slice(&2u16.to_le_bytes());
+  out.extend_from_slice(&16u16.to_le_bytes());
+  out.extend_from_slice(b"data");
+  out.extend_from_slice(&data_len.to_le_bytes());
+  for s in samples {
+    out.extend_from_slice(&((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+  }
+  out
+}
+
+#[test]
+pub fn test_render_notes() {
+  let notes = vec![Note { note: 69, start: 0.5, duration: 0.5, velocity: 127.0, channel: 0, confidence: None, bend: Vec::new() }];
+  let samples = render_notes(&notes, 1.0);
+  let rate = SAMPLE_RATE as usize;
+  assert_eq!(samples.len(), ((1.05 + 1.0) * SAMPLE_RATE as f64).ceil() as usize);
+  assert!(samples[..rate / 2].iter().all(|&s| s == 0.0));
+  assert!(samples[rate / 2..rate].iter().any(|&s| s.abs() > 0.1));
+  assert!(samples[rate * 11 / 10..].iter().all(|&s| s == 0.0));
+  assert!(samples.iter().all(|s| s.abs() <= 1.0));
+
+  let wav = encode_wav(&samples, SAMPLE_RATE);
+  assert_eq!(&wav[..4], b"RIFF");
+  assert_eq!(wav.len(), 44 + samples.len() * 2);
+  assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), SAMPLE_RATE);
+}
+
+#[test]
+pub fn test_render_guide() {
+  let notes = vec![Note { note: 69, start: 0.0, duration: 1.0, velocity: 127.0, channel: 0, confidence: None, bend: vec![(0.5, 12.0)] }];
+  let rate = SAMPLE_RATE as usize;
+  let sine = render_guide(&notes, GuideVoice::Sine, 0.0);
+  // zero crossings per quarter second: 440 Hz before the bend, 880 Hz after it
+  let crossings = |s: &[f32]| s.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+  assert!((crossings(&sine[rate / 8..rate * 3 / 8]) as i32 - 220).abs() <= 2);
+  assert!((crossings(&sine[rate * 5 / 8..rate * 7 / 8]) as i32 - 440).abs() <= 2);
+  let piano = render_guide(&notes, GuideVoice::Piano, 0.0);
+  // the piano voice decays
+  let level = |s: &[f32]| s.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+  assert!(level(&piano[rate * 3 / 4..rate * 9 / 10]) < level(&piano[..rate / 4]) * 0.6);
+  assert!(sine.iter().chain(&piano).all(|s| s.abs() <= 1.0));
+}
+
+#[test]
+pub fn test_render_backing() {
+  let note = |note, start, channel| Note { note, start, duration: 0.5, velocity: 127.0, channel, confidence: None, bend: Vec::new() };
+  let track = |index, notes| MidiTrack { index, name: None, instrument: None, notes, sustain: Vec::new() };
+  // the melody in the first second, chords and drums in the second
+  let chords: Vec<Note> = (0..12).map(|i| note(48 + i, 1.0, 0)).collect();
+  let tracks = vec![track(0, vec![note(72, 0.0, 0)]), track(1, chords), track(2, vec![note(36, 1.0, DRUM_CHANNEL), note(42, 1.5, DRUM_CHANNEL)])];
+
+  let samples = render_backing(&tracks, Some(0), 0.5);
+  let rate = SAMPLE_RATE as usize;
+  assert!(samples[..rate].iter().all(|&s| s == 0.0));
+  assert!(samples[rate..rate * 2].iter().any(|&s| s.abs() > 0.1));
+  // twelve loud notes at once are scaled down instead of clipping
+  assert!(samples.iter().all(|s| s.abs() <= 1.0));
+  assert!(render_backing(&tracks, None, 0.0)[..rate / 2].iter().any(|&s| s.abs() > 0.1));
+}
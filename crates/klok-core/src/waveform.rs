@@ -0,0 +1,56 @@
+use crate::pcm::Pcm;
+
+// bytes before the peaks in `Waveform::to_bytes`
+const HEADER_LEN: usize = 8;
+
+/// Lowest and highest sample of each of `buckets` equal slices of a song, over all channels, for
+/// drawing a waveform behind the seek bar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+  /// seconds of audio covered
+  pub duration: f32,
+  /// (min, max) per bucket
+  pub peaks: Vec<(f32, f32)>,
+}
+
+impl Waveform {
+  pub fn new(pcm: &Pcm, buckets: usize) -> Result<Waveform, String> {
+    if buckets == 0 {
+      return Err("waveform needs at least one bucket".to_string());
+    }
+    let channels = pcm.channels.max(1) as usize;
+    let frames = pcm.frames();
+    let peaks = (0..buckets)
+      .map(|b| {
+        let (from, to) = (b * frames / buckets, (b + 1) * frames / buckets);
+        pcm.samples[from * channels..to * channels].iter().fold(None, |acc: Option<(f32, f32)>, &s| Some(acc.map_or((s, s), |(lo, hi)| (lo.min(s), hi.max(s)))))
+          .unwrap_or((0.0, 0.0))
+      })
+      .collect();
+    Ok(Waveform { duration: pcm.duration() as f32, peaks })
+  }
+
+  /// Little-endian layout: `buckets: u32, duration: f32`, then `min: f32, max: f32` per bucket.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + self.peaks.len() * 8);
+    out.extend_from_slice(&(self.peaks.len() as u32).to_le_bytes());
+    out.extend_from_slice(&self.duration.to_le_bytes());
+    for (lo, hi) in &self.peaks {
+      out.extend_from_slice(&lo.to_le_bytes());
+      out.extend_from_slice(&hi.to_le_bytes());
+    }
+    out
+  }
+}
+
+#[test]
+pub fn test_waveform() {
+  let pcm = Pcm { sample_rate: 4, channels: 2, samples: vec![0.1, -0.2, 0.5, 0.0, -0.9, 0.3, 0.2, 0.2] };
+  let waveform = Waveform::new(&pcm, 2).unwrap();
+  assert_eq!(waveform.duration, 1.0);
+  assert_eq!(waveform.peaks, vec![(-0.2, 0.5), (-0.9, 0.3)]);
+  // more buckets than frames: the empty ones are silent
+  assert_eq!(Waveform::new(&pcm, 8).unwrap().peaks.iter().filter(|p| **p == (0.0, 0.0)).count(), 4);
+  assert_eq!(waveform.to_bytes().len(), HEADER_LEN + 16);
+  assert!(Waveform::new(&pcm, 0).is_err());
+}
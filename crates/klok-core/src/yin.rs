@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::simd::{dot, energy_prefix};
+
+/// Lowest and highest pitch looked for, a bass's low E to a soprano's high C and a bit.
+pub const MIN_HZ: f32 = 70.0;
+pub const MAX_HZ: f32 = 1100.0;
+// cumulative mean normalized difference below which a period is taken (the YIN paper's 0.1-0.15)
+const THRESHOLD: f32 = 0.15;
+// seconds between pitch frames, 100 per second
+const HOP: f64 = 0.01;
+
+/// Pitch of `frame` by YIN, as (Hz, confidence 0..1), or `None` when nothing periodic is found
+/// between `MIN_HZ` and `MAX_HZ`. `frame` must be at least twice the longest period.
+pub fn detect_pitch(frame: &[f32], sample_rate: u32) -> Option<(f32, f32)> {
+  let rate = sample_rate as f32;
+  let (min_lag, max_lag) = ((rate / MAX_HZ) as usize, (rate / MIN_HZ).ceil() as usize);
+  let window = frame.len().checked_sub(max_lag + 1).filter(|w| *w >= max_lag)?;
+  if frame.iter().all(|s| s.abs() < 1e-4) {
+    return None;
+  }
+  // difference function, normalized by its running mean; each lag's sum of squared differences
+  // is the two windows' energies less twice their correlation
+  let energy = energy_prefix(frame);
+  let reference = &frame[..window];
+  let mut cmnd = vec![1.0f32; max_lag + 2];
+  let mut sum = 0.0;
+  for lag in 1..=max_lag + 1 {
+    let energies = energy[window] + energy[lag + window] - energy[lag];
+    let d = (energies as f32 - 2.0 * dot(reference, &frame[lag..lag + window])).max(0.0);
+    sum += d;
+    cmnd[lag] = if sum > 0.0 { d * lag as f32 / sum } else { 1.0 };
+  }
+  // first dip under the threshold, followed to its bottom; else the deepest dip
+  let lag = (min_lag.max(2)..=max_lag)
+    .find(|&lag| cmnd[lag] < THRESHOLD)
+    .map(|mut lag| {
+      while lag < max_lag && cmnd[lag + 1] < cmnd[lag] {
+        lag += 1;
+      }
+      lag
+    })
+    .or_else(|| (min_lag.max(2)..=max_lag).min_by(|a, b| cmnd[*a].total_cmp(&cmnd[*b])))?;
+  let confidence = (1.0 - cmnd[lag]).clamp(0.0, 1.0);
+  if cmnd[lag] >= 0.5 {
+    return None;
+  }
+  // parabola through the dip for the fractional period
+  let (a, b, c) = (cmnd[lag - 1], cmnd[lag], cmnd[lag + 1]);
+  let shift = if a + c - 2.0 * b != 0.0 { 0.5 * (a - c) / (a + c - 2.0 * b) } else { 0.0 };
+  Some((rate / (lag as f32 + shift.clamp(-1.0, 1.0)), confidence))
+}
+
+/// One pitch estimate of the tracked stream.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PitchFrame {
+  /// seconds of input before the end of the analysed frame
+  pub time: f64,
+  /// Hz, `None` while unvoiced or silent
+  pub f0: Option<f32>,
+  pub confidence: f32,
+}
+
+/// Runs YIN over a mono stream fed in pieces of any size, 100 frames per second of input.
+#[derive(Debug, Clone)]
+pub struct PitchTracker {
+  sample_rate: u32,
+  frame: usize,
+  hop: usize,
+  buffer: Vec<f32>,
+  // samples fed so far
+  fed: u64,
+}
+
+impl PitchTracker {
+  pub fn new(sample_rate: u32) -> PitchTracker {
+    let frame = 2 * ((sample_rate as f32 / MIN_HZ).ceil() as usize + 1);
+    PitchTracker { sample_rate, frame, hop: ((HOP * sample_rate as f64) as usize).max(1), buffer: Vec::new(), fed: 0 }
+  }
+
+  pub fn feed(&mut self, samples: &[f32]) -> Vec<PitchFrame> {
+    let mut out = Vec::new();
+    for &s in samples {
+      self.buffer.push(s);
+      self.fed += 1;
+      if self.buffer.len() == self.frame {
+        let pitch = detect_pitch(&self.buffer, self.sample_rate);
+        let time = self.fed as f64 / self.sample_rate as f64;
+        out.push(PitchFrame { time, f0: pitch.map(|p| p.0), confidence: pitch.map_or(0.0, |p| p.1) });
+        self.buffer.drain(..self.hop);
+      }
+    }
+    out
+  }
+}
+
+#[test]
+pub fn test_yin() {
+  let rate = 16000;
+  let tone = |hz: f32, n: usize| (0..n).map(|i| (2.0 * std::f32::consts::PI * hz * i as f32 / rate as f32).sin()).collect::<Vec<f32>>();
+  for hz in [82.4, 220.0, 440.0, 987.8] {
+    let (f0, confidence) = detect_pitch(&tone(hz, 1024), rate).unwrap();
+    assert!((f0 - hz).abs() / hz < 0.01, "{} detected as {}", hz, f0);
+    assert!(confidence > 0.9);
+  }
+  assert_eq!(detect_pitch(&vec![0.0; 1024], rate), None);
+  // noise has no period
+  let mut seed = 1u32;
+  let noise: Vec<f32> = (0..1024).map(|_| { seed = seed.wrapping_mul(1664525).wrapping_add(1013904223); (seed >> 8) as f32 / (1 << 23) as f32 - 1.0 }).collect();
+  assert_eq!(detect_pitch(&noise, rate), None);
+
+  // a second of tone in pieces gives 100 frames a second once the first frame is full
+  let mut tracker = PitchTracker::new(rate);
+  let frames: Vec<PitchFrame> = tone(440.0, rate as usize).chunks(333).flat_map(|c| tracker.feed(c)).collect();
+  assert!(frames.len() > 90 && frames.len() <= 100);
+  assert!(frames.iter().all(|f| f.f0.is_some_and(|f0| (f0 - 440.0).abs() < 5.0)));
+  assert!(frames.windows(2).all(|w| (w[1].time - w[0].time - 0.01).abs() < 1e-6));
+}